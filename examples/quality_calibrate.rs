@@ -1,7 +1,9 @@
 //! Quality calibration harness for zen encoders.
 //!
 //! Encodes each image from a corpus at multiple quality levels per codec,
-//! then measures SSIMULACRA2 to build quality→SSIM2 curves.
+//! then measures SSIMULACRA2 with [`zencodecs::metric`] (the same metric
+//! `EncodeRequest::with_target_score` searches against) to build
+//! quality→SSIM2 curves.
 //!
 //! Usage:
 //!   cargo run --release --features calibrate --example quality_calibrate -- \
@@ -21,6 +23,8 @@ use rayon::prelude::*;
 use zencodec::encode::EncoderConfig;
 
 use zencodecs::DecodeRequest;
+use zencodecs::metric::{MetricReference, QualityMetric};
+use zenpixels::{PixelDescriptor, PixelSlice};
 
 // bytemuck is a dependency of zencodecs, available transitively
 extern crate bytemuck;
@@ -148,7 +152,7 @@ fn main() {
 
         sources.par_iter().for_each(|(name, source_rgb)| {
             // Precompute SSIM2 reference once per image
-            let source_ref = imgref_u8_to_ssim2_ref(source_rgb.as_ref());
+            let source_ref = metric_reference(source_rgb.as_ref());
             let source_ref = match source_ref {
                 Some(r) => r,
                 None => return,
@@ -224,7 +228,7 @@ fn encode_and_measure(
     codec_name: &'static str,
     image_name: &str,
     source: ImgRef<[u8; 3]>,
-    ssim2_ref: &fast_ssim2::Ssimulacra2Reference,
+    ssim2_ref: &MetricReference,
     quality: f32,
     codec: &Codec,
 ) -> Option<Measurement> {
@@ -268,19 +272,18 @@ fn encode_and_measure(
 
     let file_size = encoded.len();
 
-    // Decode back to RGB8
+    // Decode back and measure SSIM2 against the precomputed reference.
     // Use libjpeg-turbo's own decoder for libjpeg-turbo codec (pure roundtrip),
-    // otherwise use zencodecs' format-matched decoder.
-    let decoded_rgb = if matches!(codec, Codec::LibjpegTurbo(_)) {
-        decode_with_libjpeg_turbo(&encoded)?
+    // otherwise use zencodecs' format-matched decoder; the metric converts
+    // whatever pixel format it gets to sRGB RGB8.
+    let ssim2 = if matches!(codec, Codec::LibjpegTurbo(_)) {
+        let decoded_rgb = decode_with_libjpeg_turbo(&encoded)?;
+        ssim2_ref.compare(&rgb8_slice(decoded_rgb.as_ref())?).ok()?
     } else {
         let decoded = DecodeRequest::new(&encoded).decode_full_frame().ok()?;
-        decode_output_to_rgb8(&decoded)?
+        ssim2_ref.compare(&decoded.pixels()).ok()?
     };
 
-    // Measure SSIM2 using precomputed reference
-    let ssim2 = measure_ssim2_with_ref(ssim2_ref, decoded_rgb.as_ref())?;
-
     Some(Measurement {
         codec: codec_name,
         image_name: image_name.to_string(),
@@ -450,16 +453,20 @@ fn decode_output_to_rgb8(output: &zencodecs::DecodeOutput) -> Option<ImgVec<[u8;
 }
 
 /// Create an SSIM2 reference from an sRGB u8 image.
-fn imgref_u8_to_ssim2_ref(img: ImgRef<[u8; 3]>) -> Option<fast_ssim2::Ssimulacra2Reference> {
-    fast_ssim2::Ssimulacra2Reference::new(img).ok()
+fn metric_reference(img: ImgRef<[u8; 3]>) -> Option<MetricReference> {
+    MetricReference::new(QualityMetric::Ssimulacra2, &rgb8_slice(img)?).ok()
 }
 
-/// Measure SSIM2 between a precomputed reference and a distorted image.
-fn measure_ssim2_with_ref(
-    reference: &fast_ssim2::Ssimulacra2Reference,
-    distorted: ImgRef<[u8; 3]>,
-) -> Option<f64> {
-    reference.compare(distorted).ok()
+/// View an sRGB u8 image as a `PixelSlice` for the metric.
+fn rgb8_slice(img: ImgRef<'_, [u8; 3]>) -> Option<PixelSlice<'_>> {
+    PixelSlice::new(
+        bytemuck::cast_slice(img.buf()),
+        img.width() as u32,
+        img.height() as u32,
+        img.stride() * 3,
+        PixelDescriptor::RGB8_SRGB,
+    )
+    .ok()
 }
 
 /// Write per-codec CSV.
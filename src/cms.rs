@@ -483,6 +483,50 @@ pub fn png_srgb_transform_icc_ex(data: &[u8], honor_gama_only: bool) -> Option<(
     Some((src_icc, dst_icc))
 }
 
+// ─── CMYK ───
+
+/// Convert interleaved CMYK8 (0 = no ink) to sRGB RGB8 through `cmyk_icc`.
+///
+/// Returns `None` if the profile does not parse or is not a CMYK profile.
+pub(crate) fn cmyk_to_srgb8(cmyk: &[u8], cmyk_icc: &[u8]) -> Option<Vec<u8>> {
+    let src = moxcms::ColorProfile::new_from_slice(cmyk_icc).ok()?;
+    if src.color_space != moxcms::DataColorSpace::Cmyk {
+        return None;
+    }
+    // moxcms takes 8-bit CMYK through the four-channel Rgba layout.
+    let transform = src
+        .create_transform_8bit(
+            moxcms::Layout::Rgba,
+            &moxcms::ColorProfile::new_srgb(),
+            moxcms::Layout::Rgb,
+            moxcms::TransformOptions::default(),
+        )
+        .ok()?;
+    let mut rgb = vec![0u8; cmyk.len() / 4 * 3];
+    transform.transform(cmyk, &mut rgb).ok()?;
+    Some(rgb)
+}
+
+/// 16-bit variant of [`cmyk_to_srgb8`].
+#[cfg(feature = "psd")]
+pub(crate) fn cmyk16_to_srgb16(cmyk: &[u16], cmyk_icc: &[u8]) -> Option<Vec<u16>> {
+    let src = moxcms::ColorProfile::new_from_slice(cmyk_icc).ok()?;
+    if src.color_space != moxcms::DataColorSpace::Cmyk {
+        return None;
+    }
+    let transform = src
+        .create_transform_16bit(
+            moxcms::Layout::Rgba,
+            &moxcms::ColorProfile::new_srgb(),
+            moxcms::Layout::Rgb,
+            moxcms::TransformOptions::default(),
+        )
+        .ok()?;
+    let mut rgb = vec![0u16; cmyk.len() / 4 * 3];
+    transform.transform(cmyk, &mut rgb).ok()?;
+    Some(rgb)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,397 @@
+//! CMYK and YCCK sources (JPEG and TIFF).
+//!
+//! PSD CMYK composites go through the same conversion, with the profile
+//! from the document's image resources.
+//!
+//! Print-origin JPEGs and TIFFs store ink coverage instead of light.
+//! [`DecodeRequest`](crate::DecodeRequest) recognizes them and returns RGB:
+//!
+//! - With the `cms` feature, ink values go through the embedded CMYK ICC
+//!   profile, or the one given to
+//!   [`with_cmyk_profile`](crate::DecodeRequest::with_cmyk_profile) (e.g. a
+//!   SWOP or FOGRA profile) when the file has none, into sRGB.
+//! - Otherwise, or when no usable profile exists, the naive
+//!   `rgb = (1 - c)(1 - k)` conversion is used.
+//!
+//! The output is tagged sRGB. [`detect`] reports the source kind without
+//! decoding, probes report 4 channels and keep the CMYK profile, and
+//! [`decode_cmyk`](crate::DecodeRequest::decode_cmyk) returns the ink
+//! values alongside the RGB output.
+
+use alloc::vec::Vec;
+
+#[cfg(any(feature = "jpeg", feature = "tiff"))]
+use crate::error::Result;
+#[cfg(any(feature = "jpeg", feature = "tiff"))]
+use crate::{CodecError, DecodeOutput, ImageFormat};
+#[cfg(any(feature = "jpeg", feature = "tiff"))]
+use whereat::at;
+#[cfg(any(feature = "jpeg", feature = "tiff"))]
+use zenpixels::{ChannelLayout, ChannelType, PixelBuffer, PixelDescriptor};
+
+/// How the four channels are coded in the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmykKind {
+    /// Cyan, magenta, yellow, black.
+    Cmyk,
+    /// JPEG with C, M, Y coded as YCbCr (Adobe transform 2) and K as is.
+    Ycck,
+}
+
+/// A CMYK or YCCK source, found by [`detect`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CmykSource {
+    /// Channel coding.
+    pub kind: CmykKind,
+    /// Samples are stored inverted (255 = no ink), as Adobe applications
+    /// write JPEGs carrying an Adobe APP14 marker.
+    pub inverted: bool,
+    /// Embedded CMYK ICC profile.
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+/// Ink values of a decoded CMYK or YCCK image.
+#[derive(Clone, Debug)]
+pub struct DecodedCmyk {
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Interleaved C, M, Y, K, 8 bits each, 0 = no ink (already
+    /// un-inverted and, for YCCK, converted to CMYK).
+    pub data: Vec<u8>,
+    /// What was detected in the file.
+    pub source: CmykSource,
+}
+
+impl DecodedCmyk {
+    /// Copy one plane out: 0 = cyan, 1 = magenta, 2 = yellow, 3 = black.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel > 3`.
+    pub fn plane(&self, channel: usize) -> Vec<u8> {
+        assert!(channel < 4, "CMYK has 4 planes");
+        self.data.iter().skip(channel).step_by(4).copied().collect()
+    }
+}
+
+/// Detect a CMYK or YCCK JPEG or TIFF from its headers.
+///
+/// Returns `None` for other formats and color spaces.
+pub fn detect(data: &[u8]) -> Option<CmykSource> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        detect_jpeg(data)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        detect_tiff(data)
+    } else {
+        None
+    }
+}
+
+fn detect_jpeg(data: &[u8]) -> Option<CmykSource> {
+    let mut at = 2;
+    let mut components = None;
+    let mut adobe_transform = None;
+    let mut icc_chunks: Vec<(u8, &[u8])> = Vec::new();
+    while at + 4 <= data.len() {
+        if data[at] != 0xFF {
+            return None;
+        }
+        let marker = data[at + 1];
+        if marker == 0xFF {
+            at += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            at += 2;
+            continue;
+        }
+        // Start of scan or end of image: all headers seen.
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let len = u16::from_be_bytes([data[at + 2], data[at + 3]]) as usize;
+        let payload = data.get(at + 4..at + 2 + len)?;
+        match marker {
+            // SOFn; C4, C8 and CC are DHT, JPG and DAC.
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                components = payload.get(5).copied();
+            }
+            0xEE if payload.starts_with(b"Adobe") => {
+                adobe_transform = Some(payload.get(11).copied().unwrap_or(0));
+            }
+            0xE2 if payload.starts_with(b"ICC_PROFILE\0") && payload.len() > 14 => {
+                icc_chunks.push((payload[12], &payload[14..]));
+            }
+            _ => {}
+        }
+        at += 2 + len;
+    }
+    if components != Some(4) {
+        return None;
+    }
+    icc_chunks.sort_by_key(|&(seq, _)| seq);
+    let icc_profile = (!icc_chunks.is_empty())
+        .then(|| icc_chunks.iter().flat_map(|&(_, c)| c).copied().collect());
+    Some(CmykSource {
+        kind: if adobe_transform == Some(2) {
+            CmykKind::Ycck
+        } else {
+            CmykKind::Cmyk
+        },
+        inverted: adobe_transform.is_some(),
+        icc_profile,
+    })
+}
+
+const TAG_PHOTOMETRIC: u16 = 262;
+const TAG_INK_SET: u16 = 332;
+const TAG_ICC_PROFILE: u16 = 34675;
+const PHOTOMETRIC_SEPARATED: u32 = 5;
+const INK_SET_NOT_CMYK: u32 = 2;
+
+fn detect_tiff(data: &[u8]) -> Option<CmykSource> {
+    let le = data[0] == b'I';
+    let u16_at = |at: usize| {
+        let b = data.get(at..at + 2)?;
+        Some(if le {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |at: usize| {
+        let b = data.get(at..at + 4)?;
+        Some(if le {
+            u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        } else {
+            u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+        })
+    };
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+    let mut photometric = None;
+    let mut ink_set = None;
+    let mut icc_profile = None;
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        let tag = u16_at(entry)?;
+        let kind = u16_at(entry + 2)?;
+        // SHORT values sit left-justified in the value field.
+        let value = match kind {
+            3 => u16_at(entry + 8)? as u32,
+            _ => u32_at(entry + 8)?,
+        };
+        match tag {
+            TAG_PHOTOMETRIC => photometric = Some(value),
+            TAG_INK_SET => ink_set = Some(value),
+            TAG_ICC_PROFILE => {
+                let len = u32_at(entry + 4)? as usize;
+                let start = if len <= 4 { entry + 8 } else { value as usize };
+                icc_profile = data.get(start..start.checked_add(len)?).map(<[u8]>::to_vec);
+            }
+            _ => {}
+        }
+    }
+    if photometric != Some(PHOTOMETRIC_SEPARATED) || ink_set == Some(INK_SET_NOT_CMYK) {
+        return None;
+    }
+    Some(CmykSource {
+        kind: CmykKind::Cmyk,
+        inverted: false,
+        icc_profile,
+    })
+}
+
+/// Convert a codec's CMYK output to sRGB RGB8.
+///
+/// Passes `output` through when the source is not CMYK or the codec already
+/// converted to RGB. Returns the ink values too when `keep_cmyk` is set.
+#[cfg(any(feature = "jpeg", feature = "tiff"))]
+pub(crate) fn convert_output(
+    output: DecodeOutput,
+    format: ImageFormat,
+    data: &[u8],
+    fallback_profile: Option<&[u8]>,
+    keep_cmyk: bool,
+) -> Result<(DecodeOutput, Option<DecodedCmyk>)> {
+    if !matches!(format, ImageFormat::Jpeg | ImageFormat::Tiff) {
+        return Ok((output, None));
+    }
+    let Some(source) = detect(data) else {
+        return Ok((output, None));
+    };
+    let pixels = output.pixels();
+    let descriptor = pixels.descriptor();
+    if descriptor.layout() != ChannelLayout::Rgba || descriptor.channel_type() != ChannelType::U8 {
+        return Ok((output, None));
+    }
+
+    let (width, height) = (pixels.width(), pixels.rows());
+    let row_bytes = width as usize * 4;
+    let bytes = pixels.as_strided_bytes();
+    let mut cmyk = Vec::with_capacity(row_bytes * height as usize);
+    for y in 0..height as usize {
+        cmyk.extend_from_slice(&bytes[y * pixels.stride()..][..row_bytes]);
+    }
+    if source.inverted {
+        cmyk.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+
+    let profile = source.icc_profile.as_deref().or(fallback_profile);
+    let rgb = cmyk_to_srgb(&cmyk, profile);
+    let rgb = PixelBuffer::from_vec(rgb, width, height, PixelDescriptor::RGB8_SRGB)
+        .map_err(|_| at!(CodecError::InvalidInput("CMYK: bad dimensions".into())))?;
+
+    let mut info = output.info().clone();
+    info.source_color.icc_profile = None;
+    info.source_color.cicp = Some(zenpixels::Cicp::SRGB);
+    info.has_alpha = false;
+
+    let decoded = keep_cmyk.then_some(DecodedCmyk {
+        width,
+        height,
+        data: cmyk,
+        source,
+    });
+    Ok((DecodeOutput::new(rgb, info), decoded))
+}
+
+/// Interleaved CMYK (0 = no ink) to sRGB RGB8.
+pub(crate) fn cmyk_to_srgb(cmyk: &[u8], profile: Option<&[u8]>) -> Vec<u8> {
+    #[cfg(feature = "cms")]
+    if let Some(rgb) = profile.and_then(|icc| crate::cms::cmyk_to_srgb8(cmyk, icc)) {
+        return rgb;
+    }
+    #[cfg(not(feature = "cms"))]
+    let _ = profile;
+    cmyk.chunks_exact(4)
+        .flat_map(|px| {
+            let white = 255 - px[3] as u32;
+            [0, 1, 2].map(|i| (((255 - px[i] as u32) * white + 127) / 255) as u8)
+        })
+        .collect()
+}
+
+/// Interleaved 16-bit CMYK (0 = no ink) to sRGB RGB16.
+#[cfg(feature = "psd")]
+#[cfg_attr(not(feature = "cms"), allow(unused_variables))]
+pub(crate) fn cmyk16_to_srgb(cmyk: &[u16], profile: Option<&[u8]>) -> Vec<u16> {
+    #[cfg(feature = "cms")]
+    if let Some(rgb) = profile.and_then(|icc| crate::cms::cmyk16_to_srgb16(cmyk, icc)) {
+        return rgb;
+    }
+    cmyk.chunks_exact(4)
+        .flat_map(|px| {
+            let white = 65535 - px[3] as u32;
+            [0, 1, 2].map(|i| (((65535 - px[i] as u32) * white + 32767) / 65535) as u16)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0xFF, marker];
+        out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn jpeg(components: u8, adobe_transform: Option<u8>, icc: &[&[u8]]) -> Vec<u8> {
+        let mut out = vec![0xFF, 0xD8];
+        for (i, chunk) in icc.iter().enumerate().rev() {
+            let mut payload = b"ICC_PROFILE\0".to_vec();
+            payload.extend_from_slice(&[i as u8 + 1, icc.len() as u8]);
+            payload.extend_from_slice(chunk);
+            out.extend(segment(0xE2, &payload));
+        }
+        if let Some(transform) = adobe_transform {
+            let mut payload = b"Adobe".to_vec();
+            payload.extend_from_slice(&[0, 100, 0, 0, 0, 0, transform]);
+            out.extend(segment(0xEE, &payload));
+        }
+        let mut sof = vec![8, 0, 1, 0, 1, components];
+        for id in 1..=components {
+            sof.extend_from_slice(&[id, 0x11, 0]);
+        }
+        out.extend(segment(0xC0, &sof));
+        out.extend([0xFF, 0xDA, 0, 2, 0xFF, 0xD9]);
+        out
+    }
+
+    #[test]
+    fn jpeg_cmyk_ycck_and_rgb() {
+        let plain = detect(&jpeg(4, None, &[])).unwrap();
+        assert_eq!(plain.kind, CmykKind::Cmyk);
+        assert!(!plain.inverted);
+
+        let ycck = detect(&jpeg(4, Some(2), &[b"ab", b"cd"])).unwrap();
+        assert_eq!(ycck.kind, CmykKind::Ycck);
+        assert!(ycck.inverted);
+        assert_eq!(ycck.icc_profile.as_deref(), Some(&b"abcd"[..]));
+
+        assert!(detect(&jpeg(4, Some(0), &[])).unwrap().inverted);
+        assert!(detect(&jpeg(3, Some(1), &[])).is_none());
+    }
+
+    #[test]
+    fn tiff_separated_with_profile() {
+        // Big-endian IFD: PhotometricInterpretation = 5, ICC profile of
+        // 6 bytes stored after the IFD.
+        let mut data = b"MM\0*".to_vec();
+        data.extend_from_slice(&8u32.to_be_bytes());
+        data.extend_from_slice(&2u16.to_be_bytes());
+        data.extend_from_slice(&262u16.to_be_bytes());
+        data.extend_from_slice(&3u16.to_be_bytes());
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(&[0, 5, 0, 0]);
+        data.extend_from_slice(&34675u16.to_be_bytes());
+        data.extend_from_slice(&7u16.to_be_bytes());
+        data.extend_from_slice(&6u32.to_be_bytes());
+        data.extend_from_slice(&38u32.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        data.extend_from_slice(b"cmyk!!");
+
+        let source = detect(&data).unwrap();
+        assert_eq!(source.kind, CmykKind::Cmyk);
+        assert!(!source.inverted);
+        assert_eq!(source.icc_profile.as_deref(), Some(&b"cmyk!!"[..]));
+
+        data[19] = 2; // RGB
+        assert!(detect(&data).is_none());
+    }
+
+    #[test]
+    fn naive_conversion_and_planes() {
+        let cmyk = [0, 255, 255, 0, 0, 0, 0, 255, 0, 0, 0, 0];
+        assert_eq!(
+            cmyk_to_srgb(&cmyk, None),
+            [255, 0, 0, 0, 0, 0, 255, 255, 255]
+        );
+
+        let decoded = DecodedCmyk {
+            width: 3,
+            height: 1,
+            data: cmyk.to_vec(),
+            source: CmykSource {
+                kind: CmykKind::Cmyk,
+                inverted: false,
+                icc_profile: None,
+            },
+        };
+        assert_eq!(decoded.plane(3), [0, 255, 0]);
+    }
+
+    #[cfg(feature = "cms")]
+    #[test]
+    fn non_cmyk_profile_falls_back_to_naive() {
+        let srgb = crate::cms::srgb_icc_profile();
+        assert_eq!(cmyk_to_srgb(&[0, 0, 0, 255], Some(&srgb)), [0, 0, 0]);
+    }
+}
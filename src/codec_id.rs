@@ -71,6 +71,18 @@ pub enum CodecId {
     FarbfeldDecode,
     /// zenbitmaps Farbfeld encoder
     FarbfeldEncode,
+    /// zenbitmaps QOI decoder
+    QoiDecode,
+    /// zenbitmaps QOI encoder
+    QoiEncode,
+    /// zenbitmaps TGA decoder
+    TgaDecode,
+    /// zenbitmaps TGA encoder
+    TgaEncode,
+    /// zenbitmaps Radiance HDR decoder
+    HdrDecode,
+    /// zenbitmaps Radiance HDR encoder
+    HdrEncode,
 
     // TIFF
     /// zentiff decoder
@@ -78,11 +90,95 @@ pub enum CodecId {
     /// zentiff encoder
     TiffEncode,
 
+    // OpenEXR
+    /// exr crate decoder
+    ExrDecode,
+    /// exr crate encoder
+    ExrEncode,
+
+    // ICO/CUR
+    /// ICO/CUR container decoder (PNG/BMP entries)
+    IcoDecode,
+    /// ICO container encoder (PNG entries)
+    IcoEncode,
+
+    // PSD/PSB
+    /// PSD/PSB flattened composite decoder
+    PsdDecode,
+
+    // DDS / KTX2
+    /// DDS texture decoder (uncompressed, BC1–BC7)
+    DdsDecode,
+    /// DDS texture encoder (RGBA8, BC1–BC5, BC7)
+    DdsEncode,
+    /// KTX2 texture decoder (uncompressed, BC1–BC7)
+    Ktx2Decode,
+    /// KTX2 texture encoder (RGBA8, BC1–BC5, BC7)
+    Ktx2Encode,
+
+    // JPEG 2000
+    /// JPEG 2000 (JP2/J2K) decoder
+    Jp2Decode,
+
     /// Third-party or dynamically registered codec.
     Custom(&'static str),
 }
 
 impl CodecId {
+    /// The built-in decoder for a format, if there is one.
+    pub fn decoder_for(format: ImageFormat) -> Option<Self> {
+        Some(match format {
+            ImageFormat::Jpeg => Self::ZenjpegDecode,
+            ImageFormat::WebP => Self::ZenwebpDecode,
+            ImageFormat::Gif => Self::ZengifDecode,
+            ImageFormat::Png => Self::PngDecode,
+            ImageFormat::Avif => Self::ZenavifDecode,
+            ImageFormat::Jxl => Self::ZenjxlDecode,
+            ImageFormat::Heic => Self::HeicDecode,
+            ImageFormat::Pnm => Self::PnmDecode,
+            ImageFormat::Bmp => Self::BmpDecode,
+            ImageFormat::Farbfeld => Self::FarbfeldDecode,
+            ImageFormat::Qoi => Self::QoiDecode,
+            ImageFormat::Tga => Self::TgaDecode,
+            ImageFormat::Hdr => Self::HdrDecode,
+            ImageFormat::Tiff => Self::TiffDecode,
+            ImageFormat::Custom(def) if def.name == "dng" || def.name == "raw" => {
+                Self::ZenrawDecode
+            }
+            ImageFormat::Custom(def) if def.name == "exr" => Self::ExrDecode,
+            ImageFormat::Custom(def) if def.name == "ico" => Self::IcoDecode,
+            ImageFormat::Custom(def) if def.name == "psd" => Self::PsdDecode,
+            ImageFormat::Custom(def) if def.name == "dds" => Self::DdsDecode,
+            ImageFormat::Custom(def) if def.name == "ktx2" => Self::Ktx2Decode,
+            ImageFormat::Custom(def) if def.name == "jp2" => Self::Jp2Decode,
+            _ => return None,
+        })
+    }
+
+    /// The built-in encoder for a format, if there is one.
+    pub fn encoder_for(format: ImageFormat) -> Option<Self> {
+        Some(match format {
+            ImageFormat::Jpeg => Self::ZenjpegEncode,
+            ImageFormat::WebP => Self::ZenwebpEncode,
+            ImageFormat::Gif => Self::ZengifEncode,
+            ImageFormat::Png => Self::PngEncode,
+            ImageFormat::Avif => Self::RavifEncode,
+            ImageFormat::Jxl => Self::JxlEncoderEncode,
+            ImageFormat::Pnm => Self::PnmEncode,
+            ImageFormat::Bmp => Self::BmpEncode,
+            ImageFormat::Farbfeld => Self::FarbfeldEncode,
+            ImageFormat::Qoi => Self::QoiEncode,
+            ImageFormat::Tga => Self::TgaEncode,
+            ImageFormat::Hdr => Self::HdrEncode,
+            ImageFormat::Tiff => Self::TiffEncode,
+            ImageFormat::Custom(def) if def.name == "exr" => Self::ExrEncode,
+            ImageFormat::Custom(def) if def.name == "ico" => Self::IcoEncode,
+            ImageFormat::Custom(def) if def.name == "dds" => Self::DdsEncode,
+            ImageFormat::Custom(def) if def.name == "ktx2" => Self::Ktx2Encode,
+            _ => return None,
+        })
+    }
+
     /// The image format this codec handles.
     pub fn format(&self) -> ImageFormat {
         match self {
@@ -93,13 +189,43 @@ impl CodecId {
             Self::ZenavifDecode | Self::RavifEncode => ImageFormat::Avif,
             Self::ZenjxlDecode | Self::JxlEncoderEncode => ImageFormat::Jxl,
             Self::HeicDecode => ImageFormat::Heic,
-            // ZenrawDecode uses Custom format; return Unknown since there's no
-            // built-in RAW variant — callers should use CodecId-level matching instead.
+            // RAW/DNG is a zenraw Custom format; DNG stands for both. Without
+            // zenraw there's no definition to return.
+            #[cfg(feature = "raw-decode")]
+            Self::ZenrawDecode => crate::codecs::raw::dng_format(),
+            #[cfg(not(feature = "raw-decode"))]
             Self::ZenrawDecode => ImageFormat::Unknown,
             Self::PnmDecode | Self::PnmEncode => ImageFormat::Pnm,
             Self::BmpDecode | Self::BmpEncode => ImageFormat::Bmp,
             Self::FarbfeldDecode | Self::FarbfeldEncode => ImageFormat::Farbfeld,
+            Self::QoiDecode | Self::QoiEncode => ImageFormat::Qoi,
+            Self::TgaDecode | Self::TgaEncode => ImageFormat::Tga,
+            Self::HdrDecode | Self::HdrEncode => ImageFormat::Hdr,
             Self::TiffDecode | Self::TiffEncode => ImageFormat::Tiff,
+            #[cfg(feature = "exr")]
+            Self::ExrDecode | Self::ExrEncode => crate::exr::format(),
+            #[cfg(not(feature = "exr"))]
+            Self::ExrDecode | Self::ExrEncode => ImageFormat::Unknown,
+            #[cfg(feature = "ico")]
+            Self::IcoDecode | Self::IcoEncode => crate::ico::format(),
+            #[cfg(not(feature = "ico"))]
+            Self::IcoDecode | Self::IcoEncode => ImageFormat::Unknown,
+            #[cfg(feature = "psd")]
+            Self::PsdDecode => crate::psd::format(),
+            #[cfg(not(feature = "psd"))]
+            Self::PsdDecode => ImageFormat::Unknown,
+            #[cfg(feature = "dds")]
+            Self::DdsDecode | Self::DdsEncode => crate::dds::format(),
+            #[cfg(not(feature = "dds"))]
+            Self::DdsDecode | Self::DdsEncode => ImageFormat::Unknown,
+            #[cfg(feature = "ktx2")]
+            Self::Ktx2Decode | Self::Ktx2Encode => crate::ktx2::format(),
+            #[cfg(not(feature = "ktx2"))]
+            Self::Ktx2Decode | Self::Ktx2Encode => ImageFormat::Unknown,
+            #[cfg(feature = "jp2-decode")]
+            Self::Jp2Decode => crate::jp2::format(),
+            #[cfg(not(feature = "jp2-decode"))]
+            Self::Jp2Decode => ImageFormat::Unknown,
             // Custom codecs: caller is responsible for correct format association.
             // We return Jpeg as a fallback but this should never be relied upon.
             Self::Custom(_) => ImageFormat::Jpeg, // TODO: Custom needs format stored
@@ -121,7 +247,16 @@ impl CodecId {
                 | Self::PnmDecode
                 | Self::BmpDecode
                 | Self::FarbfeldDecode
+                | Self::QoiDecode
+                | Self::TgaDecode
+                | Self::HdrDecode
                 | Self::TiffDecode
+                | Self::ExrDecode
+                | Self::IcoDecode
+                | Self::PsdDecode
+                | Self::DdsDecode
+                | Self::Ktx2Decode
+                | Self::Jp2Decode
         )
     }
 
@@ -138,7 +273,14 @@ impl CodecId {
                 | Self::PnmEncode
                 | Self::BmpEncode
                 | Self::FarbfeldEncode
+                | Self::QoiEncode
+                | Self::TgaEncode
+                | Self::HdrEncode
                 | Self::TiffEncode
+                | Self::ExrEncode
+                | Self::IcoEncode
+                | Self::DdsEncode
+                | Self::Ktx2Encode
         )
     }
 
@@ -165,8 +307,24 @@ impl CodecId {
             Self::BmpEncode => "zenbitmaps-bmp (encode)",
             Self::FarbfeldDecode => "zenbitmaps-farbfeld (decode)",
             Self::FarbfeldEncode => "zenbitmaps-farbfeld (encode)",
+            Self::QoiDecode => "zenbitmaps-qoi (decode)",
+            Self::QoiEncode => "zenbitmaps-qoi (encode)",
+            Self::TgaDecode => "zenbitmaps-tga (decode)",
+            Self::TgaEncode => "zenbitmaps-tga (encode)",
+            Self::HdrDecode => "zenbitmaps-hdr (decode)",
+            Self::HdrEncode => "zenbitmaps-hdr (encode)",
             Self::TiffDecode => "zentiff (decode)",
             Self::TiffEncode => "zentiff (encode)",
+            Self::ExrDecode => "exr (decode)",
+            Self::ExrEncode => "exr (encode)",
+            Self::IcoDecode => "ico (decode)",
+            Self::IcoEncode => "ico (encode)",
+            Self::PsdDecode => "psd (decode)",
+            Self::DdsDecode => "dds (decode)",
+            Self::DdsEncode => "dds (encode)",
+            Self::Ktx2Decode => "ktx2 (decode)",
+            Self::Ktx2Encode => "ktx2 (encode)",
+            Self::Jp2Decode => "jp2 (decode)",
             Self::Custom(name) => name,
         }
     }
@@ -192,6 +350,25 @@ mod tests {
         assert_eq!(CodecId::RavifEncode.format(), ImageFormat::Avif);
     }
 
+    #[test]
+    fn format_lookup_round_trips() {
+        for format in crate::FormatSet::all().iter() {
+            let decoder = CodecId::decoder_for(format).expect("every known format decodes");
+            assert!(decoder.is_decoder());
+            assert_eq!(decoder.format(), format, "{decoder}");
+            if let Some(encoder) = CodecId::encoder_for(format) {
+                assert!(encoder.is_encoder());
+                assert_eq!(encoder.format(), format, "{encoder}");
+            }
+        }
+        // No pure-Rust HEVC encoder exists.
+        assert_eq!(CodecId::encoder_for(ImageFormat::Heic), None);
+        assert_eq!(
+            CodecId::decoder_for(ImageFormat::Qoi),
+            Some(CodecId::QoiDecode)
+        );
+    }
+
     #[test]
     fn custom_codec_id() {
         let custom = CodecId::Custom("my-codec");
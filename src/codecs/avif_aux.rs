@@ -0,0 +1,566 @@
+//! Box-level editing of zenavif output: adds the auxiliary depth item that
+//! zenavif's encoder doesn't write itself.
+//!
+//! Only `meta` and the `mdat` payload change. The depth bitstream is
+//! appended to `mdat`, its properties are copied from a single-image AVIF
+//! encode of the depth map, and `iloc` is rewritten with 32-bit absolute
+//! offsets shifted past the grown `meta`.
+
+use alloc::vec::Vec;
+
+use crate::CodecError;
+use crate::error::Result;
+use whereat::at;
+
+/// `auxC` type for depth auxiliary images (MPEG-B Part 23).
+const DEPTH_URN: &[u8] = b"urn:mpeg:mpegB:cicp:systems:auxiliary:depth";
+
+fn invalid(detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!(
+        "AVIF depth item: {detail}"
+    )))
+}
+
+/// One box: type, absolute byte range, and payload (after the header).
+#[derive(Clone, Copy)]
+struct Bmff<'a> {
+    typ: [u8; 4],
+    start: usize,
+    end: usize,
+    payload: &'a [u8],
+}
+
+impl Bmff<'_> {
+    fn raw<'d>(&self, file: &'d [u8]) -> &'d [u8] {
+        &file[self.start..self.end]
+    }
+}
+
+/// Split `data[from..to]` into boxes. Offsets stay absolute to `data`.
+fn children(data: &[u8], from: usize, to: usize) -> Result<Vec<Bmff<'_>>> {
+    let mut out = Vec::new();
+    let mut pos = from;
+    while pos < to {
+        if to - pos < 8 {
+            return Err(invalid("truncated box header"));
+        }
+        let size32 = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as u64;
+        let typ: [u8; 4] = data[pos + 4..pos + 8].try_into().unwrap();
+        let (size, header) = match size32 {
+            0 => ((to - pos) as u64, 8),
+            1 => {
+                if to - pos < 16 {
+                    return Err(invalid("truncated largesize header"));
+                }
+                (
+                    u64::from_be_bytes(data[pos + 8..pos + 16].try_into().unwrap()),
+                    16,
+                )
+            }
+            n => (n, 8),
+        };
+        if size < header as u64 || size > (to - pos) as u64 {
+            return Err(invalid("box size out of range"));
+        }
+        let end = pos + size as usize;
+        out.push(Bmff {
+            typ,
+            start: pos,
+            end,
+            payload: &data[pos + header..end],
+        });
+        pos = end;
+    }
+    Ok(out)
+}
+
+fn find<'a>(boxes: &[Bmff<'a>], typ: &[u8; 4]) -> Option<Bmff<'a>> {
+    boxes.iter().copied().find(|b| &b.typ == typ)
+}
+
+/// Big-endian reader over a box payload.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| invalid("truncated box"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// Unsigned big-endian integer of `n` bytes (0, 1, 2, 4 or 8).
+    fn uint(&mut self, n: usize) -> Result<u64> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b)))
+    }
+
+    /// FullBox header: (version, flags).
+    fn full_box(&mut self) -> Result<(u8, u32)> {
+        let v = self.uint(4)? as u32;
+        Ok(((v >> 24) as u8, v & 0x00FF_FFFF))
+    }
+}
+
+fn push_box(out: &mut Vec<u8>, typ: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(&((payload.len() + 8) as u32).to_be_bytes());
+    out.extend_from_slice(typ);
+    out.extend_from_slice(payload);
+}
+
+fn full_box_header(version: u8, flags: u32) -> [u8; 4] {
+    ((u32::from(version) << 24) | flags).to_be_bytes()
+}
+
+#[derive(Clone)]
+struct IlocItem {
+    id: u32,
+    method: u16,
+    data_reference: u16,
+    /// (offset, length), with the base offset already added.
+    extents: Vec<(u64, u64)>,
+}
+
+fn parse_iloc(payload: &[u8]) -> Result<Vec<IlocItem>> {
+    let mut r = Reader::new(payload);
+    let (version, _) = r.full_box()?;
+    if version > 2 {
+        return Err(invalid("unsupported iloc version"));
+    }
+    let sizes = r.uint(2)? as u16;
+    let offset_size = usize::from(sizes >> 12);
+    let length_size = usize::from((sizes >> 8) & 0xF);
+    let base_offset_size = usize::from((sizes >> 4) & 0xF);
+    let index_size = if version >= 1 {
+        usize::from(sizes & 0xF)
+    } else {
+        0
+    };
+    let count = if version < 2 { r.uint(2)? } else { r.uint(4)? };
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let id = if version < 2 { r.uint(2)? } else { r.uint(4)? } as u32;
+        let method = if version >= 1 {
+            (r.uint(2)? & 0xF) as u16
+        } else {
+            0
+        };
+        let data_reference = r.uint(2)? as u16;
+        let base = r.uint(base_offset_size)?;
+        let extent_count = r.uint(2)?;
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            if r.uint(index_size)? != 0 {
+                return Err(invalid("iloc extent indices are not supported"));
+            }
+            let offset = r.uint(offset_size)?;
+            let length = r.uint(length_size)?;
+            extents.push((base + offset, length));
+        }
+        items.push(IlocItem {
+            id,
+            method,
+            data_reference,
+            extents,
+        });
+    }
+    Ok(items)
+}
+
+/// `iloc` with 32-bit offsets and lengths and no base offsets.
+fn write_iloc(items: &[IlocItem]) -> Result<Vec<u8>> {
+    let wide = items.iter().any(|i| i.id > 0xFFFF);
+    let version = if wide { 2 } else { 1 };
+    let mut p = Vec::new();
+    p.extend_from_slice(&full_box_header(version, 0));
+    p.extend_from_slice(&[0x44, 0x00]);
+    if wide {
+        p.extend_from_slice(&(items.len() as u32).to_be_bytes());
+    } else {
+        p.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    }
+    for item in items {
+        if wide {
+            p.extend_from_slice(&item.id.to_be_bytes());
+        } else {
+            p.extend_from_slice(&(item.id as u16).to_be_bytes());
+        }
+        p.extend_from_slice(&item.method.to_be_bytes());
+        p.extend_from_slice(&item.data_reference.to_be_bytes());
+        p.extend_from_slice(&(item.extents.len() as u16).to_be_bytes());
+        for &(offset, length) in &item.extents {
+            let offset = u32::try_from(offset).map_err(|_| invalid("offset exceeds 4 GiB"))?;
+            let length = u32::try_from(length).map_err(|_| invalid("extent exceeds 4 GiB"))?;
+            p.extend_from_slice(&offset.to_be_bytes());
+            p.extend_from_slice(&length.to_be_bytes());
+        }
+    }
+    let mut out = Vec::new();
+    push_box(&mut out, b"iloc", &p);
+    Ok(out)
+}
+
+/// `ipma` entries: item ID → (essential, 1-based `ipco` index).
+type Associations = Vec<(u32, Vec<(bool, u16)>)>;
+
+fn parse_ipma(payload: &[u8]) -> Result<Associations> {
+    let mut r = Reader::new(payload);
+    let (version, flags) = r.full_box()?;
+    let count = r.uint(4)?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let id = if version < 1 { r.uint(2)? } else { r.uint(4)? } as u32;
+        let n = r.uint(1)?;
+        let mut props = Vec::new();
+        for _ in 0..n {
+            if flags & 1 != 0 {
+                let v = r.uint(2)? as u16;
+                props.push((v & 0x8000 != 0, v & 0x7FFF));
+            } else {
+                let v = r.uint(1)? as u16;
+                props.push((v & 0x80 != 0, v & 0x7F));
+            }
+        }
+        entries.push((id, props));
+    }
+    Ok(entries)
+}
+
+fn write_ipma(entries: &Associations) -> Vec<u8> {
+    let wide_ids = entries.iter().any(|(id, _)| *id > 0xFFFF);
+    let wide_props = entries
+        .iter()
+        .flat_map(|(_, props)| props)
+        .any(|&(_, index)| index > 0x7F);
+    let mut p = Vec::new();
+    p.extend_from_slice(&full_box_header(u8::from(wide_ids), u32::from(wide_props)));
+    p.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    for (id, props) in entries {
+        if wide_ids {
+            p.extend_from_slice(&id.to_be_bytes());
+        } else {
+            p.extend_from_slice(&(*id as u16).to_be_bytes());
+        }
+        p.push(props.len() as u8);
+        for &(essential, index) in props {
+            if wide_props {
+                p.extend_from_slice(&(index | if essential { 0x8000 } else { 0 }).to_be_bytes());
+            } else {
+                p.push(index as u8 | if essential { 0x80 } else { 0 });
+            }
+        }
+    }
+    let mut out = Vec::new();
+    push_box(&mut out, b"ipma", &p);
+    out
+}
+
+/// Item ID from an `infe` payload.
+fn infe_id(payload: &[u8]) -> Result<u32> {
+    let mut r = Reader::new(payload);
+    let (version, _) = r.full_box()?;
+    Ok(if version >= 3 { r.uint(4)? } else { r.uint(2)? } as u32)
+}
+
+fn pitm_id(payload: &[u8]) -> Result<u32> {
+    let mut r = Reader::new(payload);
+    let (version, _) = r.full_box()?;
+    Ok(if version == 0 { r.uint(2)? } else { r.uint(4)? } as u32)
+}
+
+/// `meta` children plus the file's top-level `meta` box.
+fn meta_children(file: &[u8]) -> Result<(Bmff<'_>, Vec<Bmff<'_>>)> {
+    let top = children(file, 0, file.len())?;
+    let meta = find(&top, b"meta").ok_or_else(|| invalid("no meta box"))?;
+    let payload_start = meta.end - meta.payload.len();
+    if meta.payload.len() < 4 {
+        return Err(invalid("truncated meta box"));
+    }
+    Ok((meta, children(file, payload_start + 4, meta.end)?))
+}
+
+/// Raw property boxes with their essential flags.
+type Properties = Vec<(bool, Vec<u8>)>;
+
+/// The primary image of a single-image AVIF: its bitstream and its
+/// properties.
+fn primary_image(file: &[u8]) -> Result<(Vec<u8>, Properties)> {
+    let (_, meta) = meta_children(file)?;
+    let id = pitm_id(
+        find(&meta, b"pitm")
+            .ok_or_else(|| invalid("no pitm"))?
+            .payload,
+    )?;
+    let iloc = parse_iloc(
+        find(&meta, b"iloc")
+            .ok_or_else(|| invalid("no iloc"))?
+            .payload,
+    )?;
+    let item = iloc
+        .iter()
+        .find(|i| i.id == id)
+        .ok_or_else(|| invalid("primary item has no location"))?;
+    if item.method != 0 {
+        return Err(invalid("primary item is not stored in mdat"));
+    }
+    let mut bitstream = Vec::new();
+    for &(offset, length) in &item.extents {
+        let range = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(length).ok())
+            .and_then(|(o, l)| Some(o..o.checked_add(l)?))
+            .filter(|r| r.end <= file.len())
+            .ok_or_else(|| invalid("primary extent out of range"))?;
+        bitstream.extend_from_slice(&file[range]);
+    }
+
+    let iprp = find(&meta, b"iprp").ok_or_else(|| invalid("no iprp"))?;
+    let iprp_children = children(file, iprp.end - iprp.payload.len(), iprp.end)?;
+    let ipco = find(&iprp_children, b"ipco").ok_or_else(|| invalid("no ipco"))?;
+    let ipco_children = children(file, ipco.end - ipco.payload.len(), ipco.end)?;
+    let ipma = parse_ipma(
+        find(&iprp_children, b"ipma")
+            .ok_or_else(|| invalid("no ipma"))?
+            .payload,
+    )?;
+    let mut properties = Vec::new();
+    for &(essential, index) in ipma
+        .iter()
+        .find(|(item, _)| *item == id)
+        .map(|(_, props)| props.as_slice())
+        .unwrap_or_default()
+    {
+        let prop = ipco_children
+            .get(usize::from(index).wrapping_sub(1))
+            .ok_or_else(|| invalid("property index out of range"))?;
+        properties.push((essential, prop.raw(file).to_vec()));
+    }
+    Ok((bitstream, properties))
+}
+
+/// Add the primary image of `depth_avif` to `base` as an auxiliary depth
+/// item of `base`'s primary image (`auxl` reference, `auxC` depth URN).
+pub(crate) fn add_depth_item(base: &[u8], depth_avif: &[u8]) -> Result<Vec<u8>> {
+    let (bitstream, mut properties) = primary_image(depth_avif)?;
+    let mut auxc = Vec::new();
+    let mut urn = full_box_header(0, 0).to_vec();
+    urn.extend_from_slice(DEPTH_URN);
+    urn.push(0);
+    push_box(&mut auxc, b"auxC", &urn);
+    properties.push((false, auxc));
+
+    let top = children(base, 0, base.len())?;
+    let (meta, meta_boxes) = meta_children(base)?;
+    let mdat = find(&top, b"mdat").ok_or_else(|| invalid("no mdat box"))?;
+    if mdat.start < meta.end {
+        return Err(invalid("mdat precedes meta"));
+    }
+
+    let primary = pitm_id(
+        find(&meta_boxes, b"pitm")
+            .ok_or_else(|| invalid("no pitm"))?
+            .payload,
+    )?;
+    let iloc = parse_iloc(
+        find(&meta_boxes, b"iloc")
+            .ok_or_else(|| invalid("no iloc"))?
+            .payload,
+    )?;
+
+    // New item ID: one past every ID in iinf and iloc.
+    let iinf = find(&meta_boxes, b"iinf").ok_or_else(|| invalid("no iinf"))?;
+    let mut r = Reader::new(iinf.payload);
+    let (iinf_version, _) = r.full_box()?;
+    let iinf_count = if iinf_version == 0 {
+        r.uint(2)?
+    } else {
+        r.uint(4)?
+    };
+    let iinf_header = r.pos;
+    let infes = children(base, iinf.end - iinf.payload.len() + iinf_header, iinf.end)?;
+    let mut max_id = iloc.iter().map(|i| i.id).max().unwrap_or(0);
+    for infe in &infes {
+        max_id = max_id.max(infe_id(infe.payload)?);
+    }
+    let depth_id = max_id
+        .checked_add(1)
+        .ok_or_else(|| invalid("no free item ID"))?;
+
+    // iinf + infe (version 2, or 3 for 32-bit IDs)
+    let mut infe = Vec::new();
+    if depth_id > 0xFFFF {
+        infe.extend_from_slice(&full_box_header(3, 0));
+        infe.extend_from_slice(&depth_id.to_be_bytes());
+    } else {
+        infe.extend_from_slice(&full_box_header(2, 0));
+        infe.extend_from_slice(&(depth_id as u16).to_be_bytes());
+    }
+    infe.extend_from_slice(&0u16.to_be_bytes());
+    infe.extend_from_slice(b"av01");
+    infe.push(0);
+    let mut iinf_payload = full_box_header(iinf_version, 0).to_vec();
+    if iinf_version == 0 {
+        let count = u16::try_from(iinf_count + 1).map_err(|_| invalid("too many items"))?;
+        iinf_payload.extend_from_slice(&count.to_be_bytes());
+    } else {
+        iinf_payload.extend_from_slice(&((iinf_count + 1) as u32).to_be_bytes());
+    }
+    iinf_payload.extend_from_slice(&iinf.payload[iinf_header..]);
+    push_box(&mut iinf_payload, b"infe", &infe);
+    let mut new_iinf = Vec::new();
+    push_box(&mut new_iinf, b"iinf", &iinf_payload);
+
+    // iref + auxl
+    let iref = find(&meta_boxes, b"iref");
+    let iref_version = match iref {
+        Some(b) => Reader::new(b.payload).full_box()?.0,
+        None => 0,
+    };
+    let mut auxl = Vec::new();
+    if iref_version == 0 {
+        if depth_id > 0xFFFF || primary > 0xFFFF {
+            return Err(invalid("item IDs exceed 16-bit iref"));
+        }
+        auxl.extend_from_slice(&(depth_id as u16).to_be_bytes());
+        auxl.extend_from_slice(&1u16.to_be_bytes());
+        auxl.extend_from_slice(&(primary as u16).to_be_bytes());
+    } else {
+        auxl.extend_from_slice(&depth_id.to_be_bytes());
+        auxl.extend_from_slice(&1u16.to_be_bytes());
+        auxl.extend_from_slice(&primary.to_be_bytes());
+    }
+    let mut iref_payload = match iref {
+        Some(b) => b.payload.to_vec(),
+        None => full_box_header(0, 0).to_vec(),
+    };
+    push_box(&mut iref_payload, b"auxl", &auxl);
+    let mut new_iref = Vec::new();
+    push_box(&mut new_iref, b"iref", &iref_payload);
+
+    // iprp: properties appended to ipco, a new ipma entry
+    let iprp = find(&meta_boxes, b"iprp").ok_or_else(|| invalid("no iprp"))?;
+    let iprp_boxes = children(base, iprp.end - iprp.payload.len(), iprp.end)?;
+    let ipco = find(&iprp_boxes, b"ipco").ok_or_else(|| invalid("no ipco"))?;
+    let existing = children(base, ipco.end - ipco.payload.len(), ipco.end)?.len();
+    let mut ipco_payload = ipco.payload.to_vec();
+    let mut associations = Vec::new();
+    for (i, (essential, raw)) in properties.iter().enumerate() {
+        ipco_payload.extend_from_slice(raw);
+        let index = u16::try_from(existing + i + 1)
+            .ok()
+            .filter(|&i| i <= 0x7FFF)
+            .ok_or_else(|| invalid("too many properties"))?;
+        associations.push((*essential, index));
+    }
+    let mut ipma = parse_ipma(
+        find(&iprp_boxes, b"ipma")
+            .ok_or_else(|| invalid("no ipma"))?
+            .payload,
+    )?;
+    ipma.push((depth_id, associations));
+    let mut iprp_payload = Vec::new();
+    for b in &iprp_boxes {
+        match &b.typ {
+            b"ipco" => push_box(&mut iprp_payload, b"ipco", &ipco_payload),
+            b"ipma" => iprp_payload.extend_from_slice(&write_ipma(&ipma)),
+            _ => iprp_payload.extend_from_slice(b.raw(base)),
+        }
+    }
+    let mut new_iprp = Vec::new();
+    push_box(&mut new_iprp, b"iprp", &iprp_payload);
+
+    // mdat grows by the depth bitstream, appended at its end.
+    let old_mdat_end = mdat.end;
+    let mdat_header = mdat.end - mdat.start - mdat.payload.len();
+    let new_mdat_size = (mdat.end - mdat.start + bitstream.len()) as u64;
+    let mut new_mdat_header = Vec::new();
+    match mdat_header {
+        16 => {
+            new_mdat_header.extend_from_slice(&1u32.to_be_bytes());
+            new_mdat_header.extend_from_slice(b"mdat");
+            new_mdat_header.extend_from_slice(&new_mdat_size.to_be_bytes());
+        }
+        _ => {
+            let size = u32::try_from(new_mdat_size).map_err(|_| invalid("mdat exceeds 4 GiB"))?;
+            new_mdat_header.extend_from_slice(&size.to_be_bytes());
+            new_mdat_header.extend_from_slice(b"mdat");
+        }
+    }
+
+    // Build meta twice: its size is independent of the offsets in iloc,
+    // so the first pass measures how far everything after it moves.
+    let build_meta = |shift: i64| -> Result<Vec<u8>> {
+        let moved = |offset: u64| -> u64 {
+            let mut o = offset as i64;
+            if offset >= meta.end as u64 {
+                o += shift;
+            }
+            if offset >= old_mdat_end as u64 {
+                o += bitstream.len() as i64;
+            }
+            o as u64
+        };
+        let mut items: Vec<IlocItem> = iloc
+            .iter()
+            .map(|item| IlocItem {
+                extents: if item.method == 0 {
+                    item.extents.iter().map(|&(o, l)| (moved(o), l)).collect()
+                } else {
+                    item.extents.clone()
+                },
+                ..item.clone()
+            })
+            .collect();
+        items.push(IlocItem {
+            id: depth_id,
+            method: 0,
+            data_reference: 0,
+            extents: alloc::vec![((old_mdat_end as i64 + shift) as u64, bitstream.len() as u64)],
+        });
+
+        let mut payload = meta.payload[..4].to_vec();
+        for b in &meta_boxes {
+            match &b.typ {
+                b"iinf" => payload.extend_from_slice(&new_iinf),
+                b"iloc" => payload.extend_from_slice(&write_iloc(&items)?),
+                b"iref" => payload.extend_from_slice(&new_iref),
+                b"iprp" => payload.extend_from_slice(&new_iprp),
+                _ => payload.extend_from_slice(b.raw(base)),
+            }
+        }
+        if iref.is_none() {
+            payload.extend_from_slice(&new_iref);
+        }
+        let mut out = Vec::new();
+        push_box(&mut out, b"meta", &payload);
+        Ok(out)
+    };
+    let probe = build_meta(0)?;
+    let shift = probe.len() as i64 - (meta.end - meta.start) as i64;
+    let new_meta = build_meta(shift)?;
+
+    let mut out = Vec::with_capacity(base.len() + new_meta.len() + bitstream.len());
+    for b in &top {
+        match &b.typ {
+            b"meta" => out.extend_from_slice(&new_meta),
+            b"mdat" => {
+                out.extend_from_slice(&new_mdat_header);
+                out.extend_from_slice(b.payload);
+                out.extend_from_slice(&bitstream);
+            }
+            _ => out.extend_from_slice(b.raw(base)),
+        }
+    }
+    Ok(out)
+}
@@ -83,34 +83,18 @@ pub(crate) fn encode_with_precomputed_gainmap(
     stop: Option<&zencodec::StopToken>,
 ) -> crate::error::Result<crate::EncodeOutput> {
     use crate::{CodecError, ImageFormat};
-    use whereat::{ResultAtExt, at, at_crate};
+    use whereat::{ResultAtExt, at_crate};
 
     // Step 1: Encode gain map pixels as a small AVIF to get AV1 bytes
-    let gm_enc = zenavif::EncoderConfig::new(); // Default quality for gain map
-
     let gm_av1_data = if gain_map.channels == 1 {
-        // Grayscale gain map — encode as grayscale AVIF
-        // Convert to RGB (ravif doesn't have a direct gray path in the simple API)
-        let rgb_pixels: alloc::vec::Vec<rgb::Rgb<u8>> = gain_map
-            .data
-            .iter()
-            .map(|&v| rgb::Rgb { r: v, g: v, b: v })
-            .collect();
-        let img = imgref::ImgVec::new(
-            rgb_pixels,
-            gain_map.width as usize,
-            gain_map.height as usize,
-        );
-        let result = at_crate!(zenavif::encode_rgb8(
-            img.as_ref(),
-            &gm_enc,
-            crate::StopToken::new(enough::Unstoppable),
-        ))
-        .map_err_at(|e| CodecError::from_codec(ImageFormat::Avif, e))?;
-        // Extract AV1 data from the AVIF file by re-parsing
-        extract_av1_from_avif(&result.avif_file)?
+        extract_av1_from_avif(&encode_gray_avif(
+            &gain_map.data,
+            gain_map.width,
+            gain_map.height,
+        )?)?
     } else {
         // RGB gain map
+        let gm_enc = zenavif::EncoderConfig::new(); // Default quality for gain map
         let rgb_pixels: &[rgb::Rgb<u8>] = bytemuck::cast_slice(&gain_map.data);
         let img = imgref::Img::new(
             rgb_pixels,
@@ -127,7 +111,10 @@ pub(crate) fn encode_with_precomputed_gainmap(
     };
 
     // Step 2: Serialize ISO 21496-1 metadata
-    let iso_metadata = zenjpeg::ultrahdr::serialize_iso21496(metadata);
+    let iso_metadata = zenjpeg::ultrahdr::serialize_iso21496(
+        metadata,
+        zenjpeg::ultrahdr::Iso21496Format::AvifTmap,
+    );
 
     // Step 3: Build the main encoder with gain map attached
     let mut enc = build_encoding(quality, effort, codec_config);
@@ -140,6 +127,74 @@ pub(crate) fn encode_with_precomputed_gainmap(
     );
 
     // Step 4: Encode the base image through the normal trait path
+    encode_base(enc, pixel_data, width, height, descriptor, limits, stop)
+}
+
+/// Encode pixels + a depth map to AVIF with an auxiliary depth image
+/// (`auxl` reference, `auxC` depth URN).
+///
+/// The depth image is stored as 8-bit gray in AV1. AVIF carries no
+/// near/far/units for auxiliary depth, so only the normalized values
+/// survive.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn encode_with_depth_map(
+    pixel_data: &[u8],
+    width: u32,
+    height: u32,
+    descriptor: zenpixels::PixelDescriptor,
+    quality: Option<f32>,
+    effort: Option<u32>,
+    codec_config: Option<&crate::config::CodecConfig>,
+    depth: &crate::depthmap::DecodedDepthMap,
+    limits: Option<&crate::Limits>,
+    stop: Option<&zencodec::StopToken>,
+) -> crate::error::Result<crate::EncodeOutput> {
+    let gray = depth.normalized_gray8();
+    let depth_avif = encode_gray_avif(&gray, depth.depth.width, depth.depth.height)?;
+    let enc = build_encoding(quality, effort, codec_config);
+    let base = encode_base(enc, pixel_data, width, height, descriptor, limits, stop)?;
+    let data = super::avif_aux::add_depth_item(base.data(), &depth_avif)?;
+    Ok(crate::EncodeOutput::new(data, crate::ImageFormat::Avif))
+}
+
+/// Encode a single-channel 8-bit image as a standalone AVIF (the source
+/// of auxiliary image bitstreams and properties).
+fn encode_gray_avif(
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> crate::error::Result<alloc::vec::Vec<u8>> {
+    use crate::{CodecError, ImageFormat};
+    use whereat::{ResultAtExt, at_crate};
+
+    // Convert to RGB (ravif doesn't have a direct gray path in the simple API)
+    let rgb_pixels: alloc::vec::Vec<rgb::Rgb<u8>> = data
+        .iter()
+        .map(|&v| rgb::Rgb { r: v, g: v, b: v })
+        .collect();
+    let img = imgref::ImgVec::new(rgb_pixels, width as usize, height as usize);
+    let result = at_crate!(zenavif::encode_rgb8(
+        img.as_ref(),
+        &zenavif::EncoderConfig::new(),
+        crate::StopToken::new(enough::Unstoppable),
+    ))
+    .map_err_at(|e| CodecError::from_codec(ImageFormat::Avif, e))?;
+    Ok(result.avif_file)
+}
+
+/// Encode the primary image with a prepared encoder config (auxiliary
+/// items already attached).
+fn encode_base(
+    enc: zenavif::AvifEncoderConfig,
+    pixel_data: &[u8],
+    width: u32,
+    height: u32,
+    descriptor: zenpixels::PixelDescriptor,
+    limits: Option<&crate::Limits>,
+    stop: Option<&zencodec::StopToken>,
+) -> crate::error::Result<crate::EncodeOutput> {
+    use crate::{CodecError, ImageFormat};
+    use whereat::{ResultAtExt, at_crate};
     use zencodec::encode::{EncodeJob as _, Encoder as _};
     let mut job = enc.job();
     if let Some(lim) = limits {
@@ -179,7 +234,7 @@ pub(crate) fn encode_with_precomputed_gainmap(
 }
 
 /// Extract the primary item's AV1 data from an AVIF file.
-#[cfg(all(feature = "avif-decode", feature = "jpeg-ultrahdr"))]
+#[cfg(feature = "jpeg-ultrahdr")]
 fn extract_av1_from_avif(avif_data: &[u8]) -> crate::error::Result<alloc::vec::Vec<u8>> {
     use crate::CodecError;
     use whereat::at;
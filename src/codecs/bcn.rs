@@ -0,0 +1,1003 @@
+//! BC1–BC7 block codecs. Every block covers 4×4 pixels, stored row-major.
+//!
+//! Decoders follow the D3D11 functional spec. Encoders fit endpoints along
+//! the principal axis of each block: BC1–BC5 use their only modes, BC7
+//! uses mode 6 (one subset, RGBA endpoints, 4-bit indices).
+
+/// One decoded LDR block.
+pub(crate) type Rgba8Block = [[u8; 4]; 16];
+
+// ─── BC1–BC5 ───
+
+fn expand_565(c: u16) -> [u8; 3] {
+    let r = ((c >> 11) & 31) as u8;
+    let g = ((c >> 5) & 63) as u8;
+    let b = (c & 31) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+fn pack_565(rgb: [f32; 3]) -> u16 {
+    let q = |v: f32, max: f32| (v.clamp(0.0, 255.0) * max / 255.0 + 0.5) as u16;
+    (q(rgb[0], 31.0) << 11) | (q(rgb[1], 63.0) << 5) | q(rgb[2], 31.0)
+}
+
+/// Palette of a BC1 color block. `four_color` forces the four-color mode
+/// (BC2/BC3); otherwise `c0 <= c1` selects three colors plus transparent
+/// black, opaque black when `alpha` is false.
+fn color_palette(c0: u16, c1: u16, four_color: bool, alpha: bool) -> [[u8; 4]; 4] {
+    let (a, b) = (expand_565(c0), expand_565(c1));
+    let mix = |wa: u32, wb: u32| {
+        let d = wa + wb;
+        let m = |i: usize| ((u32::from(a[i]) * wa + u32::from(b[i]) * wb + d / 2) / d) as u8;
+        [m(0), m(1), m(2), 255]
+    };
+    let (p0, p1) = ([a[0], a[1], a[2], 255], [b[0], b[1], b[2], 255]);
+    if four_color || c0 > c1 {
+        [p0, p1, mix(2, 1), mix(1, 2)]
+    } else {
+        [p0, p1, mix(1, 1), [0, 0, 0, if alpha { 0 } else { 255 }]]
+    }
+}
+
+fn decode_color(block: &[u8], four_color: bool, alpha: bool, out: &mut Rgba8Block) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let palette = color_palette(c0, c1, four_color, alpha);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, px) in out.iter_mut().enumerate() {
+        *px = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+/// BC1: 8 bytes. `alpha` enables punch-through transparency.
+pub(crate) fn decode_bc1(block: &[u8], alpha: bool, out: &mut Rgba8Block) {
+    decode_color(block, false, alpha, out);
+}
+
+/// BC2: explicit 4-bit alpha, then a BC1 color block.
+pub(crate) fn decode_bc2(block: &[u8], out: &mut Rgba8Block) {
+    decode_color(&block[8..], true, false, out);
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    for (i, px) in out.iter_mut().enumerate() {
+        px[3] = ((alpha >> (4 * i)) & 15) as u8 * 17;
+    }
+}
+
+/// BC3: a BC4 alpha block, then a BC1 color block.
+pub(crate) fn decode_bc3(block: &[u8], out: &mut Rgba8Block) {
+    decode_color(&block[8..], true, false, out);
+    let mut alpha = [0u8; 16];
+    decode_bc4_channel(&block[..8], &mut alpha);
+    for (px, a) in out.iter_mut().zip(alpha) {
+        px[3] = a;
+    }
+}
+
+fn single_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let (a, b) = (u32::from(a0), u32::from(a1));
+    let mut p = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1..7u32 {
+            p[i as usize + 1] = (((7 - i) * a + i * b + 3) / 7) as u8;
+        }
+    } else {
+        for i in 1..5u32 {
+            p[i as usize + 1] = (((5 - i) * a + i * b + 2) / 5) as u8;
+        }
+    }
+    p
+}
+
+fn decode_bc4_channel(block: &[u8], out: &mut [u8; 16]) {
+    let palette = single_palette(block[0], block[1]);
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    for (i, v) in out.iter_mut().enumerate() {
+        *v = palette[((indices >> (3 * i)) & 7) as usize];
+    }
+}
+
+/// BC4: one unsigned channel, decoded as gray.
+pub(crate) fn decode_bc4(block: &[u8], out: &mut Rgba8Block) {
+    let mut v = [0u8; 16];
+    decode_bc4_channel(block, &mut v);
+    for (px, v) in out.iter_mut().zip(v) {
+        *px = [v, v, v, 255];
+    }
+}
+
+/// BC5: two unsigned channels, decoded as red and green.
+pub(crate) fn decode_bc5(block: &[u8], out: &mut Rgba8Block) {
+    let (mut r, mut g) = ([0u8; 16], [0u8; 16]);
+    decode_bc4_channel(&block[..8], &mut r);
+    decode_bc4_channel(&block[8..16], &mut g);
+    for i in 0..16 {
+        out[i] = [r[i], g[i], 0, 255];
+    }
+}
+
+// ─── BC6H and BC7 shared ───
+
+const WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS2,
+        3 => &WEIGHTS3,
+        _ => &WEIGHTS4,
+    }
+}
+
+/// Two-subset partitions; bit `i` is the subset of pixel `i`.
+const PARTITIONS2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Three-subset partitions; bits `2i..2i+2` are the subset of pixel `i`.
+const PARTITIONS3: [u32; 64] = {
+    const ROWS: [&[u8; 16]; 64] = [
+        b"0011001102212222",
+        b"0001001122112221",
+        b"0000200122112211",
+        b"0222002200110111",
+        b"0000000011221122",
+        b"0011001100220022",
+        b"0022002211111111",
+        b"0011001122112211",
+        b"0000000011112222",
+        b"0000111111112222",
+        b"0000111122222222",
+        b"0012001200120012",
+        b"0112011201120112",
+        b"0122012201220122",
+        b"0011011211221222",
+        b"0011200122002220",
+        b"0001001101121122",
+        b"0111001120012200",
+        b"0000112211221122",
+        b"0022002200221111",
+        b"0111011102220222",
+        b"0001000122212221",
+        b"0000001101220122",
+        b"0000110022102210",
+        b"0122012200110000",
+        b"0012001211222222",
+        b"0110122112210110",
+        b"0000011012211221",
+        b"0022110211020022",
+        b"0110011020022222",
+        b"0011012201220011",
+        b"0000200022112221",
+        b"0000000211221222",
+        b"0222002200120011",
+        b"0011001200220222",
+        b"0120012001200120",
+        b"0000111122220000",
+        b"0120120120120120",
+        b"0120201212010120",
+        b"0011220011220011",
+        b"0011112222000011",
+        b"0101010122222222",
+        b"0000000021212121",
+        b"0022112200221122",
+        b"0022001100220011",
+        b"0220122102201221",
+        b"0101222222220101",
+        b"0000212121212121",
+        b"0101010101012222",
+        b"0222011102220111",
+        b"0002111200021112",
+        b"0000211221122112",
+        b"0222011101110222",
+        b"0002111211120002",
+        b"0110011001102222",
+        b"0000000021122112",
+        b"0110011022222222",
+        b"0022001100110022",
+        b"0022112211220022",
+        b"0000000000002112",
+        b"0002000100020001",
+        b"0222122202221222",
+        b"0101222222222222",
+        b"0111201122012220",
+    ];
+    let mut out = [0u32; 64];
+    let mut p = 0;
+    while p < 64 {
+        let mut i = 0;
+        while i < 16 {
+            out[p] |= ((ROWS[p][i] - b'0') as u32) << (2 * i);
+            i += 1;
+        }
+        p += 1;
+    }
+    out
+};
+
+/// Anchor pixel of subset 1 in two-subset partitions.
+const ANCHOR2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of subsets 1 and 2 in three-subset partitions.
+const ANCHOR3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+fn subset(subsets: u32, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        2 => usize::from((PARTITIONS2[partition] >> pixel) & 1 != 0),
+        3 => ((PARTITIONS3[partition] >> (2 * pixel)) & 3) as usize,
+        _ => 0,
+    }
+}
+
+fn is_anchor(subsets: u32, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => pixel == usize::from(ANCHOR2[partition]),
+            3 => ANCHOR3[partition].contains(&(pixel as u8)),
+            _ => false,
+        }
+}
+
+/// Little-endian bit reader over one 16-byte block.
+struct Bits {
+    value: u128,
+    pos: u32,
+}
+
+impl Bits {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            value: u128::from_le_bytes(block[..16].try_into().unwrap()),
+            pos: 0,
+        }
+    }
+
+    fn read(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        let v = ((self.value >> self.pos) & ((1u128 << n) - 1)) as u32;
+        self.pos += n;
+        v
+    }
+}
+
+// ─── BC7 ───
+
+struct Bc7Mode {
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn bc7_mode(
+    subsets: u32,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        index2_bits,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+/// Expand an `n`-bit value to 8 bits by bit replication.
+fn expand_bits(v: u32, n: u32) -> u8 {
+    let v = v << (8 - n);
+    (v | (v >> n)) as u8
+}
+
+fn interpolate(e0: u32, e1: u32, w: u32) -> u32 {
+    ((64 - w) * e0 + w * e1 + 32) >> 6
+}
+
+/// BC7: 16 bytes. Reserved modes decode to transparent black.
+pub(crate) fn decode_bc7(block: &[u8], out: &mut Rgba8Block) {
+    let Some(mode_index) = (block[0] != 0).then(|| block[0].trailing_zeros() as usize) else {
+        *out = [[0; 4]; 16];
+        return;
+    };
+    let mode = &BC7_MODES[mode_index];
+    let mut bits = Bits::new(block);
+    bits.read(mode_index as u32 + 1);
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoints = (mode.subsets * 2) as usize;
+    let mut ep = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for e in ep.iter_mut().take(endpoints) {
+            e[c] = bits.read(mode.color_bits);
+        }
+    }
+    for e in ep.iter_mut().take(endpoints) {
+        e[3] = bits.read(mode.alpha_bits);
+    }
+
+    let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for p in pbits.iter_mut().take(endpoints) {
+                *p = bits.read(1);
+            }
+        } else {
+            for s in 0..mode.subsets as usize {
+                let p = bits.read(1);
+                pbits[2 * s] = p;
+                pbits[2 * s + 1] = p;
+            }
+        }
+        for (e, p) in ep.iter_mut().zip(pbits).take(endpoints) {
+            for v in e.iter_mut() {
+                *v = (*v << 1) | p;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+    for e in ep.iter_mut().take(endpoints) {
+        for v in &mut e[..3] {
+            *v = u32::from(expand_bits(*v, color_bits));
+        }
+        e[3] = if alpha_bits > 0 {
+            u32::from(expand_bits(e[3], alpha_bits))
+        } else {
+            255
+        };
+    }
+
+    let mut index = [0u32; 16];
+    let mut index2 = [0u32; 16];
+    for (i, v) in index.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, i);
+        *v = bits.read(mode.index_bits - u32::from(anchor));
+    }
+    if mode.index2_bits > 0 {
+        for (i, v) in index2.iter_mut().enumerate() {
+            *v = bits.read(mode.index2_bits - u32::from(i == 0));
+        }
+    }
+
+    for (i, px) in out.iter_mut().enumerate() {
+        let s = subset(mode.subsets, partition, i);
+        let (e0, e1) = (ep[2 * s], ep[2 * s + 1]);
+        let (color_w, alpha_w) = if mode.index2_bits == 0 {
+            let w = weights(mode.index_bits)[index[i] as usize];
+            (w, w)
+        } else if index_selection == 0 {
+            (
+                weights(mode.index_bits)[index[i] as usize],
+                weights(mode.index2_bits)[index2[i] as usize],
+            )
+        } else {
+            (
+                weights(mode.index2_bits)[index2[i] as usize],
+                weights(mode.index_bits)[index[i] as usize],
+            )
+        };
+        let mut rgba = [0u8; 4];
+        for c in 0..3 {
+            rgba[c] = interpolate(e0[c], e1[c], color_w) as u8;
+        }
+        rgba[3] = interpolate(e0[3], e1[3], alpha_w) as u8;
+        match rotation {
+            1 => rgba.swap(0, 3),
+            2 => rgba.swap(1, 3),
+            3 => rgba.swap(2, 3),
+            _ => {}
+        }
+        *px = rgba;
+    }
+}
+
+// ─── BC6H ───
+
+/// Endpoint fields, in spec order: `rw gw bw rx gx bx ry gy by rz gz bz`.
+const R0: u8 = 0;
+const G0: u8 = 1;
+const B0: u8 = 2;
+const R1: u8 = 3;
+const G1: u8 = 4;
+const B1: u8 = 5;
+const R2: u8 = 6;
+const G2: u8 = 7;
+const B2: u8 = 8;
+const R3: u8 = 9;
+const G3: u8 = 10;
+const B3: u8 = 11;
+
+struct Bc6Mode {
+    transformed: bool,
+    two_regions: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// `(field, first bit, bit count)`, read in order.
+    layout: &'static [(u8, u8, u8)],
+}
+
+/// Modes keyed by their 2- or 5-bit mode value.
+fn bc6_mode(value: u32) -> Option<Bc6Mode> {
+    let m = |transformed: bool,
+             two_regions: bool,
+             endpoint_bits: u32,
+             delta_bits: [u32; 3],
+             layout: &'static [(u8, u8, u8)]| {
+        Some(Bc6Mode {
+            transformed,
+            two_regions,
+            endpoint_bits,
+            delta_bits,
+            layout,
+        })
+    };
+    #[rustfmt::skip]
+    let mode = match value {
+        0x00 => m(true, true, 10, [5, 5, 5], &[
+            (G2, 4, 1), (B2, 4, 1), (B3, 4, 1), (R0, 0, 10), (G0, 0, 10), (B0, 0, 10),
+            (R1, 0, 5), (G3, 4, 1), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4),
+            (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5),
+            (B3, 3, 1),
+        ]),
+        0x01 => m(true, true, 7, [6, 6, 6], &[
+            (G2, 5, 1), (G3, 4, 1), (G3, 5, 1), (R0, 0, 7), (B3, 0, 1), (B3, 1, 1),
+            (B2, 4, 1), (G0, 0, 7), (B2, 5, 1), (B3, 2, 1), (G2, 4, 1), (B0, 0, 7),
+            (B3, 3, 1), (B3, 5, 1), (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 6),
+            (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 6), (R3, 0, 6),
+        ]),
+        0x02 => m(true, true, 11, [5, 4, 4], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 5), (R0, 10, 1), (G2, 0, 4),
+            (G1, 0, 4), (G0, 10, 1), (B3, 0, 1), (G3, 0, 4), (B1, 0, 4), (B0, 10, 1),
+            (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+        ]),
+        0x06 => m(true, true, 11, [4, 5, 4], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 1), (G3, 4, 1),
+            (G2, 0, 4), (G1, 0, 5), (G0, 10, 1), (G3, 0, 4), (B1, 0, 4), (B0, 10, 1),
+            (B3, 1, 1), (B2, 0, 4), (R2, 0, 4), (B3, 0, 1), (B3, 2, 1), (R3, 0, 4),
+            (G2, 4, 1), (B3, 3, 1),
+        ]),
+        0x0A => m(true, true, 11, [4, 4, 5], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4), (R0, 10, 1), (B2, 4, 1),
+            (G2, 0, 4), (G1, 0, 4), (G0, 10, 1), (B3, 0, 1), (G3, 0, 4), (B1, 0, 5),
+            (B0, 10, 1), (B2, 0, 4), (R2, 0, 4), (B3, 1, 1), (B3, 2, 1), (R3, 0, 4),
+            (B3, 4, 1), (B3, 3, 1),
+        ]),
+        0x0E => m(true, true, 9, [5, 5, 5], &[
+            (R0, 0, 9), (B2, 4, 1), (G0, 0, 9), (G2, 4, 1), (B0, 0, 9), (B3, 4, 1),
+            (R1, 0, 5), (G3, 4, 1), (G2, 0, 4), (G1, 0, 5), (B3, 0, 1), (G3, 0, 4),
+            (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5), (B3, 2, 1), (R3, 0, 5),
+            (B3, 3, 1),
+        ]),
+        0x12 => m(true, true, 8, [6, 5, 5], &[
+            (R0, 0, 8), (G3, 4, 1), (B2, 4, 1), (G0, 0, 8), (B3, 2, 1), (G2, 4, 1),
+            (B0, 0, 8), (B3, 3, 1), (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 5),
+            (B3, 0, 1), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 6),
+            (R3, 0, 6),
+        ]),
+        0x16 => m(true, true, 8, [5, 6, 5], &[
+            (R0, 0, 8), (B3, 0, 1), (B2, 4, 1), (G0, 0, 8), (G2, 5, 1), (G2, 4, 1),
+            (B0, 0, 8), (G3, 5, 1), (B3, 4, 1), (R1, 0, 5), (G3, 4, 1), (G2, 0, 4),
+            (G1, 0, 6), (G3, 0, 4), (B1, 0, 5), (B3, 1, 1), (B2, 0, 4), (R2, 0, 5),
+            (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+        ]),
+        0x1A => m(true, true, 8, [5, 5, 6], &[
+            (R0, 0, 8), (B3, 1, 1), (B2, 4, 1), (G0, 0, 8), (B2, 5, 1), (G2, 4, 1),
+            (B0, 0, 8), (B3, 5, 1), (B3, 4, 1), (R1, 0, 5), (G3, 4, 1), (G2, 0, 4),
+            (G1, 0, 5), (B3, 0, 1), (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 5),
+            (B3, 2, 1), (R3, 0, 5), (B3, 3, 1),
+        ]),
+        0x1E => m(false, true, 6, [6, 6, 6], &[
+            (R0, 0, 6), (G3, 4, 1), (B3, 0, 1), (B3, 1, 1), (B2, 4, 1), (G0, 0, 6),
+            (G2, 5, 1), (B2, 5, 1), (B3, 2, 1), (G2, 4, 1), (B0, 0, 6), (G3, 5, 1),
+            (B3, 3, 1), (B3, 5, 1), (B3, 4, 1), (R1, 0, 6), (G2, 0, 4), (G1, 0, 6),
+            (G3, 0, 4), (B1, 0, 6), (B2, 0, 4), (R2, 0, 6), (R3, 0, 6),
+        ]),
+        0x03 => m(false, false, 10, [10, 10, 10], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 10), (G1, 0, 10), (B1, 0, 10),
+        ]),
+        0x07 => m(true, false, 11, [9, 9, 9], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 9), (R0, 10, 1), (G1, 0, 9),
+            (G0, 10, 1), (B1, 0, 9), (B0, 10, 1),
+        ]),
+        // Bits above 10 are stored most significant first.
+        0x0B => m(true, false, 12, [8, 8, 8], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 8), (R0, 11, 1), (R0, 10, 1),
+            (G1, 0, 8), (G0, 11, 1), (G0, 10, 1), (B1, 0, 8), (B0, 11, 1), (B0, 10, 1),
+        ]),
+        0x0F => m(true, false, 16, [4, 4, 4], &[
+            (R0, 0, 10), (G0, 0, 10), (B0, 0, 10), (R1, 0, 4),
+            (R0, 15, 1), (R0, 14, 1), (R0, 13, 1), (R0, 12, 1), (R0, 11, 1), (R0, 10, 1),
+            (G1, 0, 4),
+            (G0, 15, 1), (G0, 14, 1), (G0, 13, 1), (G0, 12, 1), (G0, 11, 1), (G0, 10, 1),
+            (B1, 0, 4),
+            (B0, 15, 1), (B0, 14, 1), (B0, 13, 1), (B0, 12, 1), (B0, 11, 1), (B0, 10, 1),
+        ]),
+        _ => None,
+    };
+    mode
+}
+
+fn sign_extend(v: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((v << shift) as i32) >> shift
+}
+
+fn unquantize(v: i32, bits: u32, signed: bool) -> i32 {
+    if signed {
+        if bits >= 16 {
+            return v;
+        }
+        let (negative, mag) = (v < 0, v.abs());
+        let q = if mag == 0 {
+            0
+        } else if mag >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((mag << 15) + 0x4000) >> (bits - 1)
+        };
+        if negative { -q } else { q }
+    } else if bits >= 15 {
+        v
+    } else if v == 0 {
+        0
+    } else if v == (1 << bits) - 1 {
+        0xFFFF
+    } else {
+        ((v << 16) + 0x8000) >> bits
+    }
+}
+
+/// Scale an interpolated value to half-float bits.
+fn finish_unquantize(v: i32, signed: bool) -> u16 {
+    if signed {
+        if v < 0 {
+            0x8000 | (((-v) * 31) >> 5) as u16
+        } else {
+            ((v * 31) >> 5) as u16
+        }
+    } else {
+        ((v * 31) >> 6) as u16
+    }
+}
+
+/// IEEE 754 half to single precision.
+pub(crate) fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = i32::from((h >> 10) & 31);
+    let mantissa = f32::from(h & 0x3FF);
+    sign * match exp {
+        0 => mantissa * (1.0 / 16_777_216.0),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => {
+            let scale = if exp >= 15 {
+                (1u32 << (exp - 15)) as f32
+            } else {
+                1.0 / (1u32 << (15 - exp)) as f32
+            };
+            (1.0 + mantissa / 1024.0) * scale
+        }
+    }
+}
+
+/// BC6H: 16 bytes to linear RGB. Reserved modes decode to black.
+pub(crate) fn decode_bc6h(block: &[u8], signed: bool, out: &mut [[f32; 3]; 16]) {
+    let mut bits = Bits::new(block);
+    let mut value = bits.read(2);
+    if value > 1 {
+        value |= bits.read(3) << 2;
+    }
+    let Some(mode) = bc6_mode(value) else {
+        *out = [[0.0; 3]; 16];
+        return;
+    };
+
+    let mut fields = [0u32; 12];
+    for &(field, first, count) in mode.layout {
+        fields[field as usize] |= bits.read(u32::from(count)) << first;
+    }
+    let regions = if mode.two_regions { 2 } else { 1 };
+    let partition = if mode.two_regions {
+        bits.read(5) as usize
+    } else {
+        0
+    };
+    let index_bits = if mode.two_regions { 3 } else { 4 };
+    let mut index = [0u32; 16];
+    for (i, v) in index.iter_mut().enumerate() {
+        *v = bits.read(index_bits - u32::from(is_anchor(regions, partition, i)));
+    }
+
+    let eb = mode.endpoint_bits;
+    let endpoints = regions as usize * 2;
+    let mut ep = [[0i32; 3]; 4];
+    for c in 0..3 {
+        let base = fields[c];
+        ep[0][c] = if signed {
+            sign_extend(base, eb)
+        } else {
+            base as i32
+        };
+        for (e, endpoint) in ep.iter_mut().enumerate().take(endpoints).skip(1) {
+            let raw = fields[e * 3 + c];
+            endpoint[c] = if mode.transformed {
+                let delta = sign_extend(raw, mode.delta_bits[c]);
+                let v = (base as i32 + delta) as u32 & ((1 << eb) - 1);
+                if signed { sign_extend(v, eb) } else { v as i32 }
+            } else if signed {
+                sign_extend(raw, eb)
+            } else {
+                raw as i32
+            };
+        }
+    }
+    for e in ep.iter_mut().take(endpoints) {
+        for v in e.iter_mut() {
+            *v = unquantize(*v, eb, signed);
+        }
+    }
+
+    let w = weights(index_bits);
+    for (i, px) in out.iter_mut().enumerate() {
+        let s = subset(regions, partition, i);
+        let (e0, e1) = (ep[2 * s], ep[2 * s + 1]);
+        let wi = w[index[i] as usize] as i32;
+        for c in 0..3 {
+            let v = (e0[c] * (64 - wi) + e1[c] * wi + 32) >> 6;
+            px[c] = half_to_f32(finish_unquantize(v, signed));
+        }
+    }
+}
+
+// ─── Encoders ───
+
+/// Endpoints spanning the block along its principal axis over the first
+/// `channels` components. Pixels for which `use_pixel` is false are ignored.
+fn principal_endpoints(
+    px: &Rgba8Block,
+    channels: usize,
+    use_pixel: impl Fn(&[u8; 4]) -> bool,
+) -> ([f32; 4], [f32; 4]) {
+    let points: alloc::vec::Vec<[f32; 4]> = px
+        .iter()
+        .filter(|p| use_pixel(p))
+        .map(|p| p.map(f32::from))
+        .collect();
+    if points.is_empty() {
+        return ([0.0; 4], [0.0; 4]);
+    }
+    let n = points.len() as f32;
+    let mut mean = [0.0f32; 4];
+    for p in &points {
+        for c in 0..channels {
+            mean[c] += p[c] / n;
+        }
+    }
+    let mut cov = [[0.0f32; 4]; 4];
+    for p in &points {
+        for a in 0..channels {
+            for b in 0..channels {
+                cov[a][b] += (p[a] - mean[a]) * (p[b] - mean[b]);
+            }
+        }
+    }
+    // Power iteration, seeded with the covariance row of the channel that
+    // varies most (the bounding-box diagonal misses anticorrelated channels).
+    let widest = (0..channels)
+        .max_by(|&a, &b| cov[a][a].total_cmp(&cov[b][b]))
+        .unwrap_or(0);
+    let mut axis = cov[widest];
+    for _ in 0..8 {
+        let mut next = [0.0f32; 4];
+        for a in 0..channels {
+            for b in 0..channels {
+                next[a] += cov[a][b] * axis[b];
+            }
+        }
+        let norm = next.iter().map(|v| v.abs()).fold(0.0, f32::max);
+        if norm < 1e-6 {
+            break;
+        }
+        axis = next.map(|v| v / norm);
+    }
+    let len2: f32 = axis.iter().map(|v| v * v).sum();
+    if len2 < 1e-12 {
+        return (mean, mean);
+    }
+    let (mut lo, mut hi) = (f32::MAX, f32::MIN);
+    for p in &points {
+        let t: f32 = (0..channels)
+            .map(|c| (p[c] - mean[c]) * axis[c])
+            .sum::<f32>()
+            / len2;
+        lo = lo.min(t);
+        hi = hi.max(t);
+    }
+    let at = |t: f32| core::array::from_fn(|c| (mean[c] + axis[c] * t).clamp(0.0, 255.0));
+    (at(lo), at(hi))
+}
+
+fn distance(a: &[u8; 4], b: &[u8; 4], channels: usize) -> u32 {
+    (0..channels)
+        .map(|c| {
+            let d = i32::from(a[c]) - i32::from(b[c]);
+            (d * d) as u32
+        })
+        .sum()
+}
+
+fn nearest(px: &[u8; 4], palette: &[[u8; 4]], channels: usize) -> (usize, u32) {
+    palette
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i, distance(px, p, channels)))
+        .min_by_key(|&(_, d)| d)
+        .unwrap_or((0, 0))
+}
+
+fn encode_color(px: &Rgba8Block, four_color: bool, alpha: bool) -> [u8; 8] {
+    let transparent = !four_color && alpha && px.iter().any(|p| p[3] < 128);
+    let (e0, e1) = principal_endpoints(px, 3, |p| !transparent || p[3] >= 128);
+    let (mut c0, mut c1) = (
+        pack_565([e0[0], e0[1], e0[2]]),
+        pack_565([e1[0], e1[1], e1[2]]),
+    );
+    // Four-color mode needs c0 > c1, three-color mode c0 <= c1.
+    if (c0 < c1) != transparent {
+        core::mem::swap(&mut c0, &mut c1);
+    }
+    let palette = color_palette(c0, c1, four_color, alpha);
+    let colors = if four_color || c0 > c1 { 4 } else { 3 };
+    let mut indices = 0u32;
+    for (i, p) in px.iter().enumerate() {
+        let index = if transparent && p[3] < 128 {
+            3
+        } else {
+            nearest(p, &palette[..colors], 3).0
+        };
+        indices |= (index as u32) << (2 * i);
+    }
+    let mut out = [0u8; 8];
+    out[..2].copy_from_slice(&c0.to_le_bytes());
+    out[2..4].copy_from_slice(&c1.to_le_bytes());
+    out[4..].copy_from_slice(&indices.to_le_bytes());
+    out
+}
+
+/// BC1; with `alpha`, pixels below half opacity become transparent.
+pub(crate) fn encode_bc1(px: &Rgba8Block, alpha: bool) -> [u8; 8] {
+    encode_color(px, false, alpha)
+}
+
+pub(crate) fn encode_bc2(px: &Rgba8Block) -> [u8; 16] {
+    let mut alpha = 0u64;
+    for (i, p) in px.iter().enumerate() {
+        alpha |= u64::from((u32::from(p[3]) * 15 + 127) / 255) << (4 * i);
+    }
+    let mut out = [0u8; 16];
+    out[..8].copy_from_slice(&alpha.to_le_bytes());
+    out[8..].copy_from_slice(&encode_color(px, true, false));
+    out
+}
+
+pub(crate) fn encode_bc3(px: &Rgba8Block) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    out[..8].copy_from_slice(&encode_bc4_channel(&px.map(|p| p[3])));
+    out[8..].copy_from_slice(&encode_color(px, true, false));
+    out
+}
+
+fn encode_bc4_channel(values: &[u8; 16]) -> [u8; 8] {
+    let (lo, hi) = values
+        .iter()
+        .fold((255u8, 0u8), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    // a0 > a1 selects eight interpolated values; equal endpoints need none.
+    let palette = single_palette(hi, lo);
+    let mut indices = 0u64;
+    for (i, &v) in values.iter().enumerate() {
+        let index = (0..8)
+            .min_by_key(|&j| (i32::from(palette[j]) - i32::from(v)).unsigned_abs())
+            .unwrap_or(0);
+        indices |= (index as u64) << (3 * i);
+    }
+    let mut out = [0u8; 8];
+    out[0] = hi;
+    out[1] = lo;
+    out[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    out
+}
+
+/// BC4 from the red channel.
+pub(crate) fn encode_bc4(px: &Rgba8Block) -> [u8; 8] {
+    encode_bc4_channel(&px.map(|p| p[0]))
+}
+
+/// BC5 from the red and green channels.
+pub(crate) fn encode_bc5(px: &Rgba8Block) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    out[..8].copy_from_slice(&encode_bc4_channel(&px.map(|p| p[0])));
+    out[8..].copy_from_slice(&encode_bc4_channel(&px.map(|p| p[1])));
+    out
+}
+
+/// Little-endian bit writer for one 16-byte block.
+struct BitWriter {
+    value: u128,
+    pos: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, v: u32, n: u32) {
+        self.value |= u128::from(v) << self.pos;
+        self.pos += n;
+    }
+}
+
+/// A BC7 mode 6 attempt: error, quantized endpoints, p-bits, indices.
+type Bc7Candidate = (u64, [[u32; 4]; 2], [u32; 2], [usize; 16]);
+
+/// BC7 mode 6: trying every p-bit pair for the principal-axis endpoints.
+pub(crate) fn encode_bc7(px: &Rgba8Block) -> [u8; 16] {
+    let (e0, e1) = principal_endpoints(px, 4, |_| true);
+    let quantize = |v: f32, p: u32| ((v - p as f32) / 2.0 + 0.5).clamp(0.0, 127.0) as u32;
+
+    let mut best: Option<Bc7Candidate> = None;
+    for p0 in 0..2 {
+        for p1 in 0..2 {
+            let q0 = e0.map(|v| quantize(v, p0));
+            let q1 = e1.map(|v| quantize(v, p1));
+            let full0 = q0.map(|v| (v << 1) | p0);
+            let full1 = q1.map(|v| (v << 1) | p1);
+            let palette: [[u8; 4]; 16] = core::array::from_fn(|i| {
+                core::array::from_fn(|c| interpolate(full0[c], full1[c], WEIGHTS4[i]) as u8)
+            });
+            let mut indices = [0usize; 16];
+            let mut error = 0u64;
+            for (i, p) in px.iter().enumerate() {
+                let (index, d) = nearest(p, &palette, 4);
+                indices[i] = index;
+                error += u64::from(d);
+            }
+            if best.as_ref().is_none_or(|b| error < b.0) {
+                best = Some((error, [q0, q1], [p0, p1], indices));
+            }
+        }
+    }
+    let (_, mut q, mut p, mut indices) = best.unwrap();
+    // The anchor index drops its top bit, so it must be below 8.
+    if indices[0] >= 8 {
+        q.swap(0, 1);
+        p.swap(0, 1);
+        indices = indices.map(|i| 15 - i);
+    }
+
+    let mut w = BitWriter { value: 0, pos: 0 };
+    w.write(1 << 6, 7);
+    for (&a, &b) in q[0].iter().zip(&q[1]) {
+        w.write(a, 7);
+        w.write(b, 7);
+    }
+    w.write(p[0], 1);
+    w.write(p[1], 1);
+    for (i, &index) in indices.iter().enumerate() {
+        w.write(index as u32, if i == 0 { 3 } else { 4 });
+    }
+    w.value.to_le_bytes()
+}
@@ -0,0 +1,328 @@
+//! DDS container adapter: legacy and DX10 headers. Pixel work is shared
+//! with KTX2 in [`super::texture`].
+
+use alloc::vec::Vec;
+
+use crate::codecs::texture;
+use crate::config::CodecConfig;
+use crate::dispatch::{BuiltEncoder, EncodeParams};
+use crate::error::Result;
+use crate::texture::{MipLevel, TextureFormat, TextureInfo};
+use crate::{CodecError, DecodeOutput, ImageInfo, Limits, StopToken};
+use whereat::at;
+use zencodec::encode::EncodeOutput;
+use zenpixels::PixelSlice;
+
+const HEADER_LEN: usize = 128;
+const DX10_LEN: usize = 20;
+
+const DDSD_CAPS_HEIGHT_WIDTH_PIXELFORMAT: u32 = 0x1007;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDSD_LINEARSIZE: u32 = 0x8_0000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x2_0000;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x40_0000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn invalid(detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!("DDS: {detail}")))
+}
+
+fn unsupported(detail: &'static str) -> whereat::At<CodecError> {
+    at!(CodecError::UnsupportedOperation {
+        format: crate::dds::format(),
+        detail,
+    })
+}
+
+/// Magic and header size check used by the format definition.
+pub(crate) fn is_dds(data: &[u8]) -> bool {
+    data.len() >= 8 && data.starts_with(b"DDS ") && u32_at(data, 4) == 124
+}
+
+/// Format and sRGB flag of a DXGI format code.
+fn dxgi_format(code: u32) -> Option<(TextureFormat, bool)> {
+    Some(match code {
+        2 => (TextureFormat::Rgba32f, false),
+        10 => (TextureFormat::Rgba16f, false),
+        28 => (TextureFormat::Rgba8, false),
+        29 => (TextureFormat::Rgba8, true),
+        49 => (TextureFormat::Rg8, false),
+        61 => (TextureFormat::R8, false),
+        71 => (TextureFormat::Bc1 { alpha: true }, false),
+        72 => (TextureFormat::Bc1 { alpha: true }, true),
+        74 => (TextureFormat::Bc2, false),
+        75 => (TextureFormat::Bc2, true),
+        77 => (TextureFormat::Bc3, false),
+        78 => (TextureFormat::Bc3, true),
+        80 => (TextureFormat::Bc4, false),
+        83 => (TextureFormat::Bc5, false),
+        87 => (TextureFormat::Bgra8, false),
+        88 => (TextureFormat::Bgrx8, false),
+        91 => (TextureFormat::Bgra8, true),
+        93 => (TextureFormat::Bgrx8, true),
+        95 => (TextureFormat::Bc6h { signed: false }, false),
+        96 => (TextureFormat::Bc6h { signed: true }, false),
+        98 => (TextureFormat::Bc7, false),
+        99 => (TextureFormat::Bc7, true),
+        _ => return None,
+    })
+}
+
+fn dxgi_code(format: TextureFormat, srgb: bool) -> u32 {
+    match (format, srgb) {
+        (TextureFormat::Rgba8, false) => 28,
+        (TextureFormat::Rgba8, true) => 29,
+        (TextureFormat::Bc1 { .. }, false) => 71,
+        (TextureFormat::Bc1 { .. }, true) => 72,
+        (TextureFormat::Bc2, false) => 74,
+        (TextureFormat::Bc2, true) => 75,
+        (TextureFormat::Bc3, false) => 77,
+        (TextureFormat::Bc3, true) => 78,
+        (TextureFormat::Bc4, _) => 80,
+        (TextureFormat::Bc5, _) => 83,
+        (_, false) => 98,
+        (_, true) => 99,
+    }
+}
+
+/// Legacy pixel format: a FourCC or channel masks. Legacy headers carry no
+/// color space, so like Direct3D they are read as linear (UNORM).
+fn legacy_format(data: &[u8]) -> Result<TextureFormat> {
+    let flags = u32_at(data, 80);
+    if flags & DDPF_FOURCC != 0 {
+        return Ok(match &data[84..88] {
+            b"DXT1" => TextureFormat::Bc1 { alpha: true },
+            b"DXT2" | b"DXT3" => TextureFormat::Bc2,
+            b"DXT4" | b"DXT5" => TextureFormat::Bc3,
+            b"ATI1" | b"BC4U" => TextureFormat::Bc4,
+            b"ATI2" | b"BC5U" => TextureFormat::Bc5,
+            _ => match u32_at(data, 84) {
+                113 => TextureFormat::Rgba16f,
+                116 => TextureFormat::Rgba32f,
+                _ => return Err(unsupported("unsupported DDS FourCC")),
+            },
+        });
+    }
+    let bits = u32_at(data, 88);
+    let masks = [92, 96, 100, 104].map(|at| u32_at(data, at));
+    let alpha = flags & DDPF_ALPHAPIXELS != 0;
+    if flags & DDPF_RGB != 0 {
+        return match (bits, masks[..3] == [0xff, 0xff00, 0xff_0000], alpha) {
+            (32, true, true) if masks[3] == 0xff00_0000 => Ok(TextureFormat::Rgba8),
+            (32, false, true) if masks == [0xff_0000, 0xff00, 0xff, 0xff00_0000] => {
+                Ok(TextureFormat::Bgra8)
+            }
+            (32, false, false) if masks[..3] == [0xff_0000, 0xff00, 0xff] => {
+                Ok(TextureFormat::Bgrx8)
+            }
+            (24, false, false) if masks[..3] == [0xff_0000, 0xff00, 0xff] => {
+                Ok(TextureFormat::Bgr8)
+            }
+            _ => Err(unsupported("unsupported DDS channel masks")),
+        };
+    }
+    if flags & DDPF_LUMINANCE != 0 && bits == 8 && !alpha {
+        return Ok(TextureFormat::R8);
+    }
+    Err(unsupported("unsupported DDS pixel format"))
+}
+
+pub(crate) fn info(data: &[u8]) -> Result<TextureInfo> {
+    if !is_dds(data) || data.len() < HEADER_LEN {
+        return Err(invalid("not a DDS file"));
+    }
+    let (height, width) = (u32_at(data, 12), u32_at(data, 16));
+    if width == 0 || height == 0 {
+        return Err(invalid("zero dimensions"));
+    }
+    let caps2 = u32_at(data, 112);
+    let mut depth = if caps2 & DDSCAPS2_VOLUME != 0 {
+        u32_at(data, 24).max(1)
+    } else {
+        1
+    };
+    let mut faces = if caps2 & DDSCAPS2_CUBEMAP != 0 { 6 } else { 1 };
+    let mut array_layers = 1;
+    let mut offset = HEADER_LEN;
+
+    let (format, srgb) = if &data[84..88] == b"DX10" && u32_at(data, 80) & DDPF_FOURCC != 0 {
+        if data.len() < HEADER_LEN + DX10_LEN {
+            return Err(invalid("truncated DX10 header"));
+        }
+        let dx10 = &data[HEADER_LEN..];
+        let format =
+            dxgi_format(u32_at(dx10, 0)).ok_or_else(|| unsupported("unsupported DXGI format"))?;
+        match u32_at(dx10, 4) {
+            4 => depth = u32_at(data, 24).max(1),
+            _ => depth = 1,
+        }
+        if u32_at(dx10, 8) & 0x4 != 0 {
+            faces = 6;
+        }
+        array_layers = u32_at(dx10, 12).max(1);
+        offset += DX10_LEN;
+        format
+    } else {
+        (legacy_format(data)?, false)
+    };
+
+    let mip_count = if u32_at(data, 8) & DDSD_MIPMAPCOUNT != 0 {
+        u32_at(data, 28).clamp(1, 32)
+    } else {
+        1
+    };
+    let mut mip_levels = Vec::with_capacity(mip_count as usize);
+    for i in 0..mip_count {
+        let (w, h) = ((width >> i).max(1), (height >> i).max(1));
+        let byte_len = format.image_bytes(w, h);
+        mip_levels.push(MipLevel {
+            width: w,
+            height: h,
+            offset,
+            byte_len,
+        });
+        offset += byte_len * (depth >> i).max(1) as usize;
+    }
+    Ok(TextureInfo {
+        width,
+        height,
+        depth,
+        format,
+        srgb,
+        array_layers,
+        faces,
+        mip_levels,
+    })
+}
+
+pub(crate) fn probe(data: &[u8]) -> Result<ImageInfo> {
+    let texture = info(data)?;
+    Ok(texture::image_info(
+        &texture,
+        &texture.mip_levels[0],
+        crate::dds::format(),
+    ))
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    codec_config: Option<&CodecConfig>,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<DecodeOutput> {
+    let texture = info(data)?;
+    texture::decode_level(
+        &texture,
+        data,
+        codec_config,
+        limits,
+        stop,
+        crate::dds::format(),
+        "DDS",
+    )
+}
+
+fn encode(pixels: PixelSlice<'_>, params: &EncodeParams<'_>) -> Result<EncodeOutput> {
+    let (config, levels) = texture::encode_levels(pixels, params, crate::dds::format())?;
+    let format = config.format;
+    let srgb = config.srgb && !matches!(format, TextureFormat::Bc4 | TextureFormat::Bc5);
+    let base = &levels[0];
+    let mipmapped = levels.len() > 1;
+
+    let mut flags = DDSD_CAPS_HEIGHT_WIDTH_PIXELFORMAT;
+    let pitch = if format.is_block_compressed() {
+        flags |= DDSD_LINEARSIZE;
+        base.data.len() as u32
+    } else {
+        flags |= DDSD_PITCH;
+        base.width * 4
+    };
+    if mipmapped {
+        flags |= DDSD_MIPMAPCOUNT;
+    }
+
+    let mut out = Vec::with_capacity(
+        HEADER_LEN + DX10_LEN + levels.iter().map(|l| l.data.len()).sum::<usize>(),
+    );
+    out.extend_from_slice(b"DDS ");
+    for v in [
+        124,
+        flags,
+        base.height,
+        base.width,
+        pitch,
+        0,
+        levels.len() as u32,
+    ] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out.resize(76, 0);
+
+    // Pixel format: legacy where it can express the format, else DX10.
+    let fourcc: Option<&[u8; 4]> = match format {
+        _ if srgb || format == TextureFormat::Bc7 => Some(b"DX10"),
+        TextureFormat::Bc1 { .. } => Some(b"DXT1"),
+        TextureFormat::Bc2 => Some(b"DXT3"),
+        TextureFormat::Bc3 => Some(b"DXT5"),
+        TextureFormat::Bc4 => Some(b"ATI1"),
+        TextureFormat::Bc5 => Some(b"ATI2"),
+        _ => None,
+    };
+    out.extend_from_slice(&32u32.to_le_bytes());
+    match fourcc {
+        Some(code) => {
+            out.extend_from_slice(&DDPF_FOURCC.to_le_bytes());
+            out.extend_from_slice(code);
+            out.extend_from_slice(&[0; 20]);
+        }
+        None => {
+            for v in [
+                DDPF_RGB | DDPF_ALPHAPIXELS,
+                0,
+                32,
+                0xff,
+                0xff00,
+                0xff_0000,
+                0xff00_0000,
+            ] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+    let caps = if mipmapped {
+        DDSCAPS_TEXTURE | DDSCAPS_COMPLEX | DDSCAPS_MIPMAP
+    } else {
+        DDSCAPS_TEXTURE
+    };
+    out.extend_from_slice(&caps.to_le_bytes());
+    out.resize(HEADER_LEN, 0);
+
+    if fourcc == Some(b"DX10") {
+        // Texture2D, no flags, one array element.
+        for v in [dxgi_code(format, srgb), 3, 0, 1, 0] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    for level in &levels {
+        out.extend_from_slice(&level.data);
+    }
+    Ok(EncodeOutput::new(out, crate::dds::format()))
+}
+
+pub(crate) fn build_trait_encoder<'a>(params: EncodeParams<'a>) -> BuiltEncoder<'a> {
+    BuiltEncoder {
+        encoder: alloc::boxed::Box::new(move |pixels| encode(pixels, &params)),
+        supported: &texture::SUPPORTED,
+    }
+}
@@ -0,0 +1,606 @@
+//! OpenEXR codec adapter using the `exr` crate.
+//!
+//! Full-frame decode runs on top of the block streaming path: the selected
+//! layer is decompressed block by block and the color channels are copied
+//! into the output buffer as they arrive.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use std::io::Cursor;
+
+use crate::config::CodecConfig;
+use crate::dispatch::{BuiltEncoder, EncodeParams};
+use crate::error::Result;
+use crate::exr::{
+    ExrBlock, ExrChannel, ExrChannelSamples, ExrCompression, ExrDecodeConfig, ExrEncodeConfig,
+    ExrLayer, ExrPrecision, ExrSampleType,
+};
+use crate::limits::Stop;
+use crate::{CodecError, DecodeOutput, ImageInfo, Limits, StopToken};
+use exr::block::chunk::TileCoordinates;
+use exr::block::reader::ChunksReader as _;
+use exr::meta::MetaData;
+use exr::meta::header::Header;
+use exr::prelude::f16;
+use whereat::at;
+use zencodec::encode::EncodeOutput;
+use zenpixels::{AlphaMode, ChannelLayout, ChannelType, PixelBuffer, PixelDescriptor, PixelSlice};
+use zenpixels::{Cicp, TransferFunction};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// Descriptors the encoder accepts: straight-alpha linear f32.
+static SUPPORTED: [PixelDescriptor; 2] = [
+    descriptor(ChannelType::F32, false),
+    descriptor(ChannelType::F32, true),
+];
+
+/// Magic-byte check used by the format definition.
+pub(crate) fn is_exr(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+const fn descriptor(channel_type: ChannelType, alpha: bool) -> PixelDescriptor {
+    if alpha {
+        PixelDescriptor::new(
+            channel_type,
+            ChannelLayout::Rgba,
+            Some(AlphaMode::Straight),
+            TransferFunction::Linear,
+        )
+    } else {
+        PixelDescriptor::new(
+            channel_type,
+            ChannelLayout::Rgb,
+            None,
+            TransferFunction::Linear,
+        )
+    }
+}
+
+fn map_err(e: exr::error::Error) -> whereat::At<CodecError> {
+    at!(CodecError::from_codec(crate::exr::format(), e))
+}
+
+fn read_meta(data: &[u8]) -> Result<MetaData> {
+    if !is_exr(data) {
+        return Err(at!(CodecError::InvalidInput("not an OpenEXR file".into())));
+    }
+    MetaData::read_from_buffered(Cursor::new(data), false).map_err(map_err)
+}
+
+fn sample_type(t: exr::meta::attribute::SampleType) -> ExrSampleType {
+    use exr::meta::attribute::SampleType;
+    match t {
+        SampleType::F16 => ExrSampleType::F16,
+        SampleType::F32 => ExrSampleType::F32,
+        SampleType::U32 => ExrSampleType::U32,
+    }
+}
+
+fn layer_of(header: &Header) -> ExrLayer {
+    ExrLayer {
+        name: header
+            .own_attributes
+            .layer_name
+            .as_ref()
+            .map(|n| n.to_string()),
+        width: header.layer_size.width() as u32,
+        height: header.layer_size.height() as u32,
+        channels: header
+            .channels
+            .list
+            .iter()
+            .map(|c| ExrChannel {
+                name: c.name.to_string(),
+                sample_type: sample_type(c.sample_type),
+                sampling: (c.sampling.x() as u32, c.sampling.y() as u32),
+            })
+            .collect(),
+        tiled: matches!(header.blocks, exr::meta::BlockDescription::Tiles(_)),
+        deep: header.deep,
+    }
+}
+
+pub(crate) fn layers(data: &[u8]) -> Result<Vec<ExrLayer>> {
+    Ok(read_meta(data)?.headers.iter().map(layer_of).collect())
+}
+
+/// Indices of the color channels of a layer: RGB(A), or luminance (Y)
+/// replicated to RGB.
+struct ColorChannels {
+    rgb: [usize; 3],
+    alpha: Option<usize>,
+}
+
+impl ColorChannels {
+    fn find(layer: &ExrLayer) -> Option<Self> {
+        // Prefer unprefixed channels, then the first `<group>.X` set.
+        let full_res =
+            |c: &&ExrChannel| c.sampling == (1, 1) && c.sample_type != ExrSampleType::U32;
+        let index = |name: &str| layer.channels.iter().position(|c| c.name == name);
+        let prefix = layer
+            .channels
+            .iter()
+            .filter(full_res)
+            .find_map(|c| {
+                let (group, last) = c.name.rsplit_once('.')?;
+                matches!(last, "R" | "Y").then_some(group)
+            })
+            .unwrap_or("");
+        let lookup = |suffix: &str| {
+            index(suffix)
+                .or_else(|| index(&alloc::format!("{prefix}.{suffix}")))
+                .filter(|&i| full_res(&&layer.channels[i]))
+        };
+        let alpha = lookup("A");
+        match (lookup("R"), lookup("G"), lookup("B"), lookup("Y")) {
+            (Some(r), Some(g), Some(b), _) => Some(Self {
+                rgb: [r, g, b],
+                alpha,
+            }),
+            (_, _, _, Some(y)) => Some(Self {
+                rgb: [y, y, y],
+                alpha,
+            }),
+            _ => None,
+        }
+    }
+
+    fn all(&self) -> impl Iterator<Item = usize> + '_ {
+        self.rgb.iter().copied().chain(self.alpha)
+    }
+}
+
+/// Index of the layer to decode: the named one, else the first non-deep
+/// layer with color channels.
+fn select_layer(layers: &[ExrLayer], config: &ExrDecodeConfig) -> Result<usize> {
+    if let Some(name) = &config.layer {
+        return layers
+            .iter()
+            .position(|l| l.name.as_deref() == Some(name.as_str()))
+            .ok_or_else(|| {
+                at!(CodecError::InvalidInput(alloc::format!(
+                    "EXR has no layer named {name:?}"
+                )))
+            });
+    }
+    layers
+        .iter()
+        .position(|l| !l.deep && ColorChannels::find(l).is_some())
+        .or_else(|| layers.iter().position(|l| !l.deep))
+        .ok_or_else(|| {
+            at!(CodecError::UnsupportedOperation {
+                format: crate::exr::format(),
+                detail: "EXR contains only deep data",
+            })
+        })
+}
+
+fn check_limits(limits: Option<&Limits>, layer: &ExrLayer, bytes_per_pixel: u64) -> Result<()> {
+    if let Some(limits) = limits {
+        limits
+            .check_dimensions(layer.width as u64, layer.height as u64)
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+        limits
+            .check_memory(layer.width as u64 * layer.height as u64 * bytes_per_pixel)
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+    }
+    Ok(())
+}
+
+/// Every chunk has an 8-byte offset table entry, so a header declaring
+/// more chunks than the file can index is truncated or corrupt. Checked
+/// before anything is sized from the data window.
+fn ensure_chunk_table(data: &[u8]) -> Result<()> {
+    let entries = read_meta(data)?
+        .headers
+        .iter()
+        .try_fold(0usize, |sum, h| sum.checked_add(h.chunk_count));
+    if entries
+        .and_then(|n| n.checked_mul(8))
+        .is_none_or(|n| n > data.len())
+    {
+        return Err(at!(CodecError::InvalidInput(
+            "EXR chunk table is larger than the file".into()
+        )));
+    }
+    Ok(())
+}
+
+fn ensure_decodable(layer: &ExrLayer) -> Result<()> {
+    if layer.deep {
+        return Err(at!(CodecError::UnsupportedOperation {
+            format: crate::exr::format(),
+            detail: "deep EXR data",
+        }));
+    }
+    Ok(())
+}
+
+/// Stream the blocks of layer `index` to `sink`.
+fn stream_layer(
+    data: &[u8],
+    index: usize,
+    stop: Option<StopToken>,
+    mut sink: impl FnMut(ExrBlock) -> Result<()>,
+) -> Result<()> {
+    let reader = exr::block::read(Cursor::new(data), false).map_err(map_err)?;
+    let chunks = reader
+        .filter_chunks(false, |_meta, tile: TileCoordinates, block| {
+            block.layer == index && tile.is_largest_resolution_level()
+        })
+        .map_err(map_err)?;
+
+    // Errors from the sink or the stop token abort the exr reader; the
+    // original error is kept here and returned instead of `Aborted`.
+    let mut failure: Option<whereat::At<CodecError>> = None;
+    let result = chunks.decompress_sequential(false, |meta, block| {
+        if let Some(s) = &stop
+            && s.check().is_err()
+        {
+            failure = Some(at!(CodecError::Cancelled));
+            return Err(exr::error::Error::Aborted);
+        }
+        let header = &meta.headers[block.index.layer];
+        let (x0, y0) = (
+            block.index.pixel_position.x(),
+            block.index.pixel_position.y(),
+        );
+        let (w, h) = (
+            block.index.pixel_size.width(),
+            block.index.pixel_size.height(),
+        );
+        let list = &header.channels.list;
+        let mut channels: Vec<Option<ExrChannelSamples>> = list
+            .iter()
+            .map(|c| {
+                (c.sampling.x() == 1 && c.sampling.y() == 1).then(|| ExrChannelSamples {
+                    name: c.name.to_string(),
+                    samples: vec![0.0; w * h],
+                })
+            })
+            .collect();
+
+        for line in block.lines(&header.channels) {
+            let Some(target) = channels[line.location.channel].as_mut() else {
+                continue;
+            };
+            let row = line.location.position.y() - y0;
+            let start = row * w + (line.location.position.x() - x0);
+            let out = &mut target.samples[start..start + line.location.sample_count];
+            use exr::meta::attribute::SampleType;
+            match list[line.location.channel].sample_type {
+                SampleType::F16 => {
+                    for (o, s) in out.iter_mut().zip(line.read_samples::<f16>()) {
+                        *o = s?.to_f32();
+                    }
+                }
+                SampleType::F32 => {
+                    for (o, s) in out.iter_mut().zip(line.read_samples::<f32>()) {
+                        *o = s?;
+                    }
+                }
+                SampleType::U32 => {
+                    for (o, s) in out.iter_mut().zip(line.read_samples::<u32>()) {
+                        *o = s? as f32;
+                    }
+                }
+            }
+        }
+
+        let block = ExrBlock {
+            x: x0 as u32,
+            y: y0 as u32,
+            width: w as u32,
+            height: h as u32,
+            channels: channels.into_iter().flatten().collect(),
+        };
+        sink(block).map_err(|e| {
+            failure = Some(e);
+            exr::error::Error::Aborted
+        })
+    });
+    match (result, failure) {
+        (_, Some(e)) => Err(e),
+        (Err(e), None) => Err(map_err(e)),
+        (Ok(()), None) => Ok(()),
+    }
+}
+
+pub(crate) fn decode_blocks(
+    data: &[u8],
+    config: &ExrDecodeConfig,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+    sink: impl FnMut(ExrBlock) -> Result<()>,
+) -> Result<()> {
+    let layers = layers(data)?;
+    let index = select_layer(&layers, config)?;
+    let layer = &layers[index];
+    ensure_decodable(layer)?;
+    ensure_chunk_table(data)?;
+    // Blocks carry every full-resolution channel as f32.
+    let full_res = layer
+        .channels
+        .iter()
+        .filter(|c| c.sampling == (1, 1))
+        .count();
+    check_limits(limits, layer, full_res as u64 * 4)?;
+    stream_layer(data, index, stop, sink)
+}
+
+fn decode_config(codec_config: Option<&CodecConfig>) -> ExrDecodeConfig {
+    codec_config
+        .and_then(|c| c.exr_decoder.as_deref())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Precision and channels of the layer `config` selects.
+fn plan(layer: &ExrLayer, config: &ExrDecodeConfig) -> Result<(ColorChannels, bool)> {
+    let colors = ColorChannels::find(layer).ok_or_else(|| {
+        at!(CodecError::UnsupportedOperation {
+            format: crate::exr::format(),
+            detail: "EXR layer has no RGB or Y channels",
+        })
+    })?;
+    let half = match config.precision {
+        ExrPrecision::F16 => true,
+        ExrPrecision::F32 => false,
+        ExrPrecision::Native => colors
+            .all()
+            .all(|i| layer.channels[i].sample_type == ExrSampleType::F16),
+    };
+    Ok((colors, half))
+}
+
+fn image_info(layer: &ExrLayer, has_alpha: bool, half: bool) -> ImageInfo {
+    let mut info = ImageInfo::new(layer.width, layer.height, crate::exr::format())
+        .with_bit_depth(if half { 16 } else { 32 })
+        .with_channel_count(if has_alpha { 4 } else { 3 });
+    info.has_alpha = has_alpha;
+    // BT.709 primaries, linear transfer
+    info.source_color.cicp = Some(Cicp::new(1, 8, 0, true));
+    info
+}
+
+pub(crate) fn probe(data: &[u8]) -> Result<ImageInfo> {
+    let layers = layers(data)?;
+    let layer = &layers[select_layer(&layers, &ExrDecodeConfig::default())?];
+    let (colors, half) = plan(layer, &ExrDecodeConfig::default())?;
+    Ok(image_info(layer, colors.alpha.is_some(), half))
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    codec_config: Option<&CodecConfig>,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<DecodeOutput> {
+    let config = decode_config(codec_config);
+    let layers = layers(data)?;
+    let index = select_layer(&layers, &config)?;
+    let layer = &layers[index];
+    ensure_decodable(layer)?;
+    ensure_chunk_table(data)?;
+    let (colors, half) = plan(layer, &config)?;
+    let has_alpha = colors.alpha.is_some();
+    let channels = if has_alpha { 4 } else { 3 };
+    let sample_bytes = if half { 2 } else { 4 };
+    check_limits(limits, layer, (channels * sample_bytes) as u64)?;
+
+    // Block channel lists only hold full-resolution channels; map layer
+    // channel indices to positions in that list.
+    let full_res: Vec<usize> = (0..layer.channels.len())
+        .filter(|&i| layer.channels[i].sampling == (1, 1))
+        .collect();
+    let sources = colors
+        .all()
+        .map(|i| {
+            full_res.iter().position(|&f| f == i).ok_or_else(|| {
+                at!(CodecError::InvalidInput(alloc::format!(
+                    "EXR channel {:?} is subsampled",
+                    layer.channels[i].name
+                )))
+            })
+        })
+        .collect::<Result<Vec<usize>>>()?;
+
+    // Samples are written straight into the output buffer, so the decode
+    // holds no more than the memory checked above.
+    let write = |bytes: &mut [u8], v: f32| {
+        if half {
+            bytes.copy_from_slice(&f16::from_f32(v).to_ne_bytes());
+        } else {
+            bytes.copy_from_slice(&v.to_ne_bytes());
+        }
+    };
+    let read = |bytes: &[u8]| {
+        if half {
+            f16::from_ne_bytes([bytes[0], bytes[1]]).to_f32()
+        } else {
+            f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+        }
+    };
+    let (width, height) = (layer.width as usize, layer.height as usize);
+    let pixel_bytes = channels * sample_bytes;
+    // Highly compressed chunks can still back a huge window; allocate
+    // fallibly rather than abort.
+    let mut bytes = Vec::new();
+    let len = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(pixel_bytes))
+        .filter(|&len| bytes.try_reserve_exact(len).is_ok())
+        .ok_or_else(|| {
+            at!(CodecError::LimitExceeded(
+                "EXR image too large to allocate".into()
+            ))
+        })?;
+    bytes.resize(len, 0);
+    stream_layer(data, index, stop, |block| {
+        let bw = block.width as usize;
+        for row in 0..block.height as usize {
+            let y = block.y as usize + row;
+            for col in 0..bw {
+                let px = (y * width + block.x as usize + col) * pixel_bytes;
+                for (c, &src) in sources.iter().enumerate() {
+                    let at = px + c * sample_bytes;
+                    write(
+                        &mut bytes[at..at + sample_bytes],
+                        block.channels[src].samples[row * bw + col],
+                    );
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    if has_alpha {
+        for px in bytes.chunks_exact_mut(pixel_bytes) {
+            let a = read(&px[3 * sample_bytes..]);
+            if a > 0.0 {
+                for c in px[..3 * sample_bytes].chunks_exact_mut(sample_bytes) {
+                    let v = read(c) / a;
+                    write(c, v);
+                }
+            }
+        }
+    }
+
+    let channel_type = if half {
+        ChannelType::F16
+    } else {
+        ChannelType::F32
+    };
+    let buffer = PixelBuffer::from_vec(
+        bytes,
+        layer.width,
+        layer.height,
+        descriptor(channel_type, has_alpha),
+    )
+    .map_err(|_| {
+        at!(CodecError::InvalidInput(
+            "failed to create PixelBuffer for EXR".into()
+        ))
+    })?;
+    Ok(DecodeOutput::new(
+        buffer,
+        image_info(layer, has_alpha, half),
+    ))
+}
+
+fn encode(
+    pixels: PixelSlice<'_>,
+    config: &ExrEncodeConfig,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<EncodeOutput> {
+    use exr::prelude::{
+        AnyChannel, AnyChannels, Blocks, Compression, Encoding, FlatSamples, Image, Layer,
+        LayerAttributes, LineOrder, Vec2, WritableImage as _,
+    };
+
+    let (width, height) = (pixels.width() as usize, pixels.rows() as usize);
+    if let Some(limits) = limits {
+        limits
+            .check_dimensions(width as u64, height as u64)
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+    }
+    if let Some(s) = &stop
+        && s.check().is_err()
+    {
+        return Err(at!(CodecError::Cancelled));
+    }
+
+    let descriptor = pixels.descriptor();
+    let channels = match descriptor.layout() {
+        ChannelLayout::Rgb => 3,
+        ChannelLayout::Rgba => 4,
+        other => {
+            return Err(at!(CodecError::InvalidInput(alloc::format!(
+                "EXR encode: unsupported channel layout {other:?}"
+            ))));
+        }
+    };
+    if descriptor.channel_type() != ChannelType::F32 {
+        return Err(at!(CodecError::InvalidInput(
+            "EXR encode expects f32 samples".into()
+        )));
+    }
+
+    // Planar, premultiplied copies of each channel.
+    let mut planes = vec![Vec::with_capacity(width * height); channels];
+    let data = pixels.as_strided_bytes();
+    for y in 0..height {
+        let row = &data[y * pixels.stride()..][..width * channels * 4];
+        for px in row.chunks_exact(channels * 4) {
+            let s = |i: usize| {
+                f32::from_ne_bytes([px[i * 4], px[i * 4 + 1], px[i * 4 + 2], px[i * 4 + 3]])
+            };
+            let a = if channels == 4 { s(3) } else { 1.0 };
+            for (c, plane) in planes.iter_mut().enumerate() {
+                plane.push(if c < 3 { s(c) * a } else { a });
+            }
+        }
+    }
+
+    let names = ["R", "G", "B", "A"];
+    let list: Vec<AnyChannel<FlatSamples>> = planes
+        .into_iter()
+        .zip(names)
+        .map(|(plane, name)| {
+            let samples = if config.half {
+                FlatSamples::F16(plane.into_iter().map(f16::from_f32).collect())
+            } else {
+                FlatSamples::F32(plane)
+            };
+            AnyChannel::new(name, samples)
+        })
+        .collect();
+
+    let encoding = Encoding {
+        compression: match config.compression {
+            ExrCompression::None => Compression::Uncompressed,
+            ExrCompression::Zip => Compression::ZIP16,
+            ExrCompression::Piz => Compression::PIZ,
+        },
+        blocks: match config.tile_size {
+            Some(t) => Blocks::Tiles(Vec2(t as usize, t as usize)),
+            None => Blocks::ScanLines,
+        },
+        line_order: LineOrder::Increasing,
+    };
+    let attributes = match &config.layer_name {
+        Some(name) => LayerAttributes::named(name.as_str()),
+        None => LayerAttributes::default(),
+    };
+    let layer = Layer::new(
+        (width, height),
+        attributes,
+        encoding,
+        AnyChannels::sort(list.into()),
+    );
+
+    let mut out = Vec::new();
+    Image::from_layer(layer)
+        .write()
+        .to_buffered(Cursor::new(&mut out))
+        .map_err(map_err)?;
+    Ok(EncodeOutput::new(out, crate::exr::format()))
+}
+
+pub(crate) fn build_trait_encoder<'a>(params: EncodeParams<'a>) -> BuiltEncoder<'a> {
+    let config = params
+        .codec_config
+        .and_then(|c| c.exr_encoder.as_deref())
+        .cloned()
+        .unwrap_or_default();
+    BuiltEncoder {
+        encoder: alloc::boxed::Box::new(move |pixels| {
+            encode(pixels, &config, params.limits, params.stop)
+        }),
+        supported: &SUPPORTED,
+    }
+}
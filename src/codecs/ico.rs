@@ -0,0 +1,412 @@
+//! ICO/CUR container adapter. Entries are decoded with the PNG and BMP
+//! codecs; encode packs PNG entries.
+
+use alloc::vec::Vec;
+
+use crate::config::CodecConfig;
+use crate::dispatch::{BuiltEncoder, EncodeParams};
+use crate::error::Result;
+use crate::ico::{IcoDecodeConfig, IcoDirectory, IcoEncodeConfig, IcoEntry, IcoKind, IcoPayload};
+use crate::limits::Stop;
+use crate::{CodecError, DecodeOutput, ImageInfo, Limits, StopToken};
+use whereat::at;
+use zencodec::encode::EncodeOutput;
+use zenpixels::{PixelBuffer, PixelDescriptor, PixelSlice};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const HEADER_LEN: usize = 6;
+const ENTRY_LEN: usize = 16;
+
+/// Encoder input: straight-alpha sRGB RGBA8.
+static SUPPORTED: [PixelDescriptor; 1] = [PixelDescriptor::RGBA8_SRGB];
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn invalid(detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!("ICO: {detail}")))
+}
+
+/// Header check used by the format definition. The ICO signature is only
+/// four bytes, so the first directory entry is sanity-checked too.
+pub(crate) fn is_ico(data: &[u8]) -> bool {
+    if data.len() < HEADER_LEN + ENTRY_LEN || u16_at(data, 0) != 0 {
+        return false;
+    }
+    let count = usize::from(u16_at(data, 4));
+    let directory_end = HEADER_LEN + count * ENTRY_LEN;
+    matches!(u16_at(data, 2), 1 | 2)
+        && count > 0
+        && data[HEADER_LEN + 3] == 0
+        && u32_at(data, HEADER_LEN + 8) > 0
+        && u32_at(data, HEADER_LEN + 12) as usize >= directory_end
+}
+
+pub(crate) fn directory(data: &[u8]) -> Result<IcoDirectory> {
+    if !is_ico(data) {
+        return Err(invalid("not an ICO/CUR file"));
+    }
+    let kind = if u16_at(data, 2) == 2 {
+        IcoKind::Cursor
+    } else {
+        IcoKind::Icon
+    };
+    let count = usize::from(u16_at(data, 4));
+    if data.len() < HEADER_LEN + count * ENTRY_LEN {
+        return Err(invalid("truncated directory"));
+    }
+    let entries = (0..count)
+        .map(|index| {
+            let e = &data[HEADER_LEN + index * ENTRY_LEN..][..ENTRY_LEN];
+            let (offset, byte_len) = (u32_at(e, 12), u32_at(e, 8));
+            let payload = (offset as usize)
+                .checked_add(byte_len as usize)
+                .and_then(|end| data.get(offset as usize..end))
+                .ok_or_else(|| invalid("entry data out of bounds"))?;
+            // Directory sizes are one byte (0 = 256); trust the payload.
+            let (payload_kind, width, height, bit_depth) = if payload.starts_with(&PNG_SIGNATURE) {
+                png_header(payload)?
+            } else {
+                bmp_header(payload)?
+            };
+            Ok(IcoEntry {
+                index,
+                width,
+                height,
+                bit_depth,
+                payload: payload_kind,
+                hotspot: (kind == IcoKind::Cursor).then(|| (u16_at(e, 4), u16_at(e, 6))),
+                offset,
+                byte_len,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(IcoDirectory { kind, entries })
+}
+
+fn png_header(png: &[u8]) -> Result<(IcoPayload, u32, u32, u16)> {
+    if png.len() < 26 {
+        return Err(invalid("truncated PNG entry"));
+    }
+    let be = |at: usize| u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]);
+    let channels = match png[25] {
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => 1,
+    };
+    Ok((
+        IcoPayload::Png,
+        be(16),
+        be(20),
+        u16::from(png[24]) * channels,
+    ))
+}
+
+fn bmp_header(dib: &[u8]) -> Result<(IcoPayload, u32, u32, u16)> {
+    if dib.len() < 40 || u32_at(dib, 0) < 40 {
+        return Err(invalid("truncated BMP entry"));
+    }
+    let width = (u32_at(dib, 4) as i32).unsigned_abs();
+    // The stored height covers the color data and the AND mask.
+    let height = (u32_at(dib, 8) as i32).unsigned_abs() / 2;
+    Ok((IcoPayload::Bmp, width, height, u16_at(dib, 14)))
+}
+
+fn select<'d>(dir: &'d IcoDirectory, config: &IcoDecodeConfig) -> Result<&'d IcoEntry> {
+    dir.select(config)
+        .ok_or_else(|| invalid("no entry matches the selection"))
+}
+
+fn image_info(entry: &IcoEntry) -> ImageInfo {
+    let mut info = ImageInfo::new(entry.width, entry.height, crate::ico::format())
+        .with_bit_depth(8)
+        .with_channel_count(4);
+    info.has_alpha = true;
+    info
+}
+
+pub(crate) fn probe(data: &[u8]) -> Result<ImageInfo> {
+    let dir = directory(data)?;
+    Ok(image_info(select(&dir, &IcoDecodeConfig::default())?))
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    codec_config: Option<&CodecConfig>,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<DecodeOutput> {
+    let config = codec_config
+        .and_then(|c| c.ico_decoder.as_deref())
+        .cloned()
+        .unwrap_or_default();
+    let dir = directory(data)?;
+    let entry = select(&dir, &config)?;
+    if let Some(limits) = limits {
+        limits
+            .check_dimensions(u64::from(entry.width), u64::from(entry.height))
+            .map_err(|msg| at!(CodecError::LimitExceeded(alloc::string::String::from(msg))))?;
+    }
+    let payload = &data[entry.offset as usize..][..entry.byte_len as usize];
+    let (rgba, width, height) = match entry.payload {
+        IcoPayload::Png => {
+            let output = crate::codecs::png::decode(payload, limits, stop, None)?;
+            rgba8(output)
+        }
+        IcoPayload::Bmp => decode_bmp(payload, limits, stop)?,
+    };
+    let pixels = PixelBuffer::from_vec(rgba, width, height, PixelDescriptor::RGBA8_SRGB)
+        .map_err(|_| invalid("failed to create PixelBuffer"))?;
+    let mut info = image_info(entry);
+    info.width = width;
+    info.height = height;
+    Ok(DecodeOutput::new(pixels, info))
+}
+
+/// Decoded pixels as tightly packed RGBA8.
+fn rgba8(output: DecodeOutput) -> (Vec<u8>, u32, u32) {
+    use zenpixels_convert::PixelBufferConvertTypedExt as _;
+    let rgba = output.into_buffer().to_rgba8();
+    let img = rgba.as_imgref();
+    let data = img.pixels().flat_map(|p| [p.r, p.g, p.b, p.a]).collect();
+    (data, img.width() as u32, img.height() as u32)
+}
+
+/// Alpha bits of a 32-bpp icon pixel. BI_BITFIELDS headers may name them
+/// (V3 and later); otherwise they are the byte the color masks leave unused.
+fn alpha_mask(dib: &[u8], header_len: usize, compression: u32) -> u32 {
+    let mask = |at: usize| dib.get(at..at + 4).map_or(0, |b| u32_at(b, 0));
+    if compression != 3 {
+        return 0xFF00_0000;
+    }
+    if header_len >= 56 && mask(52) != 0 {
+        return mask(52);
+    }
+    let color = mask(40) | mask(44) | mask(48);
+    if color & 0xFF00_0000 == 0 {
+        0xFF00_0000
+    } else {
+        0
+    }
+}
+
+/// Decode a headerless icon BMP: give it a file header and its real
+/// height, decode with the BMP codec, then apply the icon's transparency.
+fn decode_bmp(
+    dib: &[u8],
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<(Vec<u8>, u32, u32)> {
+    let header_len = u32_at(dib, 0) as usize;
+    let stored_height = u32_at(dib, 8) as i32;
+    let bpp = usize::from(u16_at(dib, 14));
+    let compression = u32_at(dib, 16);
+    let colors_used = if header_len >= 36 { u32_at(dib, 32) } else { 0 } as usize;
+    let palette_len = if bpp <= 8 {
+        4 * if colors_used > 0 {
+            colors_used
+        } else {
+            1 << bpp
+        }
+    } else {
+        0
+    };
+    let masks_len = if compression == 3 && header_len == 40 {
+        12
+    } else {
+        0
+    };
+    let pixels_at = header_len + masks_len + palette_len;
+
+    let mut file = Vec::with_capacity(14 + dib.len());
+    file.extend_from_slice(b"BM");
+    file.extend_from_slice(&((14 + dib.len()) as u32).to_le_bytes());
+    file.extend_from_slice(&[0; 4]);
+    file.extend_from_slice(&((14 + pixels_at) as u32).to_le_bytes());
+    file.extend_from_slice(dib);
+    let height_field = 14 + 8;
+    file[height_field..height_field + 4].copy_from_slice(&(stored_height / 2).to_le_bytes());
+
+    let (mut rgba, width, height) = rgba8(crate::codecs::bmp::decode(&file, limits, stop, None)?);
+    // BI_RGB and BI_BITFIELDS entries both carry their transparency in
+    // the alpha channel or the AND mask; RLE entries have neither.
+    if compression != 0 && compression != 3 {
+        return Ok((rgba, width, height));
+    }
+
+    // Rows are bottom-up in both the color data and the mask.
+    let (w, h) = (width as usize, height as usize);
+    let color_stride = (w * bpp).div_ceil(32) * 4;
+    let row_of = |y: usize| if stored_height > 0 { h - 1 - y } else { y };
+    if bpp == 32 {
+        let alpha_mask = alpha_mask(dib, header_len, compression);
+        let shift = alpha_mask.trailing_zeros();
+        let max = alpha_mask.checked_shr(shift).unwrap_or(0);
+        let alpha = |x: usize, y: usize| {
+            let at = pixels_at + row_of(y) * color_stride + x * 4;
+            let px = u32::from_le_bytes(dib.get(at..at + 4)?.try_into().ok()?);
+            Some((u64::from((px & alpha_mask) >> shift) * 255 / u64::from(max)) as u8)
+        };
+        let has_alpha =
+            max != 0 && (0..h).any(|y| (0..w).any(|x| alpha(x, y).is_some_and(|a| a != 0)));
+        if has_alpha {
+            for y in 0..h {
+                for x in 0..w {
+                    rgba[(y * w + x) * 4 + 3] = alpha(x, y).unwrap_or(0);
+                }
+            }
+            return Ok((rgba, width, height));
+        }
+    }
+    let mask_at = pixels_at + color_stride * h;
+    let mask_stride = w.div_ceil(32) * 4;
+    if dib.len() >= mask_at + mask_stride * h {
+        for y in 0..h {
+            let row = &dib[mask_at + row_of(y) * mask_stride..][..mask_stride];
+            for x in 0..w {
+                let transparent = (row[x / 8] >> (7 - x % 8)) & 1 == 1;
+                rgba[(y * w + x) * 4 + 3] = if transparent { 0 } else { 255 };
+            }
+        }
+    }
+    Ok((rgba, width, height))
+}
+
+/// Fit `src` into a transparent `size`×`size` square, keeping its aspect
+/// ratio, with an alpha-weighted box filter.
+fn fit_square(src: &[u8], width: usize, height: usize, size: usize) -> Vec<u8> {
+    let longest = width.max(height);
+    let dw = (width * size).div_ceil(longest).clamp(1, size);
+    let dh = (height * size).div_ceil(longest).clamp(1, size);
+    let (ox, oy) = ((size - dw) / 2, (size - dh) / 2);
+    let span = |d: usize, src_len: usize, dst_len: usize| {
+        let start = d * src_len / dst_len;
+        let end = ((d + 1) * src_len).div_ceil(dst_len).max(start + 1);
+        start..end.min(src_len)
+    };
+
+    let mut out = alloc::vec![0u8; size * size * 4];
+    for y in 0..dh {
+        let rows = span(y, height, dh);
+        for x in 0..dw {
+            let cols = span(x, width, dw);
+            let mut sum = [0u64; 4];
+            let mut count = 0u64;
+            for sy in rows.clone() {
+                for sx in cols.clone() {
+                    let px = &src[(sy * width + sx) * 4..][..4];
+                    let a = u64::from(px[3]);
+                    for c in 0..3 {
+                        sum[c] += u64::from(px[c]) * a;
+                    }
+                    sum[3] += a;
+                    count += 1;
+                }
+            }
+            let dst = &mut out[((y + oy) * size + x + ox) * 4..][..4];
+            // Fully transparent areas keep zero color.
+            for (d, s) in dst[..3].iter_mut().zip(&sum) {
+                *d = (s + sum[3] / 2).checked_div(sum[3]).unwrap_or(0) as u8;
+            }
+            dst[3] = ((sum[3] + count / 2) / count) as u8;
+        }
+    }
+    out
+}
+
+fn encode(pixels: PixelSlice<'_>, params: &EncodeParams<'_>) -> Result<EncodeOutput> {
+    let config: IcoEncodeConfig = params
+        .codec_config
+        .and_then(|c| c.ico_encoder.as_deref())
+        .cloned()
+        .unwrap_or_default();
+    let (width, height) = (pixels.width() as usize, pixels.rows() as usize);
+    if width == 0 || height == 0 {
+        return Err(invalid("empty image"));
+    }
+    let data = pixels.as_strided_bytes();
+    let src: Vec<u8> = (0..height)
+        .flat_map(|y| &data[y * pixels.stride()..][..width * 4])
+        .copied()
+        .collect();
+
+    let longest = width.max(height) as u32;
+    let mut sizes: Vec<u32> = config
+        .sizes
+        .iter()
+        .copied()
+        .filter(|&s| s <= longest)
+        .collect();
+    if sizes.is_empty() {
+        sizes.push(longest.min(256));
+    }
+    sizes.sort_unstable();
+    sizes.dedup();
+
+    let mut images = Vec::with_capacity(sizes.len());
+    for &size in &sizes {
+        if let Some(s) = &params.stop
+            && s.check().is_err()
+        {
+            return Err(at!(CodecError::Cancelled));
+        }
+        let size = size as usize;
+        let icon = if (width, height) == (size, size) {
+            src.clone()
+        } else {
+            fit_square(&src, width, height, size)
+        };
+        let slice = PixelSlice::new(
+            &icon,
+            size as u32,
+            size as u32,
+            size * 4,
+            PixelDescriptor::RGBA8_SRGB,
+        )
+        .map_err(|e| at!(CodecError::InvalidInput(alloc::format!("pixel slice: {e}"))))?;
+        let png = crate::codecs::png::build_trait_encoder(EncodeParams {
+            quality: None,
+            effort: params.effort,
+            lossless: true,
+            metadata: None,
+            codec_config: params.codec_config,
+            limits: params.limits,
+            stop: params.stop.clone(),
+            encode_policy: None,
+        });
+        images.push((size, (png.encoder)(slice)?.into_vec()));
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(images.len() as u16).to_le_bytes());
+    let mut offset = HEADER_LEN + images.len() * ENTRY_LEN;
+    for (size, png) in &images {
+        // 256 is stored as 0.
+        let dim = (*size % 256) as u8;
+        out.extend_from_slice(&[dim, dim, 0, 0]);
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&32u16.to_le_bytes());
+        out.extend_from_slice(&(png.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += png.len();
+    }
+    for (_, png) in &images {
+        out.extend_from_slice(png);
+    }
+    Ok(EncodeOutput::new(out, crate::ico::format()))
+}
+
+pub(crate) fn build_trait_encoder<'a>(params: EncodeParams<'a>) -> BuiltEncoder<'a> {
+    BuiltEncoder {
+        encoder: alloc::boxed::Box::new(move |pixels| encode(pixels, &params)),
+        supported: &SUPPORTED,
+    }
+}
@@ -0,0 +1,2076 @@
+//! JPEG 2000 Part 1 codestream decoding (ITU-T T.800): marker segments,
+//! tier-2 packet headers, EBCOT tier-1 block decoding, dequantization and
+//! the inverse wavelet and component transforms.
+//!
+//! Crate-independent so it stays testable on its own: errors are
+//! [`J2kError`], cancellation is a callback, and output is one plane of
+//! integer samples per component. The JP2 adapter turns planes into pixels.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Why a codestream couldn't be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum J2kError {
+    Invalid(&'static str),
+    Unsupported(&'static str),
+    /// A buffer sized from the headers couldn't be allocated.
+    TooLarge,
+    Cancelled,
+}
+
+type Result<T> = core::result::Result<T, J2kError>;
+
+const SOC: u16 = 0xFF4F;
+const CAP: u16 = 0xFF50;
+const SIZ: u16 = 0xFF51;
+const COD: u16 = 0xFF52;
+const COC: u16 = 0xFF53;
+const QCD: u16 = 0xFF5C;
+const QCC: u16 = 0xFF5D;
+const RGN: u16 = 0xFF5E;
+const POC: u16 = 0xFF5F;
+const PPM: u16 = 0xFF60;
+const PPT: u16 = 0xFF61;
+const SOT: u16 = 0xFF90;
+const SOP: u16 = 0xFF91;
+const EPH: u16 = 0xFF92;
+const SOD: u16 = 0xFF93;
+const EOC: u16 = 0xFFD9;
+
+/// Code-block style flags (SPcod).
+const CB_BYPASS: u8 = 0x01;
+const CB_RESET: u8 = 0x02;
+const CB_TERMALL: u8 = 0x04;
+const CB_VCAUSAL: u8 = 0x08;
+const CB_SEGSYM: u8 = 0x20;
+const CB_HT: u8 = 0x40;
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]))
+}
+
+fn ceil_div(a: i64, b: i64) -> i64 {
+    -((-a).div_euclid(b))
+}
+
+/// `2^e` as f32 for `-126 <= e <= 127`.
+fn pow2(e: i32) -> f32 {
+    f32::from_bits(((e.clamp(-126, 127) + 127) as u32) << 23)
+}
+
+/// `len` copies of `value`. Header fields size most buffers, so the
+/// allocation is fallible rather than trusted.
+fn filled<T: Clone>(len: usize, value: T) -> Result<Vec<T>> {
+    let mut v = reserved(len)?;
+    v.resize(len, value);
+    Ok(v)
+}
+
+/// An empty vector with room for `len` items.
+fn reserved<T>(len: usize) -> Result<Vec<T>> {
+    let mut v = Vec::new();
+    v.try_reserve_exact(len).map_err(|_| J2kError::TooLarge)?;
+    Ok(v)
+}
+
+/// `f` applied to each of `v`, into a fallibly allocated vector.
+fn mapped<A: Copy, B>(v: &[A], f: impl Fn(A) -> B) -> Result<Vec<B>> {
+    let mut out = reserved(v.len())?;
+    out.extend(v.iter().map(|&a| f(a)));
+    Ok(out)
+}
+
+/// `w * h`, failing on overflow.
+fn area(w: usize, h: usize) -> Result<usize> {
+    w.checked_mul(h).ok_or(J2kError::TooLarge)
+}
+
+/// Bounds-checked big-endian reader over one marker segment.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Result<u8> {
+        let v = *self
+            .data
+            .get(self.pos)
+            .ok_or(J2kError::Invalid("truncated marker segment"))?;
+        self.pos += 1;
+        Ok(v)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from(self.u8()?) << 8 | u16::from(self.u8()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from(self.u16()?) << 16 | u32::from(self.u16()?))
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+}
+
+// ─── Main and tile-part headers ───
+
+/// One image component from SIZ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ComponentInfo {
+    pub(crate) precision: u8,
+    pub(crate) signed: bool,
+    pub(crate) dx: u32,
+    pub(crate) dy: u32,
+}
+
+/// Image and tile geometry on the reference grid (SIZ).
+#[derive(Clone, Debug)]
+pub(crate) struct Siz {
+    pub(crate) x0: u32,
+    pub(crate) y0: u32,
+    pub(crate) x1: u32,
+    pub(crate) y1: u32,
+    pub(crate) tile_x0: u32,
+    pub(crate) tile_y0: u32,
+    pub(crate) tile_w: u32,
+    pub(crate) tile_h: u32,
+    pub(crate) components: Vec<ComponentInfo>,
+}
+
+impl Siz {
+    pub(crate) fn tiles_x(&self) -> u32 {
+        (self.x1 - self.tile_x0).div_ceil(self.tile_w)
+    }
+
+    pub(crate) fn tiles_y(&self) -> u32 {
+        (self.y1 - self.tile_y0).div_ceil(self.tile_h)
+    }
+
+    /// Tile `index` as `[x0, x1) × [y0, y1)` on the reference grid.
+    fn tile_rect(&self, index: u32) -> [i64; 4] {
+        let (p, q) = (index % self.tiles_x(), index / self.tiles_x());
+        let tx = i64::from(self.tile_x0) + i64::from(p) * i64::from(self.tile_w);
+        let ty = i64::from(self.tile_y0) + i64::from(q) * i64::from(self.tile_h);
+        [
+            tx.max(i64::from(self.x0)),
+            ty.max(i64::from(self.y0)),
+            (tx + i64::from(self.tile_w)).min(i64::from(self.x1)),
+            (ty + i64::from(self.tile_h)).min(i64::from(self.y1)),
+        ]
+    }
+}
+
+/// Per-component coding style (SPcod/SPcoc).
+#[derive(Clone, Debug)]
+struct CodingStyle {
+    levels: u8,
+    cb_w: u8,
+    cb_h: u8,
+    cb_style: u8,
+    reversible: bool,
+    /// `(PPx, PPy)` per resolution, lowest first.
+    precincts: Vec<(u8, u8)>,
+}
+
+/// Progression, layers, MCT and packet markers (SGcod/Scod).
+#[derive(Clone, Copy, Debug)]
+struct Progression {
+    order: u8,
+    layers: u16,
+    mct: bool,
+    sop: bool,
+    eph: bool,
+}
+
+/// Quantization (SQcd/SPqcd): guard bits, style and `(exponent, mantissa)`
+/// per subband.
+#[derive(Clone, Debug)]
+struct Quantization {
+    guard: u8,
+    style: u8,
+    steps: Vec<(u8, u16)>,
+}
+
+/// COD/COC/QCD/QCC from one header. Component entries override the
+/// defaults from the same header.
+#[derive(Clone, Debug, Default)]
+struct Params {
+    progression: Option<Progression>,
+    cod: Option<CodingStyle>,
+    coc: Vec<Option<CodingStyle>>,
+    qcd: Option<Quantization>,
+    qcc: Vec<Option<Quantization>>,
+}
+
+/// Parsed main header.
+#[derive(Clone, Debug)]
+pub(crate) struct Header {
+    pub(crate) siz: Siz,
+    main: Params,
+    /// Offset of the first SOT marker.
+    tiles_at: usize,
+}
+
+impl Header {
+    /// Decomposition levels of the main coding style; the largest valid
+    /// reduction.
+    pub(crate) fn levels(&self) -> u8 {
+        (0..self.siz.components.len())
+            .filter_map(|c| self.coding(None, c))
+            .map(|s| s.levels)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Number of quality layers.
+    pub(crate) fn layers(&self) -> u16 {
+        self.main.progression.map_or(1, |p| p.layers)
+    }
+
+    /// Whether the first component uses the reversible 5/3 wavelet.
+    pub(crate) fn reversible(&self) -> bool {
+        self.coding(None, 0).is_some_and(|s| s.reversible)
+    }
+
+    /// Precedence: tile COC, tile COD, main COC, main COD.
+    fn coding<'a>(&'a self, tile: Option<&'a Params>, c: usize) -> Option<&'a CodingStyle> {
+        tile.and_then(|t| t.coc.get(c).and_then(Option::as_ref).or(t.cod.as_ref()))
+            .or_else(|| self.main.coc.get(c).and_then(Option::as_ref))
+            .or(self.main.cod.as_ref())
+    }
+
+    fn quantization<'a>(&'a self, tile: Option<&'a Params>, c: usize) -> Option<&'a Quantization> {
+        tile.and_then(|t| t.qcc.get(c).and_then(Option::as_ref).or(t.qcd.as_ref()))
+            .or_else(|| self.main.qcc.get(c).and_then(Option::as_ref))
+            .or(self.main.qcd.as_ref())
+    }
+
+    fn progression(&self, tile: Option<&Params>) -> Option<Progression> {
+        tile.and_then(|t| t.progression).or(self.main.progression)
+    }
+}
+
+/// Whether `data` starts with SOC followed by SIZ.
+pub(crate) fn is_codestream(data: &[u8]) -> bool {
+    be16(data, 0) == Some(SOC) && be16(data, 2) == Some(SIZ)
+}
+
+fn read_siz(r: &mut Reader<'_>) -> Result<Siz> {
+    let _capabilities = r.u16()?;
+    let (x1, y1, x0, y0) = (r.u32()?, r.u32()?, r.u32()?, r.u32()?);
+    let (tile_w, tile_h, tile_x0, tile_y0) = (r.u32()?, r.u32()?, r.u32()?, r.u32()?);
+    let count = r.u16()?;
+    if count == 0 || x1 <= x0 || y1 <= y0 || tile_w == 0 || tile_h == 0 {
+        return Err(J2kError::Invalid("empty image or tile grid"));
+    }
+    let past =
+        |origin: u32, size: u32, at: u32| u64::from(origin) + u64::from(size) <= u64::from(at);
+    if tile_x0 > x0 || tile_y0 > y0 || past(tile_x0, tile_w, x0) || past(tile_y0, tile_h, y0) {
+        return Err(J2kError::Invalid("tile grid does not cover the image"));
+    }
+    let components = (0..count)
+        .map(|_| {
+            let s = r.u8()?;
+            let (dx, dy) = (r.u8()?, r.u8()?);
+            if dx == 0 || dy == 0 {
+                return Err(J2kError::Invalid("zero component subsampling"));
+            }
+            let precision = (s & 0x7f) + 1;
+            if precision > 30 {
+                return Err(J2kError::Unsupported("component precision above 30 bits"));
+            }
+            Ok(ComponentInfo {
+                precision,
+                signed: s & 0x80 != 0,
+                dx: u32::from(dx),
+                dy: u32::from(dy),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Siz {
+        x0,
+        y0,
+        x1,
+        y1,
+        tile_x0,
+        tile_y0,
+        tile_w,
+        tile_h,
+        components,
+    })
+}
+
+fn read_coding(r: &mut Reader<'_>, custom_precincts: bool) -> Result<CodingStyle> {
+    let levels = r.u8()?;
+    // Code-block size exponents are stored minus 2, each at most 8 and
+    // together at most 8.
+    let (xcb, ycb) = (r.u8()?, r.u8()?);
+    let cb_style = r.u8()?;
+    let reversible = r.u8()? == 1;
+    if levels > 32 || xcb > 8 || ycb > 8 || xcb + ycb > 8 {
+        return Err(J2kError::Invalid("bad coding style"));
+    }
+    let (cb_w, cb_h) = (xcb + 2, ycb + 2);
+    if cb_style & CB_HT != 0 {
+        return Err(J2kError::Unsupported("HTJ2K code-blocks"));
+    }
+    let precincts = (0..=levels)
+        .map(|_| {
+            if custom_precincts {
+                let b = r.u8()?;
+                Ok((b & 0xf, b >> 4))
+            } else {
+                Ok((15, 15))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if precincts.iter().skip(1).any(|&(x, y)| x == 0 || y == 0) {
+        return Err(J2kError::Invalid("zero precinct size above resolution 0"));
+    }
+    Ok(CodingStyle {
+        levels,
+        cb_w,
+        cb_h,
+        cb_style,
+        reversible,
+        precincts,
+    })
+}
+
+fn read_quantization(r: &mut Reader<'_>) -> Result<Quantization> {
+    let s = r.u8()?;
+    let style = s & 0x1f;
+    let mut steps = Vec::new();
+    match style {
+        0 => {
+            while r.remaining() > 0 {
+                steps.push((r.u8()? >> 3, 0));
+            }
+        }
+        1 | 2 => {
+            while r.remaining() >= 2 {
+                let v = r.u16()?;
+                steps.push(((v >> 11) as u8, v & 0x7ff));
+            }
+        }
+        _ => return Err(J2kError::Invalid("bad quantization style")),
+    }
+    if steps.is_empty() {
+        return Err(J2kError::Invalid("no quantization step sizes"));
+    }
+    Ok(Quantization {
+        guard: s >> 5,
+        style,
+        steps,
+    })
+}
+
+/// Apply one COD/COC/QCD/QCC segment to `params`; other markers are
+/// ignored. `components` sizes the component index.
+fn read_param_segment(
+    marker: u16,
+    body: &[u8],
+    components: usize,
+    params: &mut Params,
+) -> Result<()> {
+    let mut r = Reader { data: body, pos: 0 };
+    let component = |r: &mut Reader<'_>| -> Result<usize> {
+        let c = if components < 257 {
+            usize::from(r.u8()?)
+        } else {
+            usize::from(r.u16()?)
+        };
+        if c >= components {
+            return Err(J2kError::Invalid("component index out of range"));
+        }
+        Ok(c)
+    };
+    match marker {
+        COD => {
+            let scod = r.u8()?;
+            let order = r.u8()?;
+            let layers = r.u16()?;
+            let mct = r.u8()? != 0;
+            if order > 4 || layers == 0 {
+                return Err(J2kError::Invalid("bad progression order or layer count"));
+            }
+            params.progression = Some(Progression {
+                order,
+                layers,
+                mct,
+                sop: scod & 2 != 0,
+                eph: scod & 4 != 0,
+            });
+            params.cod = Some(read_coding(&mut r, scod & 1 != 0)?);
+        }
+        COC => {
+            let c = component(&mut r)?;
+            let scoc = r.u8()?;
+            params.coc.resize(components, None);
+            params.coc[c] = Some(read_coding(&mut r, scoc & 1 != 0)?);
+        }
+        QCD => params.qcd = Some(read_quantization(&mut r)?),
+        QCC => {
+            let c = component(&mut r)?;
+            params.qcc.resize(components, None);
+            params.qcc[c] = Some(read_quantization(&mut r)?);
+        }
+        RGN => return Err(J2kError::Unsupported("region of interest (RGN) shifts")),
+        POC => return Err(J2kError::Unsupported("progression order changes (POC)")),
+        PPM | PPT => return Err(J2kError::Unsupported("packed packet headers (PPM/PPT)")),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Walk marker segments from `pos` until `stop`, returning its offset.
+fn read_segments(
+    data: &[u8],
+    mut pos: usize,
+    stop: u16,
+    mut visit: impl FnMut(u16, &[u8]) -> Result<()>,
+) -> Result<usize> {
+    loop {
+        let marker = be16(data, pos).ok_or(J2kError::Invalid("truncated header"))?;
+        if marker == stop {
+            return Ok(pos);
+        }
+        if marker >> 8 != 0xFF {
+            return Err(J2kError::Invalid("expected a marker"));
+        }
+        let len = usize::from(be16(data, pos + 2).ok_or(J2kError::Invalid("truncated header"))?);
+        let body = data
+            .get(pos + 4..pos + 2 + len)
+            .filter(|_| len >= 2)
+            .ok_or(J2kError::Invalid("truncated marker segment"))?;
+        visit(marker, body)?;
+        pos += 2 + len;
+    }
+}
+
+/// Parse SOC, SIZ and the rest of the main header.
+pub(crate) fn read_header(data: &[u8]) -> Result<Header> {
+    if !is_codestream(data) {
+        return Err(J2kError::Invalid("missing SOC/SIZ markers"));
+    }
+    let len = usize::from(be16(data, 4).ok_or(J2kError::Invalid("truncated SIZ"))?);
+    let siz = read_siz(&mut Reader {
+        data: data
+            .get(6..4 + len)
+            .ok_or(J2kError::Invalid("truncated SIZ"))?,
+        pos: 0,
+    })?;
+    let components = siz.components.len();
+    let mut main = Params::default();
+    let tiles_at = read_segments(data, 4 + len, SOT, |marker, body| {
+        if marker == CAP {
+            return Err(J2kError::Unsupported("Part 2/15 capabilities (CAP)"));
+        }
+        read_param_segment(marker, body, components, &mut main)
+    })?;
+    if main.cod.is_none() || main.qcd.is_none() {
+        return Err(J2kError::Invalid("missing COD or QCD"));
+    }
+    Ok(Header {
+        siz,
+        main,
+        tiles_at,
+    })
+}
+
+/// One tile's header parameters and the concatenated bodies of its parts.
+#[derive(Default)]
+struct TileStream {
+    params: Params,
+    data: Vec<u8>,
+}
+
+fn read_tile_parts(data: &[u8], header: &Header) -> Result<Vec<TileStream>> {
+    let siz = &header.siz;
+    let count = (siz.tiles_x() as usize)
+        .checked_mul(siz.tiles_y() as usize)
+        .filter(|&n| n <= 65535)
+        .ok_or(J2kError::Invalid("too many tiles"))?;
+    let mut tiles: Vec<TileStream> = (0..count).map(|_| TileStream::default()).collect();
+    let mut pos = header.tiles_at;
+    while let Some(marker) = be16(data, pos) {
+        if marker == EOC {
+            break;
+        }
+        if marker != SOT {
+            return Err(J2kError::Invalid("expected SOT"));
+        }
+        let mut r = Reader {
+            data: data
+                .get(pos + 4..pos + 12)
+                .ok_or(J2kError::Invalid("truncated SOT"))?,
+            pos: 0,
+        };
+        let index = usize::from(r.u16()?);
+        let psot = r.u32()? as usize;
+        let tile = tiles
+            .get_mut(index)
+            .ok_or(J2kError::Invalid("tile index out of range"))?;
+        let components = siz.components.len();
+        let sod = read_segments(data, pos + 12, SOD, |marker, body| {
+            read_param_segment(marker, body, components, &mut tile.params)
+        })?;
+        let end = if psot == 0 {
+            data.len()
+        } else {
+            pos.saturating_add(psot).min(data.len())
+        };
+        if sod + 2 > end {
+            return Err(J2kError::Invalid("tile-part shorter than its header"));
+        }
+        tile.data.extend_from_slice(&data[sod + 2..end]);
+        pos = end;
+    }
+    Ok(tiles)
+}
+
+// ─── Tier-2: packets ───
+
+/// Packet header bit reader; a byte after 0xFF carries 7 bits.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u8,
+    ct: u8,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Option<bool> {
+        if self.ct == 0 {
+            let stuffed = self.buf == 0xFF;
+            self.buf = *self.data.get(self.pos)?;
+            self.pos += 1;
+            self.ct = if stuffed { 7 } else { 8 };
+        }
+        self.ct -= 1;
+        Some((self.buf >> self.ct) & 1 == 1)
+    }
+
+    fn bits(&mut self, n: u32) -> Option<u32> {
+        let mut v = 0;
+        for _ in 0..n {
+            v = v << 1 | u32::from(self.bit()?);
+        }
+        Some(v)
+    }
+
+    /// Skip to the end of the header, including a stuffed byte after 0xFF.
+    fn align(&mut self) {
+        if self.buf == 0xFF {
+            self.pos += 1;
+        }
+        self.buf = 0;
+        self.ct = 0;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TagNode {
+    value: u32,
+    low: u32,
+}
+
+/// Tag tree (B.10.2) over a grid of code-blocks.
+struct TagTree {
+    /// `(width, offset)` per level, leaves first.
+    levels: Vec<(usize, usize)>,
+    nodes: Vec<TagNode>,
+}
+
+impl TagTree {
+    fn new(mut w: usize, mut h: usize) -> Result<Self> {
+        let mut levels = Vec::new();
+        let mut total = 0usize;
+        loop {
+            levels.push((w, total));
+            total = total.checked_add(area(w, h)?).ok_or(J2kError::TooLarge)?;
+            if w <= 1 && h <= 1 {
+                break;
+            }
+            w = w.div_ceil(2);
+            h = h.div_ceil(2);
+        }
+        Ok(Self {
+            levels,
+            nodes: filled(
+                total,
+                TagNode {
+                    value: u32::MAX,
+                    low: 0,
+                },
+            )?,
+        })
+    }
+
+    /// Decode until the leaf's value is known to be below `threshold` or
+    /// not; returns whether it is.
+    fn decode(
+        &mut self,
+        bits: &mut BitReader<'_>,
+        x: usize,
+        y: usize,
+        threshold: u32,
+    ) -> Option<bool> {
+        let mut path = [0usize; 34];
+        let (mut x, mut y) = (x, y);
+        for (level, &(w, offset)) in self.levels.iter().enumerate() {
+            path[level] = offset + y * w + x;
+            x /= 2;
+            y /= 2;
+        }
+        let mut low = 0;
+        for &index in path[..self.levels.len()].iter().rev() {
+            let node = &mut self.nodes[index];
+            if low > node.low {
+                node.low = low;
+            } else {
+                low = node.low;
+            }
+            while low < threshold && low < node.value {
+                if bits.bit()? {
+                    node.value = low;
+                } else {
+                    low += 1;
+                }
+            }
+            node.low = low;
+        }
+        Some(self.nodes[path[0]].value < threshold)
+    }
+
+    fn value(&self, x: usize, y: usize) -> u32 {
+        self.nodes[y * self.levels[0].0 + x].value
+    }
+}
+
+/// A run of coding passes decoded with one MQ or raw coder.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: u32,
+    passes: u32,
+    len: usize,
+}
+
+/// Pass index at which the codeword segment holding `pass` ends.
+fn segment_end(style: u8, pass: u32) -> u32 {
+    if style & CB_TERMALL != 0 {
+        pass + 1
+    } else if style & CB_BYPASS != 0 {
+        match pass {
+            0..10 => 10,
+            // Raw significance + refinement pairs, then an MQ cleanup.
+            _ if (pass - 10).is_multiple_of(3) => pass + 2,
+            _ => pass + 1,
+        }
+    } else {
+        u32::MAX
+    }
+}
+
+struct CodeBlock {
+    x0: i64,
+    y0: i64,
+    x1: i64,
+    y1: i64,
+    included: bool,
+    zero_bitplanes: u32,
+    lblock: u32,
+    passes: u32,
+    data: Vec<u8>,
+    segments: Vec<Segment>,
+}
+
+/// The code-blocks of one band inside one precinct.
+struct PrecinctBand {
+    cols: usize,
+    blocks: Vec<usize>,
+    inclusion: TagTree,
+    zero_bitplanes: TagTree,
+}
+
+struct Band {
+    orient: u8,
+    x0: i64,
+    y0: i64,
+    x1: i64,
+    y1: i64,
+    /// Magnitude bit-planes, `Mb`.
+    bitplanes: u32,
+    /// Dequantization step; 1 without quantization.
+    step: f32,
+    blocks: Vec<CodeBlock>,
+}
+
+struct Resolution {
+    x0: i64,
+    y0: i64,
+    x1: i64,
+    y1: i64,
+    ppx: u8,
+    ppy: u8,
+    precincts_x: usize,
+    bands: Vec<Band>,
+    /// One entry per precinct, each with one entry per band.
+    precincts: Vec<Vec<PrecinctBand>>,
+}
+
+struct TileComponent {
+    coding: CodingStyle,
+    resolutions: Vec<Resolution>,
+}
+
+fn band_step(
+    quant: &Quantization,
+    levels: u8,
+    r: usize,
+    orient: u8,
+    precision: u8,
+) -> Result<(u32, f32)> {
+    let index = if r == 0 {
+        0
+    } else {
+        1 + 3 * (r - 1) + usize::from(orient) - 1
+    };
+    let (exponent, mantissa) = if quant.style == 1 {
+        // Derived: the LL step, exponent adjusted per level.
+        let (e0, m0) = quant.steps[0];
+        let nb = if r == 0 { levels } else { levels + 1 - r as u8 };
+        let e = i32::from(e0) - i32::from(levels) + i32::from(nb);
+        (
+            u8::try_from(e).map_err(|_| J2kError::Invalid("bad derived step size"))?,
+            m0,
+        )
+    } else {
+        *quant
+            .steps
+            .get(index)
+            .ok_or(J2kError::Invalid("missing subband step size"))?
+    };
+    let bitplanes = (u32::from(quant.guard) + u32::from(exponent)).saturating_sub(1);
+    if bitplanes > 31 {
+        return Err(J2kError::Unsupported("more than 31 magnitude bit-planes"));
+    }
+    let step = if quant.style == 0 {
+        1.0
+    } else {
+        let gain = [0, 1, 1, 2][usize::from(orient)];
+        let range = i32::from(precision) + gain;
+        pow2(range - i32::from(exponent)) * (1.0 + f32::from(mantissa) / 2048.0)
+    };
+    Ok((bitplanes, step))
+}
+
+fn build_component(
+    rect: [i64; 4],
+    coding: &CodingStyle,
+    quant: &Quantization,
+    precision: u8,
+) -> Result<TileComponent> {
+    let [tcx0, tcy0, tcx1, tcy1] = rect;
+    let levels = coding.levels;
+    let mut resolutions = Vec::with_capacity(usize::from(levels) + 1);
+    for r in 0..=usize::from(levels) {
+        let scale = 1i64 << (usize::from(levels) - r);
+        let (x0, y0) = (ceil_div(tcx0, scale), ceil_div(tcy0, scale));
+        let (x1, y1) = (ceil_div(tcx1, scale), ceil_div(tcy1, scale));
+        let (ppx, ppy) = coding.precincts[r];
+        let count = |lo: i64, hi: i64, p: u8| {
+            if hi > lo {
+                (ceil_div(hi, 1 << p) - lo.div_euclid(1 << p)) as usize
+            } else {
+                0
+            }
+        };
+        let (precincts_x, precincts_y) = (count(x0, x1, ppx), count(y0, y1, ppy));
+        // Band-domain precinct and code-block exponents.
+        let (pbx, pby) = if r == 0 {
+            (ppx, ppy)
+        } else {
+            (ppx - 1, ppy - 1)
+        };
+        let (cbx, cby) = (coding.cb_w.min(pbx), coding.cb_h.min(pby));
+
+        let orients: &[u8] = if r == 0 { &[0] } else { &[1, 2, 3] };
+        let mut bands = Vec::with_capacity(orients.len());
+        let total = area(precincts_x, precincts_y)?;
+        let mut precincts: Vec<Vec<PrecinctBand>> = reserved(total)?;
+        precincts.resize_with(total, || Vec::with_capacity(orients.len()));
+        for &orient in orients {
+            let (bx0, by0, bx1, by1) = if r == 0 {
+                (x0, y0, x1, y1)
+            } else {
+                let nb = u32::from(levels) - r as u32 + 1;
+                let (xo, yo) = (i64::from(orient & 1), i64::from(orient >> 1));
+                let half = 1i64 << (nb - 1);
+                let s = 1i64 << nb;
+                (
+                    ceil_div(tcx0 - xo * half, s),
+                    ceil_div(tcy0 - yo * half, s),
+                    ceil_div(tcx1 - xo * half, s),
+                    ceil_div(tcy1 - yo * half, s),
+                )
+            };
+            let (bitplanes, step) = band_step(quant, levels, r, orient, precision)?;
+            let mut blocks = if bx1 > bx0 && by1 > by0 {
+                reserved(area(
+                    (ceil_div(bx1, 1 << cbx) - bx0.div_euclid(1 << cbx)) as usize,
+                    (ceil_div(by1, 1 << cby) - by0.div_euclid(1 << cby)) as usize,
+                )?)?
+            } else {
+                Vec::new()
+            };
+            // Precinct origin in band coordinates, by global precinct index.
+            let (gpx0, gpy0) = (x0.div_euclid(1 << ppx), y0.div_euclid(1 << ppy));
+            for (index, precinct) in precincts.iter_mut().enumerate() {
+                let px = gpx0 + (index % precincts_x) as i64;
+                let py = gpy0 + (index / precincts_x) as i64;
+                let (rx0, ry0) = ((px << pbx).max(bx0), (py << pby).max(by0));
+                let (rx1, ry1) = (((px + 1) << pbx).min(bx1), ((py + 1) << pby).min(by1));
+                let (cols, rows) = if rx1 > rx0 && ry1 > ry0 {
+                    (
+                        (ceil_div(rx1, 1 << cbx) - rx0.div_euclid(1 << cbx)) as usize,
+                        (ceil_div(ry1, 1 << cby) - ry0.div_euclid(1 << cby)) as usize,
+                    )
+                } else {
+                    (0, 0)
+                };
+                let mut members = reserved(area(cols, rows)?)?;
+                for j in 0..rows as i64 {
+                    for i in 0..cols as i64 {
+                        let gx = rx0.div_euclid(1 << cbx) + i;
+                        let gy = ry0.div_euclid(1 << cby) + j;
+                        members.push(blocks.len());
+                        blocks.push(CodeBlock {
+                            x0: (gx << cbx).max(rx0),
+                            y0: (gy << cby).max(ry0),
+                            x1: ((gx + 1) << cbx).min(rx1),
+                            y1: ((gy + 1) << cby).min(ry1),
+                            included: false,
+                            zero_bitplanes: 0,
+                            lblock: 3,
+                            passes: 0,
+                            data: Vec::new(),
+                            segments: Vec::new(),
+                        });
+                    }
+                }
+                precinct.push(PrecinctBand {
+                    cols,
+                    blocks: members,
+                    inclusion: TagTree::new(cols, rows)?,
+                    zero_bitplanes: TagTree::new(cols, rows)?,
+                });
+            }
+            bands.push(Band {
+                orient,
+                x0: bx0,
+                y0: by0,
+                x1: bx1,
+                y1: by1,
+                bitplanes,
+                step,
+                blocks,
+            });
+        }
+        resolutions.push(Resolution {
+            x0,
+            y0,
+            x1,
+            y1,
+            ppx,
+            ppy,
+            precincts_x,
+            bands,
+            precincts,
+        });
+    }
+    Ok(TileComponent {
+        coding: coding.clone(),
+        resolutions,
+    })
+}
+
+/// Packet coordinates: layer, resolution, component, precinct.
+type PacketId = (u16, usize, usize, usize);
+
+fn packet_order(
+    order: u8,
+    layers: u16,
+    components: &[TileComponent],
+    siz: &Siz,
+    tile: [i64; 4],
+) -> Result<Vec<PacketId>> {
+    let max_res = components
+        .iter()
+        .map(|c| c.resolutions.len())
+        .max()
+        .unwrap_or(0);
+    let precincts = |c: usize, r: usize| {
+        components[c]
+            .resolutions
+            .get(r)
+            .map_or(0, |res| res.precincts.len())
+    };
+    let positions: usize = components
+        .iter()
+        .flat_map(|c| c.resolutions.iter().map(|res| res.precincts.len()))
+        .sum();
+    let mut out = reserved(
+        positions
+            .checked_mul(usize::from(layers))
+            .ok_or(J2kError::TooLarge)?,
+    )?;
+    match order {
+        // LRCP, RLCP
+        0 | 1 => {
+            for outer in 0..if order == 0 {
+                usize::from(layers)
+            } else {
+                max_res
+            } {
+                for inner in 0..if order == 0 {
+                    max_res
+                } else {
+                    usize::from(layers)
+                } {
+                    let (l, r) = if order == 0 {
+                        (outer, inner)
+                    } else {
+                        (inner, outer)
+                    };
+                    for c in 0..components.len() {
+                        for p in 0..precincts(c, r) {
+                            out.push((l as u16, r, c, p));
+                        }
+                    }
+                }
+            }
+        }
+        // RPCL, PCRL, CPRL: precincts ordered by reference-grid position.
+        _ => {
+            let mut keyed = reserved(positions)?;
+            for (c, comp) in components.iter().enumerate() {
+                let info = &siz.components[c];
+                let levels = comp.resolutions.len() - 1;
+                for (r, res) in comp.resolutions.iter().enumerate() {
+                    let scale = 1i64 << (levels - r);
+                    for p in 0..res.precincts.len() {
+                        let px = res.x0.div_euclid(1 << res.ppx) + (p % res.precincts_x) as i64;
+                        let py = res.y0.div_euclid(1 << res.ppy) + (p / res.precincts_x) as i64;
+                        let x = if px << res.ppx <= res.x0 {
+                            tile[0]
+                        } else {
+                            (px << res.ppx) * scale * i64::from(info.dx)
+                        };
+                        let y = if py << res.ppy <= res.y0 {
+                            tile[1]
+                        } else {
+                            (py << res.ppy) * scale * i64::from(info.dy)
+                        };
+                        let key = match order {
+                            2 => (r as i64, y, x, c as i64),
+                            3 => (y, x, c as i64, r as i64),
+                            _ => (c as i64, y, x, r as i64),
+                        };
+                        keyed.push((key, r, c, p));
+                    }
+                }
+            }
+            keyed.sort_by_key(|&(key, ..)| key);
+            for (_, r, c, p) in keyed {
+                for l in 0..layers {
+                    out.push((l, r, c, p));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn read_passes(bits: &mut BitReader<'_>) -> Option<u32> {
+    if !bits.bit()? {
+        return Some(1);
+    }
+    if !bits.bit()? {
+        return Some(2);
+    }
+    let v = bits.bits(2)?;
+    if v != 3 {
+        return Some(3 + v);
+    }
+    let v = bits.bits(5)?;
+    if v != 31 {
+        return Some(6 + v);
+    }
+    Some(37 + bits.bits(7)?)
+}
+
+/// A code block's share of a packet: band, block, `(passes, length)` segments.
+type Contribution = (usize, usize, Vec<(u32, usize)>);
+
+/// Read one packet at `pos`; `None` when the data runs out.
+fn read_packet(
+    data: &[u8],
+    pos: usize,
+    res: &mut Resolution,
+    precinct: usize,
+    layer: u16,
+    progression: &Progression,
+    cb_style: u8,
+) -> Option<usize> {
+    let mut pos = pos;
+    if progression.sop && be16(data, pos) == Some(SOP) {
+        pos += 6;
+    }
+    let mut bits = BitReader {
+        data,
+        pos,
+        buf: 0,
+        ct: 0,
+    };
+    let mut contributions: Vec<Contribution> = Vec::new();
+    if bits.bit()? {
+        for (b, pband) in res.precincts[precinct].iter_mut().enumerate() {
+            let band = &mut res.bands[b];
+            for (k, &index) in pband.blocks.iter().enumerate() {
+                let (x, y) = (k % pband.cols, k / pband.cols);
+                let block = &mut band.blocks[index];
+                let included = if block.included {
+                    bits.bit()?
+                } else {
+                    pband
+                        .inclusion
+                        .decode(&mut bits, x, y, u32::from(layer) + 1)?
+                };
+                if !included {
+                    continue;
+                }
+                if !block.included {
+                    let mut threshold = 1;
+                    while !pband.zero_bitplanes.decode(&mut bits, x, y, threshold)? {
+                        threshold += 1;
+                    }
+                    block.zero_bitplanes = pband.zero_bitplanes.value(x, y);
+                    block.included = true;
+                }
+                let passes = read_passes(&mut bits)?;
+                while bits.bit()? {
+                    block.lblock += 1;
+                }
+                let mut parts = Vec::new();
+                let (mut pass, end) = (block.passes, block.passes + passes);
+                while pass < end {
+                    let n = segment_end(cb_style, pass).min(end) - pass;
+                    let len = bits.bits(block.lblock + n.ilog2())? as usize;
+                    parts.push((n, len));
+                    pass += n;
+                }
+                contributions.push((b, index, parts));
+            }
+        }
+    }
+    bits.align();
+    pos = bits.pos;
+    if progression.eph && be16(data, pos) == Some(EPH) {
+        pos += 2;
+    }
+    for (b, index, parts) in contributions {
+        let block = &mut res.bands[b].blocks[index];
+        for (passes, len) in parts {
+            let bytes = data.get(pos..pos.checked_add(len)?)?;
+            pos += len;
+            let start = block.passes;
+            match block.segments.last_mut() {
+                Some(last)
+                    if segment_end(cb_style, last.start) == segment_end(cb_style, start)
+                        && last.start + last.passes == start =>
+                {
+                    last.passes += passes;
+                    last.len += len;
+                }
+                _ => block.segments.push(Segment { start, passes, len }),
+            }
+            block.data.extend_from_slice(bytes);
+            block.passes += passes;
+        }
+    }
+    Some(pos)
+}
+
+// ─── Tier-1: code-block decoding ───
+
+struct QeEntry {
+    qe: u32,
+    nmps: u8,
+    nlps: u8,
+    switch: bool,
+}
+
+const fn qe(qe: u32, nmps: u8, nlps: u8, switch: bool) -> QeEntry {
+    QeEntry {
+        qe,
+        nmps,
+        nlps,
+        switch,
+    }
+}
+
+/// MQ coder probability states (Table C.2).
+static QE_TABLE: [QeEntry; 47] = [
+    qe(0x5601, 1, 1, true),
+    qe(0x3401, 2, 6, false),
+    qe(0x1801, 3, 9, false),
+    qe(0x0AC1, 4, 12, false),
+    qe(0x0521, 5, 29, false),
+    qe(0x0221, 38, 33, false),
+    qe(0x5601, 7, 6, true),
+    qe(0x5401, 8, 14, false),
+    qe(0x4801, 9, 14, false),
+    qe(0x3801, 10, 14, false),
+    qe(0x3001, 11, 17, false),
+    qe(0x2401, 12, 18, false),
+    qe(0x1C01, 13, 20, false),
+    qe(0x1601, 29, 21, false),
+    qe(0x5601, 15, 14, true),
+    qe(0x5401, 16, 14, false),
+    qe(0x5101, 17, 15, false),
+    qe(0x4801, 18, 16, false),
+    qe(0x3801, 19, 17, false),
+    qe(0x3401, 20, 18, false),
+    qe(0x3001, 21, 19, false),
+    qe(0x2801, 22, 19, false),
+    qe(0x2401, 23, 20, false),
+    qe(0x2201, 24, 21, false),
+    qe(0x1C01, 25, 22, false),
+    qe(0x1801, 26, 23, false),
+    qe(0x1601, 27, 24, false),
+    qe(0x1401, 28, 25, false),
+    qe(0x1201, 29, 26, false),
+    qe(0x1101, 30, 27, false),
+    qe(0x0AC1, 31, 28, false),
+    qe(0x09C1, 32, 29, false),
+    qe(0x08A1, 33, 30, false),
+    qe(0x0521, 34, 31, false),
+    qe(0x0441, 35, 32, false),
+    qe(0x02A1, 36, 33, false),
+    qe(0x0221, 37, 34, false),
+    qe(0x0141, 38, 35, false),
+    qe(0x0111, 39, 36, false),
+    qe(0x0085, 40, 37, false),
+    qe(0x0049, 41, 38, false),
+    qe(0x0025, 42, 39, false),
+    qe(0x0015, 43, 40, false),
+    qe(0x0009, 44, 41, false),
+    qe(0x0005, 45, 42, false),
+    qe(0x0001, 45, 43, false),
+    qe(0x5601, 46, 46, false),
+];
+
+const CTX_RUN: usize = 17;
+const CTX_UNIFORM: usize = 18;
+
+/// `(state, mps)` for the 19 tier-1 contexts.
+type Contexts = [(u8, u8); 19];
+
+fn initial_contexts() -> Contexts {
+    let mut cx = [(0, 0); 19];
+    cx[0] = (4, 0);
+    cx[CTX_RUN] = (3, 0);
+    cx[CTX_UNIFORM] = (46, 0);
+    cx
+}
+
+/// MQ arithmetic decoder (Annex C), reading 0xFF past the end.
+struct MqDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    a: u32,
+    chigh: u32,
+    clow: u32,
+    ct: u32,
+}
+
+impl<'a> MqDecoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut mq = Self {
+            data,
+            pos: 0,
+            a: 0x8000,
+            chigh: u32::from(data.first().copied().unwrap_or(0xFF)),
+            clow: 0,
+            ct: 0,
+        };
+        mq.byte_in();
+        mq.chigh = ((mq.chigh << 7) & 0xFFFF) | ((mq.clow >> 9) & 0x7F);
+        mq.clow = (mq.clow << 7) & 0xFFFF;
+        mq.ct -= 7;
+        mq
+    }
+
+    fn byte(&self, at: usize) -> u32 {
+        u32::from(self.data.get(at).copied().unwrap_or(0xFF))
+    }
+
+    fn byte_in(&mut self) {
+        if self.byte(self.pos) == 0xFF {
+            if self.byte(self.pos + 1) > 0x8F {
+                self.clow += 0xFF00;
+                self.ct = 8;
+            } else {
+                self.pos += 1;
+                self.clow += self.byte(self.pos) << 9;
+                self.ct = 7;
+            }
+        } else {
+            self.pos += 1;
+            self.clow += self.byte(self.pos) << 8;
+            self.ct = 8;
+        }
+        if self.clow > 0xFFFF {
+            self.chigh += self.clow >> 16;
+            self.clow &= 0xFFFF;
+        }
+    }
+
+    fn decode(&mut self, cx: &mut (u8, u8)) -> u32 {
+        let entry = &QE_TABLE[usize::from(cx.0)];
+        let qe = entry.qe;
+        let mut d;
+        self.a -= qe;
+        if self.chigh < qe {
+            // LPS exchange
+            if self.a < qe {
+                self.a = qe;
+                d = cx.1;
+                cx.0 = entry.nmps;
+            } else {
+                self.a = qe;
+                d = 1 ^ cx.1;
+                if entry.switch {
+                    cx.1 = d;
+                }
+                cx.0 = entry.nlps;
+            }
+        } else {
+            self.chigh -= qe;
+            if self.a & 0x8000 != 0 {
+                return u32::from(cx.1);
+            }
+            // MPS exchange
+            if self.a < qe {
+                d = 1 ^ cx.1;
+                if entry.switch {
+                    cx.1 = d;
+                }
+                cx.0 = entry.nlps;
+            } else {
+                d = cx.1;
+                cx.0 = entry.nmps;
+            }
+        }
+        loop {
+            if self.ct == 0 {
+                self.byte_in();
+            }
+            self.a <<= 1;
+            self.chigh = ((self.chigh << 1) & 0xFFFF) | ((self.clow >> 15) & 1);
+            self.clow = (self.clow << 1) & 0xFFFF;
+            self.ct -= 1;
+            if self.a & 0x8000 != 0 {
+                break;
+            }
+        }
+        d &= 1;
+        u32::from(d)
+    }
+}
+
+/// Raw (bypass) bit reader: MSB first, 7 bits after 0xFF.
+struct RawDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    c: u32,
+    ct: u32,
+}
+
+impl RawDecoder<'_> {
+    fn bit(&mut self) -> u32 {
+        if self.ct == 0 {
+            let next = |at: usize| u32::from(self.data.get(at).copied().unwrap_or(0xFF));
+            if self.c == 0xFF {
+                if next(self.pos) > 0x8F {
+                    self.c = 0xFF;
+                    self.ct = 8;
+                } else {
+                    self.c = next(self.pos);
+                    self.pos += 1;
+                    self.ct = 7;
+                }
+            } else {
+                self.c = next(self.pos);
+                self.pos += 1;
+                self.ct = 8;
+            }
+        }
+        self.ct -= 1;
+        (self.c >> self.ct) & 1
+    }
+}
+
+enum Coder<'a> {
+    Mq(MqDecoder<'a>),
+    Raw(RawDecoder<'a>),
+}
+
+impl Coder<'_> {
+    fn decode(&mut self, cx: &mut Contexts, ctx: usize) -> u32 {
+        match self {
+            Self::Mq(mq) => mq.decode(&mut cx[ctx]),
+            Self::Raw(raw) => raw.bit(),
+        }
+    }
+}
+
+const SIG: u8 = 1;
+const VISITED: u8 = 2;
+const REFINED: u8 = 4;
+const NEGATIVE: u8 = 8;
+
+/// Zero-coding context (Table D.1) from significant horizontal, vertical
+/// and diagonal neighbor counts.
+fn zero_context(orient: u8, h: u32, v: u32, d: u32) -> usize {
+    let (h, v) = if orient == 1 { (v, h) } else { (h, v) };
+    if orient == 3 {
+        return match (d, h + v) {
+            (3.., _) => 8,
+            (2, 1..) => 7,
+            (2, 0) => 6,
+            (1, 2..) => 5,
+            (1, 1) => 4,
+            (1, 0) => 3,
+            (0, 2..) => 2,
+            (0, 1) => 1,
+            _ => 0,
+        };
+    }
+    match (h, v, d) {
+        (2, ..) => 8,
+        (1, 1.., _) => 7,
+        (1, 0, 1..) => 6,
+        (1, 0, 0) => 5,
+        (0, 2, _) => 4,
+        (0, 1, _) => 3,
+        (0, 0, 2..) => 2,
+        (0, 0, 1) => 1,
+        _ => 0,
+    }
+}
+
+struct BlockDecoder {
+    w: usize,
+    h: usize,
+    orient: u8,
+    vcausal: bool,
+    /// Flags with a one-coefficient border.
+    flags: Vec<u8>,
+    magnitude: Vec<u32>,
+    /// Lowest bit-plane coded so far for each significant coefficient.
+    plane: Vec<u8>,
+    cx: Contexts,
+}
+
+impl BlockDecoder {
+    fn at(&self, x: usize, y: usize) -> usize {
+        (y + 1) * (self.w + 2) + x + 1
+    }
+
+    /// Whether the neighbor below is outside this stripe under vertically
+    /// causal coding.
+    fn below_hidden(&self, y: usize) -> bool {
+        self.vcausal && y % 4 == 3
+    }
+
+    fn neighbors(&self, x: usize, y: usize) -> (u32, u32, u32) {
+        let i = self.at(x, y);
+        let s = self.w + 2;
+        let sig = |j: usize| u32::from(self.flags[j] & SIG);
+        let below = !self.below_hidden(y);
+        let h = sig(i - 1) + sig(i + 1);
+        let v = sig(i - s) + if below { sig(i + s) } else { 0 };
+        let d = sig(i - s - 1)
+            + sig(i - s + 1)
+            + if below {
+                sig(i + s - 1) + sig(i + s + 1)
+            } else {
+                0
+            };
+        (h, v, d)
+    }
+
+    /// Sign context and XOR bit (Table D.3).
+    fn sign_context(&self, x: usize, y: usize) -> (usize, u32) {
+        let i = self.at(x, y);
+        let s = self.w + 2;
+        let contribution = |j: usize| -> i32 {
+            match self.flags[j] & (SIG | NEGATIVE) {
+                SIG => 1,
+                f if f & SIG != 0 => -1,
+                _ => 0,
+            }
+        };
+        let h = (contribution(i - 1) + contribution(i + 1)).clamp(-1, 1);
+        let below = if self.below_hidden(y) {
+            0
+        } else {
+            contribution(i + s)
+        };
+        let v = (contribution(i - s) + below).clamp(-1, 1);
+        match (h, v) {
+            (1, 1) => (13, 0),
+            (1, 0) => (12, 0),
+            (1, -1) => (11, 0),
+            (0, 1) => (10, 0),
+            (0, 0) => (9, 0),
+            (0, -1) => (10, 1),
+            (-1, 1) => (11, 1),
+            (-1, 0) => (12, 1),
+            _ => (13, 1),
+        }
+    }
+
+    fn become_significant(&mut self, coder: &mut Coder<'_>, x: usize, y: usize, bitplane: u32) {
+        let negative = match coder {
+            Coder::Raw(raw) => raw.bit(),
+            Coder::Mq(mq) => {
+                let (ctx, xor) = self.sign_context(x, y);
+                mq.decode(&mut self.cx[ctx]) ^ xor
+            }
+        };
+        let i = self.at(x, y);
+        self.flags[i] |= SIG | if negative == 1 { NEGATIVE } else { 0 };
+        self.magnitude[y * self.w + x] |= 1 << bitplane;
+        self.plane[y * self.w + x] = bitplane as u8;
+    }
+
+    fn significance_pass(&mut self, coder: &mut Coder<'_>, bitplane: u32) {
+        for y0 in (0..self.h).step_by(4) {
+            for x in 0..self.w {
+                for y in y0..(y0 + 4).min(self.h) {
+                    let i = self.at(x, y);
+                    if self.flags[i] & SIG != 0 {
+                        continue;
+                    }
+                    let (h, v, d) = self.neighbors(x, y);
+                    if h + v + d == 0 {
+                        continue;
+                    }
+                    let ctx = zero_context(self.orient, h, v, d);
+                    if coder.decode(&mut self.cx, ctx) == 1 {
+                        self.become_significant(coder, x, y, bitplane);
+                    }
+                    self.flags[i] |= VISITED;
+                }
+            }
+        }
+    }
+
+    fn refinement_pass(&mut self, coder: &mut Coder<'_>, bitplane: u32) {
+        for y0 in (0..self.h).step_by(4) {
+            for x in 0..self.w {
+                for y in y0..(y0 + 4).min(self.h) {
+                    let i = self.at(x, y);
+                    if self.flags[i] & (SIG | VISITED) != SIG {
+                        continue;
+                    }
+                    let ctx = if self.flags[i] & REFINED != 0 {
+                        16
+                    } else {
+                        let (h, v, d) = self.neighbors(x, y);
+                        if h + v + d > 0 { 15 } else { 14 }
+                    };
+                    if coder.decode(&mut self.cx, ctx) == 1 {
+                        self.magnitude[y * self.w + x] |= 1 << bitplane;
+                    }
+                    self.plane[y * self.w + x] = bitplane as u8;
+                    self.flags[i] |= REFINED;
+                }
+            }
+        }
+    }
+
+    fn cleanup_pass(&mut self, coder: &mut Coder<'_>, bitplane: u32, segsym: bool) {
+        for y0 in (0..self.h).step_by(4) {
+            for x in 0..self.w {
+                let mut y = y0;
+                let run_mode = y0 + 4 <= self.h
+                    && (y0..y0 + 4).all(|yy| {
+                        self.flags[self.at(x, yy)] & (SIG | VISITED) == 0
+                            && self.neighbors(x, yy) == (0, 0, 0)
+                    });
+                if run_mode {
+                    if coder.decode(&mut self.cx, CTX_RUN) == 0 {
+                        continue;
+                    }
+                    let run = coder.decode(&mut self.cx, CTX_UNIFORM) << 1
+                        | coder.decode(&mut self.cx, CTX_UNIFORM);
+                    y = y0 + run as usize;
+                    self.become_significant(coder, x, y, bitplane);
+                    y += 1;
+                }
+                for y in y..(y0 + 4).min(self.h) {
+                    let i = self.at(x, y);
+                    if self.flags[i] & (SIG | VISITED) != 0 {
+                        continue;
+                    }
+                    let (h, v, d) = self.neighbors(x, y);
+                    let ctx = zero_context(self.orient, h, v, d);
+                    if coder.decode(&mut self.cx, ctx) == 1 {
+                        self.become_significant(coder, x, y, bitplane);
+                    }
+                }
+            }
+        }
+        for f in &mut self.flags {
+            *f &= !VISITED;
+        }
+        if segsym {
+            for _ in 0..4 {
+                coder.decode(&mut self.cx, CTX_UNIFORM);
+            }
+        }
+    }
+}
+
+/// Decode one code-block's passes; returns signed magnitudes in units of
+/// the quantization step, midpoint-reconstructed when truncated.
+fn decode_block(block: &CodeBlock, band: &Band, cb_style: u8, reversible: bool) -> Vec<f32> {
+    let (w, h) = (
+        (block.x1 - block.x0) as usize,
+        (block.y1 - block.y0) as usize,
+    );
+    let mut dec = BlockDecoder {
+        w,
+        h,
+        orient: band.orient,
+        vcausal: cb_style & CB_VCAUSAL != 0,
+        flags: vec![0; (w + 2) * (h + 2)],
+        magnitude: vec![0; w * h],
+        plane: vec![0; w * h],
+        cx: initial_contexts(),
+    };
+    let top = band.bitplanes as i64 - 1 - i64::from(block.zero_bitplanes);
+    let mut offset = 0;
+    'segments: for segment in &block.segments {
+        let bytes = &block.data[offset..offset + segment.len];
+        offset += segment.len;
+        let mut coder: Option<Coder<'_>> = None;
+        for pass in segment.start..segment.start + segment.passes {
+            let bitplane = top - i64::from(pass.div_ceil(3));
+            if bitplane < 0 {
+                break 'segments;
+            }
+            let bitplane = bitplane as u32;
+            // 0 = cleanup, 1 = significance, 2 = refinement.
+            let kind = if pass == 0 { 0 } else { (pass - 1) % 3 + 1 } % 3;
+            let raw = cb_style & CB_BYPASS != 0 && pass >= 10 && kind != 0;
+            let coder = coder.get_or_insert_with(|| {
+                if raw {
+                    Coder::Raw(RawDecoder {
+                        data: bytes,
+                        pos: 0,
+                        c: 0,
+                        ct: 0,
+                    })
+                } else {
+                    Coder::Mq(MqDecoder::new(bytes))
+                }
+            });
+            match kind {
+                1 => dec.significance_pass(coder, bitplane),
+                2 => dec.refinement_pass(coder, bitplane),
+                _ => dec.cleanup_pass(coder, bitplane, cb_style & CB_SEGSYM != 0),
+            }
+            if cb_style & CB_RESET != 0 {
+                dec.cx = initial_contexts();
+            }
+        }
+    }
+
+    let mut out = vec![0.0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let m = dec.magnitude[y * w + x];
+            if m != 0 {
+                // Midpoint of the interval left open below the lowest
+                // bit-plane this coefficient was coded in; reversible data
+                // is exact once bit-plane 0 is in.
+                let plane = dec.plane[y * w + x];
+                let half = if plane > 0 {
+                    pow2(i32::from(plane) - 1)
+                } else if reversible {
+                    0.0
+                } else {
+                    0.5
+                };
+                let v = m as f32 + half;
+                out[y * w + x] = if dec.flags[dec.at(x, y)] & NEGATIVE != 0 {
+                    -v
+                } else {
+                    v
+                };
+            }
+        }
+    }
+    out
+}
+
+// ─── Inverse wavelet transform ───
+
+const PAD: usize = 4;
+
+/// Periodic symmetric extension: index into `n` samples for any offset.
+fn mirror(i: i64, n: i64) -> usize {
+    if n == 1 {
+        return 0;
+    }
+    let period = 2 * (n - 1);
+    let i = i.rem_euclid(period);
+    (if i >= n { period - i } else { i }) as usize
+}
+
+/// 1-D reversible 5/3 synthesis of `x`, whose first sample sits at
+/// absolute coordinate `i0`.
+fn synthesize_53(x: &mut [i32], i0: i64, ext: &mut Vec<i32>) {
+    let n = x.len();
+    if n == 1 {
+        if i0 & 1 == 1 {
+            x[0] /= 2;
+        }
+        return;
+    }
+    ext.clear();
+    ext.extend((0..n + 2 * PAD).map(|e| x[mirror(e as i64 - PAD as i64, n as i64)]));
+    let first = i0 - PAD as i64;
+    let even = |e: usize| (first + e as i64) & 1 == 0;
+    for e in 1..ext.len() - 1 {
+        if even(e) {
+            ext[e] -= (ext[e - 1] + ext[e + 1] + 2) >> 2;
+        }
+    }
+    for e in 1..ext.len() - 1 {
+        if !even(e) {
+            ext[e] += (ext[e - 1] + ext[e + 1]) >> 1;
+        }
+    }
+    x.copy_from_slice(&ext[PAD..PAD + n]);
+}
+
+/// 1-D irreversible 9/7 synthesis (F.3.8.2).
+fn synthesize_97(x: &mut [f32], i0: i64, ext: &mut Vec<f32>) {
+    const ALPHA: f32 = -1.586_134_3;
+    const BETA: f32 = -0.052_980_118;
+    const GAMMA: f32 = 0.882_911_1;
+    const DELTA: f32 = 0.443_506_87;
+    const K: f32 = 1.230_174_1;
+    let n = x.len();
+    if n == 1 {
+        if i0 & 1 == 1 {
+            x[0] /= 2.0;
+        }
+        return;
+    }
+    ext.clear();
+    ext.extend((0..n + 2 * PAD).map(|e| x[mirror(e as i64 - PAD as i64, n as i64)]));
+    let first = i0 - PAD as i64;
+    let even = |e: usize| (first + e as i64) & 1 == 0;
+    for (e, v) in ext.iter_mut().enumerate() {
+        *v *= if even(e) { K } else { 1.0 / K };
+    }
+    for (parity, c) in [(true, DELTA), (false, GAMMA), (true, BETA), (false, ALPHA)] {
+        for e in 1..ext.len() - 1 {
+            if even(e) == parity {
+                ext[e] -= c * (ext[e - 1] + ext[e + 1]);
+            }
+        }
+    }
+    x.copy_from_slice(&ext[PAD..PAD + n]);
+}
+
+/// Samples of one tile-component at one resolution.
+enum Samples {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+}
+
+/// Interleave the lower resolution with the three high bands of `res`
+/// (2D_INTERLEAVE), then run the horizontal and vertical synthesis.
+fn synthesize<T: Copy + Default>(
+    low: &[T],
+    res: &Resolution,
+    prev: &Resolution,
+    bands: [&[T]; 3],
+    filter: &mut dyn FnMut(&mut [T], i64),
+) -> Result<Vec<T>> {
+    let (u0, v0) = (res.x0, res.y0);
+    let (w, h) = ((res.x1 - res.x0) as usize, (res.y1 - res.y0) as usize);
+    let mut out = filled(area(w, h)?, T::default())?;
+    for y in 0..h {
+        let v = v0 + y as i64;
+        for x in 0..w {
+            let u = u0 + x as i64;
+            let (hx, hy) = (u & 1, v & 1);
+            let value = match (hx, hy) {
+                (0, 0) => {
+                    let lw = (prev.x1 - prev.x0) as usize;
+                    low[((v >> 1) - prev.y0) as usize * lw + ((u >> 1) - prev.x0) as usize]
+                }
+                _ => {
+                    let band = &res.bands[(hx + 2 * hy - 1) as usize];
+                    let bw = (band.x1 - band.x0) as usize;
+                    bands[(hx + 2 * hy - 1) as usize]
+                        [((v >> 1) - band.y0) as usize * bw + ((u >> 1) - band.x0) as usize]
+                }
+            };
+            out[y * w + x] = value;
+        }
+    }
+    if w > 0 {
+        for row in out.chunks_exact_mut(w) {
+            filter(row, u0);
+        }
+    }
+    let mut column = filled(h, T::default())?;
+    for x in 0..w {
+        for y in 0..h {
+            column[y] = out[y * w + x];
+        }
+        if h > 0 {
+            filter(&mut column, v0);
+        }
+        for y in 0..h {
+            out[y * w + x] = column[y];
+        }
+    }
+    Ok(out)
+}
+
+fn reconstruct(comp: &TileComponent, bands: &[Vec<Vec<f32>>], reduce: usize) -> Result<Samples> {
+    let top = comp.resolutions.len() - 1 - reduce;
+    if comp.coding.reversible {
+        let to_int = |v: &Vec<f32>| mapped(v, |f| f as i32);
+        let mut low = to_int(&bands[0][0])?;
+        let mut ext = Vec::new();
+        for (r, band) in bands.iter().enumerate().take(top + 1).skip(1) {
+            let high = band.iter().map(to_int).collect::<Result<Vec<_>>>()?;
+            low = synthesize(
+                &low,
+                &comp.resolutions[r],
+                &comp.resolutions[r - 1],
+                [&high[0], &high[1], &high[2]],
+                &mut |x, i0| synthesize_53(x, i0, &mut ext),
+            )?;
+        }
+        Ok(Samples::Int(low))
+    } else {
+        let mut low = mapped(&bands[0][0], |v| v)?;
+        let mut ext = Vec::new();
+        for (r, band) in bands.iter().enumerate().take(top + 1).skip(1) {
+            low = synthesize(
+                &low,
+                &comp.resolutions[r],
+                &comp.resolutions[r - 1],
+                [&band[0], &band[1], &band[2]],
+                &mut |x, i0| synthesize_97(x, i0, &mut ext),
+            )?;
+        }
+        Ok(Samples::Float(low))
+    }
+}
+
+// ─── Tiles and output ───
+
+/// A decoded component: integer samples, DC level shift applied to
+/// unsigned components.
+#[derive(Clone, Debug)]
+pub(crate) struct Plane {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) data: Vec<i32>,
+    pub(crate) info: ComponentInfo,
+}
+
+/// An area of the reference grid, `[x0, x1) × [y0, y1)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Region {
+    pub(crate) x0: u32,
+    pub(crate) y0: u32,
+    pub(crate) x1: u32,
+    pub(crate) y1: u32,
+}
+
+/// Component-domain rectangle of `region` at `reduce` levels down.
+fn component_rect(region: Region, info: &ComponentInfo, reduce: u8) -> [i64; 4] {
+    let s = 1i64 << reduce;
+    let (dx, dy) = (i64::from(info.dx), i64::from(info.dy));
+    [
+        ceil_div(ceil_div(i64::from(region.x0), dx), s),
+        ceil_div(ceil_div(i64::from(region.y0), dy), s),
+        ceil_div(ceil_div(i64::from(region.x1), dx), s),
+        ceil_div(ceil_div(i64::from(region.y1), dy), s),
+    ]
+}
+
+/// Tile-component rectangle on the component grid.
+fn tile_component_rect(tile: [i64; 4], info: &ComponentInfo) -> [i64; 4] {
+    let (dx, dy) = (i64::from(info.dx), i64::from(info.dy));
+    [
+        ceil_div(tile[0], dx),
+        ceil_div(tile[1], dy),
+        ceil_div(tile[2], dx),
+        ceil_div(tile[3], dy),
+    ]
+}
+
+fn round(v: f32) -> i32 {
+    let r = v + 0.5;
+    let i = r as i32;
+    if (i as f32) > r { i - 1 } else { i }
+}
+
+/// Decode `region` of the image at `1 / 2^reduce` scale. Tiles outside the
+/// region are skipped. `cancelled` is polled between code-blocks.
+pub(crate) fn decode(
+    data: &[u8],
+    header: &Header,
+    reduce: u8,
+    region: Region,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<Vec<Plane>> {
+    let siz = &header.siz;
+    let tiles = read_tile_parts(data, header)?;
+    let mut planes: Vec<Plane> = siz
+        .components
+        .iter()
+        .map(|info| {
+            let [x0, y0, x1, y1] = component_rect(region, info, reduce);
+            let (w, h) = ((x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32);
+            Ok(Plane {
+                width: w,
+                height: h,
+                data: filled(area(w as usize, h as usize)?, 0)?,
+                info: *info,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    for (index, tile) in tiles.iter().enumerate() {
+        let rect = siz.tile_rect(index as u32);
+        let outside = rect[2] <= i64::from(region.x0)
+            || rect[0] >= i64::from(region.x1)
+            || rect[3] <= i64::from(region.y0)
+            || rect[1] >= i64::from(region.y1);
+        if outside {
+            continue;
+        }
+        let samples = decode_tile(header, tile, rect, reduce, cancelled)?;
+        for (c, (samples, plane)) in samples.into_iter().zip(&mut planes).enumerate() {
+            let info = &siz.components[c];
+            let [tx0, ty0, tx1, ty1] = component_rect(
+                Region {
+                    x0: rect[0] as u32,
+                    y0: rect[1] as u32,
+                    x1: rect[2] as u32,
+                    y1: rect[3] as u32,
+                },
+                info,
+                reduce,
+            );
+            let [rx0, ry0, ..] = component_rect(region, info, reduce);
+            let tw = (tx1 - tx0) as usize;
+            for ty in ty0..ty1 {
+                let py = ty - ry0;
+                if py < 0 || py >= i64::from(plane.height) {
+                    continue;
+                }
+                for tx in tx0..tx1 {
+                    let px = tx - rx0;
+                    if px < 0 || px >= i64::from(plane.width) {
+                        continue;
+                    }
+                    plane.data[py as usize * plane.width as usize + px as usize] =
+                        samples[(ty - ty0) as usize * tw + (tx - tx0) as usize];
+                }
+            }
+        }
+    }
+    Ok(planes)
+}
+
+fn decode_tile(
+    header: &Header,
+    tile: &TileStream,
+    rect: [i64; 4],
+    reduce: u8,
+    cancelled: &dyn Fn() -> bool,
+) -> Result<Vec<Vec<i32>>> {
+    let siz = &header.siz;
+    let params = Some(&tile.params);
+    let progression = header
+        .progression(params)
+        .ok_or(J2kError::Invalid("missing COD"))?;
+    let mut components = Vec::with_capacity(siz.components.len());
+    for (c, info) in siz.components.iter().enumerate() {
+        let coding = header
+            .coding(params, c)
+            .ok_or(J2kError::Invalid("missing COD"))?;
+        let quant = header
+            .quantization(params, c)
+            .ok_or(J2kError::Invalid("missing QCD"))?;
+        if reduce > coding.levels {
+            return Err(J2kError::Invalid("reduction exceeds decomposition levels"));
+        }
+        components.push(build_component(
+            tile_component_rect(rect, info),
+            coding,
+            quant,
+            info.precision,
+        )?);
+    }
+
+    let order = packet_order(
+        progression.order,
+        progression.layers,
+        &components,
+        siz,
+        rect,
+    )?;
+    let mut pos = 0;
+    for (layer, r, c, p) in order {
+        let comp = &mut components[c];
+        let cb_style = comp.coding.cb_style;
+        match read_packet(
+            &tile.data,
+            pos,
+            &mut comp.resolutions[r],
+            p,
+            layer,
+            &progression,
+            cb_style,
+        ) {
+            Some(next) => pos = next,
+            // Truncated: decode what arrived.
+            None => break,
+        }
+    }
+
+    let mut out = Vec::with_capacity(components.len());
+    let mut float_planes: Vec<Option<Vec<f32>>> = Vec::new();
+    for comp in &components {
+        let top = comp.resolutions.len() - 1 - usize::from(reduce);
+        let mut bands: Vec<Vec<Vec<f32>>> = Vec::with_capacity(top + 1);
+        for res in &comp.resolutions[..=top] {
+            let mut decoded = Vec::with_capacity(res.bands.len());
+            for band in &res.bands {
+                let bw = (band.x1 - band.x0).max(0) as usize;
+                let bh = (band.y1 - band.y0).max(0) as usize;
+                let mut coefficients = filled(area(bw, bh)?, 0.0f32)?;
+                for block in &band.blocks {
+                    if block.segments.is_empty() {
+                        continue;
+                    }
+                    if cancelled() {
+                        return Err(J2kError::Cancelled);
+                    }
+                    let values =
+                        decode_block(block, band, comp.coding.cb_style, comp.coding.reversible);
+                    let w = (block.x1 - block.x0) as usize;
+                    for (row, chunk) in values.chunks_exact(w).enumerate() {
+                        let y = (block.y0 - band.y0) as usize + row;
+                        let x = (block.x0 - band.x0) as usize;
+                        for (dst, &v) in coefficients[y * bw + x..][..w].iter_mut().zip(chunk) {
+                            *dst = v * band.step;
+                        }
+                    }
+                }
+                decoded.push(coefficients);
+            }
+            bands.push(decoded);
+        }
+        match reconstruct(comp, &bands, usize::from(reduce))? {
+            Samples::Int(v) => {
+                out.push(v);
+                float_planes.push(None);
+            }
+            Samples::Float(v) => {
+                out.push(Vec::new());
+                float_planes.push(Some(v));
+            }
+        }
+    }
+
+    // Component transform on the first three components.
+    let same_size = out.len() >= 3
+        && (0..3).all(|c| {
+            siz.components[c].dx == siz.components[0].dx
+                && siz.components[c].dy == siz.components[0].dy
+        });
+    if progression.mct && same_size {
+        if components[0].coding.reversible && float_planes[..3].iter().all(Option::is_none) {
+            for i in 0..out[0].len() {
+                let (y0, y1, y2) = (out[0][i], out[1][i], out[2][i]);
+                let g = y0 - ((y1 + y2) >> 2);
+                out[0][i] = y2 + g;
+                out[1][i] = g;
+                out[2][i] = y1 + g;
+            }
+        } else {
+            let mut f: Vec<Vec<f32>> = (0..3)
+                .map(|c| match float_planes[c].take() {
+                    Some(plane) => Ok(plane),
+                    None => mapped(&out[c], |v| v as f32),
+                })
+                .collect::<Result<_>>()?;
+            for i in 0..f[0].len() {
+                let (y, cb, cr) = (f[0][i], f[1][i], f[2][i]);
+                f[0][i] = y + 1.402 * cr;
+                f[1][i] = y - 0.344_13 * cb - 0.714_14 * cr;
+                f[2][i] = y + 1.772 * cb;
+            }
+            for (c, plane) in f.into_iter().enumerate() {
+                float_planes[c] = Some(plane);
+            }
+        }
+    }
+
+    // DC level shift and clamp.
+    for (c, info) in siz.components.iter().enumerate() {
+        if let Some(f) = float_planes[c].take() {
+            out[c] = f.into_iter().map(round).collect();
+        }
+        let (lo, hi, shift) = if info.signed {
+            (
+                -(1i64 << (info.precision - 1)),
+                (1i64 << (info.precision - 1)) - 1,
+                0,
+            )
+        } else {
+            (
+                0,
+                (1i64 << info.precision) - 1,
+                1i64 << (info.precision - 1),
+            )
+        };
+        for v in &mut out[c] {
+            *v = (i64::from(*v) + shift).clamp(lo, hi) as i32;
+        }
+    }
+    Ok(out)
+}
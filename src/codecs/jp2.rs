@@ -0,0 +1,560 @@
+//! JPEG 2000 adapter: JP2 boxes around the codestream decoder in
+//! [`super::j2k`], then palette, channel definitions and color space
+//! mapped to gray or RGB pixels.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::j2k::{self, J2kError, Plane, Region};
+use crate::config::CodecConfig;
+use crate::error::Result;
+use crate::jp2::{Jp2ColorSpace, Jp2Component, Jp2Header};
+use crate::limits::Stop;
+use crate::{CodecError, DecodeOutput, ImageInfo, Limits, StopToken};
+use whereat::at;
+use zenpixels::{
+    AlphaMode, ChannelLayout, ChannelType, Cicp, PixelBuffer, PixelDescriptor, TransferFunction,
+};
+
+/// JP2 signature box.
+const SIGNATURE: [u8; 12] = [0, 0, 0, 12, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A];
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn invalid(detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!(
+        "JPEG 2000: {detail}"
+    )))
+}
+
+fn unsupported(detail: &'static str) -> whereat::At<CodecError> {
+    at!(CodecError::UnsupportedOperation {
+        format: crate::jp2::format(),
+        detail,
+    })
+}
+
+fn too_large() -> whereat::At<CodecError> {
+    at!(CodecError::LimitExceeded(String::from(
+        "JPEG 2000 image too large to allocate"
+    )))
+}
+
+fn from_j2k(error: J2kError) -> whereat::At<CodecError> {
+    match error {
+        J2kError::Invalid(detail) => invalid(detail),
+        J2kError::Unsupported(detail) => unsupported(detail),
+        J2kError::TooLarge => too_large(),
+        J2kError::Cancelled => at!(CodecError::Cancelled),
+    }
+}
+
+/// Header check used by the format definition: the JP2 signature box or a
+/// bare codestream.
+pub(crate) fn is_jp2(data: &[u8]) -> bool {
+    data.starts_with(&SIGNATURE) || j2k::is_codestream(data)
+}
+
+/// Palette from `pclr`: `entries` rows of one value per column.
+struct Palette {
+    entries: usize,
+    columns: Vec<(u8, bool)>,
+    values: Vec<i32>,
+}
+
+/// What the JP2 header boxes say about the codestream.
+struct Boxes<'a> {
+    codestream: &'a [u8],
+    codestream_only: bool,
+    color_space: Jp2ColorSpace,
+    icc_profile: Option<Vec<u8>>,
+    palette: Option<Palette>,
+    /// `cmap`: `(component, palette column)` per channel.
+    mapping: Vec<(usize, Option<usize>)>,
+    /// `cdef`: `(channel, type, association)`.
+    definitions: Vec<(usize, u16, u16)>,
+}
+
+/// Split a run of boxes into `(type, body)`; a truncated last box is cut
+/// at the end of the data.
+fn split_boxes(data: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        if data.len() - pos < 8 {
+            return Err(invalid("truncated box header"));
+        }
+        let kind = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+        let (header, len) = match u32_at(data, pos) {
+            0 => (8, (data.len() - pos) as u64),
+            1 if data.len() - pos >= 16 => (
+                16,
+                u64::from(u32_at(data, pos + 8)) << 32 | u64::from(u32_at(data, pos + 12)),
+            ),
+            1 => return Err(invalid("truncated box header")),
+            len => (8, u64::from(len)),
+        };
+        if len < header as u64 {
+            return Err(invalid("bad box length"));
+        }
+        let end = (pos as u64).saturating_add(len).min(data.len() as u64) as usize;
+        out.push((kind, &data[pos + header..end]));
+        pos = end;
+    }
+    Ok(out)
+}
+
+fn read_palette(body: &[u8]) -> Result<Palette> {
+    if body.len() < 3 {
+        return Err(invalid("truncated palette"));
+    }
+    let entries = usize::from(u16_at(body, 0));
+    let count = usize::from(body[2]);
+    let columns: Vec<(u8, bool)> = body
+        .get(3..3 + count)
+        .ok_or_else(|| invalid("truncated palette"))?
+        .iter()
+        .map(|&b| ((b & 0x7f) + 1, b & 0x80 != 0))
+        .collect();
+    if entries == 0 || count == 0 || columns.iter().any(|&(p, _)| p > 16) {
+        return Err(invalid("bad palette"));
+    }
+    let mut pos = 3 + count;
+    let mut values = Vec::with_capacity(entries * count);
+    for _ in 0..entries {
+        for &(precision, signed) in &columns {
+            let size = usize::from(precision).div_ceil(8);
+            let bytes = body
+                .get(pos..pos + size)
+                .ok_or_else(|| invalid("truncated palette"))?;
+            pos += size;
+            let raw = bytes.iter().fold(0i32, |v, &b| v << 8 | i32::from(b));
+            // Stored signed values become offset unsigned, like components.
+            values.push(if signed {
+                raw ^ (1 << (precision - 1))
+            } else {
+                raw
+            });
+        }
+    }
+    Ok(Palette {
+        entries,
+        columns,
+        values,
+    })
+}
+
+fn read_boxes(data: &[u8]) -> Result<Boxes<'_>> {
+    let mut boxes = Boxes {
+        codestream: data,
+        codestream_only: true,
+        color_space: Jp2ColorSpace::Unspecified,
+        icc_profile: None,
+        palette: None,
+        mapping: Vec::new(),
+        definitions: Vec::new(),
+    };
+    if j2k::is_codestream(data) {
+        return Ok(boxes);
+    }
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("not a JP2 file or J2K codestream"));
+    }
+    boxes.codestream_only = false;
+    let mut color_found = false;
+    let mut codestream = None;
+    for (kind, body) in split_boxes(data)? {
+        match &kind {
+            b"jp2h" => {
+                for (kind, body) in split_boxes(body)? {
+                    match &kind {
+                        // The first color specification that's understood wins.
+                        b"colr" if !color_found && body.len() >= 3 => match body[0] {
+                            1 if body.len() >= 7 => {
+                                boxes.color_space = match u32_at(body, 3) {
+                                    16 => Jp2ColorSpace::Srgb,
+                                    17 => Jp2ColorSpace::Gray,
+                                    18 => Jp2ColorSpace::Sycc,
+                                    _ => Jp2ColorSpace::Unspecified,
+                                };
+                                color_found = true;
+                            }
+                            2 | 3 => {
+                                boxes.color_space = Jp2ColorSpace::Icc;
+                                boxes.icc_profile = Some(body[3..].to_vec());
+                                color_found = true;
+                            }
+                            _ => {}
+                        },
+                        b"pclr" => boxes.palette = Some(read_palette(body)?),
+                        b"cmap" => {
+                            boxes.mapping = body
+                                .chunks_exact(4)
+                                .map(|m| {
+                                    let column = (m[2] == 1).then_some(usize::from(m[3]));
+                                    (usize::from(u16_at(m, 0)), column)
+                                })
+                                .collect();
+                        }
+                        b"cdef" if body.len() >= 2 => {
+                            let count = usize::from(u16_at(body, 0));
+                            boxes.definitions = body[2..]
+                                .chunks_exact(6)
+                                .take(count)
+                                .map(|d| (usize::from(u16_at(d, 0)), u16_at(d, 2), u16_at(d, 4)))
+                                .collect();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            b"jp2c" => {
+                codestream = Some(body);
+                break;
+            }
+            _ => {}
+        }
+    }
+    boxes.codestream = codestream.ok_or_else(|| invalid("no codestream box"))?;
+    Ok(boxes)
+}
+
+pub(crate) fn header(data: &[u8]) -> Result<Jp2Header> {
+    let boxes = read_boxes(data)?;
+    let header = j2k::read_header(boxes.codestream).map_err(from_j2k)?;
+    let siz = &header.siz;
+    Ok(Jp2Header {
+        codestream_only: boxes.codestream_only,
+        width: siz.x1 - siz.x0,
+        height: siz.y1 - siz.y0,
+        components: siz
+            .components
+            .iter()
+            .map(|c| Jp2Component {
+                precision: c.precision,
+                signed: c.signed,
+                dx: c.dx,
+                dy: c.dy,
+            })
+            .collect(),
+        levels: header.levels(),
+        layers: header.layers(),
+        tile_width: siz.tile_w,
+        tile_height: siz.tile_h,
+        tiles: siz.tiles_x() * siz.tiles_y(),
+        reversible: header.reversible(),
+        color_space: boxes.color_space,
+        icc_profile: boxes.icc_profile,
+    })
+}
+
+/// One output channel: a component, optionally through a palette column.
+#[derive(Clone, Copy)]
+struct Channel {
+    component: usize,
+    column: Option<usize>,
+}
+
+/// Which channels become gray/RGB and alpha, and at what depth.
+struct Plan {
+    channels: Vec<Channel>,
+    color: Vec<usize>,
+    /// Alpha channel and whether it's premultiplied.
+    alpha: Option<(usize, bool)>,
+    /// Precision of each channel.
+    precision: Vec<u8>,
+    sycc: bool,
+}
+
+impl Plan {
+    fn new(boxes: &Boxes<'_>, header: &j2k::Header) -> Result<Self> {
+        let components = &header.siz.components;
+        let channels: Vec<Channel> = match &boxes.palette {
+            Some(palette) if !boxes.mapping.is_empty() => boxes
+                .mapping
+                .iter()
+                .map(|&(component, column)| {
+                    if component >= components.len()
+                        || column.is_some_and(|c| c >= palette.columns.len())
+                    {
+                        return Err(invalid("component mapping out of range"));
+                    }
+                    Ok(Channel { component, column })
+                })
+                .collect::<Result<_>>()?,
+            _ => (0..components.len())
+                .map(|component| Channel {
+                    component,
+                    column: None,
+                })
+                .collect(),
+        };
+        let precision: Vec<u8> = channels
+            .iter()
+            .map(|ch| match (ch.column, &boxes.palette) {
+                (Some(c), Some(palette)) => palette.columns[c].0,
+                _ => components[ch.component].precision,
+            })
+            .collect();
+
+        let (color, alpha) = if boxes.definitions.is_empty() {
+            let gray = boxes.color_space == Jp2ColorSpace::Gray || channels.len() < 3;
+            let count = if gray { 1 } else { 3 };
+            let alpha = (channels.len() > count).then_some((count, false));
+            ((0..count).collect(), alpha)
+        } else {
+            let mut slots = [None; 3];
+            let mut alpha = None;
+            for &(channel, kind, association) in &boxes.definitions {
+                if channel >= channels.len() {
+                    return Err(invalid("channel definition out of range"));
+                }
+                match (kind, association) {
+                    (0, 1..=3) => slots[usize::from(association) - 1] = Some(channel),
+                    (1 | 2, _) if alpha.is_none() => alpha = Some((channel, kind == 2)),
+                    _ => {}
+                }
+            }
+            let color: Vec<usize> = slots.iter().flatten().copied().collect();
+            if color.len() != 1 && color.len() != 3 {
+                return Err(unsupported("channel definitions other than gray or RGB"));
+            }
+            (color, alpha)
+        };
+        Ok(Self {
+            sycc: boxes.color_space == Jp2ColorSpace::Sycc && color.len() == 3,
+            channels,
+            color,
+            alpha,
+            precision,
+        })
+    }
+
+    fn output_channels(&self) -> usize {
+        self.color.len() + usize::from(self.alpha.is_some())
+    }
+
+    /// Output bits per sample: 8 when every channel fits, else 16.
+    fn bit_depth(&self) -> u8 {
+        let used = self.color.iter().chain(self.alpha.as_ref().map(|(a, _)| a));
+        if used.map(|&c| self.precision[c]).max().unwrap_or(8) <= 8 {
+            8
+        } else {
+            16
+        }
+    }
+
+    fn descriptor(&self) -> PixelDescriptor {
+        let layout = match (self.color.len(), self.alpha.is_some()) {
+            (3, true) => ChannelLayout::Rgba,
+            (3, false) => ChannelLayout::Rgb,
+            (_, true) => ChannelLayout::GrayAlpha,
+            (_, false) => ChannelLayout::Gray,
+        };
+        let channel_type = if self.bit_depth() == 8 {
+            ChannelType::U8
+        } else {
+            ChannelType::U16
+        };
+        PixelDescriptor::new(
+            channel_type,
+            layout,
+            self.alpha.map(|_| AlphaMode::Straight),
+            TransferFunction::Srgb,
+        )
+    }
+}
+
+fn image_info(
+    width: u32,
+    height: u32,
+    plan: &Plan,
+    boxes: &Boxes<'_>,
+    header: &j2k::Header,
+) -> ImageInfo {
+    let precision = plan
+        .color
+        .iter()
+        .map(|&c| plan.precision[c])
+        .max()
+        .unwrap_or(8);
+    let mut info = ImageInfo::new(width, height, crate::jp2::format())
+        .with_bit_depth(precision)
+        .with_channel_count(plan.output_channels() as u8);
+    info.has_alpha = plan.alpha.is_some();
+    if let Some(icc) = &boxes.icc_profile {
+        info = info.with_icc_profile(icc.clone());
+    } else if matches!(boxes.color_space, Jp2ColorSpace::Srgb | Jp2ColorSpace::Sycc)
+        || (boxes.codestream_only && header.siz.components.len() >= 3)
+    {
+        info.source_color.cicp = Some(Cicp::SRGB);
+    }
+    info
+}
+
+pub(crate) fn probe(data: &[u8]) -> Result<ImageInfo> {
+    let boxes = read_boxes(data)?;
+    let header = j2k::read_header(boxes.codestream).map_err(from_j2k)?;
+    let plan = Plan::new(&boxes, &header)?;
+    let siz = &header.siz;
+    Ok(image_info(
+        siz.x1 - siz.x0,
+        siz.y1 - siz.y0,
+        &plan,
+        &boxes,
+        &header,
+    ))
+}
+
+/// The requested region on the reference grid, clipped to the image.
+fn region(header: &j2k::Header, config: &crate::jp2::Jp2DecodeConfig) -> Result<Region> {
+    let siz = &header.siz;
+    let Some(r) = config.region else {
+        return Ok(Region {
+            x0: siz.x0,
+            y0: siz.y0,
+            x1: siz.x1,
+            y1: siz.y1,
+        });
+    };
+    let x0 = siz.x0.saturating_add(r.x).min(siz.x1);
+    let y0 = siz.y0.saturating_add(r.y).min(siz.y1);
+    let region = Region {
+        x0,
+        y0,
+        x1: x0.saturating_add(r.width).min(siz.x1),
+        y1: y0.saturating_add(r.height).min(siz.y1),
+    };
+    if region.x1 <= region.x0 || region.y1 <= region.y0 {
+        return Err(invalid("region is outside the image"));
+    }
+    Ok(region)
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    codec_config: Option<&CodecConfig>,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<DecodeOutput> {
+    let boxes = read_boxes(data)?;
+    let header = j2k::read_header(boxes.codestream).map_err(from_j2k)?;
+    let plan = Plan::new(&boxes, &header)?;
+    let config = codec_config
+        .and_then(|c| c.jp2_decoder.as_deref())
+        .cloned()
+        .unwrap_or_default();
+    let reduce = config.reduce.min(header.levels());
+    let region = region(&header, &config)?;
+    let scale = |v: u32| v.div_ceil(1 << reduce);
+    let (x0, y0) = (scale(region.x0), scale(region.y0));
+    let (w, h) = (scale(region.x1) - x0, scale(region.y1) - y0);
+    let bytes = usize::from(plan.bit_depth() / 8);
+    let channels = plan.output_channels();
+    if let Some(limits) = limits {
+        limits
+            .check_dimensions(u64::from(w), u64::from(h))
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+        // Output plus one i32 plane per component.
+        let components = header.siz.components.len();
+        limits
+            .check_memory(u64::from(w) * u64::from(h) * (channels * bytes + components * 4) as u64)
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+    }
+
+    let cancelled = || stop.as_ref().is_some_and(|s| s.check().is_err());
+    let planes =
+        j2k::decode(boxes.codestream, &header, reduce, region, &cancelled).map_err(from_j2k)?;
+
+    // Unsigned sample of channel `c` at output pixel (x, y); subsampled
+    // components repeat their nearest sample.
+    let sample = |c: usize, x: u32, y: u32| -> i32 {
+        let channel = plan.channels[c];
+        let plane: &Plane = &planes[channel.component];
+        let info = plane.info;
+        if plane.width == 0 || plane.height == 0 {
+            return 0;
+        }
+        let px = ((x0 + x) / info.dx)
+            .saturating_sub(x0.div_ceil(info.dx))
+            .min(plane.width - 1);
+        let py = ((y0 + y) / info.dy)
+            .saturating_sub(y0.div_ceil(info.dy))
+            .min(plane.height - 1);
+        let mut v = plane.data[py as usize * plane.width as usize + px as usize];
+        if info.signed {
+            v += 1 << (info.precision - 1);
+        }
+        match (channel.column, &boxes.palette) {
+            (Some(column), Some(palette)) => {
+                let index = (v.max(0) as usize).min(palette.entries - 1);
+                palette.values[index * palette.columns.len() + column]
+            }
+            _ => v,
+        }
+    };
+    let max = |c: usize| (1u32 << plan.precision[c]) - 1;
+    let target = (1u32 << plan.bit_depth()) - 1;
+    let rescale = |v: f32, c: usize| -> u32 {
+        let v = v.clamp(0.0, max(c) as f32);
+        (v * target as f32 / max(c) as f32 + 0.5) as u32
+    };
+
+    let mut out = Vec::new();
+    (w as usize)
+        .checked_mul(h as usize)
+        .and_then(|n| n.checked_mul(channels * bytes))
+        .and_then(|len| out.try_reserve_exact(len).ok())
+        .ok_or_else(too_large)?;
+    let mut px = [0f32; 4];
+    for y in 0..h {
+        if y % 64 == 0 && cancelled() {
+            return Err(at!(CodecError::Cancelled));
+        }
+        for x in 0..w {
+            for (slot, &c) in px.iter_mut().zip(&plan.color) {
+                *slot = sample(c, x, y) as f32;
+            }
+            if plan.sycc {
+                let offset = (1u32 << (plan.precision[plan.color[1]] - 1)) as f32;
+                let (luma, cb, cr) = (px[0], px[1] - offset, px[2] - offset);
+                px[0] = luma + 1.402 * cr;
+                px[1] = luma - 0.344_136 * cb - 0.714_136 * cr;
+                px[2] = luma + 1.772 * cb;
+            }
+            let n = plan.color.len();
+            let mut values = [0u32; 4];
+            for (i, value) in values[..n].iter_mut().enumerate() {
+                *value = rescale(px[i], plan.color[i]);
+            }
+            if let Some((a, premultiplied)) = plan.alpha {
+                let alpha = sample(a, x, y) as f32;
+                if premultiplied && alpha > 0.0 {
+                    for (i, value) in values[..n].iter_mut().enumerate() {
+                        let c = plan.color[i];
+                        *value = rescale(px[i] * max(a) as f32 / alpha, c);
+                    }
+                }
+                values[n] = rescale(alpha, a);
+            }
+            for &v in &values[..channels] {
+                if bytes == 1 {
+                    out.push(v as u8);
+                } else {
+                    out.extend_from_slice(&(v as u16).to_ne_bytes());
+                }
+            }
+        }
+    }
+
+    let pixels = PixelBuffer::from_vec(out, w, h, plan.descriptor())
+        .map_err(|_| invalid("failed to create PixelBuffer"))?;
+    let info = image_info(w, h, &plan, &boxes, &header);
+    Ok(DecodeOutput::new(pixels, info))
+}
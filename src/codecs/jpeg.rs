@@ -345,3 +345,413 @@ pub(crate) fn encode_with_precomputed_gainmap(
 
     Ok(EncodeOutput::new(out, ImageFormat::Jpeg))
 }
+
+// ═══════════════════════════════════════════════════════════════════════
+// Depth map embedding (GDepth / Dynamic Depth)
+// ═══════════════════════════════════════════════════════════════════════
+
+/// Largest main XMP packet: one APP1 segment holds 65,533 payload bytes,
+/// less the 29-byte namespace header. Some slack is left for the
+/// `<?xpacket?>` wrapper an encoder may add.
+const MAIN_XMP_LIMIT: usize = 65_504 - 512;
+
+/// Payload bytes per Extended XMP segment: 65,533 less the 35-byte
+/// namespace, 32-byte GUID and two 4-byte length/offset fields.
+const EXTENDED_XMP_CHUNK: usize = 65_458;
+
+const XMP_NS_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const EXTENDED_XMP_NS_HEADER: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+const XMP_NOTE_NS: &str = "http://ns.adobe.com/xmp/note/";
+const CONTAINER_NS: &str = "http://ns.google.com/photos/1.0/container/";
+const APPLE_PIXEL_DATA_NS: &str = "http://ns.apple.com/pixeldatainfo/1.0/";
+
+/// What to add to a JPEG to embed a depth map. Built by
+/// [`depth_segments`], applied by [`embed_depth_segments`].
+#[derive(Default)]
+pub(crate) struct DepthSegments {
+    /// Main XMP packet, replacing the request's. `None` keeps it.
+    pub(crate) xmp: Option<alloc::vec::Vec<u8>>,
+    /// Extended XMP packet (GDepth data too large for the main packet),
+    /// written as APP1 segments after the main XMP.
+    pub(crate) extended_xmp: alloc::vec::Vec<u8>,
+    /// GUID of `extended_xmp`: uppercase hex MD5, as the main packet's
+    /// `xmpNote:HasExtendedXMP` names it.
+    pub(crate) extended_guid: alloc::string::String,
+    /// Bytes appended after the primary image's EOI (Dynamic Depth items).
+    pub(crate) trailer: alloc::vec::Vec<u8>,
+    /// JPEG stored as the second MPF image (disparity).
+    pub(crate) mpf_disparity: alloc::vec::Vec<u8>,
+}
+
+/// Segments that embed `depth` in a JPEG as `container`.
+///
+/// - [`GDepth`](crate::JpegDepthContainer::GDepth): the base64 depth
+///   (and confidence) image goes in the main XMP packet when the merged
+///   packet fits one APP1 segment, otherwise in Extended XMP with only
+///   the parameters in the main packet.
+/// - [`DynamicDepth`](crate::JpegDepthContainer::DynamicDepth): a
+///   container directory in XMP; the images follow the primary's EOI.
+/// - [`MpfDisparity`](crate::JpegDepthContainer::MpfDisparity): an 8-bit
+///   disparity JPEG (255 = near) as a second MPF image, with its float
+///   range in Apple pixel-data XMP. The main XMP is left alone.
+///
+/// GDepth and Dynamic Depth descriptions are added to `existing_xmp`. An
+/// existing packet that already describes a depth map or container is
+/// replaced, since its items would no longer match the file.
+pub(crate) fn depth_segments(
+    existing_xmp: Option<&[u8]>,
+    depth: &crate::depthmap::DecodedDepthMap,
+    container: crate::depthmap::JpegDepthContainer,
+) -> Result<DepthSegments> {
+    use crate::depthmap::{
+        DepthFormat, DepthMeasureType, DepthUnits, GDEPTH_NS, JpegDepthContainer,
+        encode_depth_image, gdepth_attributes, xmp_packet,
+    };
+    use alloc::format;
+    use alloc::string::String;
+
+    if container == JpegDepthContainer::MpfDisparity {
+        return Ok(DepthSegments {
+            mpf_disparity: disparity_jpeg(depth)?,
+            ..DepthSegments::default()
+        });
+    }
+
+    let (image, metadata) = depth.to_range_image();
+    let (depth_bytes, depth_mime) = encode_depth_image(&image)?;
+    let confidence = depth
+        .confidence
+        .as_ref()
+        .map(encode_depth_image)
+        .transpose()?;
+
+    let existing = existing_xmp
+        .and_then(|x| core::str::from_utf8(x).ok())
+        .filter(|x| !x.contains(GDEPTH_NS) && !x.contains(CONTAINER_NS));
+    let merge =
+        |description: &str| match existing.and_then(|x| x.rfind("</rdf:RDF>").map(|at| (x, at))) {
+            Some((x, at)) => format!("{}{description}{}", &x[..at], &x[at..]),
+            None => xmp_packet(description),
+        };
+
+    if container == JpegDepthContainer::GDepth {
+        let mut data = format!(" GDepth:Data=\"{}\"", base64(&depth_bytes));
+        if let Some((bytes, _)) = &confidence {
+            data += &format!(" GDepth:Confidence=\"{}\"", base64(bytes));
+        }
+        let mut params = format!(
+            "xmlns:GDepth=\"{GDEPTH_NS}\" {} GDepth:Mime=\"{depth_mime}\"",
+            gdepth_attributes(&metadata)
+        );
+        if let Some((_, mime)) = &confidence {
+            params += &format!(" GDepth:ConfidenceMime=\"{mime}\"");
+        }
+
+        let inline = merge(&format!("<rdf:Description rdf:about=\"\" {params}{data}/>"));
+        if inline.len() <= MAIN_XMP_LIMIT {
+            return Ok(DepthSegments {
+                xmp: Some(inline.into_bytes()),
+                ..DepthSegments::default()
+            });
+        }
+        let extended = xmp_packet(&format!(
+            "<rdf:Description rdf:about=\"\" xmlns:GDepth=\"{GDEPTH_NS}\"{data}/>"
+        ));
+        let guid: String = md5(extended.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+        let main = merge(&format!(
+            "<rdf:Description rdf:about=\"\" xmlns:xmpNote=\"{XMP_NOTE_NS}\" {params} \
+             xmpNote:HasExtendedXMP=\"{guid}\"/>"
+        ));
+        if main.len() > MAIN_XMP_LIMIT {
+            return Err(at!(CodecError::InvalidInput(format!(
+                "XMP packet is {} bytes, too large for a JPEG APP1 segment",
+                main.len()
+            ))));
+        }
+        return Ok(DepthSegments {
+            xmp: Some(main.into_bytes()),
+            extended_xmp: extended.into_bytes(),
+            extended_guid: guid,
+            ..DepthSegments::default()
+        });
+    }
+
+    let format = match metadata.format {
+        DepthFormat::RangeInverse => "RangeInverse",
+        _ => "RangeLinear",
+    };
+    let measure_type = match metadata.measure_type {
+        DepthMeasureType::OpticRay => "OpticRay",
+        _ => "OpticalAxis",
+    };
+    let diopters = metadata.units == DepthUnits::Diopters;
+    // XMP reals can't be infinite.
+    let real = |v: f32| if v.is_finite() { v } else { f32::MAX };
+    let (near, far) = (real(metadata.near), real(metadata.far));
+
+    let item = |semantic: &str, mime: &str, len: usize| {
+        format!(
+            "<rdf:li rdf:parseType=\"Resource\"><Container:Item Item:Semantic=\"{semantic}\" \
+             Item:Mime=\"{mime}\" Item:Length=\"{len}\"/></rdf:li>"
+        )
+    };
+    let mut items = String::from(
+        "<rdf:li rdf:parseType=\"Resource\"><Container:Item Item:Semantic=\"Primary\" \
+         Item:Mime=\"image/jpeg\"/></rdf:li>",
+    );
+    let mut trailer = alloc::vec::Vec::new();
+    items += &item("DepthMap", depth_mime, depth_bytes.len());
+    trailer.extend_from_slice(&depth_bytes);
+    if let Some((bytes, mime)) = &confidence {
+        items += &item("ConfidenceMap", mime, bytes.len());
+        trailer.extend_from_slice(bytes);
+    }
+    let xmp = merge(&format!(
+        "<rdf:Description rdf:about=\"\" xmlns:Container=\"{CONTAINER_NS}\" \
+         xmlns:Item=\"{CONTAINER_NS}item/\" \
+         xmlns:DepthMap=\"http://ns.google.com/photos/dd/1.0/depthmap/\" \
+         DepthMap:Format=\"{format}\" DepthMap:Near=\"{near}\" DepthMap:Far=\"{far}\" \
+         DepthMap:Units=\"{}\" DepthMap:MeasureType=\"{measure_type}\">\
+         <Container:Directory><rdf:Seq>{items}</rdf:Seq></Container:Directory>\
+         </rdf:Description>",
+        if diopters { "Diopters" } else { "Meters" },
+    ));
+    if xmp.len() > MAIN_XMP_LIMIT {
+        return Err(at!(CodecError::InvalidInput(format!(
+            "XMP packet is {} bytes, too large for a JPEG APP1 segment",
+            xmp.len()
+        ))));
+    }
+    Ok(DepthSegments {
+        xmp: Some(xmp.into_bytes()),
+        trailer,
+        ..DepthSegments::default()
+    })
+}
+
+/// 8-bit disparity JPEG for MPF: 1/meters scaled so 255 is the near plane
+/// and 0 the far plane, with the float range in `apdi:` XMP.
+fn disparity_jpeg(depth: &crate::depthmap::DecodedDepthMap) -> Result<alloc::vec::Vec<u8>> {
+    use crate::depthmap::{DepthFormat, DepthPixelFormat, xmp_packet};
+    use zenpixels::{PixelDescriptor, PixelSlice};
+
+    // RangeInverse over near..far is disparity with 0 at the near plane.
+    // A near plane at zero would be infinite disparity; clamp it to 1 mm.
+    let (near, far) = depth.metric_range();
+    let near = near.max(1e-3);
+    let inverse = depth
+        .convert(
+            DepthFormat::RangeInverse,
+            DepthPixelFormat::Gray8,
+            near,
+            far,
+        )
+        .map_err(|e| {
+            at!(CodecError::InvalidInput(alloc::format!(
+                "depth map can't be stored as disparity: {}",
+                e.error()
+            )))
+        })?;
+    let pixels: alloc::vec::Vec<u8> = inverse.depth.data.iter().map(|v| 255 - v).collect();
+    let (w, h) = (inverse.depth.width, inverse.depth.height);
+    let slice = PixelSlice::new(&pixels, w, h, w as usize, PixelDescriptor::GRAY8)
+        .map_err(|e| at!(CodecError::InvalidInput(alloc::format!("pixel slice: {e}"))))?;
+    let max_disparity = 1.0 / near;
+    let min_disparity = if far.is_finite() { 1.0 / far } else { 0.0 };
+    let xmp = xmp_packet(&alloc::format!(
+        "<rdf:Description rdf:about=\"\" xmlns:apdi=\"{APPLE_PIXEL_DATA_NS}\" \
+         apdi:NativeFormat=\"1751411059\" apdi:FloatMinValue=\"{min_disparity}\" \
+         apdi:FloatMaxValue=\"{max_disparity}\"/>"
+    ));
+    let output = crate::EncodeRequest::new(ImageFormat::Jpeg)
+        .with_quality(95.0)
+        .with_metadata(crate::Metadata::none().with_xmp(xmp.into_bytes()))
+        .encode(slice, false)?;
+    Ok(output.into_vec())
+}
+
+/// Add `segments` to an encoded `jpeg`: Extended XMP after the main XMP
+/// segment, the MPF index and disparity image, and the trailer.
+pub(crate) fn embed_depth_segments(
+    mut jpeg: alloc::vec::Vec<u8>,
+    segments: &DepthSegments,
+) -> Result<alloc::vec::Vec<u8>> {
+    if !segments.extended_xmp.is_empty() {
+        let at = app_segments(&jpeg)?
+            .into_iter()
+            .find(|&(marker, start, _)| {
+                marker == 0xE1 && jpeg[start + 4..].starts_with(XMP_NS_HEADER)
+            })
+            .map(|(_, start, len)| start + 2 + len)
+            .ok_or_else(|| {
+                at!(CodecError::InvalidInput(
+                    "encoded JPEG has no XMP segment to extend".into()
+                ))
+            })?;
+        let total = segments.extended_xmp.len() as u32;
+        let mut inserted = alloc::vec::Vec::new();
+        for (i, chunk) in segments.extended_xmp.chunks(EXTENDED_XMP_CHUNK).enumerate() {
+            let len = 2 + EXTENDED_XMP_NS_HEADER.len() + 32 + 8 + chunk.len();
+            inserted.extend_from_slice(&[0xFF, 0xE1]);
+            inserted.extend_from_slice(&(len as u16).to_be_bytes());
+            inserted.extend_from_slice(EXTENDED_XMP_NS_HEADER);
+            inserted.extend_from_slice(segments.extended_guid.as_bytes());
+            inserted.extend_from_slice(&total.to_be_bytes());
+            inserted.extend_from_slice(&((i * EXTENDED_XMP_CHUNK) as u32).to_be_bytes());
+            inserted.extend_from_slice(chunk);
+        }
+        jpeg.splice(at..at, inserted);
+    }
+
+    if !segments.mpf_disparity.is_empty() {
+        // The MPF index goes after the other APPn segments.
+        let at = app_segments(&jpeg)?
+            .last()
+            .map_or(2, |&(_, start, len)| start + 2 + len);
+        const ENTRIES_OFFSET: u32 = 8 + 2 + 3 * 12 + 4;
+        let payload_len = 4 + ENTRIES_OFFSET as usize + 2 * 16;
+        let segment_len = 4 + payload_len;
+        let primary_len = (jpeg.len() + segment_len) as u32;
+        // Offsets count from the TIFF header, right after "MPF\0".
+        let tiff_start = (at + 8) as u32;
+
+        let mut mpf = alloc::vec::Vec::with_capacity(segment_len);
+        mpf.extend_from_slice(&[0xFF, 0xE2]);
+        mpf.extend_from_slice(&((payload_len + 2) as u16).to_be_bytes());
+        mpf.extend_from_slice(b"MPF\0MM\0\x2A");
+        mpf.extend_from_slice(&8u32.to_be_bytes());
+        mpf.extend_from_slice(&3u16.to_be_bytes());
+        // MPFVersion "0100", NumberOfImages 2, MPEntry.
+        mpf.extend_from_slice(&[0xB0, 0x00, 0, 7, 0, 0, 0, 4]);
+        mpf.extend_from_slice(b"0100");
+        mpf.extend_from_slice(&[0xB0, 0x01, 0, 4, 0, 0, 0, 1, 0, 0, 0, 2]);
+        mpf.extend_from_slice(&[0xB0, 0x02, 0, 7, 0, 0, 0, 32]);
+        mpf.extend_from_slice(&ENTRIES_OFFSET.to_be_bytes());
+        mpf.extend_from_slice(&0u32.to_be_bytes());
+        // Representative baseline primary image.
+        mpf.extend_from_slice(&0x2003_0000u32.to_be_bytes());
+        mpf.extend_from_slice(&primary_len.to_be_bytes());
+        mpf.extend_from_slice(&[0; 8]);
+        // Disparity image.
+        mpf.extend_from_slice(&0x0002_0002u32.to_be_bytes());
+        mpf.extend_from_slice(&(segments.mpf_disparity.len() as u32).to_be_bytes());
+        mpf.extend_from_slice(&(primary_len - tiff_start).to_be_bytes());
+        mpf.extend_from_slice(&[0; 4]);
+        debug_assert_eq!(mpf.len(), segment_len);
+
+        jpeg.splice(at..at, mpf);
+        jpeg.extend_from_slice(&segments.mpf_disparity);
+    }
+
+    // Dynamic Depth items follow the primary image's EOI.
+    jpeg.extend_from_slice(&segments.trailer);
+    Ok(jpeg)
+}
+
+/// `(marker, offset, length)` of each APPn/COM segment at the start of
+/// `jpeg`; the length excludes the two marker bytes.
+fn app_segments(jpeg: &[u8]) -> Result<alloc::vec::Vec<(u8, usize, usize)>> {
+    let malformed = || at!(CodecError::InvalidInput("malformed JPEG segment".into()));
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return Err(malformed());
+    }
+    let mut segments = alloc::vec::Vec::new();
+    let mut pos = 2;
+    while let [0xFF, marker @ (0xE0..=0xEF | 0xFE), hi, lo, ..] = jpeg[pos..] {
+        let len = usize::from(u16::from_be_bytes([hi, lo]));
+        if len < 2 || pos + 2 + len > jpeg.len() {
+            return Err(malformed());
+        }
+        segments.push((marker, pos, len));
+        pos += 2 + len;
+    }
+    Ok(segments)
+}
+
+/// MD5 digest (RFC 1321), for Extended XMP GUIDs.
+fn md5(data: &[u8]) -> [u8; 16] {
+    const S: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    const K: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in message.chunks_exact(64) {
+        let m: [u32; 16] = core::array::from_fn(|i| {
+            u32::from_le_bytes([
+                block[i * 4],
+                block[i * 4 + 1],
+                block[i * 4 + 2],
+                block[i * 4 + 3],
+            ])
+        });
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(K[i])
+                .wrapping_add(m[g])
+                .rotate_left(S[i]);
+            (a, d, c) = (d, c, b);
+            b = b.wrapping_add(rotated);
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+    let mut digest = [0u8; 16];
+    for (out, word) in digest.chunks_exact_mut(4).zip(state) {
+        out.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// Standard base64 with padding.
+fn base64(data: &[u8]) -> alloc::string::String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = alloc::string::String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -43,3 +43,12 @@ pub(crate) fn decode(
         .map_err_at(|e| CodecError::from_codec(ImageFormat::Jxl, e))?;
     at_crate!(decoder.decode()).map_err_at(|e| CodecError::from_codec(ImageFormat::Jxl, e))
 }
+
+/// Rebuild the original JPEG bytes from a recompressed JXL.
+///
+/// Returns `None` when the file has no `jbrd` reconstruction box (a JXL
+/// that wasn't recompressed from a JPEG, or one stored without it).
+pub(crate) fn reconstruct_jpeg(data: &[u8]) -> Result<Option<alloc::vec::Vec<u8>>> {
+    zenjxl_decoder::reconstruct_jpeg(data)
+        .map_err(|e| whereat::at!(CodecError::from_codec(ImageFormat::Jxl, e)))
+}
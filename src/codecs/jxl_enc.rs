@@ -4,7 +4,7 @@ use crate::config::CodecConfig;
 use crate::dispatch::{BuiltEncoder, EncodeParams, StreamingEncoder, build_from_config};
 
 /// Build a JxlEncoderConfig from encoding params.
-fn build_encoding(
+pub(crate) fn build_encoding(
     quality: Option<f32>,
     effort: Option<u32>,
     _codec_config: Option<&CodecConfig>,
@@ -97,7 +97,10 @@ pub(crate) fn encode_with_precomputed_gainmap(
     };
 
     // 3. Serialize ISO 21496-1 metadata
-    let iso_bytes = zenjpeg::ultrahdr::serialize_iso21496(metadata);
+    let iso_bytes = zenjpeg::ultrahdr::serialize_iso21496(
+        metadata,
+        zenjpeg::ultrahdr::Iso21496Format::JpegApp2,
+    );
 
     // 4. Build GainMapBundle and serialize
     let bundle = GainMapBundle {
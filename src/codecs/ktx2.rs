@@ -0,0 +1,277 @@
+//! KTX2 container adapter. Formats are read from `vkFormat`; supercompressed
+//! and Basis Universal files are not supported. Pixel work is shared with
+//! DDS in [`super::texture`].
+
+use alloc::vec::Vec;
+
+use crate::codecs::texture;
+use crate::config::CodecConfig;
+use crate::dispatch::{BuiltEncoder, EncodeParams};
+use crate::error::Result;
+use crate::texture::{MipLevel, TextureFormat, TextureInfo};
+use crate::{CodecError, DecodeOutput, ImageInfo, Limits, StopToken};
+use whereat::at;
+use zencodec::encode::EncodeOutput;
+use zenpixels::PixelSlice;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_LEN: usize = 80;
+const LEVEL_ENTRY_LEN: usize = 24;
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn u64_at(data: &[u8], at: usize) -> u64 {
+    u64::from(u32_at(data, at)) | (u64::from(u32_at(data, at + 4)) << 32)
+}
+
+fn invalid(detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!("KTX2: {detail}")))
+}
+
+fn unsupported(detail: &'static str) -> whereat::At<CodecError> {
+    at!(CodecError::UnsupportedOperation {
+        format: crate::ktx2::format(),
+        detail,
+    })
+}
+
+/// Identifier check used by the format definition.
+pub(crate) fn is_ktx2(data: &[u8]) -> bool {
+    data.starts_with(&IDENTIFIER)
+}
+
+/// Format and sRGB flag of a `VkFormat`.
+fn vk_format(code: u32) -> Option<(TextureFormat, bool)> {
+    Some(match code {
+        9 => (TextureFormat::R8, false),
+        15 => (TextureFormat::R8, true),
+        16 => (TextureFormat::Rg8, false),
+        37 => (TextureFormat::Rgba8, false),
+        43 => (TextureFormat::Rgba8, true),
+        44 => (TextureFormat::Bgra8, false),
+        50 => (TextureFormat::Bgra8, true),
+        97 => (TextureFormat::Rgba16f, false),
+        109 => (TextureFormat::Rgba32f, false),
+        131 => (TextureFormat::Bc1 { alpha: false }, false),
+        132 => (TextureFormat::Bc1 { alpha: false }, true),
+        133 => (TextureFormat::Bc1 { alpha: true }, false),
+        134 => (TextureFormat::Bc1 { alpha: true }, true),
+        135 => (TextureFormat::Bc2, false),
+        136 => (TextureFormat::Bc2, true),
+        137 => (TextureFormat::Bc3, false),
+        138 => (TextureFormat::Bc3, true),
+        139 => (TextureFormat::Bc4, false),
+        141 => (TextureFormat::Bc5, false),
+        143 => (TextureFormat::Bc6h { signed: false }, false),
+        144 => (TextureFormat::Bc6h { signed: true }, false),
+        145 => (TextureFormat::Bc7, false),
+        146 => (TextureFormat::Bc7, true),
+        _ => return None,
+    })
+}
+
+fn vk_code(format: TextureFormat, srgb: bool) -> u32 {
+    let base = match format {
+        TextureFormat::Rgba8 => 37,
+        TextureFormat::Bc1 { alpha: false } => 131,
+        TextureFormat::Bc1 { alpha: true } => 133,
+        TextureFormat::Bc2 => 135,
+        TextureFormat::Bc3 => 137,
+        TextureFormat::Bc4 => return 139,
+        TextureFormat::Bc5 => return 141,
+        _ => 145,
+    };
+    match (format, srgb) {
+        (TextureFormat::Rgba8, true) => 43,
+        (_, true) => base + 1,
+        (_, false) => base,
+    }
+}
+
+pub(crate) fn info(data: &[u8]) -> Result<TextureInfo> {
+    if !is_ktx2(data) || data.len() < HEADER_LEN {
+        return Err(invalid("not a KTX2 file"));
+    }
+    match u32_at(data, 12) {
+        0 => {
+            return Err(unsupported(
+                "Basis Universal and other VK_FORMAT_UNDEFINED data",
+            ));
+        }
+        _ if u32_at(data, 44) != 0 => return Err(unsupported("supercompressed KTX2")),
+        _ => {}
+    }
+    let (format, srgb) =
+        vk_format(u32_at(data, 12)).ok_or_else(|| unsupported("unsupported KTX2 vkFormat"))?;
+    let width = u32_at(data, 20);
+    let height = u32_at(data, 24).max(1);
+    if width == 0 {
+        return Err(invalid("zero width"));
+    }
+    let level_count = u32_at(data, 40).max(1) as usize;
+    let index_end = HEADER_LEN + level_count * LEVEL_ENTRY_LEN;
+    if level_count > 32 || data.len() < index_end {
+        return Err(invalid("truncated level index"));
+    }
+    let mip_levels = (0..level_count)
+        .map(|i| {
+            let entry = HEADER_LEN + i * LEVEL_ENTRY_LEN;
+            let (w, h) = ((width >> i).max(1), (height >> i).max(1));
+            let offset = usize::try_from(u64_at(data, entry))
+                .map_err(|_| invalid("level offset out of range"))?;
+            let byte_len = format.image_bytes(w, h);
+            if u64_at(data, entry + 8) < byte_len as u64 {
+                return Err(invalid("level smaller than its first image"));
+            }
+            Ok(MipLevel {
+                width: w,
+                height: h,
+                offset,
+                byte_len,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(TextureInfo {
+        width,
+        height,
+        depth: u32_at(data, 28).max(1),
+        format,
+        srgb,
+        array_layers: u32_at(data, 32).max(1),
+        faces: u32_at(data, 36).max(1),
+        mip_levels,
+    })
+}
+
+pub(crate) fn probe(data: &[u8]) -> Result<ImageInfo> {
+    let texture = info(data)?;
+    Ok(texture::image_info(
+        &texture,
+        &texture.mip_levels[0],
+        crate::ktx2::format(),
+    ))
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    codec_config: Option<&CodecConfig>,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<DecodeOutput> {
+    let texture = info(data)?;
+    texture::decode_level(
+        &texture,
+        data,
+        codec_config,
+        limits,
+        stop,
+        crate::ktx2::format(),
+        "KTX2",
+    )
+}
+
+/// Basic data format descriptor: color model and one sample per stored
+/// channel, as `(channel id, bit offset, bit length)`.
+fn data_format_descriptor(format: TextureFormat, srgb: bool) -> Vec<u8> {
+    const ALPHA: u8 = 15;
+    let (model, samples): (u8, &[(u8, u16, u16)]) = match format {
+        TextureFormat::Bc1 { alpha } => (128, if alpha { &[(1, 0, 64)] } else { &[(0, 0, 64)] }),
+        TextureFormat::Bc2 => (129, &[(ALPHA, 0, 64), (0, 64, 64)]),
+        TextureFormat::Bc3 => (130, &[(ALPHA, 0, 64), (0, 64, 64)]),
+        TextureFormat::Bc4 => (131, &[(0, 0, 64)]),
+        TextureFormat::Bc5 => (132, &[(0, 0, 64), (1, 64, 64)]),
+        TextureFormat::Bc7 => (134, &[(0, 0, 128)]),
+        // RGBSDA
+        _ => (1, &[(0, 0, 8), (1, 8, 8), (2, 16, 8), (ALPHA, 24, 8)]),
+    };
+    let block_len = 24 + 16 * samples.len();
+    let mut dfd = Vec::with_capacity(4 + block_len);
+    dfd.extend_from_slice(&((4 + block_len) as u32).to_le_bytes());
+    // Khronos vendor, basic descriptor type, version 2.
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&(2 | ((block_len as u32) << 16)).to_le_bytes());
+    // BT.709 primaries; sRGB or linear transfer; straight alpha.
+    dfd.extend_from_slice(&[model, 1, if srgb { 2 } else { 1 }, 0]);
+    let (block_dim, plane_bytes) = if format.is_block_compressed() {
+        (3, format.unit_bytes() as u8)
+    } else {
+        (0, 4)
+    };
+    dfd.extend_from_slice(&[block_dim, block_dim, 0, 0]);
+    dfd.extend_from_slice(&[plane_bytes, 0, 0, 0, 0, 0, 0, 0]);
+    for &(channel, offset, len) in samples {
+        // Alpha is linear even in sRGB textures.
+        let qualifiers = if channel == ALPHA && srgb { 0x80 } else { 0 };
+        dfd.extend_from_slice(&offset.to_le_bytes());
+        dfd.extend_from_slice(&[(len - 1) as u8, channel | qualifiers]);
+        dfd.extend_from_slice(&[0; 4]);
+        let upper = if len == 8 { 255 } else { u32::MAX };
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&upper.to_le_bytes());
+    }
+    dfd
+}
+
+fn encode(pixels: PixelSlice<'_>, params: &EncodeParams<'_>) -> Result<EncodeOutput> {
+    let (config, levels) = texture::encode_levels(pixels, params, crate::ktx2::format())?;
+    let format = config.format;
+    let srgb = config.srgb && !matches!(format, TextureFormat::Bc4 | TextureFormat::Bc5);
+    let dfd = data_format_descriptor(format, srgb);
+    let dfd_offset = HEADER_LEN + levels.len() * LEVEL_ENTRY_LEN;
+    // Levels are aligned to lcm(texel block size, 4).
+    let align = if format.is_block_compressed() {
+        format.unit_bytes()
+    } else {
+        4
+    };
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&IDENTIFIER);
+    let base = &levels[0];
+    for v in [
+        vk_code(format, srgb),
+        1,
+        base.width,
+        base.height,
+        0,
+        0,
+        1,
+        levels.len() as u32,
+        0,
+        dfd_offset as u32,
+        dfd.len() as u32,
+        0,
+        0,
+    ] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out.resize(dfd_offset, 0);
+    out.extend_from_slice(&dfd);
+
+    // Smallest level first, as the spec recommends for streaming.
+    let mut offsets = alloc::vec![0usize; levels.len()];
+    for (i, level) in levels.iter().enumerate().rev() {
+        out.resize(out.len().next_multiple_of(align), 0);
+        offsets[i] = out.len();
+        out.extend_from_slice(&level.data);
+    }
+    for (i, level) in levels.iter().enumerate() {
+        let entry = HEADER_LEN + i * LEVEL_ENTRY_LEN;
+        let len = level.data.len() as u64;
+        out[entry..entry + 8].copy_from_slice(&(offsets[i] as u64).to_le_bytes());
+        out[entry + 8..entry + 16].copy_from_slice(&len.to_le_bytes());
+        out[entry + 16..entry + 24].copy_from_slice(&len.to_le_bytes());
+    }
+    Ok(EncodeOutput::new(out, crate::ktx2::format()))
+}
+
+pub(crate) fn build_trait_encoder<'a>(params: EncodeParams<'a>) -> BuiltEncoder<'a> {
+    BuiltEncoder {
+        encoder: alloc::boxed::Box::new(move |pixels| encode(pixels, &params)),
+        supported: &texture::SUPPORTED,
+    }
+}
@@ -18,6 +18,8 @@ pub(crate) mod png;
 #[cfg(feature = "avif-decode")]
 pub(crate) mod avif_dec;
 
+#[cfg(feature = "avif-encode")]
+pub(crate) mod avif_aux;
 #[cfg(feature = "avif-encode")]
 pub(crate) mod avif_enc;
 
@@ -53,3 +55,30 @@ pub(crate) mod tiff;
 
 #[cfg(feature = "raw-decode")]
 pub(crate) mod raw;
+
+#[cfg(feature = "exr")]
+pub(crate) mod exr;
+
+#[cfg(feature = "ico")]
+pub(crate) mod ico;
+
+#[cfg(feature = "psd")]
+pub(crate) mod psd;
+
+#[cfg(any(feature = "dds", feature = "ktx2"))]
+pub(crate) mod bcn;
+
+#[cfg(any(feature = "dds", feature = "ktx2"))]
+pub(crate) mod texture;
+
+#[cfg(feature = "dds")]
+pub(crate) mod dds;
+
+#[cfg(feature = "ktx2")]
+pub(crate) mod ktx2;
+
+#[cfg(feature = "jp2-decode")]
+pub(crate) mod j2k;
+
+#[cfg(feature = "jp2-decode")]
+pub(crate) mod jp2;
@@ -0,0 +1,591 @@
+//! PSD/PSB adapter: header and image resources, plus the flattened
+//! composite stored after the layer section. Layers are never composited.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::limits::Stop;
+use crate::psd::{PsdColorMode, PsdHeader, PsdResources, PsdThumbnail};
+use crate::{CodecError, DecodeOutput, ImageInfo, Limits, StopToken};
+use whereat::at;
+use zenpixels::{
+    AlphaMode, ChannelLayout, ChannelType, PixelBuffer, PixelDescriptor, TransferFunction,
+};
+
+const HEADER_LEN: usize = 26;
+
+const RESOURCE_ICC: u16 = 1039;
+const RESOURCE_IPTC: u16 = 1028;
+const RESOURCE_XMP: u16 = 1060;
+const RESOURCE_EXIF: u16 = 1058;
+const RESOURCE_THUMBNAIL: u16 = 1036;
+const RESOURCE_THUMBNAIL_PS4: u16 = 1033;
+const RESOURCE_TRANSPARENT_INDEX: u16 = 1047;
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn invalid(detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!("PSD: {detail}")))
+}
+
+fn unsupported(detail: &'static str) -> whereat::At<CodecError> {
+    at!(CodecError::UnsupportedOperation {
+        format: crate::psd::format(),
+        detail,
+    })
+}
+
+/// Header check used by the format definition.
+pub(crate) fn is_psd(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN
+        && data.starts_with(b"8BPS")
+        && matches!(u16_at(data, 4), 1 | 2)
+        && (1..=56).contains(&u16_at(data, 12))
+}
+
+pub(crate) fn header(data: &[u8]) -> Result<PsdHeader> {
+    if !is_psd(data) {
+        return Err(invalid("not a PSD/PSB file"));
+    }
+    let color_mode = match u16_at(data, 24) {
+        0 => PsdColorMode::Bitmap,
+        1 => PsdColorMode::Grayscale,
+        2 => PsdColorMode::Indexed,
+        3 => PsdColorMode::Rgb,
+        4 => PsdColorMode::Cmyk,
+        7 => PsdColorMode::Multichannel,
+        8 => PsdColorMode::Duotone,
+        9 => PsdColorMode::Lab,
+        other => return Err(invalid(&alloc::format!("unknown color mode {other}"))),
+    };
+    let depth = u16_at(data, 22);
+    if !matches!(depth, 1 | 8 | 16 | 32) {
+        return Err(invalid(&alloc::format!("unsupported bit depth {depth}")));
+    }
+    Ok(PsdHeader {
+        large: u16_at(data, 4) == 2,
+        width: u32_at(data, 18),
+        height: u32_at(data, 14),
+        channels: u16_at(data, 12),
+        depth,
+        color_mode,
+    })
+}
+
+/// The file's top-level sections.
+struct Sections<'a> {
+    header: PsdHeader,
+    color_data: &'a [u8],
+    resources: &'a [u8],
+    /// Negative when the composite's first extra channel is transparency.
+    layer_count: i16,
+    image_data: &'a [u8],
+}
+
+/// Read a length-prefixed section at `*at` and move past it.
+fn section<'a>(data: &'a [u8], at: &mut usize, len_bytes: usize) -> Result<&'a [u8]> {
+    let len_field = data
+        .get(*at..*at + len_bytes)
+        .ok_or_else(|| invalid("truncated section length"))?;
+    let len = len_field.iter().fold(0u64, |n, &b| n << 8 | u64::from(b));
+    let start = *at + len_bytes;
+    let body = usize::try_from(len)
+        .ok()
+        .and_then(|len| data.get(start..start.checked_add(len)?))
+        .ok_or_else(|| invalid("section out of bounds"))?;
+    *at = start + body.len();
+    Ok(body)
+}
+
+fn sections(data: &[u8]) -> Result<Sections<'_>> {
+    let header = header(data)?;
+    let mut at = HEADER_LEN;
+    let color_data = section(data, &mut at, 4)?;
+    let resources = section(data, &mut at, 4)?;
+    let length_size = if header.large { 8 } else { 4 };
+    let layers = section(data, &mut at, length_size)?;
+    let layer_count = layers
+        .get(length_size..length_size + 2)
+        .map_or(0, |b| i16::from_be_bytes([b[0], b[1]]));
+    Ok(Sections {
+        header,
+        color_data,
+        resources,
+        layer_count,
+        image_data: &data[at..],
+    })
+}
+
+/// Iterate `(id, data)` over the image resource blocks.
+fn resource_blocks(resources: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut at = 0;
+    core::iter::from_fn(move || {
+        let block = resources.get(at..)?;
+        if block.len() < 12 {
+            return None;
+        }
+        let id = u16_at(block, 4);
+        // Pascal name, padded to an even length including the length byte.
+        let name_len = (1 + usize::from(block[6])).next_multiple_of(2);
+        let size_at = 6 + name_len;
+        let size = u32_at(block.get(size_at..size_at + 4)?, 0) as usize;
+        let body = block.get(size_at + 4..size_at + 4 + size)?;
+        at += size_at + 4 + size.next_multiple_of(2);
+        Some((id, body))
+    })
+}
+
+fn thumbnail(body: &[u8]) -> Option<PsdThumbnail> {
+    // 28-byte header; format 1 is JPEG.
+    if body.len() <= 28 || u32_at(body, 0) != 1 {
+        return None;
+    }
+    Some(PsdThumbnail {
+        width: u32_at(body, 4),
+        height: u32_at(body, 8),
+        jpeg: body[28..].to_vec(),
+    })
+}
+
+fn parse_resources(resources: &[u8]) -> PsdResources {
+    let mut out = PsdResources::default();
+    let mut ps4_thumbnail = None;
+    for (id, body) in resource_blocks(resources) {
+        match id {
+            RESOURCE_ICC => out.icc_profile = Some(body.to_vec()),
+            RESOURCE_IPTC => out.iptc = Some(body.to_vec()),
+            RESOURCE_XMP => out.xmp = Some(body.to_vec()),
+            RESOURCE_EXIF => out.exif = Some(body.to_vec()),
+            RESOURCE_THUMBNAIL => out.thumbnail = thumbnail(body),
+            RESOURCE_THUMBNAIL_PS4 => ps4_thumbnail = thumbnail(body),
+            _ => {}
+        }
+    }
+    // Photoshop 4 thumbnails store BGR in the JPEG; only use one as a
+    // last resort.
+    if out.thumbnail.is_none() {
+        out.thumbnail = ps4_thumbnail;
+    }
+    out
+}
+
+pub(crate) fn resources(data: &[u8]) -> Result<PsdResources> {
+    Ok(parse_resources(sections(data)?.resources))
+}
+
+/// Color channels stored for a mode, and whether output is RGB.
+fn color_channels(mode: PsdColorMode) -> Result<(usize, bool)> {
+    match mode {
+        PsdColorMode::Bitmap | PsdColorMode::Grayscale | PsdColorMode::Duotone => Ok((1, false)),
+        PsdColorMode::Indexed => Ok((1, true)),
+        PsdColorMode::Rgb => Ok((3, true)),
+        PsdColorMode::Cmyk => Ok((4, true)),
+        PsdColorMode::Multichannel => Err(unsupported("multichannel composite")),
+        PsdColorMode::Lab => Err(unsupported("Lab composite")),
+    }
+}
+
+/// What a decode produces for a file.
+struct Plan {
+    header: PsdHeader,
+    /// Color planes read from the composite.
+    color: usize,
+    rgb: bool,
+    /// An alpha plane follows the color planes.
+    alpha_plane: bool,
+    /// Transparent palette index of an indexed image.
+    transparent_index: Option<u8>,
+}
+
+impl Plan {
+    fn new(sections: &Sections<'_>) -> Result<Self> {
+        let header = sections.header.clone();
+        let (color, rgb) = color_channels(header.color_mode)?;
+        match (header.color_mode, header.depth) {
+            (PsdColorMode::Bitmap, 1) => {}
+            (PsdColorMode::Bitmap, _) | (_, 1) => {
+                return Err(invalid("bitmap mode and 1-bit depth must go together"));
+            }
+            (PsdColorMode::Indexed, 8) => {}
+            (PsdColorMode::Indexed, _) => return Err(invalid("indexed data must be 8-bit")),
+            _ => {}
+        }
+        if usize::from(header.channels) < color {
+            return Err(invalid("fewer channels than the color mode needs"));
+        }
+        if header.color_mode == PsdColorMode::Indexed && sections.color_data.len() < 768 {
+            return Err(invalid("missing palette"));
+        }
+        let transparent_index = if header.color_mode == PsdColorMode::Indexed {
+            resource_blocks(sections.resources)
+                .find(|&(id, body)| id == RESOURCE_TRANSPARENT_INDEX && body.len() >= 2)
+                .and_then(|(_, body)| u8::try_from(u16_at(body, 0)).ok())
+        } else {
+            None
+        };
+        Ok(Self {
+            alpha_plane: sections.layer_count < 0 && usize::from(header.channels) > color,
+            header,
+            color,
+            rgb,
+            transparent_index,
+        })
+    }
+
+    fn has_alpha(&self) -> bool {
+        self.alpha_plane || self.transparent_index.is_some()
+    }
+
+    fn channel_type(&self) -> ChannelType {
+        match self.header.depth {
+            16 => ChannelType::U16,
+            32 => ChannelType::F32,
+            _ => ChannelType::U8,
+        }
+    }
+
+    fn descriptor(&self) -> PixelDescriptor {
+        let layout = match (self.rgb, self.has_alpha()) {
+            (true, true) => ChannelLayout::Rgba,
+            (true, false) => ChannelLayout::Rgb,
+            (false, true) => ChannelLayout::GrayAlpha,
+            (false, false) => ChannelLayout::Gray,
+        };
+        // 32-bit documents are scene-linear.
+        let transfer = if self.header.depth == 32 {
+            TransferFunction::Linear
+        } else {
+            TransferFunction::Srgb
+        };
+        PixelDescriptor::new(
+            self.channel_type(),
+            layout,
+            self.has_alpha().then_some(AlphaMode::Straight),
+            transfer,
+        )
+    }
+
+    fn output_channels(&self) -> usize {
+        (if self.rgb { 3 } else { 1 }) + usize::from(self.has_alpha())
+    }
+}
+
+fn image_info(plan: &Plan, resources: &PsdResources) -> ImageInfo {
+    let header = &plan.header;
+    let mut info = ImageInfo::new(header.width, header.height, crate::psd::format())
+        .with_bit_depth(header.depth as u8)
+        .with_channel_count(plan.output_channels() as u8);
+    info.has_alpha = plan.has_alpha();
+    // A CMYK profile doesn't describe the converted RGB pixels.
+    if header.color_mode != PsdColorMode::Cmyk
+        && let Some(icc) = &resources.icc_profile
+    {
+        info = info.with_icc_profile(icc.clone());
+    }
+    if let Some(exif) = &resources.exif {
+        info = info.with_exif(exif.clone());
+    }
+    if let Some(xmp) = &resources.xmp {
+        info = info.with_xmp(xmp.clone());
+    }
+    info
+}
+
+pub(crate) fn probe(data: &[u8]) -> Result<ImageInfo> {
+    let sections = sections(data)?;
+    let plan = Plan::new(&sections)?;
+    Ok(image_info(&plan, &parse_resources(sections.resources)))
+}
+
+/// Expand one PackBits-compressed row into `out`.
+fn unpack_bits(mut src: &[u8], out: &mut [u8]) -> Result<()> {
+    let mut filled = 0;
+    while filled < out.len() {
+        let (&n, rest) = src
+            .split_first()
+            .ok_or_else(|| invalid("truncated RLE row"))?;
+        src = rest;
+        let n = n as i8;
+        if n >= 0 {
+            let len = n as usize + 1;
+            let run = src
+                .get(..len)
+                .ok_or_else(|| invalid("truncated RLE literal"))?;
+            out.get_mut(filled..filled + len)
+                .ok_or_else(|| invalid("RLE row overflow"))?
+                .copy_from_slice(run);
+            src = &src[len..];
+            filled += len;
+        } else if n != -128 {
+            let len = 1 - n as isize;
+            let (&value, rest) = src
+                .split_first()
+                .ok_or_else(|| invalid("truncated RLE run"))?;
+            src = rest;
+            out.get_mut(filled..filled + len as usize)
+                .ok_or_else(|| invalid("RLE row overflow"))?
+                .fill(value);
+            filled += len as usize;
+        }
+    }
+    Ok(())
+}
+
+/// Read the first `count` planes of the composite, each `rows` rows of
+/// `row_bytes` bytes.
+fn read_planes(
+    sections: &Sections<'_>,
+    count: usize,
+    row_bytes: usize,
+    stop: Option<&StopToken>,
+) -> Result<Vec<Vec<u8>>> {
+    let header = &sections.header;
+    let data = sections.image_data;
+    if data.len() < 2 {
+        return Err(invalid("missing image data"));
+    }
+    let rows = header.height as usize;
+    let plane_len = rows
+        .checked_mul(row_bytes)
+        .ok_or_else(|| invalid("image too large"))?;
+    let mut planes = Vec::with_capacity(count);
+    match u16_at(data, 0) {
+        0 => {
+            for c in 0..count {
+                let plane = c
+                    .checked_mul(plane_len)
+                    .and_then(|start| data.get(2 + start..)?.get(..plane_len))
+                    .ok_or_else(|| invalid("truncated raw image data"))?;
+                planes.push(plane.to_vec());
+            }
+        }
+        1 => {
+            // Byte counts for every row of every channel, then the rows.
+            let count_size = if header.large { 4 } else { 2 };
+            let table = usize::from(header.channels)
+                .checked_mul(rows)
+                .and_then(|n| n.checked_mul(count_size))
+                .and_then(|len| data.get(2..)?.get(..len))
+                .ok_or_else(|| invalid("truncated RLE byte counts"))?;
+            let row_len = |i: usize| {
+                if header.large {
+                    u32_at(table, i * 4) as usize
+                } else {
+                    usize::from(u16_at(table, i * 2))
+                }
+            };
+            let mut at = 2 + table.len();
+            for c in 0..count {
+                if let Some(s) = stop
+                    && s.check().is_err()
+                {
+                    return Err(at!(CodecError::Cancelled));
+                }
+                // PackBits turns every 2 bytes into at most 128, so the
+                // plane is only allocated once the data can fill it.
+                let mut available = 0usize;
+                for y in 0..rows {
+                    let len = row_len(c * rows + y);
+                    if len.div_ceil(2).saturating_mul(128) < row_bytes {
+                        return Err(invalid("RLE row too short"));
+                    }
+                    available = available.saturating_add(len);
+                }
+                if data.len().saturating_sub(at) < available {
+                    return Err(invalid("truncated RLE data"));
+                }
+                let mut plane = alloc::vec![0u8; plane_len];
+                for (y, out) in plane.chunks_exact_mut(row_bytes.max(1)).enumerate() {
+                    let len = row_len(c * rows + y);
+                    let src = data
+                        .get(at..at + len)
+                        .ok_or_else(|| invalid("truncated RLE data"))?;
+                    unpack_bits(src, out)?;
+                    at += len;
+                }
+                planes.push(plane);
+            }
+        }
+        2 | 3 => return Err(unsupported("ZIP-compressed composite")),
+        other => return Err(invalid(&alloc::format!("unknown compression {other}"))),
+    }
+    Ok(planes)
+}
+
+pub(crate) fn decode(
+    data: &[u8],
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+) -> Result<DecodeOutput> {
+    let sections = sections(data)?;
+    let plan = Plan::new(&sections)?;
+    let header = &plan.header;
+    let (w, h) = (header.width as usize, header.height as usize);
+    let sample_bytes = usize::from(header.depth.max(8) / 8);
+    let channels = plan.output_channels();
+    if let Some(limits) = limits {
+        // CMYK also holds the ink and converted RGB samples.
+        let cmyk_bytes = if header.color_mode == PsdColorMode::Cmyk {
+            7 * sample_bytes as u64
+        } else {
+            0
+        };
+        limits
+            .check_dimensions(u64::from(header.width), u64::from(header.height))
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+        limits
+            .check_memory(
+                u64::from(header.width)
+                    * u64::from(header.height)
+                    * ((channels * sample_bytes) as u64 + cmyk_bytes),
+            )
+            .map_err(|msg| at!(CodecError::LimitExceeded(String::from(msg))))?;
+    }
+
+    let row_bytes = (w * usize::from(header.depth)).div_ceil(8);
+    let planes = read_planes(
+        &sections,
+        plan.color + usize::from(plan.alpha_plane),
+        row_bytes,
+        stop.as_ref(),
+    )?;
+
+    // Samples normalized to 0..=1 (32-bit data is used as is).
+    let sample = |plane: &[u8], y: usize, x: usize| -> f32 {
+        let row = &plane[y * row_bytes..];
+        match header.depth {
+            1 => {
+                // 1 is black.
+                let bit = (row[x / 8] >> (7 - x % 8)) & 1;
+                f32::from(1 - bit)
+            }
+            8 => f32::from(row[x]) / 255.0,
+            16 => f32::from(u16_at(row, x * 2)) / 65535.0,
+            _ => f32::from_bits(u32_at(row, x * 4)),
+        }
+    };
+    let palette =
+        |index: u8, c: usize| f32::from(sections.color_data[c * 256 + usize::from(index)]) / 255.0;
+    let resources = parse_resources(sections.resources);
+    let cmyk_rgb = (header.color_mode == PsdColorMode::Cmyk)
+        .then(|| cmyk_to_rgb(&planes, header, row_bytes, resources.icc_profile.as_deref()))
+        .flatten();
+
+    let mut out = Vec::with_capacity(w * h * channels * sample_bytes);
+    let mut px = [0f32; 4];
+    for y in 0..h {
+        if let Some(s) = &stop
+            && y % 64 == 0
+            && s.check().is_err()
+        {
+            return Err(at!(CodecError::Cancelled));
+        }
+        for x in 0..w {
+            let mut alpha = plan.alpha_plane.then(|| sample(&planes[plan.color], y, x));
+            let color = match header.color_mode {
+                PsdColorMode::Indexed => {
+                    let index = planes[0][y * row_bytes + x];
+                    if plan.transparent_index.is_some() {
+                        alpha = Some(if plan.transparent_index == Some(index) {
+                            0.0
+                        } else {
+                            1.0
+                        });
+                    }
+                    px[..3].copy_from_slice(&[
+                        palette(index, 0),
+                        palette(index, 1),
+                        palette(index, 2),
+                    ]);
+                    &mut px[..3]
+                }
+                PsdColorMode::Cmyk => {
+                    if let Some(rgb) = &cmyk_rgb {
+                        let at = (y * w + x) * 3;
+                        for (v, &s) in px[..3].iter_mut().zip(&rgb[at..at + 3]) {
+                            *v = f32::from(s) / 65535.0;
+                        }
+                    } else {
+                        // Ink coverage is stored inverted: 1 is no ink.
+                        let k = sample(&planes[3], y, x);
+                        for (c, v) in px[..3].iter_mut().enumerate() {
+                            *v = sample(&planes[c], y, x) * k;
+                        }
+                    }
+                    &mut px[..3]
+                }
+                _ => {
+                    for (c, v) in px[..plan.color].iter_mut().enumerate() {
+                        *v = sample(&planes[c], y, x);
+                    }
+                    &mut px[..plan.color]
+                }
+            };
+            // The composite is matted against white where transparent.
+            if plan.alpha_plane
+                && let Some(a) = alpha
+                && a > 0.0
+                && a < 1.0
+            {
+                for v in color.iter_mut() {
+                    *v = ((*v - (1.0 - a)) / a).clamp(0.0, 1.0);
+                }
+            }
+            let n = color.len();
+            if let Some(a) = alpha {
+                px[n] = a;
+            }
+            for &v in &px[..channels] {
+                match header.depth {
+                    16 => out.extend_from_slice(
+                        &((v.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16).to_ne_bytes(),
+                    ),
+                    32 => out.extend_from_slice(&v.to_ne_bytes()),
+                    _ => out.push((v.clamp(0.0, 1.0) * 255.0 + 0.5) as u8),
+                }
+            }
+        }
+    }
+
+    let pixels = PixelBuffer::from_vec(out, header.width, header.height, plan.descriptor())
+        .map_err(|_| invalid("failed to create PixelBuffer"))?;
+    let info = image_info(&plan, &resources);
+    Ok(DecodeOutput::new(pixels, info))
+}
+
+/// sRGB for an 8- or 16-bit CMYK composite through [`crate::cmyk`], which
+/// applies `icc` with the `cms` feature. Samples are 16-bit, 3 per pixel.
+/// `None` for 32-bit data, which keeps the naive conversion.
+fn cmyk_to_rgb(
+    planes: &[Vec<u8>],
+    header: &PsdHeader,
+    row_bytes: usize,
+    icc: Option<&[u8]>,
+) -> Option<Vec<u16>> {
+    let (w, h) = (header.width as usize, header.height as usize);
+    let pixels = (0..h).flat_map(|y| (0..w).map(move |x| (y * row_bytes, x)));
+    match header.depth {
+        // Ink coverage is stored inverted: 255 is no ink.
+        8 => {
+            let ink: Vec<u8> = pixels
+                .flat_map(|(row, x)| [0, 1, 2, 3].map(|c| 255 - planes[c][row + x]))
+                .collect();
+            let rgb = crate::cmyk::cmyk_to_srgb(&ink, icc);
+            Some(rgb.into_iter().map(|v| u16::from(v) * 257).collect())
+        }
+        16 => {
+            let ink: Vec<u16> = pixels
+                .flat_map(|(row, x)| [0, 1, 2, 3].map(|c| 65535 - u16_at(&planes[c], row + x * 2)))
+                .collect();
+            Some(crate::cmyk::cmyk16_to_srgb(&ink, icc))
+        }
+        _ => None,
+    }
+}
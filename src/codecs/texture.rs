@@ -0,0 +1,349 @@
+//! Shared CPU decode and encode for the DDS and KTX2 texture containers.
+//! The container adapters parse headers into a [`TextureInfo`]; this module
+//! turns one mip level into pixels and RGBA8 pixels into mip levels.
+
+use alloc::vec::Vec;
+
+use crate::codecs::bcn::{self, Rgba8Block};
+use crate::config::CodecConfig;
+use crate::dispatch::EncodeParams;
+use crate::error::Result;
+use crate::limits::Stop;
+use crate::texture::{MipLevel, TextureEncodeConfig, TextureFormat, TextureInfo};
+use crate::{CodecError, DecodeOutput, ImageFormat, ImageInfo, Limits, StopToken};
+use whereat::at;
+use zenpixels::{AlphaMode, ChannelLayout, ChannelType, PixelBuffer, PixelDescriptor, PixelSlice};
+use zenpixels::{Cicp, TransferFunction};
+
+/// Encoder input: straight-alpha sRGB RGBA8.
+pub(crate) static SUPPORTED: [PixelDescriptor; 1] = [PixelDescriptor::RGBA8_SRGB];
+
+/// One encoded mip level.
+pub(crate) struct EncodedLevel {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+fn invalid(container: &str, detail: &str) -> whereat::At<CodecError> {
+    at!(CodecError::InvalidInput(alloc::format!(
+        "{container}: {detail}"
+    )))
+}
+
+fn check_stop(stop: Option<&StopToken>) -> Result<()> {
+    if let Some(s) = stop
+        && s.check().is_err()
+    {
+        return Err(at!(CodecError::Cancelled));
+    }
+    Ok(())
+}
+
+pub(crate) fn image_info(
+    texture: &TextureInfo,
+    level: &MipLevel,
+    format: ImageFormat,
+) -> ImageInfo {
+    let bit_depth = match texture.format {
+        TextureFormat::Rgba32f => 32,
+        TextureFormat::Rgba16f | TextureFormat::Bc6h { .. } => 16,
+        _ => 8,
+    };
+    let mut info = ImageInfo::new(level.width, level.height, format)
+        .with_bit_depth(bit_depth)
+        .with_channel_count(4);
+    info.has_alpha = texture.format.has_alpha();
+    if texture.format.is_hdr() {
+        // BT.709 primaries, linear transfer
+        info.source_color.cicp = Some(Cicp::new(1, 8, 0, true));
+    } else if texture.srgb {
+        info.source_color.cicp = Some(Cicp::SRGB);
+    }
+    info
+}
+
+/// Decode the mip level picked by the texture decode config: first layer,
+/// first face, first depth slice.
+pub(crate) fn decode_level(
+    texture: &TextureInfo,
+    data: &[u8],
+    codec_config: Option<&CodecConfig>,
+    limits: Option<&Limits>,
+    stop: Option<StopToken>,
+    format: ImageFormat,
+    container: &str,
+) -> Result<DecodeOutput> {
+    let config = codec_config
+        .and_then(|c| c.texture_decoder.as_deref())
+        .cloned()
+        .unwrap_or_default();
+    let level = texture
+        .mip_levels
+        .get(config.mip_level)
+        .ok_or_else(|| invalid(container, "mip level out of range"))?;
+    if let Some(limits) = limits {
+        limits
+            .check_dimensions(u64::from(level.width), u64::from(level.height))
+            .map_err(|msg| at!(CodecError::LimitExceeded(alloc::string::String::from(msg))))?;
+    }
+    let bytes = level
+        .offset
+        .checked_add(level.byte_len)
+        .and_then(|end| data.get(level.offset..end))
+        .filter(|b| b.len() >= texture.format.image_bytes(level.width, level.height))
+        .ok_or_else(|| invalid(container, "truncated level data"))?;
+
+    let (w, h) = (level.width as usize, level.height as usize);
+    let (pixels, descriptor) = if texture.format.is_hdr() {
+        let rgba = decode_hdr(texture.format, bytes, w, h, stop.as_ref())?;
+        let descriptor = PixelDescriptor::new(
+            ChannelType::F32,
+            ChannelLayout::Rgba,
+            Some(AlphaMode::Straight),
+            TransferFunction::Linear,
+        );
+        (
+            rgba.iter().flat_map(|v| v.to_ne_bytes()).collect(),
+            descriptor,
+        )
+    } else {
+        let rgba = decode_ldr(texture.format, bytes, w, h, stop.as_ref())?;
+        let descriptor = if texture.srgb {
+            PixelDescriptor::RGBA8_SRGB
+        } else {
+            PixelDescriptor::new(
+                ChannelType::U8,
+                ChannelLayout::Rgba,
+                Some(AlphaMode::Straight),
+                TransferFunction::Linear,
+            )
+        };
+        (rgba, descriptor)
+    };
+    let pixels = PixelBuffer::from_vec(pixels, level.width, level.height, descriptor)
+        .map_err(|_| invalid(container, "failed to create PixelBuffer"))?;
+    Ok(DecodeOutput::new(
+        pixels,
+        image_info(texture, level, format),
+    ))
+}
+
+/// Walk the 4×4 blocks of a `w`×`h` image, handing each block's bytes and
+/// top-left pixel to `visit`.
+fn for_each_block(
+    bytes: &[u8],
+    format: TextureFormat,
+    w: usize,
+    h: usize,
+    stop: Option<&StopToken>,
+    mut visit: impl FnMut(&[u8], usize, usize),
+) -> Result<()> {
+    let unit = format.unit_bytes();
+    let blocks_x = w.div_ceil(4);
+    for by in 0..h.div_ceil(4) {
+        check_stop(stop)?;
+        for bx in 0..blocks_x {
+            visit(
+                &bytes[(by * blocks_x + bx) * unit..][..unit],
+                bx * 4,
+                by * 4,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn decode_ldr(
+    format: TextureFormat,
+    bytes: &[u8],
+    w: usize,
+    h: usize,
+    stop: Option<&StopToken>,
+) -> Result<Vec<u8>> {
+    let mut out = alloc::vec![0u8; w * h * 4];
+    if format.is_block_compressed() {
+        let mut block: Rgba8Block = [[0; 4]; 16];
+        for_each_block(bytes, format, w, h, stop, |data, x0, y0| {
+            match format {
+                TextureFormat::Bc1 { alpha } => bcn::decode_bc1(data, alpha, &mut block),
+                TextureFormat::Bc2 => bcn::decode_bc2(data, &mut block),
+                TextureFormat::Bc3 => bcn::decode_bc3(data, &mut block),
+                TextureFormat::Bc4 => bcn::decode_bc4(data, &mut block),
+                TextureFormat::Bc5 => bcn::decode_bc5(data, &mut block),
+                _ => bcn::decode_bc7(data, &mut block),
+            }
+            for (i, px) in block.iter().enumerate() {
+                let (x, y) = (x0 + i % 4, y0 + i / 4);
+                if x < w && y < h {
+                    out[(y * w + x) * 4..][..4].copy_from_slice(px);
+                }
+            }
+        })?;
+        return Ok(out);
+    }
+
+    let unit = format.unit_bytes();
+    for (y, row) in out.chunks_exact_mut(w * 4).enumerate() {
+        if y % 64 == 0 {
+            check_stop(stop)?;
+        }
+        let src = &bytes[y * w * unit..][..w * unit];
+        for (dst, p) in row.chunks_exact_mut(4).zip(src.chunks_exact(unit)) {
+            let rgba = match format {
+                TextureFormat::Rgba8 => [p[0], p[1], p[2], p[3]],
+                TextureFormat::Bgra8 => [p[2], p[1], p[0], p[3]],
+                TextureFormat::Bgrx8 | TextureFormat::Bgr8 => [p[2], p[1], p[0], 255],
+                TextureFormat::Rg8 => [p[0], p[1], 0, 255],
+                _ => [p[0], p[0], p[0], 255],
+            };
+            dst.copy_from_slice(&rgba);
+        }
+    }
+    Ok(out)
+}
+
+fn decode_hdr(
+    format: TextureFormat,
+    bytes: &[u8],
+    w: usize,
+    h: usize,
+    stop: Option<&StopToken>,
+) -> Result<Vec<f32>> {
+    let mut out = alloc::vec![0f32; w * h * 4];
+    if let TextureFormat::Bc6h { signed } = format {
+        let mut block = [[0f32; 3]; 16];
+        for_each_block(bytes, format, w, h, stop, |data, x0, y0| {
+            bcn::decode_bc6h(data, signed, &mut block);
+            for (i, rgb) in block.iter().enumerate() {
+                let (x, y) = (x0 + i % 4, y0 + i / 4);
+                if x < w && y < h {
+                    out[(y * w + x) * 4..][..4].copy_from_slice(&[rgb[0], rgb[1], rgb[2], 1.0]);
+                }
+            }
+        })?;
+        return Ok(out);
+    }
+
+    let half = format == TextureFormat::Rgba16f;
+    for (i, v) in out.iter_mut().enumerate() {
+        if i % (w * 4 * 64) == 0 {
+            check_stop(stop)?;
+        }
+        *v = if half {
+            bcn::half_to_f32(u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]))
+        } else {
+            let b = &bytes[i * 4..][..4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        };
+    }
+    Ok(out)
+}
+
+/// The encode config, and the compressed mip chain base level first.
+pub(crate) fn encode_levels(
+    pixels: PixelSlice<'_>,
+    params: &EncodeParams<'_>,
+    format: ImageFormat,
+) -> Result<(TextureEncodeConfig, Vec<EncodedLevel>)> {
+    let config: TextureEncodeConfig = params
+        .codec_config
+        .and_then(|c| c.texture_encoder.as_deref())
+        .cloned()
+        .unwrap_or_default();
+    if !matches!(
+        config.format,
+        TextureFormat::Rgba8
+            | TextureFormat::Bc1 { .. }
+            | TextureFormat::Bc2
+            | TextureFormat::Bc3
+            | TextureFormat::Bc4
+            | TextureFormat::Bc5
+            | TextureFormat::Bc7
+    ) {
+        return Err(at!(CodecError::UnsupportedOperation {
+            format,
+            detail: "texture encode supports RGBA8, BC1-BC5 and BC7 only",
+        }));
+    }
+    let (mut width, mut height) = (pixels.width() as usize, pixels.rows() as usize);
+    if width == 0 || height == 0 {
+        return Err(at!(CodecError::InvalidInput("empty image".into())));
+    }
+    let data = pixels.as_strided_bytes();
+    let mut rgba: Vec<u8> = (0..height)
+        .flat_map(|y| &data[y * pixels.stride()..][..width * 4])
+        .copied()
+        .collect();
+
+    let mut levels = Vec::new();
+    loop {
+        levels.push(EncodedLevel {
+            width: width as u32,
+            height: height as u32,
+            data: compress(config.format, &rgba, width, height, params.stop.as_ref())?,
+        });
+        if !config.mipmaps || (width, height) == (1, 1) {
+            break;
+        }
+        (rgba, width, height) = downsample(&rgba, width, height);
+    }
+    Ok((config, levels))
+}
+
+fn compress(
+    format: TextureFormat,
+    rgba: &[u8],
+    w: usize,
+    h: usize,
+    stop: Option<&StopToken>,
+) -> Result<Vec<u8>> {
+    if format == TextureFormat::Rgba8 {
+        return Ok(rgba.to_vec());
+    }
+    let mut out = Vec::with_capacity(format.image_bytes(w as u32, h as u32));
+    for by in 0..h.div_ceil(4) {
+        check_stop(stop)?;
+        for bx in 0..w.div_ceil(4) {
+            // Partial edge blocks repeat the last row and column.
+            let block: Rgba8Block = core::array::from_fn(|i| {
+                let x = (bx * 4 + i % 4).min(w - 1);
+                let y = (by * 4 + i / 4).min(h - 1);
+                let p = &rgba[(y * w + x) * 4..][..4];
+                [p[0], p[1], p[2], p[3]]
+            });
+            match format {
+                TextureFormat::Bc1 { alpha } => {
+                    out.extend_from_slice(&bcn::encode_bc1(&block, alpha))
+                }
+                TextureFormat::Bc2 => out.extend_from_slice(&bcn::encode_bc2(&block)),
+                TextureFormat::Bc3 => out.extend_from_slice(&bcn::encode_bc3(&block)),
+                TextureFormat::Bc4 => out.extend_from_slice(&bcn::encode_bc4(&block)),
+                TextureFormat::Bc5 => out.extend_from_slice(&bcn::encode_bc5(&block)),
+                _ => out.extend_from_slice(&bcn::encode_bc7(&block)),
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Halve both dimensions (down to 1) with a 2×2 box filter.
+fn downsample(src: &[u8], w: usize, h: usize) -> (Vec<u8>, usize, usize) {
+    let (dw, dh) = ((w / 2).max(1), (h / 2).max(1));
+    let mut out = Vec::with_capacity(dw * dh * 4);
+    for y in 0..dh {
+        for x in 0..dw {
+            let xs = [(2 * x).min(w - 1), (2 * x + 1).min(w - 1)];
+            let ys = [(2 * y).min(h - 1), (2 * y + 1).min(h - 1)];
+            for c in 0..4 {
+                let sum: u32 = ys
+                    .iter()
+                    .flat_map(|&sy| xs.iter().map(move |&sx| (sy * w + sx) * 4 + c))
+                    .map(|i| u32::from(src[i]))
+                    .sum();
+                out.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    (out, dw, dh)
+}
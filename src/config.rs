@@ -166,6 +166,34 @@ pub struct CodecConfig {
     /// RAW/DNG decoder configuration (demosaic method, gamma, crop, orientation).
     #[cfg(feature = "raw-decode")]
     pub raw_decoder: Option<Box<raw::RawDecodeConfig>>,
+
+    /// OpenEXR decoder configuration (layer, output precision).
+    #[cfg(feature = "exr")]
+    pub exr_decoder: Option<Box<crate::exr::ExrDecodeConfig>>,
+
+    /// OpenEXR encoder configuration (compression, half floats, tiling).
+    #[cfg(feature = "exr")]
+    pub exr_encoder: Option<Box<crate::exr::ExrEncodeConfig>>,
+
+    /// ICO/CUR decoder configuration (entry selection).
+    #[cfg(feature = "ico")]
+    pub ico_decoder: Option<Box<crate::ico::IcoDecodeConfig>>,
+
+    /// ICO encoder configuration (generated sizes).
+    #[cfg(feature = "ico")]
+    pub ico_encoder: Option<Box<crate::ico::IcoEncodeConfig>>,
+
+    /// DDS/KTX2 decoder configuration (mip level).
+    #[cfg(any(feature = "dds", feature = "ktx2"))]
+    pub texture_decoder: Option<Box<crate::texture::TextureDecodeConfig>>,
+
+    /// DDS/KTX2 encoder configuration (block format, sRGB, mipmaps).
+    #[cfg(any(feature = "dds", feature = "ktx2"))]
+    pub texture_encoder: Option<Box<crate::texture::TextureEncodeConfig>>,
+
+    /// JPEG 2000 decoder configuration (reduction, region).
+    #[cfg(feature = "jp2-decode")]
+    pub jp2_decoder: Option<Box<crate::jp2::Jp2DecodeConfig>>,
 }
 
 impl CodecConfig {
@@ -259,6 +287,55 @@ impl CodecConfig {
         self.raw_decoder = Some(Box::new(config));
         self
     }
+
+    /// Set OpenEXR decoder configuration.
+    #[cfg(feature = "exr")]
+    pub fn with_exr_decoder(mut self, config: crate::exr::ExrDecodeConfig) -> Self {
+        self.exr_decoder = Some(Box::new(config));
+        self
+    }
+
+    /// Set OpenEXR encoder configuration.
+    #[cfg(feature = "exr")]
+    pub fn with_exr_encoder(mut self, config: crate::exr::ExrEncodeConfig) -> Self {
+        self.exr_encoder = Some(Box::new(config));
+        self
+    }
+
+    /// Set ICO/CUR decoder configuration.
+    #[cfg(feature = "ico")]
+    pub fn with_ico_decoder(mut self, config: crate::ico::IcoDecodeConfig) -> Self {
+        self.ico_decoder = Some(Box::new(config));
+        self
+    }
+
+    /// Set ICO encoder configuration.
+    #[cfg(feature = "ico")]
+    pub fn with_ico_encoder(mut self, config: crate::ico::IcoEncodeConfig) -> Self {
+        self.ico_encoder = Some(Box::new(config));
+        self
+    }
+
+    /// Set DDS/KTX2 decoder configuration.
+    #[cfg(any(feature = "dds", feature = "ktx2"))]
+    pub fn with_texture_decoder(mut self, config: crate::texture::TextureDecodeConfig) -> Self {
+        self.texture_decoder = Some(Box::new(config));
+        self
+    }
+
+    /// Set DDS/KTX2 encoder configuration.
+    #[cfg(any(feature = "dds", feature = "ktx2"))]
+    pub fn with_texture_encoder(mut self, config: crate::texture::TextureEncodeConfig) -> Self {
+        self.texture_encoder = Some(Box::new(config));
+        self
+    }
+
+    /// Set JPEG 2000 decoder configuration.
+    #[cfg(feature = "jp2-decode")]
+    pub fn with_jp2_decoder(mut self, config: crate::jp2::Jp2DecodeConfig) -> Self {
+        self.jp2_decoder = Some(Box::new(config));
+        self
+    }
 }
 
 impl core::fmt::Debug for CodecConfig {
@@ -292,6 +369,23 @@ impl core::fmt::Debug for CodecConfig {
         }
         #[cfg(feature = "raw-decode")]
         d.field("raw_decoder", &self.raw_decoder.is_some());
+        #[cfg(feature = "exr")]
+        {
+            d.field("exr_decoder", &self.exr_decoder.is_some());
+            d.field("exr_encoder", &self.exr_encoder.is_some());
+        }
+        #[cfg(feature = "ico")]
+        {
+            d.field("ico_decoder", &self.ico_decoder.is_some());
+            d.field("ico_encoder", &self.ico_encoder.is_some());
+        }
+        #[cfg(any(feature = "dds", feature = "ktx2"))]
+        {
+            d.field("texture_decoder", &self.texture_decoder.is_some());
+            d.field("texture_encoder", &self.texture_encoder.is_some());
+        }
+        #[cfg(feature = "jp2-decode")]
+        d.field("jp2_decoder", &self.jp2_decoder.is_some());
 
         d.finish()
     }
@@ -0,0 +1,189 @@
+//! DirectDraw Surface textures (`dds` feature).
+//!
+//! Both legacy headers (FourCC `DXT1`–`DXT5`, `ATI1`/`ATI2`, channel
+//! masks) and the DX10 extension are read; see [`crate::texture`] for the
+//! formats and what decode and encode produce. DDS is a custom format,
+//! `ImageFormat::Custom(&DDS_FORMAT)`. Legacy headers carry no color
+//! space and are read as linear; sRGB output is written with a DX10
+//! header.
+
+use crate::ImageFormat;
+use crate::error::Result;
+use crate::texture::TextureInfo;
+
+/// Format definition for DDS.
+pub static DDS_FORMAT: zencodec::ImageFormatDefinition = zencodec::ImageFormatDefinition::new(
+    "dds",
+    None,
+    "DDS",
+    "dds",
+    &["dds"],
+    "image/vnd-ms.dds",
+    &["image/vnd-ms.dds", "image/x-dds"],
+    true,
+    false,
+    true,
+    true,
+    8,
+    crate::codecs::dds::is_dds,
+);
+
+/// The DDS [`ImageFormat`].
+pub fn format() -> ImageFormat {
+    ImageFormat::Custom(&DDS_FORMAT)
+}
+
+/// Read the header: format, dimensions, layers and the mip chain.
+pub fn info(data: &[u8]) -> Result<TextureInfo> {
+    crate::codecs::dds::info(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::config::CodecConfig;
+    use crate::texture::{TextureDecodeConfig, TextureEncodeConfig, TextureFormat};
+    use crate::{AllowedFormats, CodecError, DecodeRequest, EncodeRequest};
+    use zenpixels::{PixelDescriptor, PixelSlice};
+
+    fn rgba(output: &crate::DecodeOutput) -> Vec<u8> {
+        let pixels = output.pixels();
+        let row_bytes = pixels.width() as usize * 4;
+        let bytes = pixels.as_strided_bytes();
+        (0..pixels.rows() as usize)
+            .flat_map(|y| &bytes[y * pixels.stride()..][..row_bytes])
+            .copied()
+            .collect()
+    }
+
+    /// 8×6 horizontal gradient, half-transparent right half.
+    fn source() -> Vec<u8> {
+        (0..6 * 8u8)
+            .flat_map(|i| {
+                let x = i % 8;
+                [x * 32, x * 16, 128, if x < 4 { 255 } else { 128 }]
+            })
+            .collect()
+    }
+
+    fn encode(config: TextureEncodeConfig) -> Vec<u8> {
+        let src = source();
+        let slice = PixelSlice::new(&src, 8, 6, 8 * 4, PixelDescriptor::RGBA8_SRGB).unwrap();
+        EncodeRequest::new(format())
+            .with_codec_config(&CodecConfig::default().with_texture_encoder(config))
+            .encode(slice, false)
+            .unwrap()
+            .into_vec()
+    }
+
+    #[test]
+    fn bc3_round_trip_with_mip_chain() {
+        let data = encode(
+            TextureEncodeConfig::new()
+                .with_format(TextureFormat::Bc3)
+                .with_mipmaps(true),
+        );
+        let texture = info(&data).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc3);
+        assert!(texture.srgb);
+        let dims: Vec<_> = texture
+            .mip_levels
+            .iter()
+            .map(|l| (l.width, l.height))
+            .collect();
+        assert_eq!(dims, [(8, 6), (4, 3), (2, 1), (1, 1)]);
+        assert_eq!(crate::from_bytes(&data).unwrap().width, 8);
+
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(output.info().format, format());
+        assert!(output.info().has_alpha);
+        let max_error = rgba(&output)
+            .iter()
+            .zip(source())
+            .map(|(&a, b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+        assert!(max_error <= 8, "max error {max_error}");
+
+        let last = CodecConfig::default()
+            .with_texture_decoder(TextureDecodeConfig::new().with_mip_level(3));
+        let output = DecodeRequest::new(&data)
+            .with_codec_config(&last)
+            .decode_full_frame()
+            .unwrap();
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (1, 1));
+    }
+
+    #[test]
+    fn legacy_headers() {
+        // Uncompressed RGBA8 is written with channel masks and reads back
+        // exactly; BC1 uses a DXT1 FourCC.
+        let data = encode(
+            TextureEncodeConfig::new()
+                .with_format(TextureFormat::Rgba8)
+                .with_srgb(false),
+        );
+        assert_eq!(data.len(), 128 + 8 * 6 * 4);
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(rgba(&output), source());
+
+        let data = encode(
+            TextureEncodeConfig::new()
+                .with_format(TextureFormat::Bc1 { alpha: false })
+                .with_srgb(false),
+        );
+        assert_eq!(&data[84..88], b"DXT1");
+        let texture = info(&data).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc1 { alpha: true });
+        assert!(!texture.srgb);
+        assert_eq!(texture.mip_levels[0].byte_len, 2 * 2 * 8);
+    }
+
+    #[test]
+    fn bgrx_cube_map_decodes_the_first_face() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"DDS ");
+        for v in [124u32, 0x1007, 1, 2, 8, 0, 0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.resize(76, 0);
+        for v in [32u32, 0x40, 0, 32, 0xff_0000, 0xff00, 0xff, 0] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        for v in [0x1008u32, 0xfe00] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.resize(128, 0);
+        for face in 0..6u8 {
+            data.extend_from_slice(&[10, 20, 30, face, 40, 50, 60, face]);
+        }
+
+        let texture = info(&data).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bgrx8);
+        assert_eq!(texture.faces, 6);
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert!(!output.info().has_alpha);
+        assert_eq!(rgba(&output), [30, 20, 10, 255, 60, 50, 40, 255]);
+    }
+
+    #[test]
+    fn disabled_and_truncated() {
+        let data = encode(TextureEncodeConfig::new());
+        let registry = AllowedFormats::all().with_decode(format(), false);
+        let result = DecodeRequest::new(&data)
+            .with_registry(&registry)
+            .decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::DisabledFormat(_))
+        ));
+
+        let result = DecodeRequest::new(&data[..data.len() - 1]).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+    }
+}
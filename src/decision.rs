@@ -31,6 +31,7 @@ use crate::trace::SelectionStep;
 ///     lossless: false,
 ///     hints: Default::default(),
 ///     matte: None,
+///     lossless_jpeg_transcode: false,
 ///     trace: Vec::new(),
 /// };
 /// assert_eq!(decision.format, ImageFormat::WebP);
@@ -48,6 +49,23 @@ pub struct FormatDecision {
     pub hints: BTreeMap<String, String>,
     /// Matte color for alpha compositing (RGBA to opaque format).
     pub matte: Option<[u8; 3]>,
+    /// Transcode to JPEG without touching pixels.
+    ///
+    /// From JXL, rebuilds the original JPEG bytes when the JXL carries a
+    /// `jbrd` reconstruction box (requires `jxl-decode`). From JPEG, copies
+    /// the DCT coefficients with the EXIF orientation applied losslessly
+    /// and the tag reset to 1 (see [`JpegTransform`](crate::JpegTransform)).
+    /// Other format pairs, JXL files without `jbrd`, and JPEGs whose
+    /// rotation would trim partial edge blocks use the normal decode/encode
+    /// path.
+    /// [`select_format_from_intent`](crate::select::select_format_from_intent)
+    /// sets this for a lossless JPEG request from a JXL or JPEG source.
+    /// See [`transcode`](crate::transcode::transcode).
+    ///
+    /// The forward direction, JPEG → JXL coefficient recompression, is not
+    /// provided yet: jxl-encoder's JPEG re-encoder depends on zenjpeg 0.7,
+    /// which doesn't build against the magetypes the rest of the tree uses.
+    pub lossless_jpeg_transcode: bool,
     /// Explanation trace for debugging/auditing.
     pub trace: Vec<SelectionStep>,
 }
@@ -60,6 +78,7 @@ impl Default for FormatDecision {
             lossless: false,
             hints: BTreeMap::new(),
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         }
     }
@@ -198,6 +217,7 @@ mod tests {
             lossless: false,
             hints: BTreeMap::new(),
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         };
         assert_eq!(decision.format, ImageFormat::Jpeg);
@@ -217,6 +237,7 @@ mod tests {
             lossless: false,
             hints,
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         };
         assert_eq!(decision.jpeg_quality(), 75);
@@ -232,6 +253,7 @@ mod tests {
             lossless: false,
             hints,
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         };
         assert!((decision.webp_quality() - 80.5).abs() < 0.01);
@@ -247,6 +269,7 @@ mod tests {
             lossless: false,
             hints,
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         };
         assert!((decision.jxl_distance() - 1.5).abs() < 0.01);
@@ -260,6 +283,7 @@ mod tests {
             lossless: false,
             hints: BTreeMap::new(),
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         };
         // From calibration table, generic 73 -> JPEG 73
@@ -274,6 +298,7 @@ mod tests {
             lossless: false,
             hints: BTreeMap::new(),
             matte: Some([255, 255, 255]),
+            lossless_jpeg_transcode: false,
             trace: Vec::new(),
         };
         assert_eq!(decision.matte, Some([255, 255, 255]));
@@ -287,6 +312,7 @@ mod tests {
             lossless: false,
             hints: BTreeMap::new(),
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: alloc::vec![
                 SelectionStep::FormatSkipped {
                     format: ImageFormat::Jxl,
@@ -33,6 +33,16 @@ pub struct DecodeRequest<'a> {
     /// When true, codecs that support gain maps will extract and attach
     /// gain map data to the `DecodeOutput` extras. Default: false.
     extract_gain_map: bool,
+    /// When true, the stored orientation is applied to the decoded pixels.
+    auto_orient: bool,
+    /// CMYK profile for CMYK/YCCK sources that embed none.
+    #[cfg(any(feature = "jpeg", feature = "tiff"))]
+    cmyk_profile: Option<&'a [u8]>,
+    /// When set, decode base + gain map and render for this headroom.
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    display_headroom: Option<f32>,
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    hdr_output: crate::gainmap::HdrOutput,
 }
 
 impl<'a> DecodeRequest<'a> {
@@ -51,6 +61,13 @@ impl<'a> DecodeRequest<'a> {
             policy: None,
             decode_policy: None,
             extract_gain_map: false,
+            auto_orient: false,
+            #[cfg(any(feature = "jpeg", feature = "tiff"))]
+            cmyk_profile: None,
+            #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+            display_headroom: None,
+            #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+            hdr_output: crate::gainmap::HdrOutput::Linear,
         }
     }
 
@@ -86,9 +103,10 @@ impl<'a> DecodeRequest<'a> {
 
     /// Set a per-request codec policy for filtering and preferences.
     ///
-    /// Currently reserved for future use with fallback chains and
-    /// multi-decoder-per-format support. The policy's format restrictions
-    /// are checked during format detection.
+    /// Killbits and allowlists are checked against the format's decoder
+    /// once the format is known; a rejected decoder fails with
+    /// [`CodecError::DisabledFormat`]. Fallback chains and
+    /// multi-decoder-per-format support are reserved for future use.
     pub fn with_policy(mut self, policy: CodecPolicy) -> Self {
         self.policy = Some(policy);
         self
@@ -131,6 +149,84 @@ impl<'a> DecodeRequest<'a> {
         self
     }
 
+    /// Apply the image's orientation to the decoded pixels.
+    ///
+    /// The orientation comes from the EXIF tag (JPEG, PNG, WebP, TIFF) or
+    /// the AVIF `irot`/`imir` properties. HEIC and JXL decoders already
+    /// return upright pixels, so they are left as decoded whatever their
+    /// EXIF says. Rotated
+    /// images come back with width and height swapped, and the output's
+    /// [`ImageInfo`] reports identity orientation (with the EXIF tag reset
+    /// to 1), so passing its metadata on to an encoder won't rotate the
+    /// image a second time.
+    ///
+    /// Applies to [`decode_full_frame`](Self::decode_full_frame) and
+    /// [`push_decode`](Self::push_decode). Push decode has to buffer the
+    /// whole frame when the orientation isn't identity, since the first
+    /// output row can depend on the last stored one.
+    /// [`decode_gain_map`](Self::decode_gain_map) and
+    /// [`decode_depth_map`](Self::decode_depth_map) return pixels in stored
+    /// order so they stay aligned with their auxiliary images; orient both
+    /// with [`DecodedGainMap::orient`](crate::DecodedGainMap::orient) and
+    /// [`orient_pixels`](crate::orient::orient_pixels) to keep them together.
+    ///
+    /// Default: `false`.
+    pub fn with_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// CMYK ICC profile (e.g. SWOP or FOGRA) for CMYK/YCCK JPEG and TIFF
+    /// sources that embed none.
+    ///
+    /// Used only with the `cms` feature; see [`cmyk`](crate::cmyk).
+    #[cfg(any(feature = "jpeg", feature = "tiff"))]
+    pub fn with_cmyk_profile(mut self, icc: &'a [u8]) -> Self {
+        self.cmyk_profile = Some(icc);
+        self
+    }
+
+    /// Render the image for a display with `hdr_capacity` headroom.
+    ///
+    /// `hdr_capacity` is log2 of the display's peak luminance over SDR
+    /// white: 0 for an SDR display, 2 for a display with 4× headroom.
+    /// [`decode_full_frame`](Self::decode_full_frame) then decodes the base
+    /// image and its gain map and returns the rendition for that display,
+    /// weighted between the renditions per ISO 21496-1, as f32 in the
+    /// encoding chosen with [`with_hdr_output`](Self::with_hdr_output).
+    /// The output's CICP describes that encoding and the ICC profile is
+    /// dropped.
+    ///
+    /// Images without a gain map are converted to the same encoding
+    /// without changing their range.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use zencodecs::{DecodeRequest, HdrOutput};
+    ///
+    /// let data: &[u8] = &[]; // UltraHDR JPEG bytes
+    /// let output = DecodeRequest::new(data)
+    ///     .with_display_headroom(1.5)
+    ///     .with_hdr_output(HdrOutput::Pq)
+    ///     .decode_full_frame()?;
+    /// # Ok::<(), whereat::At<zencodecs::CodecError>>(())
+    /// ```
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    pub fn with_display_headroom(mut self, hdr_capacity: f32) -> Self {
+        self.display_headroom = Some(hdr_capacity);
+        self
+    }
+
+    /// Pixel encoding for [`with_display_headroom`](Self::with_display_headroom).
+    ///
+    /// Default: [`HdrOutput::Linear`](crate::HdrOutput::Linear).
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    pub fn with_hdr_output(mut self, output: crate::gainmap::HdrOutput) -> Self {
+        self.hdr_output = output;
+        self
+    }
+
     /// Resolve format (auto-detect or explicit) and check registry.
     fn resolve_format(&self) -> Result<ImageFormat> {
         let default_registry = AllowedFormats::all();
@@ -140,7 +236,11 @@ impl<'a> DecodeRequest<'a> {
             None => crate::info::detect_format(self.data)
                 .ok_or_else(|| at!(CodecError::UnrecognizedFormat))?,
         };
-        if !registry.can_decode(format) {
+        let decoder_allowed = self
+            .policy
+            .as_ref()
+            .is_none_or(|p| p.is_decoder_allowed(format));
+        if !registry.can_decode(format) || !decoder_allowed {
             return Err(at!(CodecError::DisabledFormat(format)));
         }
         Ok(format)
@@ -152,8 +252,79 @@ impl<'a> DecodeRequest<'a> {
     /// decode without full materialization, use [`push_decode`](Self::push_decode)
     /// or the top-level [`push_decode`](crate::push_decode) convenience function.
     pub fn decode_full_frame(self) -> Result<DecodeOutput> {
-        let format = self.resolve_format()?;
-        self.decode_format(format)
+        let auto_orient = self.auto_orient;
+        let limits = self.limits;
+        #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+        let output = match self.display_headroom {
+            Some(headroom) => {
+                let hdr_output = self.hdr_output;
+                self.decode_for_display(headroom, hdr_output)?
+            }
+            None => {
+                let format = self.resolve_format()?;
+                self.decode_format(format)?
+            }
+        };
+        #[cfg(not(all(feature = "std", feature = "jpeg-ultrahdr")))]
+        let output = {
+            let format = self.resolve_format()?;
+            self.decode_format(format)?
+        };
+        if !auto_orient {
+            return Ok(output);
+        }
+        let orientation = crate::orient::source_orientation(output.info());
+        if orientation.to_exif() == 1 {
+            return Ok(output);
+        }
+        let stored = output.pixels();
+        crate::orient::check_limits(
+            limits,
+            orientation,
+            stored.width(),
+            stored.rows(),
+            stored.descriptor().bytes_per_pixel(),
+        )
+        .map_err(|msg| at!(CodecError::LimitExceeded(alloc::string::String::from(msg))))?;
+        let pixels = crate::orient::orient_pixels(stored, orientation)?;
+        let info = crate::orient::normalized_info(output.info(), pixels.width(), pixels.height());
+        Ok(DecodeOutput::new(pixels, info))
+    }
+
+    /// Decode base + gain map and render for `headroom`.
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    fn decode_for_display(
+        self,
+        headroom: f32,
+        hdr_output: crate::gainmap::HdrOutput,
+    ) -> Result<DecodeOutput> {
+        use crate::gainmap::HdrOutput;
+
+        let (output, gain_map) = self.decode_gain_map()?;
+        let transfer = crate::tonemap::source_transfer(output.info(), output.descriptor());
+        let pixels = match &gain_map {
+            Some(gm) => gm.render_for_display(output.pixels(), transfer, headroom, hdr_output)?,
+            None => {
+                let base = output.pixels();
+                let (rgb, alpha) = crate::tonemap::linear_pixels(&base, transfer)?;
+                crate::tonemap::hdr_from_linear(
+                    rgb,
+                    alpha.as_deref(),
+                    base.width(),
+                    base.rows(),
+                    hdr_output,
+                )?
+            }
+        };
+
+        let mut info = output.info().clone();
+        info.source_color.icc_profile = None;
+        info.source_color.cicp = Some(match hdr_output {
+            // BT.709 / BT.2020 primaries, linear / PQ transfer, RGB, full range
+            HdrOutput::Linear => zenpixels::Cicp::new(1, 8, 0, true),
+            HdrOutput::Pq => zenpixels::Cicp::new(9, 16, 0, true),
+        });
+        Ok(DecodeOutput::new(pixels, info))
     }
 
     /// Decode the image to pixels.
@@ -183,9 +354,10 @@ impl<'a> DecodeRequest<'a> {
     ///   (Apple ProRAW). Requires the `raw-decode-gainmap` feature.
     /// - **Other formats**: Returns `None` for gain map.
     ///
-    /// The returned [`DecodedGainMap`] can reconstruct HDR from SDR (or vice versa)
-    /// via its [`reconstruct_hdr`](crate::DecodedGainMap::reconstruct_hdr) /
-    /// [`reconstruct_sdr`](crate::DecodedGainMap::reconstruct_sdr) methods.
+    /// The returned [`DecodedGainMap`] can render the base for any display via
+    /// [`render_for_display`](crate::DecodedGainMap::render_for_display), or
+    /// use [`with_display_headroom`](Self::with_display_headroom) to get
+    /// that rendition straight from [`decode_full_frame`](Self::decode_full_frame).
     ///
     /// # Example
     ///
@@ -209,27 +381,7 @@ impl<'a> DecodeRequest<'a> {
         // Enable gain map extraction so codecs attach gain map data to extras.
         self.extract_gain_map = true;
         let output = self.decode_format(format)?;
-
-        let gainmap = match format {
-            ImageFormat::Jpeg => {
-                let gm = extract_jpeg_gainmap(&output);
-                // If standard UltraHDR extraction didn't find a gain map,
-                // try Apple MPF extraction (for AMPF files detected as JPEG).
-                #[cfg(feature = "raw-decode-gainmap")]
-                let gm = gm.or_else(|| extract_raw_gainmap(data));
-                gm
-            }
-            #[cfg(feature = "avif-decode")]
-            ImageFormat::Avif => extract_avif_gainmap(&output),
-            #[cfg(feature = "jxl-decode")]
-            ImageFormat::Jxl => extract_jxl_gainmap(&output),
-            #[cfg(feature = "raw-decode-gainmap")]
-            ImageFormat::Custom(def) if def.name == "dng" || def.name == "raw" => {
-                extract_raw_gainmap(data)
-            }
-            _ => None,
-        };
-
+        let gainmap = extract_gain_map(format, &output, data);
         Ok((output, gainmap))
     }
 
@@ -271,18 +423,59 @@ impl<'a> DecodeRequest<'a> {
         let format = self.resolve_format()?;
         let data = self.data; // Save reference before consuming self
         let output = self.decode_format(format)?;
+        let depth = extract_depth_map(format, &output, data);
+        Ok((output, depth))
+    }
 
-        let depth = match format {
-            #[cfg(feature = "jpeg")]
-            ImageFormat::Jpeg => extract_jpeg_depth(&output, data),
-            #[cfg(feature = "heic-decode")]
-            ImageFormat::Heic => extract_heic_depth(data),
-            #[cfg(feature = "avif-decode")]
-            ImageFormat::Avif => extract_avif_depth(data),
-            _ => None,
-        };
+    /// Decode to RGB and keep the ink values of a CMYK/YCCK source.
+    ///
+    /// The output is what [`decode_full_frame`](Self::decode_full_frame)
+    /// returns without auto-orientation. The ink values are `None` for
+    /// non-CMYK sources and when the codec already converted to RGB.
+    #[cfg(any(feature = "jpeg", feature = "tiff"))]
+    pub fn decode_cmyk(self) -> Result<(DecodeOutput, Option<crate::cmyk::DecodedCmyk>)> {
+        let format = self.resolve_format()?;
+        let data = self.data;
+        let profile = self.cmyk_profile;
+        let output = self.decode_codec(format)?;
+        crate::cmyk::convert_output(output, format, data, profile, true)
+    }
 
-        Ok((output, depth))
+    /// Decode once and extract the requested supplements.
+    ///
+    /// `gain_map` is ignored without the `jpeg-ultrahdr` feature.
+    pub(crate) fn decode_supplements(
+        self,
+        gain_map: bool,
+        depth_map: bool,
+    ) -> Result<(DecodeOutput, Supplements)> {
+        let format = self.resolve_format()?;
+        let data = self.data;
+        #[cfg(feature = "jpeg-ultrahdr")]
+        let request = Self {
+            extract_gain_map: self.extract_gain_map || gain_map,
+            ..self
+        };
+        #[cfg(not(feature = "jpeg-ultrahdr"))]
+        let request = {
+            let _ = gain_map;
+            self
+        };
+        let output = request.decode_format(format)?;
+        let supplements = Supplements {
+            #[cfg(feature = "jpeg-ultrahdr")]
+            gain_map: if gain_map {
+                extract_gain_map(format, &output, data)
+            } else {
+                None
+            },
+            depth_map: if depth_map {
+                extract_depth_map(format, &output, data)
+            } else {
+                None
+            },
+        };
+        Ok((output, supplements))
     }
 
     // ═══════════════════════════════════════════════════════════════════
@@ -336,11 +529,29 @@ impl<'a> DecodeRequest<'a> {
     ///
     /// This is the most memory-efficient decode path — the caller provides
     /// buffers via the sink, and the decoder fills them in order.
+    ///
+    /// With [`with_auto_orient`](Self::with_auto_orient) and a non-identity
+    /// orientation, the sink receives the oriented frame in one strip after
+    /// decoding completes, and the returned [`OutputInfo`](zencodec::decode::OutputInfo)
+    /// reports the oriented dimensions. Buffering the frame and its oriented
+    /// copy is checked against [`Limits`] before the frame is accepted.
     pub fn push_decode(
         self,
         sink: &mut dyn zencodec::decode::DecodeRowSink,
     ) -> Result<zencodec::decode::OutputInfo> {
         let format = self.resolve_format()?;
+        if self.auto_orient {
+            let orientation = crate::orient::source_orientation(&self.probe()?);
+            if orientation.to_exif() != 1 {
+                let mut sink = crate::orient::OrientingSink::new(sink, orientation, self.limits);
+                let mut info =
+                    crate::dyn_dispatch::dyn_push_decode(format, &self.decode_params(), &mut sink)?;
+                if crate::orient::swaps_dimensions(orientation) {
+                    core::mem::swap(&mut info.width, &mut info.height);
+                }
+                return Ok(info);
+            }
+        }
         crate::dyn_dispatch::dyn_push_decode(format, &self.decode_params(), sink)
     }
 
@@ -433,6 +644,14 @@ impl<'a> DecodeRequest<'a> {
         crate::info::probe_format(self.data, format)
     }
 
+    /// Probe headers into [`ImageFacts`](crate::ImageFacts) for format
+    /// selection, including the estimated quality of a JPEG or lossy WebP
+    /// source ([`ImageFacts::source_quality`](crate::ImageFacts::source_quality)).
+    pub fn probe_facts(&self) -> Result<crate::ImageFacts> {
+        let info = self.probe()?;
+        Ok(crate::ImageFacts::from_image_info(&info).with_source_quality(self.data))
+    }
+
     // ═══════════════════════════════════════════════════════════════════
     // Internal helpers
     // ═══════════════════════════════════════════════════════════════════
@@ -449,8 +668,21 @@ impl<'a> DecodeRequest<'a> {
         }
     }
 
-    /// Dispatch to format-specific decoder.
+    /// Decode `format`, converting CMYK sources to RGB.
     fn decode_format(self, format: ImageFormat) -> Result<DecodeOutput> {
+        #[cfg(any(feature = "jpeg", feature = "tiff"))]
+        {
+            let data = self.data;
+            let profile = self.cmyk_profile;
+            let output = self.decode_codec(format)?;
+            crate::cmyk::convert_output(output, format, data, profile, false).map(|(o, _)| o)
+        }
+        #[cfg(not(any(feature = "jpeg", feature = "tiff")))]
+        self.decode_codec(format)
+    }
+
+    /// Dispatch to format-specific decoder.
+    fn decode_codec(self, format: ImageFormat) -> Result<DecodeOutput> {
         let dp = self.decode_policy;
         match format {
             #[cfg(feature = "jpeg")]
@@ -562,11 +794,97 @@ impl<'a> DecodeRequest<'a> {
                 crate::codecs::raw::decode(self.data, self.codec_config, self.limits, self.stop)
             }
 
+            #[cfg(feature = "exr")]
+            ImageFormat::Custom(def) if def.name == "exr" => {
+                crate::codecs::exr::decode(self.data, self.codec_config, self.limits, self.stop)
+            }
+
+            #[cfg(feature = "ico")]
+            ImageFormat::Custom(def) if def.name == "ico" => {
+                crate::codecs::ico::decode(self.data, self.codec_config, self.limits, self.stop)
+            }
+
+            #[cfg(feature = "psd")]
+            ImageFormat::Custom(def) if def.name == "psd" => {
+                crate::codecs::psd::decode(self.data, self.limits, self.stop)
+            }
+
+            #[cfg(feature = "dds")]
+            ImageFormat::Custom(def) if def.name == "dds" => {
+                crate::codecs::dds::decode(self.data, self.codec_config, self.limits, self.stop)
+            }
+
+            #[cfg(feature = "ktx2")]
+            ImageFormat::Custom(def) if def.name == "ktx2" => {
+                crate::codecs::ktx2::decode(self.data, self.codec_config, self.limits, self.stop)
+            }
+
+            #[cfg(feature = "jp2-decode")]
+            ImageFormat::Custom(def) if def.name == "jp2" => {
+                crate::codecs::jp2::decode(self.data, self.codec_config, self.limits, self.stop)
+            }
+
             _ => Err(at!(CodecError::UnsupportedFormat(format))),
         }
     }
 }
 
+/// Supplements extracted by [`DecodeRequest::decode_supplements`].
+#[derive(Default)]
+pub(crate) struct Supplements {
+    #[cfg(feature = "jpeg-ultrahdr")]
+    pub(crate) gain_map: Option<crate::gainmap::DecodedGainMap>,
+    pub(crate) depth_map: Option<crate::depthmap::DecodedDepthMap>,
+}
+
+/// Pull the gain map for `format` out of a decode with gain map
+/// extraction enabled.
+#[cfg(feature = "jpeg-ultrahdr")]
+#[cfg_attr(not(feature = "raw-decode-gainmap"), allow(unused_variables))]
+fn extract_gain_map(
+    format: ImageFormat,
+    output: &DecodeOutput,
+    data: &[u8],
+) -> Option<crate::gainmap::DecodedGainMap> {
+    match format {
+        ImageFormat::Jpeg => {
+            let gm = extract_jpeg_gainmap(output);
+            // If standard UltraHDR extraction didn't find a gain map,
+            // try Apple MPF extraction (for AMPF files detected as JPEG).
+            #[cfg(feature = "raw-decode-gainmap")]
+            let gm = gm.or_else(|| extract_raw_gainmap(data));
+            gm
+        }
+        #[cfg(feature = "avif-decode")]
+        ImageFormat::Avif => extract_avif_gainmap(output),
+        #[cfg(feature = "jxl-decode")]
+        ImageFormat::Jxl => extract_jxl_gainmap(output),
+        #[cfg(feature = "raw-decode-gainmap")]
+        ImageFormat::Custom(def) if def.name == "dng" || def.name == "raw" => {
+            extract_raw_gainmap(data)
+        }
+        _ => None,
+    }
+}
+
+/// Pull the depth map for `format` out of a decode of `data`.
+#[cfg_attr(not(feature = "jpeg"), allow(unused_variables))]
+fn extract_depth_map(
+    format: ImageFormat,
+    output: &DecodeOutput,
+    data: &[u8],
+) -> Option<crate::depthmap::DecodedDepthMap> {
+    match format {
+        #[cfg(feature = "jpeg")]
+        ImageFormat::Jpeg => extract_jpeg_depth(output, data),
+        #[cfg(feature = "heic-decode")]
+        ImageFormat::Heic => extract_heic_depth(data),
+        #[cfg(feature = "avif-decode")]
+        ImageFormat::Avif => extract_avif_depth(data),
+        _ => None,
+    }
+}
+
 /// Extract a gain map from a JPEG DecodeOutput's extras, if present.
 ///
 /// Returns `None` if the JPEG doesn't contain UltraHDR gain map data.
@@ -597,24 +915,20 @@ fn extract_jpeg_gainmap(output: &DecodeOutput) -> Option<crate::gainmap::Decoded
 }
 
 /// Extract a gain map from an AVIF DecodeOutput's extras, if present.
-///
-/// The gain map image is returned as raw AV1 bytes — the caller must
-/// decode them separately to get pixels. For now we store the raw bytes
-/// in `GainMap` with channels=0 to signal "not yet decoded."
 #[cfg(all(feature = "avif-decode", feature = "jpeg-ultrahdr"))]
 fn extract_avif_gainmap(output: &DecodeOutput) -> Option<crate::gainmap::DecodedGainMap> {
     use crate::gainmap::DecodedGainMap;
 
-    let avif_gm = output.extras::<zenavif::AvifGainMap>()?;
-
-    // Convert zenavif-parse's rational metadata → zencodec GainMapParams (log2 domain)
-    // → ultrahdr GainMapMetadata (linear domain). This avoids the domain confusion
-    // bug where log2 rational values were previously assigned directly to linear fields.
-    let params = avif_gain_map_to_params(&avif_gm.metadata);
-    let uhdr_metadata = crate::gainmap::params_to_metadata(&params);
+    // zenavif attaches the tmap item as a gain map source: the ISO 21496-1
+    // metadata already in the log2 domain plus the raw AV1 gain map.
+    let source = output.extras::<zencodec::gainmap::GainMapSource>()?;
+    if source.format != ImageFormat::Avif {
+        return None;
+    }
+    let uhdr_metadata = crate::gainmap::params_to_metadata(&source.metadata.params);
 
     // Decode the raw AV1 gain map to pixels
-    let (gm_data, gm_w, gm_h, gm_ch) = zenavif::decode_av1_obu(&avif_gm.gain_map_data).ok()?;
+    let (gm_data, gm_w, gm_h, gm_ch) = zenavif::decode_av1_obu(&source.data).ok()?;
 
     Some(DecodedGainMap {
         gain_map: crate::gainmap::GainMap {
@@ -629,76 +943,22 @@ fn extract_avif_gainmap(output: &DecodeOutput) -> Option<crate::gainmap::Decoded
     })
 }
 
-/// Convert zenavif-parse's rational gain map metadata to zencodec's canonical
-/// log2-domain [`GainMapParams`](zencodec::GainMapParams).
-///
-/// ISO 21496-1 stores gains and headroom as log2 rational fractions.
-/// zenavif-parse preserves these as raw numerator/denominator pairs.
-/// This function divides them to f64 and stores in `GainMapParams` which
-/// keeps gains/headroom in log2 domain (gamma/offsets are already linear).
-#[cfg(all(feature = "avif-decode", feature = "jpeg-ultrahdr"))]
-fn avif_gain_map_to_params(meta: &zenavif::GainMapMetadata) -> zencodec::GainMapParams {
-    let safe_div = |n: i64, d: u64| -> f64 { if d == 0 { 0.0 } else { n as f64 / d as f64 } };
-    let safe_div_u = |n: u64, d: u64| -> f64 { if d == 0 { 0.0 } else { n as f64 / d as f64 } };
-
-    let convert_channel = |ch: &zenavif::GainMapChannel| -> zencodec::GainMapChannel {
-        zencodec::GainMapChannel {
-            // Gains: n/d produces log2 value — GainMapParams stores log2
-            min: safe_div(ch.gain_map_min_n as i64, ch.gain_map_min_d as u64),
-            max: safe_div(ch.gain_map_max_n as i64, ch.gain_map_max_d as u64),
-            // Gamma and offsets: n/d produces linear value — GainMapParams stores linear
-            gamma: safe_div_u(ch.gamma_n as u64, ch.gamma_d as u64),
-            base_offset: safe_div(ch.base_offset_n as i64, ch.base_offset_d as u64),
-            alternate_offset: safe_div(ch.alternate_offset_n as i64, ch.alternate_offset_d as u64),
-        }
-    };
-
-    let mut channels = [zencodec::GainMapChannel::default(); 3];
-    channels[0] = convert_channel(&meta.channels[0]);
-    if meta.is_multichannel {
-        channels[1] = convert_channel(&meta.channels[1]);
-        channels[2] = convert_channel(&meta.channels[2]);
-    } else {
-        channels[1] = channels[0];
-        channels[2] = channels[0];
-    }
-
-    let mut params = zencodec::GainMapParams::default();
-    params.channels = channels;
-    // Headroom: n/d produces log2 value — GainMapParams stores log2
-    params.base_hdr_headroom = safe_div_u(
-        meta.base_hdr_headroom_n as u64,
-        meta.base_hdr_headroom_d as u64,
-    );
-    params.alternate_hdr_headroom = safe_div_u(
-        meta.alternate_hdr_headroom_n as u64,
-        meta.alternate_hdr_headroom_d as u64,
-    );
-    params.use_base_color_space = meta.use_base_colour_space;
-    params
-}
-
 /// Extract a gain map from a JXL DecodeOutput's extras, if present.
 #[cfg(all(feature = "jxl-decode", feature = "jpeg-ultrahdr"))]
 fn extract_jxl_gainmap(output: &DecodeOutput) -> Option<crate::gainmap::DecodedGainMap> {
     use crate::gainmap::{DecodedGainMap, GainMap};
 
-    let bundle = output.extras::<zenjxl::GainMapBundle>()?;
-
-    // Parse ISO 21496-1 binary metadata from the jhgm bundle
-    let metadata = if !bundle.metadata.is_empty() {
-        zenjpeg::ultrahdr::parse_iso21496(
-            &bundle.metadata,
-            zenjpeg::ultrahdr::Iso21496Format::JpegApp2,
-        )
-        .ok()?
-    } else {
-        crate::gainmap::GainMapMetadata::default()
-    };
+    // zenjxl attaches the jhgm bundle as a gain map source: the parsed
+    // ISO 21496-1 metadata plus the bare gain map codestream.
+    let source = output.extras::<zencodec::gainmap::GainMapSource>()?;
+    if source.format != ImageFormat::Jxl {
+        return None;
+    }
+    let metadata = crate::gainmap::params_to_metadata(&source.metadata.params);
 
     // Decode the bare JXL codestream to get gain map pixels
     use alloc::vec::Vec;
-    let gm_output = zenjxl::decode(&bundle.gain_map_codestream, None, &[]).ok()?;
+    let gm_output = zenjxl::decode(&source.data, None, &[]).ok()?;
     use zenpixels_convert::PixelBufferConvertTypedExt as _;
     let gm_rgb8 = gm_output.pixels.to_rgb8();
     let gm_ref = gm_rgb8.as_imgref();
@@ -767,8 +1027,7 @@ fn extract_jpeg_depth(
     file_data: &[u8],
 ) -> Option<crate::depthmap::DecodedDepthMap> {
     use crate::depthmap::{
-        DecodedDepthMap, DepthFormat, DepthImage, DepthMapMetadata, DepthMeasureType,
-        DepthPixelFormat, DepthSource, DepthUnits,
+        DecodedDepthMap, DepthFormat, DepthMapMetadata, DepthMeasureType, DepthSource, DepthUnits,
     };
 
     let extras = output.extras::<zenjpeg::decoder::DecodedExtras>()?;
@@ -777,16 +1036,7 @@ fn extract_jpeg_depth(
     let depth_data = extras.extract_depth_map(Some(file_data))?;
 
     // Decode the depth image bytes (JPEG or PNG) to get grayscale pixels
-    let depth_output =
-        crate::codecs::jpeg::decode(&depth_data.data, None, None, None, None).ok()?;
-    use zenpixels_convert::PixelBufferConvertTypedExt as _;
-    let gray = depth_output.into_buffer().to_gray8();
-    let gray_ref = gray.as_imgref();
-    let w = gray_ref.width() as u32;
-    let h = gray_ref.height() as u32;
-
-    // Extract grayscale bytes
-    let pixel_data: alloc::vec::Vec<u8> = gray_ref.buf().iter().map(|g| g.value()).collect();
+    let depth = decode_depth_image(&depth_data.data)?;
 
     // Map zenjpeg's DepthSource → zencodecs' DepthSource
     let source_device = match depth_data.source {
@@ -828,28 +1078,12 @@ fn extract_jpeg_depth(
     };
 
     // Decode confidence map if present
-    let confidence = depth_data.confidence.and_then(|conf_bytes| {
-        let conf_output = crate::codecs::jpeg::decode(&conf_bytes, None, None, None, None).ok()?;
-        let conf_gray = conf_output.into_buffer().to_gray8();
-        let conf_ref = conf_gray.as_imgref();
-        let conf_w = conf_ref.width() as u32;
-        let conf_h = conf_ref.height() as u32;
-        let conf_data: alloc::vec::Vec<u8> = conf_ref.buf().iter().map(|g| g.value()).collect();
-        Some(DepthImage {
-            data: conf_data,
-            width: conf_w,
-            height: conf_h,
-            pixel_format: DepthPixelFormat::Gray8,
-        })
-    });
+    let confidence = depth_data
+        .confidence
+        .and_then(|conf_bytes| decode_depth_image(&conf_bytes));
 
     Some(DecodedDepthMap {
-        depth: DepthImage {
-            data: pixel_data,
-            width: w,
-            height: h,
-            pixel_format: DepthPixelFormat::Gray8,
-        },
+        depth,
         metadata,
         confidence,
         source_format: ImageFormat::Jpeg,
@@ -857,6 +1091,53 @@ fn extract_jpeg_depth(
     })
 }
 
+/// Decode an embedded depth or confidence image to grayscale.
+///
+/// The image is a JPEG, or a PNG (8- or 16-bit) when written by
+/// [`EncodeRequest::with_depth_map`](crate::EncodeRequest::with_depth_map)
+/// with the `png` feature. 16-bit PNGs stay 16-bit.
+#[cfg(feature = "jpeg")]
+fn decode_depth_image(bytes: &[u8]) -> Option<crate::depthmap::DepthImage> {
+    use crate::depthmap::{DepthImage, DepthPixelFormat};
+    use zenpixels::{ChannelLayout, ChannelType};
+    use zenpixels_convert::PixelBufferConvertTypedExt as _;
+
+    let output = match crate::info::detect_format(bytes)? {
+        ImageFormat::Jpeg => crate::codecs::jpeg::decode(bytes, None, None, None, None).ok()?,
+        #[cfg(feature = "png")]
+        ImageFormat::Png => crate::codecs::png::decode(bytes, None, None, None).ok()?,
+        _ => return None,
+    };
+    let buffer = output.into_buffer();
+    let descriptor = buffer.descriptor();
+    if descriptor.layout() == ChannelLayout::Gray && descriptor.channel_type() == ChannelType::U16 {
+        let slice = buffer.as_slice();
+        let mut data =
+            alloc::vec::Vec::with_capacity(slice.width() as usize * slice.rows() as usize * 2);
+        for y in 0..slice.rows() {
+            let row = &slice.row(y)[..slice.width() as usize * 2];
+            for px in row.chunks_exact(2) {
+                data.extend_from_slice(&u16::from_ne_bytes([px[0], px[1]]).to_le_bytes());
+            }
+        }
+        return Some(DepthImage {
+            data,
+            width: slice.width(),
+            height: slice.rows(),
+            pixel_format: DepthPixelFormat::Gray16,
+        });
+    }
+
+    let gray = buffer.to_gray8();
+    let gray_ref = gray.as_imgref();
+    Some(DepthImage {
+        data: gray_ref.buf().iter().map(|g| g.value()).collect(),
+        width: gray_ref.width() as u32,
+        height: gray_ref.height() as u32,
+        pixel_format: DepthPixelFormat::Gray8,
+    })
+}
+
 /// Extract a depth map from a HEIC DecodeOutput's extras, if present.
 ///
 /// HEIC files can contain auxiliary depth images (Apple portrait mode).
@@ -926,17 +1207,16 @@ fn extract_heic_depth(data: &[u8]) -> Option<crate::depthmap::DecodedDepthMap> {
 
 /// Extract depth map from an AVIF auxiliary depth image.
 ///
-/// Parses the AVIF container to detect `auxl`-linked depth items (with `auxC`
-/// depth URN), then decodes the depth AV1 bitstream via zenavif to obtain
-/// grayscale pixels.
+/// Parses the AVIF container with zenavif-parse to find `auxl`-linked depth
+/// items (with `auxC` depth URN), then decodes the depth AV1 bitstream via
+/// zenavif to obtain grayscale pixels.
 #[cfg(feature = "avif-decode")]
 fn extract_avif_depth(data: &[u8]) -> Option<crate::depthmap::DecodedDepthMap> {
     use crate::depthmap::*;
 
     // Parse the AVIF container to find the depth auxiliary item
-    let parser = zenavif::ManagedAvifDecoder::new(data, &zenavif::DecoderConfig::default()).ok()?;
-    let info = parser.probe_info().ok()?;
-    let avif_depth = info.depth_map?;
+    let parser = zenavif_parse::AvifParser::from_bytes(data).ok()?;
+    let avif_depth = parser.depth_map()?.ok()?;
 
     if avif_depth.data.is_empty() {
         return None;
@@ -997,6 +1277,65 @@ mod tests {
         let data = b"test";
         let request = DecodeRequest::new(data).with_format(ImageFormat::Jpeg);
         assert_eq!(request.format, Some(ImageFormat::Jpeg));
+        assert!(!request.auto_orient);
+        assert!(request.with_auto_orient(true).auto_orient);
+    }
+
+    /// Without a gain map, display rendering only re-encodes the base.
+    #[cfg(all(feature = "png", feature = "std", feature = "jpeg-ultrahdr"))]
+    #[test]
+    fn display_headroom_without_gain_map_linearizes() {
+        let png_data = {
+            let mut buf = alloc::vec::Vec::new();
+            let mut encoder = png::Encoder::new(&mut buf, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+            writer.finish().unwrap();
+            buf
+        };
+
+        let output = DecodeRequest::new(&png_data)
+            .with_display_headroom(2.0)
+            .decode_full_frame()
+            .unwrap();
+        assert_eq!((output.width(), output.height()), (2, 1));
+        let values: alloc::vec::Vec<f32> = output
+            .pixels()
+            .as_strided_bytes()
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let expected = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        for (v, e) in values.iter().zip(expected) {
+            assert!((v - e).abs() < 1e-3, "{values:?}");
+        }
+        let cicp = output.info().source_color.cicp.unwrap();
+        assert_eq!(cicp.transfer_characteristics, 8);
+    }
+
+    /// Auto-orient leaves images without an orientation untouched.
+    #[cfg(feature = "png")]
+    #[test]
+    fn auto_orient_without_orientation_is_noop() {
+        let png_data = {
+            let mut buf = alloc::vec::Vec::new();
+            let mut encoder = png::Encoder::new(&mut buf, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+            writer.finish().unwrap();
+            buf
+        };
+
+        let output = DecodeRequest::new(&png_data)
+            .with_auto_orient(true)
+            .decode_full_frame()
+            .unwrap();
+        assert_eq!((output.width(), output.height()), (2, 1));
+        assert_eq!(output.info().orientation.to_exif(), 1);
     }
 
     #[test]
@@ -1014,6 +1353,21 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn killbit_disables_decoder() {
+        let jpeg_data = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        let policy = CodecPolicy::new().with_disabled(crate::CodecId::ZenjpegDecode);
+
+        let result = DecodeRequest::new(&jpeg_data)
+            .with_policy(policy)
+            .decode_full_frame();
+
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::DisabledFormat(ImageFormat::Jpeg))
+        ));
+    }
+
     /// Verify decode_depth_map returns None for formats that don't support depth maps.
     #[cfg(feature = "png")]
     #[test]
@@ -17,7 +17,9 @@
 //!
 //! Use [`DecodedDepthMap::to_normalized_f32`] to convert any representation
 //! to a uniform [0.0, 1.0] range (near=0, far=1), or [`DecodedDepthMap::to_meters`]
-//! for metric depth values.
+//! for metric depth values. [`DecodedDepthMap::convert`] re-encodes a map in
+//! another representation and precision, and [`DecodedDepthMap::export_pfm`]
+//! / `export_png` write it as a standalone image with GDepth XMP metadata.
 
 use alloc::vec::Vec;
 
@@ -128,6 +130,23 @@ pub enum DepthMeasureType {
     OpticRay,
 }
 
+/// How a depth map is stored in a JPEG; see
+/// [`EncodeRequest::with_jpeg_depth_container`](crate::EncodeRequest::with_jpeg_depth_container).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JpegDepthContainer {
+    /// Android GDepth XMP: the depth image base64-encoded in the main XMP
+    /// packet, or in Extended XMP when it doesn't fit.
+    #[default]
+    GDepth,
+    /// Android Dynamic Depth: a container directory in XMP, with the
+    /// depth and confidence images appended after the primary image.
+    DynamicDepth,
+    /// Apple-style MPF: an 8-bit disparity JPEG as a second MPF image.
+    /// Readers get the disparity range from its XMP, not GDepth near/far.
+    MpfDisparity,
+}
+
 /// Metadata describing how to interpret depth values.
 #[derive(Clone, Debug, PartialEq)]
 pub struct DepthMapMetadata {
@@ -367,14 +386,22 @@ impl DecodedDepthMap {
     /// Integer pixel formats (Gray8, Gray16) are treated as normalized 0..1 for
     /// `RangeLinear` and `RangeInverse`. Float formats store actual values.
     pub fn to_meters(&self) -> Option<Vec<f32>> {
-        let units_scale = match self.metadata.units {
-            DepthUnits::Meters => 1.0,
+        match self.metadata.units {
+            DepthUnits::Normalized => None,
+            units => Some(self.distances(units)),
+        }
+    }
+
+    /// Per-pixel distance: meters for metric units, the map's own
+    /// near/far scale for [`DepthUnits::Normalized`].
+    fn distances(&self, units: DepthUnits) -> Vec<f32> {
+        let units_scale = match units {
+            DepthUnits::Meters | DepthUnits::Normalized => 1.0,
             DepthUnits::Millimeters => 0.001,
             DepthUnits::Diopters => {
                 // Diopters are 1/meters — handled specially below
                 -1.0
             }
-            DepthUnits::Normalized => return None,
         };
 
         let pixel_count = self.depth.pixel_count() as usize;
@@ -477,7 +504,176 @@ impl DecodedDepthMap {
             }
         }
 
-        Some(output)
+        output
+    }
+
+    /// Re-encode the depth map in another representation.
+    ///
+    /// Distances are recovered from the current encoding, then written as
+    /// `format` over `near`..`far` in `pixel_format`:
+    ///
+    /// - `RangeLinear` / `RangeInverse`: integer formats store the
+    ///   normalized position (0 = near, 1 = far, linear in depth or in
+    ///   inverse depth); float formats store the depth or inverse depth.
+    /// - `Disparity`: 1/depth. Integer formats clamp it to [0, 1], so use a
+    ///   float format for subjects closer than one unit.
+    /// - `AbsoluteDepth`: the depth itself. Integer formats clamp to [0, 1].
+    ///
+    /// Metric maps are converted to meters and `near`/`far` are taken in
+    /// meters; [`DepthUnits::Normalized`] maps keep their own scale. Values
+    /// outside `near`..`far` are clamped for the range formats. The
+    /// confidence map is carried over unchanged.
+    ///
+    /// # Errors
+    ///
+    /// [`CodecError::InvalidInput`] if the depth buffer's length doesn't
+    /// match its dimensions and pixel format, or unless `0 <= near < far`.
+    /// `RangeInverse` needs `near > 0` and accepts an infinite `far`; the
+    /// other formats need a finite `far`.
+    pub fn convert(
+        &self,
+        format: DepthFormat,
+        pixel_format: DepthPixelFormat,
+        near: f32,
+        far: f32,
+    ) -> crate::error::Result<DecodedDepthMap> {
+        self.depth.validate().map_err(|e| whereat::at!(e))?;
+        let valid = near >= 0.0
+            && near < far
+            && match format {
+                DepthFormat::RangeInverse => near > 0.0,
+                _ => far.is_finite(),
+            };
+        if !valid {
+            return Err(whereat::at!(CodecError::InvalidInput(alloc::format!(
+                "invalid depth range {near}..{far} for {format:?}"
+            ))));
+        }
+        let units = match self.metadata.units {
+            DepthUnits::Normalized => DepthUnits::Normalized,
+            _ => DepthUnits::Meters,
+        };
+        let is_integer_format = matches!(
+            pixel_format,
+            DepthPixelFormat::Gray8 | DepthPixelFormat::Gray16
+        );
+        let inverse = |d: f32| if d > 0.0 { 1.0 / d } else { f32::INFINITY };
+        let (inv_near, inv_far) = (inverse(near), inverse(far));
+
+        let values: Vec<f32> = self
+            .distances(self.metadata.units)
+            .into_iter()
+            .map(|d| match format {
+                DepthFormat::RangeLinear if is_integer_format => {
+                    ((d - near) / (far - near)).clamp(0.0, 1.0)
+                }
+                DepthFormat::RangeLinear => d.clamp(near, far),
+                DepthFormat::RangeInverse if is_integer_format => {
+                    ((inv_near - inverse(d.clamp(near, far))) / (inv_near - inv_far))
+                        .clamp(0.0, 1.0)
+                }
+                DepthFormat::RangeInverse => 1.0 / d.clamp(near, far),
+                DepthFormat::Disparity => {
+                    let v = if d.is_infinite() { 0.0 } else { inverse(d) };
+                    if is_integer_format {
+                        v.clamp(0.0, 1.0)
+                    } else {
+                        v
+                    }
+                }
+                DepthFormat::AbsoluteDepth => d,
+            })
+            .collect();
+
+        Ok(DecodedDepthMap {
+            depth: DepthImage {
+                data: write_f32_to_format(&values, pixel_format, pixel_format.bytes_per_pixel()),
+                width: self.depth.width,
+                height: self.depth.height,
+                pixel_format,
+            },
+            metadata: DepthMapMetadata {
+                format,
+                near,
+                far,
+                units,
+                measure_type: self.metadata.measure_type,
+            },
+            confidence: self.confidence.clone(),
+            source_format: self.source_format,
+            source_device: self.source_device,
+        })
+    }
+
+    /// Export as a 16-bit grayscale PNG with GDepth XMP metadata.
+    ///
+    /// Integer `RangeLinear`/`RangeInverse` maps keep their encoding
+    /// (widened to 16 bits); anything else is converted to 16-bit
+    /// `RangeLinear` over the map's near/far. The returned XMP is also
+    /// embedded in the PNG.
+    #[cfg(feature = "png")]
+    pub fn export_png(&self) -> crate::error::Result<DepthExport> {
+        let (image, metadata) = self.to_range_image();
+        image.validate().map_err(|e| whereat::at!(e))?;
+        let values: Vec<crate::pixel::Gray<u16>> = read_raw_f32(&image)
+            .into_iter()
+            .map(|v| crate::pixel::Gray::new((v.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16))
+            .collect();
+        let img = imgref::ImgVec::new(values, image.width as usize, image.height as usize);
+        let xmp = xmp_packet(&alloc::format!(
+            "<rdf:Description rdf:about=\"\" xmlns:GDepth=\"{GDEPTH_NS}\" {}/>",
+            gdepth_attributes(&metadata)
+        ))
+        .into_bytes();
+        let output = crate::EncodeRequest::new(ImageFormat::Png)
+            .with_metadata(crate::Metadata::none().with_xmp(xmp.clone()))
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)?;
+        Ok(DepthExport {
+            data: output.into_vec(),
+            mime_type: "image/png",
+            xmp,
+        })
+    }
+
+    /// Export per-pixel distances as a grayscale PFM (little-endian f32),
+    /// with the metadata as a sidecar XMP packet.
+    ///
+    /// Values are meters for metric maps and the map's own scale for
+    /// [`DepthUnits::Normalized`] maps; the sidecar records them as float
+    /// `RangeLinear` over the map's near/far.
+    ///
+    /// # Errors
+    ///
+    /// [`CodecError::InvalidInput`] if the depth buffer's length doesn't
+    /// match its dimensions and pixel format.
+    pub fn export_pfm(&self) -> crate::error::Result<DepthExport> {
+        self.depth.validate().map_err(|e| whereat::at!(e))?;
+        let (w, h) = (self.depth.width as usize, self.depth.height as usize);
+        let distances = self.distances(self.metadata.units);
+        let mut data = alloc::format!("Pf\n{w} {h}\n-1.0\n").into_bytes();
+        data.reserve(distances.len() * 4);
+        // PFM rows run bottom to top.
+        for row in distances.chunks_exact(w.max(1)).rev() {
+            for v in row {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        let mut metadata = self.metadata.clone();
+        metadata.format = DepthFormat::RangeLinear;
+        if metadata.units != DepthUnits::Normalized {
+            (metadata.near, metadata.far) = self.metric_range();
+            metadata.units = DepthUnits::Meters;
+        }
+        let xmp = xmp_packet(&alloc::format!(
+            "<rdf:Description rdf:about=\"\" xmlns:GDepth=\"{GDEPTH_NS}\" {}/>",
+            gdepth_attributes(&metadata)
+        ))
+        .into_bytes();
+        Ok(DepthExport {
+            data,
+            mime_type: "image/x-portable-floatmap",
+            xmp,
+        })
     }
 
     /// Resize the depth image to match a target resolution using bilinear interpolation.
@@ -517,31 +713,7 @@ impl DecodedDepthMap {
 
         // Read source as f32 for interpolation
         let src_f32 = read_raw_f32(&self.depth);
-        let mut dst_f32 = alloc::vec![0.0f32; total_pixels as usize];
-
-        for y in 0..target_height {
-            for x in 0..target_width {
-                // Map target pixel center to source coordinates
-                let src_x = (x as f32 + 0.5) * (src_w as f32 / target_width as f32) - 0.5;
-                let src_y = (y as f32 + 0.5) * (src_h as f32 / target_height as f32) - 0.5;
-
-                let x0 = src_x.floor().max(0.0) as u32;
-                let y0 = src_y.floor().max(0.0) as u32;
-                let x1 = (x0 + 1).min(src_w - 1);
-                let y1 = (y0 + 1).min(src_h - 1);
-
-                let fx = src_x - src_x.floor().max(0.0);
-                let fy = src_y - src_y.floor().max(0.0);
-
-                let v00 = src_f32[(y0 * src_w + x0) as usize];
-                let v10 = src_f32[(y0 * src_w + x1) as usize];
-                let v01 = src_f32[(y1 * src_w + x0) as usize];
-                let v11 = src_f32[(y1 * src_w + x1) as usize];
-
-                let val = bilinear(v00, v10, v01, v11, fx, fy);
-                dst_f32[(y * target_width + x) as usize] = val;
-            }
-        }
+        let dst_f32 = resize_bilinear_f32(&src_f32, src_w, src_h, target_width, target_height);
 
         // Write back in the original pixel format
         let data = write_f32_to_format(&dst_f32, self.depth.pixel_format, bpp);
@@ -553,6 +725,185 @@ impl DecodedDepthMap {
             pixel_format: self.depth.pixel_format,
         }
     }
+
+    /// [`to_normalized_f32`](Self::to_normalized_f32) quantized to 8 bits
+    /// (0 = near, 255 = far).
+    #[cfg(feature = "avif-encode")]
+    pub(crate) fn normalized_gray8(&self) -> Vec<u8> {
+        write_f32_to_format(&self.to_normalized_f32(), DepthPixelFormat::Gray8, 1)
+    }
+
+    /// The depth map as an integer range image, the only representation
+    /// GDepth and Dynamic Depth can store.
+    ///
+    /// Gray8/Gray16 `RangeLinear` and `RangeInverse` maps pass through
+    /// unchanged, with millimeters rescaled to meters. Anything else is
+    /// converted to a Gray16 range image in meters over the same near/far:
+    /// `RangeLinear`, or `RangeInverse` when the far plane is at infinity.
+    #[cfg(any(feature = "jpeg", feature = "png"))]
+    pub(crate) fn to_range_image(&self) -> (DepthImage, DepthMapMetadata) {
+        let mut metadata = self.metadata.clone();
+        let is_integer_format = matches!(
+            self.depth.pixel_format,
+            DepthPixelFormat::Gray8 | DepthPixelFormat::Gray16
+        );
+        if is_integer_format
+            && matches!(
+                metadata.format,
+                DepthFormat::RangeLinear | DepthFormat::RangeInverse
+            )
+        {
+            if metadata.units == DepthUnits::Millimeters {
+                metadata.near /= 1000.0;
+                metadata.far /= 1000.0;
+                metadata.units = DepthUnits::Meters;
+            }
+            return (self.depth.clone(), metadata);
+        }
+
+        let (near, far) = self.metric_range();
+        let format = if far.is_finite() {
+            DepthFormat::RangeLinear
+        } else {
+            DepthFormat::RangeInverse
+        };
+        if let Ok(converted) = self.convert(format, DepthPixelFormat::Gray16, near, far) {
+            return (converted.depth, converted.metadata);
+        }
+        // Degenerate range: every pixel sits at the near plane.
+        metadata.format = DepthFormat::RangeLinear;
+        (metadata.near, metadata.far) = (near, far);
+        if metadata.units != DepthUnits::Normalized {
+            metadata.units = DepthUnits::Meters;
+        }
+        let depth = DepthImage {
+            data: alloc::vec![0; self.depth.pixel_count() as usize * 2],
+            width: self.depth.width,
+            height: self.depth.height,
+            pixel_format: DepthPixelFormat::Gray16,
+        };
+        (depth, metadata)
+    }
+
+    /// Near/far in meters, ordered (diopters invert the order), or in the
+    /// map's own scale for [`DepthUnits::Normalized`].
+    pub(crate) fn metric_range(&self) -> (f32, f32) {
+        let meters = |v: f32| match self.metadata.units {
+            DepthUnits::Millimeters => v * 0.001,
+            DepthUnits::Diopters if v > 0.0 => 1.0 / v,
+            DepthUnits::Diopters => f32::INFINITY,
+            _ => v,
+        };
+        let (near, far) = (meters(self.metadata.near), meters(self.metadata.far));
+        (near.min(far), near.max(far))
+    }
+}
+
+/// A depth map written as a standalone image.
+#[derive(Clone, Debug)]
+pub struct DepthExport {
+    /// Encoded image bytes.
+    pub data: Vec<u8>,
+    /// MIME type of [`data`](Self::data).
+    pub mime_type: &'static str,
+    /// XMP packet with the depth parameters (GDepth namespace), for a
+    /// `.xmp` sidecar. PNG exports also embed it.
+    pub xmp: Vec<u8>,
+}
+
+/// GDepth XMP namespace.
+pub(crate) const GDEPTH_NS: &str = "http://ns.google.com/photos/1.0/depthmap/";
+
+/// GDepth attributes (`GDepth:` prefix) describing `metadata`: format,
+/// near/far, units and measure type.
+///
+/// GDepth only knows `RangeLinear`/`RangeInverse` in meters or diopters;
+/// other formats are written as `RangeLinear` and normalized units as
+/// meters.
+pub(crate) fn gdepth_attributes(metadata: &DepthMapMetadata) -> alloc::string::String {
+    let format = match metadata.format {
+        DepthFormat::RangeInverse => "RangeInverse",
+        _ => "RangeLinear",
+    };
+    let units = match metadata.units {
+        DepthUnits::Diopters => "diop",
+        _ => "m",
+    };
+    let measure_type = match metadata.measure_type {
+        DepthMeasureType::OpticRay => "OpticRay",
+        _ => "OpticalAxis",
+    };
+    // XMP reals can't be infinite.
+    let real = |v: f32| if v.is_finite() { v } else { f32::MAX };
+    alloc::format!(
+        "GDepth:Format=\"{format}\" GDepth:Near=\"{}\" GDepth:Far=\"{}\" \
+         GDepth:Units=\"{units}\" GDepth:MeasureType=\"{measure_type}\"",
+        real(metadata.near),
+        real(metadata.far),
+    )
+}
+
+/// Wrap `rdf:Description` elements in a standalone XMP packet.
+pub(crate) fn xmp_packet(descriptions: &str) -> alloc::string::String {
+    alloc::format!(
+        "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+         {descriptions}</rdf:RDF></x:xmpmeta>"
+    )
+}
+
+/// Whether `format` can embed a depth map in this build.
+pub(crate) fn carries_depth_map(format: ImageFormat) -> bool {
+    match format {
+        #[cfg(feature = "jpeg")]
+        ImageFormat::Jpeg => true,
+        #[cfg(feature = "avif-encode")]
+        ImageFormat::Avif => true,
+        _ => false,
+    }
+}
+
+/// Compress a depth or confidence image for embedding in another file.
+///
+/// PNG (16-bit when the image is) with the `png` feature, otherwise 8-bit
+/// grayscale JPEG. Returns the bytes and their MIME type.
+#[cfg(feature = "jpeg")]
+pub(crate) fn encode_depth_image(
+    image: &DepthImage,
+) -> crate::error::Result<(Vec<u8>, &'static str)> {
+    use crate::EncodeRequest;
+    use whereat::at;
+    use zenpixels::{PixelDescriptor, PixelSlice};
+
+    image.validate().map_err(|e| at!(e))?;
+    let (w, h) = (image.width, image.height);
+
+    #[cfg(feature = "png")]
+    if image.pixel_format != DepthPixelFormat::Gray8 {
+        let values: Vec<crate::pixel::Gray<u16>> = read_raw_f32(image)
+            .into_iter()
+            .map(|v| crate::pixel::Gray::new((v.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16))
+            .collect();
+        let img = imgref::ImgVec::new(values, w as usize, h as usize);
+        let output = EncodeRequest::new(ImageFormat::Png)
+            .encode(PixelSlice::from(img.as_ref()).erase(), false)?;
+        return Ok((output.into_vec(), "image/png"));
+    }
+
+    let data = match image.pixel_format {
+        DepthPixelFormat::Gray8 => image.data.clone(),
+        _ => write_f32_to_format(&read_raw_f32(image), DepthPixelFormat::Gray8, 1),
+    };
+    let slice = PixelSlice::new(&data, w, h, w as usize, PixelDescriptor::GRAY8)
+        .map_err(|e| at!(CodecError::InvalidInput(alloc::format!("pixel slice: {e}"))))?;
+    #[cfg(feature = "png")]
+    let (format, mime) = (ImageFormat::Png, "image/png");
+    #[cfg(not(feature = "png"))]
+    let (format, mime) = (ImageFormat::Jpeg, "image/jpeg");
+    let output = EncodeRequest::new(format)
+        .with_quality(95.0)
+        .encode(slice, false)?;
+    Ok((output.into_vec(), mime))
 }
 
 /// Read depth image pixels as f32 values.
@@ -623,7 +974,7 @@ fn write_f32_to_format(values: &[f32], format: DepthPixelFormat, bpp: usize) ->
 }
 
 /// Convert IEEE 754 half-precision (f16) to f32.
-fn f16_to_f32(h: u16) -> f32 {
+pub(crate) fn f16_to_f32(h: u16) -> f32 {
     let sign = ((h >> 15) & 1) as u32;
     let exp = ((h >> 10) & 0x1F) as u32;
     let mant = (h & 0x3FF) as u32;
@@ -694,6 +1045,44 @@ fn f32_to_f16(v: f32) -> u16 {
 
 /// Bilinear interpolation.
 #[inline(always)]
+/// Bilinear resample of a single-channel `src_w`×`src_h` plane, sampling
+/// at pixel centers and clamping at the edges.
+///
+/// Both source dimensions must be non-zero and `src` must hold
+/// `src_w * src_h` values.
+pub(crate) fn resize_bilinear_f32(
+    src: &[f32],
+    src_w: u32,
+    src_h: u32,
+    dst_w: u32,
+    dst_h: u32,
+) -> Vec<f32> {
+    // Source coordinate of a destination pixel center, clamped to the
+    // first sample; truncation of a non-negative value is floor.
+    let map = |dst: u32, src_len: u32, dst_len: u32| {
+        let pos = ((dst as f32 + 0.5) * (src_len as f32 / dst_len as f32) - 0.5).max(0.0);
+        let i0 = (pos as u32).min(src_len - 1);
+        (i0, (i0 + 1).min(src_len - 1), pos - i0 as f32)
+    };
+    let mut out = Vec::with_capacity(dst_w as usize * dst_h as usize);
+    for y in 0..dst_h {
+        let (y0, y1, fy) = map(y, src_h, dst_h);
+        for x in 0..dst_w {
+            let (x0, x1, fx) = map(x, src_w, dst_w);
+            let at = |sx: u32, sy: u32| src[sy as usize * src_w as usize + sx as usize];
+            out.push(bilinear(
+                at(x0, y0),
+                at(x1, y0),
+                at(x0, y1),
+                at(x1, y1),
+                fx,
+                fy,
+            ));
+        }
+    }
+    out
+}
+
 fn bilinear(v00: f32, v10: f32, v01: f32, v11: f32, fx: f32, fy: f32) -> f32 {
     let top = v00 * (1.0 - fx) + v10 * fx;
     let bottom = v01 * (1.0 - fx) + v11 * fx;
@@ -706,6 +1095,13 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn depth_map_targets() {
+        assert_eq!(carries_depth_map(ImageFormat::Jpeg), cfg!(feature = "jpeg"));
+        assert!(!carries_depth_map(ImageFormat::Png));
+        assert!(!carries_depth_map(ImageFormat::Gif));
+    }
+
     // =====================================================================
     // DepthImage validation
     // =====================================================================
@@ -1485,4 +1881,207 @@ mod tests {
         assert_eq!(dm.source_format, ImageFormat::Heic);
         assert_eq!(dm.source_device, DepthSource::AppleHeic);
     }
+
+    // =====================================================================
+    // Conversion and export
+    // =====================================================================
+
+    fn metric_gray16(values: &[u16]) -> DecodedDepthMap {
+        DecodedDepthMap {
+            depth: DepthImage {
+                data: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                width: values.len() as u32,
+                height: 1,
+                pixel_format: DepthPixelFormat::Gray16,
+            },
+            metadata: DepthMapMetadata {
+                format: DepthFormat::RangeLinear,
+                near: 1.0,
+                far: 5.0,
+                units: DepthUnits::Meters,
+                measure_type: DepthMeasureType::OpticalAxis,
+            },
+            confidence: None,
+            source_format: ImageFormat::Jpeg,
+            source_device: DepthSource::Unknown,
+        }
+    }
+
+    #[test]
+    fn convert_round_trips_through_inverse_and_disparity() {
+        // 1m, 3m, 5m
+        let dm = metric_gray16(&[0, 32768, 65535]);
+        let inverse = dm
+            .convert(
+                DepthFormat::RangeInverse,
+                DepthPixelFormat::Gray16,
+                1.0,
+                5.0,
+            )
+            .unwrap();
+        let disparity = inverse
+            .convert(DepthFormat::Disparity, DepthPixelFormat::Float32, 1.0, 5.0)
+            .unwrap();
+        let back = disparity
+            .convert(DepthFormat::RangeLinear, DepthPixelFormat::Gray16, 1.0, 5.0)
+            .unwrap();
+        let meters = back.to_meters().unwrap();
+        for (got, want) in meters.iter().zip([1.0, 3.0, 5.0]) {
+            assert!((got - want).abs() < 1e-3, "got {got}, want {want}");
+        }
+        // 3m sits at (1 - 1/3) / (1 - 1/5) = 0.833 in inverse space.
+        let raw = read_raw_f32(&inverse.depth);
+        assert!((raw[1] - 0.8333).abs() < 1e-3, "got {}", raw[1]);
+        assert_eq!(disparity.metadata.format, DepthFormat::Disparity);
+        assert!((read_raw_f32(&disparity.depth)[2] - 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn convert_requantizes_with_new_range() {
+        let dm = metric_gray16(&[0, 32768, 65535]);
+        let narrow = dm
+            .convert(DepthFormat::RangeLinear, DepthPixelFormat::Gray8, 2.0, 4.0)
+            .unwrap();
+        assert_eq!(narrow.depth.pixel_format, DepthPixelFormat::Gray8);
+        assert_eq!(narrow.depth.data, vec![0, 128, 255]);
+        assert_eq!((narrow.metadata.near, narrow.metadata.far), (2.0, 4.0));
+
+        let half = dm
+            .convert(
+                DepthFormat::AbsoluteDepth,
+                DepthPixelFormat::Float16,
+                1.0,
+                5.0,
+            )
+            .unwrap();
+        assert!(half.depth.validate().is_ok());
+        assert!((read_raw_f32(&half.depth)[1] - 3.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn convert_rejects_bad_range() {
+        let dm = metric_gray16(&[0]);
+        for (format, near, far) in [
+            (DepthFormat::RangeLinear, 5.0, 1.0),
+            (DepthFormat::RangeLinear, 0.0, f32::INFINITY),
+            (DepthFormat::RangeInverse, 0.0, 1.0),
+            (DepthFormat::Disparity, f32::NAN, 1.0),
+        ] {
+            let result = dm.convert(format, DepthPixelFormat::Gray16, near, far);
+            assert!(
+                matches!(
+                    result.as_ref().map_err(|e| e.error()),
+                    Err(CodecError::InvalidInput(_))
+                ),
+                "{format:?} {near}..{far}"
+            );
+        }
+        assert!(
+            dm.convert(
+                DepthFormat::RangeInverse,
+                DepthPixelFormat::Gray16,
+                0.5,
+                f32::INFINITY
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn short_depth_buffer_is_rejected() {
+        let mut dm = metric_gray16(&[0, 65535, 32768, 0]);
+        dm.depth.width = 4;
+        dm.depth.height = 4;
+        let pfm = dm.export_pfm();
+        assert!(matches!(
+            pfm.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+        let converted = dm.convert(DepthFormat::RangeLinear, DepthPixelFormat::Gray8, 1.0, 5.0);
+        assert!(matches!(
+            converted.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn export_pfm_writes_meters_bottom_up() {
+        let mut dm = metric_gray16(&[0, 65535, 32768, 0]);
+        dm.depth.width = 2;
+        dm.depth.height = 2;
+        dm.metadata.units = DepthUnits::Millimeters;
+        dm.metadata.near = 1000.0;
+        dm.metadata.far = 5000.0;
+        let export = dm.export_pfm().unwrap();
+        assert_eq!(export.mime_type, "image/x-portable-floatmap");
+        let header = b"Pf\n2 2\n-1.0\n";
+        assert_eq!(&export.data[..header.len()], header);
+        let floats: Vec<f32> = export.data[header.len()..]
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        // Bottom row first: 3m, 1m, then top row: 1m, 5m.
+        let expected = [3.0, 1.0, 1.0, 5.0];
+        for (got, want) in floats.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-3, "got {got}, want {want}");
+        }
+        let xmp = core::str::from_utf8(&export.xmp).unwrap();
+        assert!(xmp.contains(GDEPTH_NS));
+        assert!(xmp.contains("GDepth:Near=\"1\""), "{xmp}");
+        assert!(xmp.contains("GDepth:Far=\"5\""), "{xmp}");
+        assert!(xmp.contains("GDepth:Units=\"m\""), "{xmp}");
+    }
+
+    #[test]
+    #[cfg(any(feature = "jpeg", feature = "png"))]
+    fn range_image_converts_diopter_range_to_meters() {
+        // Float disparity-like depths in diopters: 2 diop = 0.5 m, 0.25 diop = 4 m.
+        let values = [2.0f32, 0.25, 0.5];
+        let dm = DecodedDepthMap {
+            depth: DepthImage {
+                data: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                width: 3,
+                height: 1,
+                pixel_format: DepthPixelFormat::Float32,
+            },
+            metadata: DepthMapMetadata {
+                format: DepthFormat::AbsoluteDepth,
+                near: 2.0,
+                far: 0.25,
+                units: DepthUnits::Diopters,
+                measure_type: DepthMeasureType::OpticalAxis,
+            },
+            confidence: None,
+            source_format: ImageFormat::Jpeg,
+            source_device: DepthSource::Unknown,
+        };
+        let (image, metadata) = dm.to_range_image();
+        assert_eq!(metadata.units, DepthUnits::Meters);
+        assert_eq!(metadata.format, DepthFormat::RangeLinear);
+        assert_eq!((metadata.near, metadata.far), (0.5, 4.0));
+        let ranged = DecodedDepthMap {
+            depth: image,
+            metadata,
+            ..dm
+        };
+        let meters = ranged.to_meters().unwrap();
+        for (got, want) in meters.iter().zip([0.5, 4.0, 2.0]) {
+            assert!((got - want).abs() < 1e-3, "got {got}, want {want}");
+        }
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn export_png_is_16_bit_with_xmp() {
+        let mut dm = metric_gray16(&[0, 128, 255]);
+        dm.depth.data = vec![0, 128, 255];
+        dm.depth.pixel_format = DepthPixelFormat::Gray8;
+        let export = dm.export_png().unwrap();
+        assert_eq!(export.mime_type, "image/png");
+        let decoded = crate::DecodeRequest::new(&export.data).decode().unwrap();
+        assert_eq!(decoded.info().width, 3);
+        assert_eq!(decoded.descriptor().bytes_per_pixel(), 2);
+        let xmp = core::str::from_utf8(&export.xmp).unwrap();
+        assert!(xmp.contains("GDepth:Format=\"RangeLinear\""), "{xmp}");
+    }
 }
@@ -423,6 +423,26 @@ pub(crate) fn build_encoder<'a>(
         #[cfg(not(feature = "bitmaps-hdr"))]
         ImageFormat::Hdr => Err(at!(CodecError::UnsupportedFormat(format))),
 
+        #[cfg(feature = "exr")]
+        ImageFormat::Custom(def) if def.name == "exr" => {
+            Ok(crate::codecs::exr::build_trait_encoder(params))
+        }
+
+        #[cfg(feature = "ico")]
+        ImageFormat::Custom(def) if def.name == "ico" => {
+            Ok(crate::codecs::ico::build_trait_encoder(params))
+        }
+
+        #[cfg(feature = "dds")]
+        ImageFormat::Custom(def) if def.name == "dds" => {
+            Ok(crate::codecs::dds::build_trait_encoder(params))
+        }
+
+        #[cfg(feature = "ktx2")]
+        ImageFormat::Custom(def) if def.name == "ktx2" => {
+            Ok(crate::codecs::ktx2::build_trait_encoder(params))
+        }
+
         _ => Err(at!(CodecError::UnsupportedFormat(format))),
     }
 }
@@ -565,6 +565,32 @@ pub(crate) fn dyn_animation_frame_encoder(
             ))
         }
 
+        #[cfg(feature = "avif-encode")]
+        ImageFormat::Avif => {
+            let mut config = crate::codecs::avif_enc::build_encoding(
+                params.quality,
+                params.effort,
+                params.codec_config,
+            );
+            if params.lossless {
+                config = config.with_lossless(true);
+            }
+            build_ffe!(config)
+        }
+
+        #[cfg(feature = "jxl-encode")]
+        ImageFormat::Jxl => {
+            let mut config = crate::codecs::jxl_enc::build_encoding(
+                params.quality,
+                params.effort,
+                params.codec_config,
+            );
+            if params.lossless {
+                config = config.with_lossless(true);
+            }
+            build_ffe!(config)
+        }
+
         _ => Err(at!(CodecError::UnsupportedOperation {
             format,
             detail: "animation encoding not supported for this format",
@@ -579,22 +605,7 @@ pub(crate) fn dyn_animation_frame_encoder(
 /// Get the decoder CodecId for a format.
 #[allow(dead_code)]
 pub(crate) fn decoder_id_for_format(format: ImageFormat) -> CodecId {
-    match format {
-        ImageFormat::Jpeg => CodecId::ZenjpegDecode,
-        ImageFormat::WebP => CodecId::ZenwebpDecode,
-        ImageFormat::Gif => CodecId::ZengifDecode,
-        ImageFormat::Png => CodecId::PngDecode,
-        ImageFormat::Avif => CodecId::ZenavifDecode,
-        ImageFormat::Jxl => CodecId::ZenjxlDecode,
-        ImageFormat::Heic => CodecId::HeicDecode,
-        ImageFormat::Pnm => CodecId::PnmDecode,
-        ImageFormat::Bmp => CodecId::BmpDecode,
-        ImageFormat::Farbfeld => CodecId::FarbfeldDecode,
-        ImageFormat::Tiff => CodecId::TiffDecode,
-        #[cfg(feature = "raw-decode")]
-        ImageFormat::Custom(def) if def.name == "dng" || def.name == "raw" => CodecId::ZenrawDecode,
-        _ => CodecId::Custom("unknown"),
-    }
+    CodecId::decoder_for(format).unwrap_or(CodecId::Custom("unknown"))
 }
 
 /// Build a selection trace for a decode operation.
@@ -12,6 +12,8 @@ use crate::pixel::{ImgRef, Rgb, Rgba};
 use crate::policy::CodecPolicy;
 use crate::quality::{QualityIntent, QualityProfile};
 use crate::select::ImageFacts;
+use crate::target::TargetEncodeOutput;
+use crate::trace::{SelectionStep, SelectionTrace};
 use crate::{AllowedFormats, CodecError, ImageFormat, Limits, Metadata, StopToken};
 use whereat::at;
 use zencodec::encode::EncodePolicy;
@@ -48,12 +50,30 @@ pub struct EncodeRequest<'a> {
     policy: Option<CodecPolicy>,
     encode_policy: Option<EncodePolicy>,
     image_facts: Option<ImageFacts>,
+    /// Never exceed `image_facts.source_quality`.
+    cap_to_source_quality: bool,
+    /// Quality search goal: byte budget or perceptual score.
+    target: Option<crate::target::EncodeTarget>,
+    /// Cap on encode attempts during quality search.
+    max_search_iterations: u32,
+    /// Auto mode: encode every candidate format and keep the smallest.
+    competitive: bool,
+    /// Encode competitive candidates on separate threads (`std` only).
+    parallel: bool,
+    /// HDR → SDR tone mapping for formats that can't carry HDR.
+    tone_mapping: Option<crate::tonemap::ToneMapping>,
     /// Quality for UltraHDR gain map JPEG (0-100). Only used by `encode_ultrahdr_*`.
     #[cfg(feature = "jpeg-ultrahdr")]
     gainmap_quality: Option<f32>,
     /// Gain map source for embedding in the encoded output.
     #[cfg(feature = "jpeg-ultrahdr")]
     gain_map_source: Option<crate::gainmap::GainMapSource<'a>>,
+    /// Depth map to embed in the encoded output.
+    depth_map: Option<&'a crate::depthmap::DecodedDepthMap>,
+    /// How a JPEG stores `depth_map`.
+    jpeg_depth_container: crate::depthmap::JpegDepthContainer,
+    /// Animation loop count for `animation_frame_encoder` (0 = forever).
+    loop_count: Option<u32>,
 }
 
 impl<'a> EncodeRequest<'a> {
@@ -74,10 +94,19 @@ impl<'a> EncodeRequest<'a> {
             policy: None,
             encode_policy: None,
             image_facts: None,
+            cap_to_source_quality: false,
+            target: None,
+            max_search_iterations: crate::target::DEFAULT_MAX_ITERATIONS,
+            competitive: false,
+            parallel: false,
+            tone_mapping: None,
             #[cfg(feature = "jpeg-ultrahdr")]
             gainmap_quality: None,
             #[cfg(feature = "jpeg-ultrahdr")]
             gain_map_source: None,
+            depth_map: None,
+            jpeg_depth_container: crate::depthmap::JpegDepthContainer::GDepth,
+            loop_count: None,
         }
     }
 
@@ -98,10 +127,19 @@ impl<'a> EncodeRequest<'a> {
             policy: None,
             encode_policy: None,
             image_facts: None,
+            cap_to_source_quality: false,
+            target: None,
+            max_search_iterations: crate::target::DEFAULT_MAX_ITERATIONS,
+            competitive: false,
+            parallel: false,
+            tone_mapping: None,
             #[cfg(feature = "jpeg-ultrahdr")]
             gainmap_quality: None,
             #[cfg(feature = "jpeg-ultrahdr")]
             gain_map_source: None,
+            depth_map: None,
+            jpeg_depth_container: crate::depthmap::JpegDepthContainer::GDepth,
+            loop_count: None,
         }
     }
 
@@ -135,12 +173,33 @@ impl<'a> EncodeRequest<'a> {
         self
     }
 
+    /// Set how many times an animation plays; 0 loops forever.
+    ///
+    /// Only used by [`animation_frame_encoder`](Self::animation_frame_encoder).
+    /// Without it, the codec's default applies (GIF, WebP and APNG loop
+    /// forever).
+    pub fn with_loop_count(mut self, count: u32) -> Self {
+        self.loop_count = Some(count);
+        self
+    }
+
     /// Set metadata to embed in the output (ICC profile, EXIF, XMP).
     ///
     /// Not all formats support all metadata types. Unsupported metadata
     /// is silently ignored — GIF ignores all metadata, AVIF encode only
-    /// supports EXIF, etc.
-    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+    /// supports EXIF, etc. EXIF may be bare TIFF or keep the `Exif\0\0`
+    /// prefix JPEG decoders report it with.
+    pub fn with_metadata(mut self, mut metadata: Metadata) -> Self {
+        // Encoders take bare TIFF; a JPEG would otherwise get the prefix
+        // twice and lose its orientation tag.
+        let tiff = metadata
+            .exif
+            .as_deref()
+            .and_then(|exif| exif.strip_prefix(b"Exif\0\0"))
+            .map(alloc::sync::Arc::from);
+        if tiff.is_some() {
+            metadata.exif = tiff;
+        }
         self.metadata = Some(metadata);
         self
     }
@@ -212,12 +271,121 @@ impl<'a> EncodeRequest<'a> {
     ///
     /// When using `auto()`, providing facts about the source image
     /// improves format selection (e.g., preferring AVIF for small images,
-    /// JPEG for large opaque images).
+    /// JPEG for large opaque images). Facts from
+    /// [`ImageFacts::analyze`] also route screenshots and logos to a
+    /// lossless encode even when lossy quality was requested.
     pub fn with_image_facts(mut self, facts: ImageFacts) -> Self {
         self.image_facts = Some(facts);
         self
     }
 
+    /// Never encode above the source's own estimated quality.
+    ///
+    /// Uses [`ImageFacts::source_quality`] from
+    /// [`with_image_facts`](Self::with_image_facts) (see
+    /// [`ImageFacts::with_source_quality`]); without it, or for lossless
+    /// output, this has no effect. Re-encoding a q60 JPEG at a "high"
+    /// profile only adds bytes.
+    pub fn with_source_quality_cap(mut self, cap: bool) -> Self {
+        self.cap_to_source_quality = cap;
+        self
+    }
+
+    /// Search for the highest quality whose output fits in `bytes`.
+    ///
+    /// The encoder is re-run at different generic quality values (bisection),
+    /// with the configured quality acting as the ceiling. Works the same way
+    /// for JPEG, WebP, AVIF and JXL; other formats and lossless requests
+    /// fail with [`CodecError::UnsupportedOperation`]. If no attempt fits,
+    /// the encode fails with [`CodecError::LimitExceeded`].
+    ///
+    /// The stop token is checked between attempts. Use
+    /// [`encode_to_target`](Self::encode_to_target) to learn which quality
+    /// was chosen.
+    ///
+    /// Format-specific quality overrides in [`CodecConfig`] bypass the
+    /// generic quality and defeat the search.
+    ///
+    /// Replaces any earlier [`with_target_score`](Self::with_target_score).
+    pub fn with_target_size(mut self, bytes: usize) -> Self {
+        self.target = Some(crate::target::EncodeTarget::Size(bytes));
+        self
+    }
+
+    /// Search for the smallest output that reaches `value` on `metric`.
+    ///
+    /// Each attempt is encoded, decoded again, and scored against the
+    /// source pixels, so this costs several encode+decode+measure rounds;
+    /// the configured quality is only the starting guess. The chosen format
+    /// must be decodable as well as encodable. If even quality 100 falls
+    /// short, the best-scoring attempt is returned and
+    /// [`TargetEncodeOutput::score`] is below `value`.
+    ///
+    /// The same format and iteration rules apply as for
+    /// [`with_target_size`](Self::with_target_size), which this replaces.
+    #[cfg(feature = "metrics")]
+    pub fn with_target_score(mut self, metric: crate::metric::QualityMetric, value: f64) -> Self {
+        self.target = Some(crate::target::EncodeTarget::Score(metric, value));
+        self
+    }
+
+    /// Cap the number of encode attempts made by a quality search (default 8).
+    pub fn with_max_search_iterations(mut self, iterations: u32) -> Self {
+        self.max_search_iterations = iterations;
+        self
+    }
+
+    /// Encode every candidate format and keep the smallest acceptable output.
+    ///
+    /// Only applies to [`auto()`](Self::auto) requests. Instead of taking
+    /// the first format in the static preference order, JXL, AVIF, WebP,
+    /// JPEG and PNG are each encoded (subject to the registry, policy, alpha
+    /// and lossless constraints) and the smallest output wins.
+    ///
+    /// Candidates share the generic quality, whose per-codec calibration
+    /// targets similar perceptual quality. With a score target
+    /// (`with_target_score`), each candidate is searched to the score
+    /// instead, and candidates that can't reach it are rejected. Every
+    /// candidate's size is recorded in [`TargetEncodeOutput::trace`]
+    /// (see [`encode_to_target`](Self::encode_to_target)). If no candidate
+    /// produces output, the error is
+    /// [`CodecError::NoCandidateSucceeded`] carrying that trace.
+    ///
+    /// The quality threshold is a score target, which needs the `metrics`
+    /// feature. Without it there is no threshold: every candidate that
+    /// encodes is acceptable and the smallest wins.
+    pub fn with_competition(mut self, competitive: bool) -> Self {
+        self.competitive = competitive;
+        self
+    }
+
+    /// Encode competitive candidates in parallel, one thread per format.
+    ///
+    /// Requires the `std` feature; without it candidates run sequentially.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Tone-map PQ/HLG pixels when the output format can't carry HDR.
+    ///
+    /// Applies when the pixel descriptor's transfer function is PQ or HLG
+    /// and the format is not one of those in
+    /// [`format_supports_hdr`](crate::tonemap::format_supports_hdr);
+    /// otherwise the pixels are encoded unchanged. Without it, HDR code
+    /// values are written as-is and highlights clip. Don't attach the
+    /// source's HDR ICC profile via [`with_metadata`](Self::with_metadata)
+    /// when tone mapping — the output is sRGB.
+    ///
+    /// Requires the `std` feature; without it, input that would be tone
+    /// mapped fails with
+    /// [`UnsupportedOperation`](crate::CodecError::UnsupportedOperation)
+    /// unless the operator is `Clip`.
+    pub fn with_tone_mapping(mut self, mapping: crate::tonemap::ToneMapping) -> Self {
+        self.tone_mapping = Some(mapping);
+        self
+    }
+
     /// Set the quality for the UltraHDR gain map JPEG (0-100).
     ///
     /// Only used by `encode_ultrahdr_rgb_f32` / `encode_ultrahdr_rgba_f32`.
@@ -233,7 +401,7 @@ impl<'a> EncodeRequest<'a> {
     /// The gain map will be embedded in a format-appropriate way:
     /// - **JPEG**: Embedded as UltraHDR (MPF secondary image + XMP metadata)
     /// - **JXL**: Embedded as jhgm box (requires `jxl-encode` + `jxl-decode` features)
-    /// - **AVIF**: Embedded as tmap item (future, not yet implemented)
+    /// - **AVIF**: Embedded as tmap item (requires `avif-encode` + `avif-decode` features)
     ///
     /// Currently only [`GainMapSource::Precomputed`] is supported.
     ///
@@ -259,6 +427,50 @@ impl<'a> EncodeRequest<'a> {
         self
     }
 
+    /// Attach a depth map to the encoded output.
+    ///
+    /// The depth map is embedded in a format-appropriate way:
+    /// - **JPEG**: Android GDepth XMP by default, with the depth image in
+    ///   the main XMP packet when it fits one APP1 segment and in Extended
+    ///   XMP otherwise. [`with_jpeg_depth_container`](Self::with_jpeg_depth_container)
+    ///   selects Dynamic Depth or an MPF disparity image instead. Format,
+    ///   near/far, units and measure type are recorded; maps that aren't an
+    ///   integer range image are converted to a 16-bit range image in
+    ///   meters.
+    /// - **AVIF**: Auxiliary depth image (requires `avif-encode`). AVIF has
+    ///   no fields for near/far or units, so only normalized depth survives.
+    ///
+    /// Other formats fail with [`CodecError::UnsupportedOperation`], as
+    /// does combining a depth map with a gain map.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use zencodecs::{DecodeRequest, EncodeRequest, ImageFormat};
+    ///
+    /// # fn example(portrait: &[u8]) -> zencodecs::Result<()> {
+    /// let (output, depth) = DecodeRequest::new(portrait).decode_depth_map()?;
+    /// let mut request = EncodeRequest::new(ImageFormat::Jpeg).with_quality(85.0);
+    /// if let Some(depth) = &depth {
+    ///     request = request.with_depth_map(depth);
+    /// }
+    /// let jpeg = request.encode(output.pixels(), output.descriptor().has_alpha())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_depth_map(mut self, depth: &'a crate::depthmap::DecodedDepthMap) -> Self {
+        self.depth_map = Some(depth);
+        self
+    }
+
+    /// Choose how [`with_depth_map`](Self::with_depth_map) stores the depth
+    /// map in a JPEG (default: [`GDepth`](crate::JpegDepthContainer::GDepth)).
+    /// Other formats ignore this.
+    pub fn with_jpeg_depth_container(mut self, container: crate::JpegDepthContainer) -> Self {
+        self.jpeg_depth_container = container;
+        self
+    }
+
     // ═══════════════════════════════════════════════════════════════════
     // UltraHDR encode (JPEG-specific, bypasses dispatch)
     // ═══════════════════════════════════════════════════════════════════
@@ -320,7 +532,24 @@ impl<'a> EncodeRequest<'a> {
     /// Create a full-frame animation encoder.
     ///
     /// Push frames sequentially, then call `finish()` to get the encoded output.
-    /// Supported formats: GIF, WebP, PNG (APNG).
+    /// Supported formats: GIF, WebP, PNG (APNG), AVIF (AV1 image sequence,
+    /// `avif-encode`) and JXL (`jxl-encode`).
+    ///
+    /// For PNG, frames become an APNG: the first frame is the default image
+    /// (`IDAT`) and later frames are `fcTL` + `fdAT` chunks numbered by one
+    /// shared sequence counter. Lossless unless a quality below 100 is set,
+    /// which quantizes frames when `png-zenquant` is enabled.
+    ///
+    /// Dispose/blend ops and palettes are not configurable here: zenpng
+    /// picks each frame's `fcTL` dispose and blend ops, and quantized frames
+    /// each get their own palette; there is no cross-frame palette
+    /// optimization.
+    ///
+    /// Frames are `width`×`height`; delays are in milliseconds. Set the loop
+    /// count with [`with_loop_count`](Self::with_loop_count). The request's
+    /// [`Limits`] (including `max_frames` and `max_duration_ms`) and stop
+    /// token are handed to the codec, which checks them as each frame is
+    /// pushed.
     ///
     /// # Example
     ///
@@ -352,9 +581,7 @@ impl<'a> EncodeRequest<'a> {
             }
         };
 
-        if !registry.can_encode(format) {
-            return Err(at!(CodecError::DisabledFormat(format)));
-        }
+        self.check_encodable(registry, format)?;
 
         let resolved_quality = self.resolve_quality();
 
@@ -371,7 +598,7 @@ impl<'a> EncodeRequest<'a> {
                 encode_policy: self.encode_policy,
                 canvas_width: width,
                 canvas_height: height,
-                loop_count: None,
+                loop_count: self.loop_count,
             },
         )
     }
@@ -430,22 +657,21 @@ impl<'a> EncodeRequest<'a> {
         let default_policy = CodecPolicy::new();
         let policy = self.policy.as_ref().unwrap_or(&default_policy);
 
-        let format = match self.format {
-            Some(f) => f,
+        let (format, lossless) = match self.format {
+            Some(f) => (f, self.lossless),
             None => {
                 let facts = self.image_facts.clone().unwrap_or(ImageFacts {
                     pixel_count: width as u64 * height as u64,
                     ..Default::default()
                 });
                 let intent = self.quality_intent();
-                crate::select::select_format(&facts, &intent, registry, policy)?.format
+                let sel = crate::select::select_format(&facts, &intent, registry, policy)?;
+                (sel.format, sel.lossless)
             }
         };
 
-        if !registry.can_encode(format) {
-            return Err(at!(CodecError::DisabledFormat(format)));
-        }
-        if self.lossless && !format.supports_lossless() {
+        self.check_encodable(registry, format)?;
+        if lossless && !format.supports_lossless() {
             return Err(at!(CodecError::UnsupportedOperation {
                 format,
                 detail: "lossless encoding not supported",
@@ -457,7 +683,7 @@ impl<'a> EncodeRequest<'a> {
         let params = crate::dispatch::EncodeParams {
             quality: Some(resolved_quality),
             effort: self.effort,
-            lossless: self.lossless,
+            lossless,
             metadata: self.metadata,
             codec_config: self.codec_config,
             limits: self.limits,
@@ -489,6 +715,22 @@ impl<'a> EncodeRequest<'a> {
         pixels: zenpixels::PixelSlice<'_>,
         has_meaningful_alpha: bool,
     ) -> Result<EncodeOutput> {
+        self.encode_to_target(pixels, has_meaningful_alpha)
+            .map(|t| t.output)
+    }
+
+    /// Encode pixels and report the quality that was used.
+    ///
+    /// Same as [`encode`](Self::encode), but also returns the generic quality
+    /// and the number of encode attempts. Without a target
+    /// ([`with_target_size`](Self::with_target_size) or `with_target_score`)
+    /// this is a single encode
+    /// at the configured quality.
+    pub fn encode_to_target(
+        self,
+        pixels: zenpixels::PixelSlice<'_>,
+        has_meaningful_alpha: bool,
+    ) -> Result<TargetEncodeOutput> {
         self.encode_dispatch(
             pixels.as_strided_bytes(),
             pixels.descriptor(),
@@ -503,11 +745,31 @@ impl<'a> EncodeRequest<'a> {
     // Core dispatch
     // ═══════════════════════════════════════════════════════════════════
 
+    /// Whether the policy, if any, leaves the encoder for `format` enabled.
+    fn encoder_allowed(&self, format: ImageFormat) -> bool {
+        self.policy
+            .as_ref()
+            .is_none_or(|p| p.is_encoder_allowed(format))
+    }
+
+    /// Fail unless `format` has an encoder that the registry and policy allow.
+    fn check_encodable(&self, registry: &AllowedFormats, format: ImageFormat) -> Result<()> {
+        // HEIC is decode-only: there is no pure-Rust HEVC encoder. Say so
+        // rather than blaming the registry.
+        if format == ImageFormat::Heic {
+            return Err(at!(CodecError::UnsupportedFormat(format)));
+        }
+        if !registry.can_encode(format) || !self.encoder_allowed(format) {
+            return Err(at!(CodecError::DisabledFormat(format)));
+        }
+        Ok(())
+    }
+
     /// Resolve the effective quality value.
     ///
     /// Priority: quality_profile (with optional DPR) > raw quality > default (Good profile).
     fn resolve_quality(&self) -> f32 {
-        if let Some(profile) = self.quality_profile {
+        let quality = if let Some(profile) = self.quality_profile {
             let intent = match self.dpr {
                 Some(dpr) => profile.to_intent_with_dpr(dpr),
                 None => profile.to_intent(),
@@ -517,6 +779,15 @@ impl<'a> EncodeRequest<'a> {
             q
         } else {
             QualityProfile::default().generic_quality()
+        };
+        self.cap_quality(quality)
+    }
+
+    /// Apply [`with_source_quality_cap`](Self::with_source_quality_cap).
+    fn cap_quality(&self, quality: f32) -> f32 {
+        match self.image_facts.as_ref().and_then(|f| f.source_quality) {
+            Some(source) if self.cap_to_source_quality && !self.lossless => quality.min(source),
+            _ => quality,
         }
     }
 
@@ -532,6 +803,7 @@ impl<'a> EncodeRequest<'a> {
         } else {
             QualityIntent::default()
         };
+        intent.quality = self.cap_quality(intent.quality);
         if let Some(e) = self.effort {
             intent = intent.with_effort(e);
         }
@@ -545,8 +817,8 @@ impl<'a> EncodeRequest<'a> {
     // Core dispatch
     // ═══════════════════════════════════════════════════════════════════
 
-    /// Internal: resolve format → validate → build encoder →
-    /// negotiate pixel format via zenpixels → encode.
+    /// Internal: resolve format, then hand off to [`encode_as`](Self::encode_as)
+    /// (or to the competitive path).
     fn encode_dispatch(
         self,
         data: &[u8],
@@ -555,55 +827,401 @@ impl<'a> EncodeRequest<'a> {
         height: u32,
         stride: usize,
         has_alpha: bool,
-    ) -> Result<EncodeOutput> {
+    ) -> Result<TargetEncodeOutput> {
         let default_registry = AllowedFormats::all();
         let registry = self.registry.unwrap_or(&default_registry);
         let default_policy = CodecPolicy::new();
         let policy = self.policy.as_ref().unwrap_or(&default_policy);
 
-        let format = match self.format {
-            Some(f) => f,
+        let facts = self.image_facts.clone().unwrap_or(ImageFacts {
+            has_alpha,
+            pixel_count: width as u64 * height as u64,
+            ..Default::default()
+        });
+
+        if self.format.is_none() && self.competitive {
+            return self.encode_competitive(&facts, data, descriptor, width, height, stride);
+        }
+
+        let mut trace = SelectionTrace::new();
+        let (format, lossless) = match self.format {
+            Some(f) => (f, self.lossless),
             None => {
                 // Use the new format selection engine
-                let facts = self.image_facts.clone().unwrap_or(ImageFacts {
-                    has_alpha,
-                    pixel_count: width as u64 * height as u64,
-                    ..Default::default()
-                });
                 let intent = self.quality_intent();
-                crate::select::select_format(&facts, &intent, registry, policy)?.format
+                let sel = crate::select::select_format(&facts, &intent, registry, policy)?;
+                trace = sel.trace;
+                // A search target only makes sense for lossy output, so don't
+                // let content analysis upgrade the request to lossless.
+                (sel.format, sel.lossless && self.target.is_none())
             }
         };
 
-        if !registry.can_encode(format) {
-            return Err(at!(CodecError::DisabledFormat(format)));
+        let mut out = self.encode_as(format, lossless, data, descriptor, width, height, stride)?;
+        out.trace = trace;
+        Ok(out)
+    }
+
+    /// Internal: encode every competition candidate, keep the smallest
+    /// acceptable output.
+    fn encode_competitive(
+        self,
+        facts: &ImageFacts,
+        data: &[u8],
+        descriptor: PixelDescriptor,
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<TargetEncodeOutput> {
+        let default_registry = AllowedFormats::all();
+        let registry = self.registry.unwrap_or(&default_registry);
+        let default_policy = CodecPolicy::new();
+        let policy = self.policy.as_ref().unwrap_or(&default_policy);
+
+        let mut trace = SelectionTrace::new();
+        let candidates = crate::select::competition_candidates(
+            facts,
+            &self.quality_intent(),
+            self.target.is_some(),
+            registry,
+            policy,
+            &mut trace,
+        );
+        if candidates.is_empty() {
+            trace.push(SelectionStep::Info {
+                message: "no competition candidates available",
+            });
+            return Err(at!(CodecError::NoCandidateSucceeded(
+                alloc::boxed::Box::new(trace)
+            )));
         }
-        if self.lossless && !format.supports_lossless() {
+
+        let lossless = self.lossless;
+        let requests: alloc::vec::Vec<(ImageFormat, EncodeRequest<'a>)> = candidates
+            .iter()
+            .map(|&format| (format, self.candidate(format)))
+            .collect();
+
+        #[cfg(feature = "std")]
+        let results: alloc::vec::Vec<(ImageFormat, Result<TargetEncodeOutput>)> = if self.parallel {
+            std::thread::scope(|scope| {
+                let handles: alloc::vec::Vec<_> = requests
+                    .into_iter()
+                    .map(|(format, request)| {
+                        scope.spawn(move || {
+                            let result = request.encode_as(
+                                format, lossless, data, descriptor, width, height, stride,
+                            );
+                            (format, result)
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|h| h.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+                    .collect()
+            })
+        } else {
+            requests
+                .into_iter()
+                .map(|(format, request)| {
+                    let result = request
+                        .encode_as(format, lossless, data, descriptor, width, height, stride);
+                    (format, result)
+                })
+                .collect()
+        };
+        #[cfg(not(feature = "std"))]
+        let results: alloc::vec::Vec<(ImageFormat, Result<TargetEncodeOutput>)> = requests
+            .into_iter()
+            .map(|(format, request)| {
+                let result =
+                    request.encode_as(format, lossless, data, descriptor, width, height, stride);
+                (format, result)
+            })
+            .collect();
+
+        let mut accepted: Option<(ImageFormat, TargetEncodeOutput)> = None;
+        let mut fallback: Option<(ImageFormat, TargetEncodeOutput)> = None;
+        for (format, result) in results {
+            let out = match result {
+                Ok(out) => out,
+                Err(e) => {
+                    if matches!(e.error(), CodecError::Cancelled) {
+                        return Err(e);
+                    }
+                    trace.push(SelectionStep::CandidateRejected {
+                        format,
+                        reason: alloc::format!("{}", e.error()),
+                    });
+                    continue;
+                }
+            };
+            trace.push(SelectionStep::CandidateEncoded {
+                format,
+                bytes: out.output.data().len(),
+                quality: out.quality,
+                score: out.score,
+            });
+
+            if self.meets_score_target(out.score) {
+                if accepted
+                    .as_ref()
+                    .is_none_or(|(_, best)| out.output.data().len() < best.output.data().len())
+                {
+                    accepted = Some((format, out));
+                }
+            } else {
+                trace.push(SelectionStep::CandidateRejected {
+                    format,
+                    reason: alloc::string::String::from("below score threshold"),
+                });
+                if fallback
+                    .as_ref()
+                    .is_none_or(|(_, best)| self.score_beats(out.score, best.score))
+                {
+                    fallback = Some((format, out));
+                }
+            }
+        }
+
+        let (format, mut out, reason) = match (accepted, fallback) {
+            (Some((format, out)), _) => (format, out, "smallest acceptable candidate"),
+            (None, Some((format, out))) => (format, out, "no candidate met threshold, best score"),
+            (None, None) => {
+                trace.push(SelectionStep::Info {
+                    message: "every competition candidate failed",
+                });
+                return Err(at!(CodecError::NoCandidateSucceeded(
+                    alloc::boxed::Box::new(trace)
+                )));
+            }
+        };
+        trace.push(SelectionStep::FormatChosen { format, reason });
+        out.trace = trace;
+        Ok(out)
+    }
+
+    /// Whether a candidate's score satisfies the score target, if any.
+    #[cfg(feature = "metrics")]
+    fn meets_score_target(&self, score: Option<f64>) -> bool {
+        match (self.target, score) {
+            (Some(crate::target::EncodeTarget::Score(metric, value)), Some(score)) => {
+                metric.meets(score, value)
+            }
+            _ => true,
+        }
+    }
+
+    /// Without `metrics` there are no scores, so no threshold to miss.
+    #[cfg(not(feature = "metrics"))]
+    fn meets_score_target(&self, _score: Option<f64>) -> bool {
+        true
+    }
+
+    /// Whether score `a` is strictly better than `b` under the score target.
+    #[cfg(feature = "metrics")]
+    fn score_beats(&self, a: Option<f64>, b: Option<f64>) -> bool {
+        match (self.target, a, b) {
+            (Some(crate::target::EncodeTarget::Score(metric, _)), Some(a), Some(b)) => {
+                a != b && metric.meets(a, b)
+            }
+            _ => a.is_some() && b.is_none(),
+        }
+    }
+
+    /// Unreachable without `metrics`: every candidate meets the (absent)
+    /// threshold, so there is no fallback to rank.
+    #[cfg(not(feature = "metrics"))]
+    fn score_beats(&self, _a: Option<f64>, _b: Option<f64>) -> bool {
+        false
+    }
+
+    /// A copy of this request pinned to one competition candidate.
+    fn candidate(&self, format: ImageFormat) -> EncodeRequest<'a> {
+        EncodeRequest {
+            format: Some(format),
+            quality: self.quality,
+            quality_profile: self.quality_profile,
+            dpr: self.dpr,
+            effort: self.effort,
+            lossless: self.lossless,
+            limits: self.limits,
+            stop: self.stop.clone(),
+            metadata: self.metadata.clone(),
+            registry: self.registry,
+            codec_config: self.codec_config,
+            policy: self.policy.clone(),
+            encode_policy: self.encode_policy,
+            image_facts: self.image_facts.clone(),
+            cap_to_source_quality: self.cap_to_source_quality,
+            target: self.target,
+            max_search_iterations: self.max_search_iterations,
+            competitive: false,
+            parallel: false,
+            tone_mapping: self.tone_mapping,
+            #[cfg(feature = "jpeg-ultrahdr")]
+            gainmap_quality: self.gainmap_quality,
+            #[cfg(feature = "jpeg-ultrahdr")]
+            gain_map_source: self.gain_map_source,
+            depth_map: self.depth_map,
+            jpeg_depth_container: self.jpeg_depth_container,
+            loop_count: self.loop_count,
+        }
+    }
+
+    /// Internal: validate → build encoder → negotiate pixel format via
+    /// zenpixels → encode (or run the quality search).
+    #[allow(clippy::too_many_arguments)]
+    fn encode_as(
+        self,
+        format: ImageFormat,
+        lossless: bool,
+        data: &[u8],
+        descriptor: PixelDescriptor,
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<TargetEncodeOutput> {
+        let default_registry = AllowedFormats::all();
+        let registry = self.registry.unwrap_or(&default_registry);
+
+        self.check_encodable(registry, format)?;
+        if lossless && !format.supports_lossless() {
             return Err(at!(CodecError::UnsupportedOperation {
                 format,
                 detail: "lossless encoding not supported",
             }));
         }
+        if self.target.is_some() {
+            if lossless {
+                return Err(at!(CodecError::UnsupportedOperation {
+                    format,
+                    detail: "quality search requires lossy encoding",
+                }));
+            }
+            if !matches!(
+                format,
+                ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Avif | ImageFormat::Jxl
+            ) {
+                return Err(at!(CodecError::UnsupportedOperation {
+                    format,
+                    detail: "quality search not supported for this format",
+                }));
+            }
+        }
+
+        #[cfg(not(feature = "std"))]
+        if let Some(mapping) = self.tone_mapping
+            && crate::tonemap::needs_tone_mapping(format, descriptor.transfer(), &mapping)
+        {
+            return Err(crate::tonemap::unavailable(format));
+        }
+        #[cfg(feature = "std")]
+        if let Some(mapping) = self.tone_mapping
+            && crate::tonemap::needs_tone_mapping(format, descriptor.transfer(), &mapping)
+        {
+            let hdr = zenpixels::PixelSlice::new(data, width, height, stride, descriptor)
+                .map_err(|e| at!(CodecError::InvalidInput(alloc::format!("pixel slice: {e}"))))?;
+            let sdr = crate::tonemap::tone_map_to_sdr(hdr, descriptor.transfer(), &mapping)?;
+            let sdr = sdr.as_slice();
+            let request = EncodeRequest {
+                tone_mapping: None,
+                ..self
+            };
+            return request.encode_as(
+                format,
+                lossless,
+                sdr.as_strided_bytes(),
+                sdr.descriptor(),
+                sdr.width(),
+                sdr.rows(),
+                sdr.stride(),
+            );
+        }
+
+        if self.depth_map.is_some() {
+            #[cfg(feature = "jpeg-ultrahdr")]
+            if self.gain_map_source.is_some() {
+                return Err(at!(CodecError::UnsupportedOperation {
+                    format,
+                    detail: "depth map together with a gain map",
+                }));
+            }
+            if !crate::depthmap::carries_depth_map(format) {
+                return Err(at!(CodecError::UnsupportedOperation {
+                    format,
+                    detail: "depth map embedding not supported for this format",
+                }));
+            }
+            // The search would size and score the primary image alone.
+            if self.target.is_some() {
+                return Err(at!(CodecError::UnsupportedOperation {
+                    format,
+                    detail: "quality search with an embedded depth map",
+                }));
+            }
+            #[cfg(feature = "jpeg")]
+            if format == ImageFormat::Jpeg
+                && let Some(depth) = self.depth_map
+            {
+                let existing = self.metadata.as_ref().and_then(|m| m.xmp.as_deref());
+                let segments = crate::codecs::jpeg::depth_segments(
+                    existing,
+                    depth,
+                    self.jpeg_depth_container,
+                )?;
+                let metadata = match &segments.xmp {
+                    Some(xmp) => Some(
+                        self.metadata
+                            .clone()
+                            .unwrap_or_else(Metadata::none)
+                            .with_xmp(xmp.clone()),
+                    ),
+                    None => self.metadata.clone(),
+                };
+                let request = EncodeRequest {
+                    metadata,
+                    depth_map: None,
+                    ..self
+                };
+                let mut encoded =
+                    request.encode_as(format, lossless, data, descriptor, width, height, stride)?;
+                let bytes = crate::codecs::jpeg::embed_depth_segments(
+                    encoded.output.into_vec(),
+                    &segments,
+                )?;
+                encoded.output = EncodeOutput::new(bytes, format);
+                return Ok(encoded);
+            }
+        }
 
         let resolved_quality = self.resolve_quality();
+        let single = |output| TargetEncodeOutput {
+            output,
+            quality: resolved_quality,
+            iterations: 1,
+            score: None,
+            trace: SelectionTrace::new(),
+        };
 
-        let params = EncodeParams {
-            quality: Some(resolved_quality),
+        let params_at = |quality: f32| EncodeParams {
+            quality: Some(quality),
             effort: self.effort,
-            lossless: self.lossless,
-            metadata: self.metadata,
+            lossless,
+            metadata: self.metadata.clone(),
             codec_config: self.codec_config,
             limits: self.limits,
             stop: self.stop.clone(),
             encode_policy: self.encode_policy,
         };
 
-        let built = crate::dispatch::build_encoder(format, params)?;
+        let built = crate::dispatch::build_encoder(format, params_at(resolved_quality))?;
 
         // Use zenpixels to negotiate the cheapest pixel format conversion.
         // Returns Cow::Borrowed (zero-copy) when the input already matches
         // one of the encoder's supported formats.
-        let adapted = zenpixels_convert::adapt::adapt_for_encode(
+        let adapted = zenpixels_convert::adapt::adapt_for_encode_cow(
             data,
             descriptor,
             width,
@@ -616,18 +1234,82 @@ impl<'a> EncodeRequest<'a> {
                 "pixel format negotiation: {e}"
             )))
         })?;
+        let pixel_slice = adapted.as_slice();
 
-        // Adapted data is always packed (stride = width * bpp).
-        let adapted_stride = adapted.width as usize * adapted.descriptor.bytes_per_pixel();
+        if let Some(target) = self.target {
+            #[cfg(feature = "jpeg-ultrahdr")]
+            if self.gain_map_source.is_some() {
+                return Err(at!(CodecError::UnsupportedOperation {
+                    format,
+                    detail: "quality search with an embedded gain map",
+                }));
+            }
+            // The first build only served to negotiate the pixel format.
+            drop(built);
+            let encode_at = |quality: f32| {
+                let built = crate::dispatch::build_encoder(format, params_at(quality))?;
+                (built.encoder)(adapted.as_slice())
+            };
+            return match target {
+                crate::target::EncodeTarget::Size(budget) => crate::target::search_size(
+                    budget,
+                    resolved_quality,
+                    self.max_search_iterations,
+                    self.stop.as_ref(),
+                    encode_at,
+                ),
+                #[cfg(feature = "metrics")]
+                crate::target::EncodeTarget::Score(metric, value) => {
+                    // Score against the caller's pixels, not the adapted copy.
+                    let source =
+                        zenpixels::PixelSlice::new(data, width, height, stride, descriptor)
+                            .map_err(|e| {
+                                at!(CodecError::InvalidInput(alloc::format!("pixel slice: {e}")))
+                            })?;
+                    let reference = crate::metric::MetricReference::new(metric, &source)?;
+                    crate::target::search_score(
+                        metric,
+                        value,
+                        resolved_quality,
+                        self.max_search_iterations,
+                        self.stop.as_ref(),
+                        encode_at,
+                        |output| {
+                            let mut request =
+                                crate::DecodeRequest::new(output.data()).with_format(format);
+                            if let Some(lim) = self.limits {
+                                request = request.with_limits(lim);
+                            }
+                            if let Some(s) = &self.stop {
+                                request = request.with_stop(s.clone());
+                            }
+                            let decoded = request.decode_full_frame()?;
+                            reference.compare(&decoded.pixels())
+                        },
+                    )
+                }
+            };
+        }
 
-        let pixel_slice = zenpixels::PixelSlice::new(
-            &adapted.data,
-            adapted.width,
-            adapted.rows,
-            adapted_stride,
-            adapted.descriptor,
-        )
-        .map_err(|e| at!(CodecError::InvalidInput(alloc::format!("pixel slice: {e}"))))?;
+        // AVIF auxiliary depth image (JPEG was handled above)
+        #[cfg(feature = "avif-encode")]
+        if let Some(depth) = self.depth_map
+            && format == ImageFormat::Avif
+        {
+            let output = crate::codecs::avif_enc::encode_with_depth_map(
+                &pixel_slice.contiguous_bytes(),
+                pixel_slice.width(),
+                pixel_slice.rows(),
+                pixel_slice.descriptor(),
+                Some(resolved_quality),
+                self.effort,
+                self.codec_config,
+                depth,
+                self.limits,
+                self.stop.as_ref(),
+            )?;
+            return Ok(single(output));
+        }
 
         // Check if we should embed a precomputed gain map
         #[cfg(feature = "jpeg-ultrahdr")]
@@ -637,43 +1319,46 @@ impl<'a> EncodeRequest<'a> {
             if format == ImageFormat::Jpeg {
                 // For JPEG: use the specialized gain map encoder that produces
                 // UltraHDR JPEG (base + gain map + XMP metadata)
-                let channels = if adapted.descriptor.layout() == zenpixels::ChannelLayout::Rgba {
-                    4u8
-                } else {
-                    3u8
-                };
+                let channels =
+                    if pixel_slice.descriptor().layout() == zenpixels::ChannelLayout::Rgba {
+                        4u8
+                    } else {
+                        3u8
+                    };
                 return crate::codecs::jpeg::encode_with_precomputed_gainmap(
-                    &adapted.data,
-                    adapted.width,
-                    adapted.rows,
+                    &pixel_slice.contiguous_bytes(),
+                    pixel_slice.width(),
+                    pixel_slice.rows(),
                     channels,
                     Some(resolved_quality),
                     self.codec_config,
                     gain_map,
                     metadata,
                     self.stop,
-                );
+                )
+                .map(single);
             }
             #[cfg(all(feature = "jxl-encode", feature = "jxl-decode"))]
             if format == ImageFormat::Jxl {
                 return crate::codecs::jxl_enc::encode_with_precomputed_gainmap(
-                    &adapted.data,
-                    adapted.width,
-                    adapted.rows,
-                    adapted.descriptor,
+                    &pixel_slice.contiguous_bytes(),
+                    pixel_slice.width(),
+                    pixel_slice.rows(),
+                    pixel_slice.descriptor(),
                     Some(resolved_quality),
                     gain_map,
                     metadata,
                     self.stop.as_ref(),
-                );
+                )
+                .map(single);
             }
             #[cfg(all(feature = "avif-encode", feature = "avif-decode"))]
             if format == ImageFormat::Avif {
-                return crate::codecs::avif_enc::encode_with_precomputed_gainmap(
-                    &adapted.data,
-                    adapted.width,
-                    adapted.rows,
-                    adapted.descriptor,
+                let output = crate::codecs::avif_enc::encode_with_precomputed_gainmap(
+                    &pixel_slice.contiguous_bytes(),
+                    pixel_slice.width(),
+                    pixel_slice.rows(),
+                    pixel_slice.descriptor(),
                     Some(resolved_quality),
                     self.effort,
                     self.codec_config,
@@ -681,7 +1366,8 @@ impl<'a> EncodeRequest<'a> {
                     metadata,
                     self.limits,
                     self.stop.as_ref(),
-                );
+                )?;
+                return Ok(single(output));
             }
             return Err(at!(CodecError::UnsupportedOperation {
                 format,
@@ -689,7 +1375,7 @@ impl<'a> EncodeRequest<'a> {
             }));
         }
 
-        (built.encoder)(pixel_slice)
+        (built.encoder)(pixel_slice).map(single)
     }
 }
 
@@ -710,6 +1396,183 @@ mod tests {
         assert!(!request.lossless);
     }
 
+    #[cfg(feature = "png")]
+    #[test]
+    fn apng_chunks_are_sequenced() {
+        let frame = |f: &dyn Fn(usize, usize) -> [u8; 4]| -> alloc::vec::Vec<u8> {
+            (0..8 * 8).flat_map(|i| f(i % 8, i / 8)).collect()
+        };
+        let red = [255, 0, 0, 255];
+        let frames = [
+            frame(&|_, _| red),
+            frame(&|x, y| {
+                if x < 2 && y < 2 {
+                    [0, 255, 0, 255]
+                } else {
+                    red
+                }
+            }),
+            frame(&|x, y| if (x, y) == (7, 7) { [0, 0, 0, 0] } else { red }),
+        ];
+
+        let mut encoder = EncodeRequest::new(ImageFormat::Png)
+            .with_loop_count(2)
+            .animation_frame_encoder(8, 8)
+            .unwrap();
+        for pixels in &frames {
+            let slice = zenpixels::PixelSlice::new(
+                pixels,
+                8,
+                8,
+                32,
+                zenpixels::PixelDescriptor::RGBA8_SRGB,
+            )
+            .unwrap();
+            encoder.push_frame(slice, 100, None).unwrap();
+        }
+        let data = encoder.finish(None).unwrap().into_vec();
+
+        // Walk the chunks after the signature.
+        let mut chunks = alloc::vec::Vec::new();
+        let mut at = 8;
+        while at + 12 <= data.len() {
+            let len = u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+            chunks.push((&data[at + 4..at + 8], &data[at + 8..at + 8 + len]));
+            at += 12 + len;
+        }
+        let be32 = |b: &[u8]| u32::from_be_bytes(b[..4].try_into().unwrap());
+        let actl = chunks.iter().find(|(t, _)| *t == b"acTL").unwrap().1;
+        assert_eq!((be32(actl), be32(&actl[4..])), (3, 2));
+
+        let mut sequence = alloc::vec::Vec::new();
+        let mut frame_controls = 0;
+        for (i, (kind, body)) in chunks.iter().enumerate() {
+            match *kind {
+                b"fcTL" => {
+                    frame_controls += 1;
+                    sequence.push(be32(body));
+                    let (w, h, x, y) = (
+                        be32(&body[4..]),
+                        be32(&body[8..]),
+                        be32(&body[12..]),
+                        be32(&body[16..]),
+                    );
+                    assert!(x + w <= 8 && y + h <= 8);
+                    let num = u32::from(u16::from_be_bytes([body[20], body[21]]));
+                    let den = match u16::from_be_bytes([body[22], body[23]]) {
+                        0 => 100,
+                        d => u32::from(d),
+                    };
+                    assert_eq!(num * 1000, 100 * den, "delay is 100 ms");
+                    assert!(body[24] <= 2, "dispose op");
+                    assert!(body[25] <= 1, "blend op");
+                }
+                b"fdAT" => sequence.push(be32(body)),
+                b"IDAT" => {
+                    assert_eq!(frame_controls, 1, "first frame is the default image");
+                    assert!(chunks[..i].iter().all(|(t, _)| *t != b"fdAT"));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(frame_controls, 3);
+        assert_eq!(
+            sequence,
+            (0..sequence.len() as u32).collect::<alloc::vec::Vec<_>>()
+        );
+
+        let mut decoder = crate::DecodeRequest::new(&data)
+            .animation_frame_decoder()
+            .unwrap();
+        let mut decoded = 0;
+        while decoder.render_next_frame_owned(None).unwrap().is_some() {
+            decoded += 1;
+        }
+        assert_eq!(decoded, 3);
+    }
+
+    /// Encodes three 16×16 frames with `format` and decodes them back;
+    /// lossless frames must come back within `max_error` per sample.
+    #[cfg(any(
+        all(feature = "avif-encode", feature = "avif-decode"),
+        all(feature = "jxl-encode", feature = "jxl-decode")
+    ))]
+    fn animation_round_trip(format: ImageFormat, lossless: bool, max_error: u8) {
+        let frames: alloc::vec::Vec<_> = [0u8, 128, 255]
+            .into_iter()
+            .map(|shade| [shade, 64, 255 - shade, 255].repeat(16 * 16))
+            .collect();
+        let mut encoder = EncodeRequest::new(format)
+            .with_quality(80.0)
+            .with_lossless(lossless)
+            .with_loop_count(0)
+            .animation_frame_encoder(16, 16)
+            .unwrap();
+        for pixels in &frames {
+            let slice = zenpixels::PixelSlice::new(
+                pixels,
+                16,
+                16,
+                64,
+                zenpixels::PixelDescriptor::RGBA8_SRGB,
+            )
+            .unwrap();
+            encoder.push_frame(slice, 40, None).unwrap();
+        }
+        let data = encoder.finish(None).unwrap().into_vec();
+        assert_eq!(crate::from_bytes(&data).unwrap().format, format);
+
+        let mut decoder = crate::DecodeRequest::new(&data)
+            .animation_frame_decoder()
+            .unwrap();
+        let mut decoded = 0;
+        while let Some(frame) = decoder.render_next_frame_owned(None).unwrap() {
+            let pixels = frame.pixels();
+            assert_eq!((pixels.width(), pixels.rows()), (16, 16));
+            if lossless {
+                // Opaque alpha may be dropped on encode; compare color.
+                let descriptor = pixels.descriptor();
+                let channels = match descriptor.layout() {
+                    zenpixels::ChannelLayout::Rgb => 3,
+                    zenpixels::ChannelLayout::Rgba => 4,
+                    other => panic!("unexpected layout {other:?}"),
+                };
+                assert_eq!(descriptor.channel_type(), zenpixels::ChannelType::U8);
+                let bytes = pixels.as_strided_bytes();
+                for y in 0..16 {
+                    let row = &bytes[y * pixels.stride()..][..16 * channels];
+                    let expected = &frames[decoded][y * 64..][..64];
+                    assert!(
+                        row.chunks_exact(channels)
+                            .zip(expected.chunks_exact(4))
+                            .all(|(a, b)| a
+                                .iter()
+                                .zip(b)
+                                .all(|(a, b)| a.abs_diff(*b) <= max_error)),
+                        "frame {decoded} row {y}: {row:?} vs {expected:?}"
+                    );
+                }
+            }
+            decoded += 1;
+        }
+        assert_eq!(decoded, 3);
+    }
+
+    #[cfg(all(feature = "avif-encode", feature = "avif-decode"))]
+    #[test]
+    fn avif_animation_round_trips() {
+        animation_round_trip(ImageFormat::Avif, false, 0);
+        // Lossless AV1 still goes through YUV, which may round by one step.
+        animation_round_trip(ImageFormat::Avif, true, 1);
+    }
+
+    #[cfg(all(feature = "jxl-encode", feature = "jxl-decode"))]
+    #[test]
+    fn jxl_animation_round_trips() {
+        animation_round_trip(ImageFormat::Jxl, false, 0);
+        animation_round_trip(ImageFormat::Jxl, true, 0);
+    }
+
     #[test]
     fn lossless_with_jpeg_error() {
         let img = imgref::ImgVec::new(
@@ -735,6 +1598,20 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn heic_encode_is_unsupported() {
+        let pixels = [0u8; 4 * 4 * 3];
+        let slice =
+            zenpixels::PixelSlice::new(&pixels, 4, 4, 12, zenpixels::PixelDescriptor::RGB8_SRGB)
+                .unwrap();
+        let result = EncodeRequest::new(ImageFormat::Heic).encode(slice, false);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::UnsupportedFormat(ImageFormat::Heic))
+        ));
+        assert!(!AllowedFormats::all().can_encode(ImageFormat::Heic));
+    }
+
     #[test]
     fn codec_config_builder() {
         let config = CodecConfig::default();
@@ -958,4 +1835,402 @@ mod tests {
         // With alpha, should pick a format that supports alpha (not JPEG)
         assert_ne!(output.format(), ImageFormat::Jpeg);
     }
+
+    /// 64x64 pattern with enough detail that quality visibly changes size.
+    fn detailed_rgb() -> imgref::ImgVec<Rgb<u8>> {
+        let mut pixels = vec::Vec::with_capacity(64 * 64);
+        for y in 0..64u32 {
+            for x in 0..64u32 {
+                let n =
+                    (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)).wrapping_mul(2_654_435_761);
+                pixels.push(Rgb {
+                    r: (x * 4) as u8 ^ (n >> 24) as u8,
+                    g: (y * 4) as u8 ^ (n >> 16) as u8,
+                    b: (n >> 8) as u8,
+                });
+            }
+        }
+        imgref::ImgVec::new(pixels, 64, 64)
+    }
+
+    #[test]
+    fn target_size_fits_budget() {
+        let img = detailed_rgb();
+        let full = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(90.0)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let budget = full.data().len() / 2;
+
+        let target = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(90.0)
+            .with_target_size(budget)
+            .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        assert!(target.output.data().len() <= budget);
+        assert!(target.quality < 90.0);
+        assert!(target.iterations > 1 && target.iterations <= 8);
+    }
+
+    #[test]
+    fn target_size_generous_budget_is_single_encode() {
+        let img = detailed_rgb();
+        let target = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(75.0)
+            .with_target_size(usize::MAX)
+            .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        assert_eq!(target.iterations, 1);
+        assert_eq!(target.quality, 75.0);
+    }
+
+    #[test]
+    fn target_size_impossible_budget_errors() {
+        let img = detailed_rgb();
+        let result = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_target_size(16)
+            .with_max_search_iterations(3)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn target_score_reaches_score() {
+        use crate::metric::QualityMetric;
+        // Smooth content: 4:2:0 JPEG can't reach 70 on `detailed_rgb` noise.
+        let img = imgref::ImgVec::new(
+            (0..64 * 64)
+                .map(|i| {
+                    let (x, y) = ((i % 64) as u8, (i / 64) as u8);
+                    Rgb {
+                        r: x * 4,
+                        g: y * 4,
+                        b: 128 + x - y,
+                    }
+                })
+                .collect(),
+            64,
+            64,
+        );
+        let target = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_target_score(QualityMetric::Ssimulacra2, 70.0)
+            .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let score = target.score.expect("score-targeted search reports a score");
+        assert!(score >= 70.0 || target.quality == 100.0, "score {score}");
+        assert!(target.iterations <= 8);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn target_size_rejects_png() {
+        let img = detailed_rgb();
+        let result = EncodeRequest::new(ImageFormat::Png)
+            .with_target_size(1000)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::UnsupportedOperation { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "jpeg", feature = "png"))]
+    fn competitive_picks_smallest_candidate() {
+        let img = detailed_rgb();
+        let out = EncodeRequest::auto()
+            .with_quality(75.0)
+            .with_policy(CodecPolicy::web_safe_output())
+            .with_competition(true)
+            .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let sizes = out.trace.candidate_sizes();
+        assert!(
+            sizes.len() >= 2,
+            "expected JPEG and PNG candidates: {sizes:?}"
+        );
+        let smallest = sizes.iter().map(|&(_, b)| b).min().unwrap();
+        assert_eq!(out.output.data().len(), smallest);
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "jpeg", feature = "png"))]
+    fn competitive_parallel_matches_sequential() {
+        let img = detailed_rgb();
+        let run = |parallel| {
+            EncodeRequest::auto()
+                .with_quality(75.0)
+                .with_policy(CodecPolicy::web_safe_output())
+                .with_competition(true)
+                .with_parallel(parallel)
+                .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+                .unwrap()
+        };
+        let (seq, par) = (run(false), run(true));
+        assert_eq!(seq.format(), par.format());
+        assert_eq!(seq.data().len(), par.data().len());
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn competitive_ignored_for_explicit_format() {
+        let img = detailed_rgb();
+        let out = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_competition(true)
+            .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        assert_eq!(out.output.format(), ImageFormat::Jpeg);
+        assert!(out.trace.candidate_sizes().is_empty());
+    }
+
+    #[test]
+    fn competitive_failure_returns_trace() {
+        let img = detailed_rgb();
+        let registry = AllowedFormats::none();
+        let result = EncodeRequest::auto()
+            .with_registry(&registry)
+            .with_competition(true)
+            .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false);
+        let Err(e) = result else {
+            panic!("expected no candidates");
+        };
+        let CodecError::NoCandidateSucceeded(trace) = e.error() else {
+            panic!("unexpected error: {e:?}");
+        };
+        let skipped = trace
+            .steps()
+            .iter()
+            .filter(|s| matches!(s, SelectionStep::FormatSkipped { .. }))
+            .count();
+        assert_eq!(skipped, 5);
+    }
+
+    #[cfg(feature = "jpeg")]
+    fn portrait_depth() -> crate::depthmap::DecodedDepthMap {
+        use crate::depthmap::*;
+        DecodedDepthMap {
+            depth: DepthImage {
+                data: (0..16 * 16).map(|i| (i % 16 * 16) as u8).collect(),
+                width: 16,
+                height: 16,
+                pixel_format: DepthPixelFormat::Gray8,
+            },
+            metadata: DepthMapMetadata {
+                format: DepthFormat::RangeInverse,
+                near: 0.5,
+                far: 4.0,
+                units: DepthUnits::Meters,
+                measure_type: DepthMeasureType::OpticRay,
+            },
+            confidence: None,
+            source_format: ImageFormat::Jpeg,
+            source_device: DepthSource::AndroidGDepth,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn depth_map_xmp_extends_existing_packet() {
+        let existing = b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF \
+            xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+            <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+            dc:format=\"image/jpeg\"/></rdf:RDF></x:xmpmeta>";
+        let segments = crate::codecs::jpeg::depth_segments(
+            Some(existing),
+            &portrait_depth(),
+            crate::JpegDepthContainer::GDepth,
+        )
+        .unwrap();
+        let xmp = core::str::from_utf8(segments.xmp.as_deref().unwrap()).unwrap();
+        assert!(xmp.contains("dc:format=\"image/jpeg\""));
+        assert!(xmp.contains("GDepth:Format=\"RangeInverse\""));
+        assert!(xmp.contains("GDepth:Near=\"0.5\" GDepth:Far=\"4\""));
+        assert!(xmp.contains("GDepth:MeasureType=\"OpticRay\""));
+        assert!(xmp.contains("GDepth:Data=\""));
+        assert_eq!(xmp.matches("</rdf:RDF>").count(), 1);
+        assert!(
+            segments.extended_xmp.is_empty(),
+            "small maps are stored inline"
+        );
+        assert!(segments.trailer.is_empty());
+    }
+
+    /// A noisy 256×256 map is too large for the main XMP packet.
+    #[cfg(feature = "jpeg")]
+    fn large_depth() -> crate::depthmap::DecodedDepthMap {
+        let mut depth = portrait_depth();
+        let mut seed = 1u32;
+        depth.depth.data = (0..256 * 256)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 24) as u8
+            })
+            .collect();
+        (depth.depth.width, depth.depth.height) = (256, 256);
+        depth
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn large_gdepth_moves_to_extended_xmp() {
+        let segments = crate::codecs::jpeg::depth_segments(
+            None,
+            &large_depth(),
+            crate::JpegDepthContainer::GDepth,
+        )
+        .unwrap();
+        let xmp = core::str::from_utf8(segments.xmp.as_deref().unwrap()).unwrap();
+        assert!(xmp.len() < 65_000);
+        assert!(!xmp.contains("GDepth:Data="));
+        assert!(xmp.contains(&alloc::format!(
+            "xmpNote:HasExtendedXMP=\"{}\"",
+            segments.extended_guid
+        )));
+        assert!(segments.extended_xmp.len() > 65_000);
+
+        let img = detailed_rgb();
+        let jpeg = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_depth_map(&large_depth())
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let header = b"http://ns.adobe.com/xmp/extension/\0";
+        let chunks = jpeg
+            .data()
+            .windows(header.len())
+            .filter(|w| w == header)
+            .count();
+        assert!(chunks >= 2, "extended XMP spans {chunks} segments");
+        let (_, decoded) = crate::DecodeRequest::new(jpeg.data())
+            .decode_depth_map()
+            .unwrap();
+        let decoded = decoded.expect("depth map survives");
+        assert_eq!((decoded.depth.width, decoded.depth.height), (256, 256));
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn jpeg_dynamic_depth_round_trip() {
+        let img = detailed_rgb();
+        let depth = portrait_depth();
+        let jpeg = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_depth_map(&depth)
+            .with_jpeg_depth_container(crate::JpegDepthContainer::DynamicDepth)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let (_, decoded) = crate::DecodeRequest::new(jpeg.data())
+            .decode_depth_map()
+            .unwrap();
+        let decoded = decoded.expect("depth map survives");
+        assert_eq!(decoded.source_device, crate::DepthSource::AndroidDdf);
+        assert_eq!((decoded.depth.width, decoded.depth.height), (16, 16));
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn jpeg_mpf_disparity_round_trip() {
+        let img = detailed_rgb();
+        let depth = portrait_depth();
+        let jpeg = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_depth_map(&depth)
+            .with_jpeg_depth_container(crate::JpegDepthContainer::MpfDisparity)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let data = jpeg.data();
+
+        // The second MPF entry points at a JPEG appended after the primary.
+        let mpf = data.windows(4).position(|w| w == b"MPF\0").unwrap();
+        let tiff = mpf + 4;
+        let be32 = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+        let entries = tiff + be32(tiff + 8 + 2 + 2 * 12 + 8);
+        assert_eq!(be32(entries + 16), 0x0002_0002);
+        let (size, offset) = (be32(entries + 20), be32(entries + 24));
+        assert_eq!(be32(entries + 4), tiff + offset);
+        assert_eq!(tiff + offset + size, data.len());
+        assert_eq!(&data[tiff + offset..tiff + offset + 2], &[0xFF, 0xD8]);
+
+        let (_, decoded) = crate::DecodeRequest::new(data).decode_depth_map().unwrap();
+        let decoded = decoded.expect("depth map survives");
+        assert_eq!(decoded.source_device, crate::DepthSource::AppleMpf);
+        assert_eq!((decoded.depth.width, decoded.depth.height), (16, 16));
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn jpeg_depth_map_round_trip() {
+        let img = detailed_rgb();
+        let depth = portrait_depth();
+        let jpeg = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_depth_map(&depth)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let (_, decoded) = crate::DecodeRequest::new(jpeg.data())
+            .decode_depth_map()
+            .unwrap();
+        let decoded = decoded.expect("depth map survives");
+        assert_eq!((decoded.depth.width, decoded.depth.height), (16, 16));
+        assert_eq!(decoded.metadata.format, depth.metadata.format);
+        assert_eq!(decoded.metadata.near, 0.5);
+        assert_eq!(decoded.metadata.far, 4.0);
+        assert_eq!(decoded.metadata.measure_type, depth.metadata.measure_type);
+    }
+
+    #[test]
+    #[cfg(all(feature = "jpeg", feature = "avif-encode", feature = "avif-decode"))]
+    fn avif_depth_map_round_trip() {
+        let img = detailed_rgb();
+        let depth = portrait_depth();
+        let avif = EncodeRequest::new(ImageFormat::Avif)
+            .with_depth_map(&depth)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let (_, decoded) = crate::DecodeRequest::new(avif.data())
+            .decode_depth_map()
+            .unwrap();
+        let decoded = decoded.expect("depth map survives");
+        assert_eq!((decoded.depth.width, decoded.depth.height), (16, 16));
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn depth_map_rejects_target_size() {
+        let img = detailed_rgb();
+        let depth = portrait_depth();
+        let mut formats = vec![ImageFormat::Jpeg];
+        #[cfg(feature = "avif-encode")]
+        formats.push(ImageFormat::Avif);
+        for format in formats {
+            let result = EncodeRequest::new(format)
+                .with_depth_map(&depth)
+                .with_target_size(4000)
+                .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false);
+            assert!(
+                matches!(
+                    result.as_ref().map_err(|e| e.error()),
+                    Err(CodecError::UnsupportedOperation {
+                        detail: "quality search with an embedded depth map",
+                        ..
+                    })
+                ),
+                "{format:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "jpeg", feature = "webp"))]
+    fn depth_map_rejected_for_webp() {
+        let img = detailed_rgb();
+        let depth = portrait_depth();
+        let result = EncodeRequest::new(ImageFormat::WebP)
+            .with_depth_map(&depth)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::UnsupportedOperation { .. })
+        ));
+    }
 }
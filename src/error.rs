@@ -44,6 +44,10 @@ pub enum CodecError {
     /// No suitable encoder found for auto-selection.
     #[error("no suitable encoder found for auto-selection")]
     NoSuitableEncoder,
+    /// Competitive encoding produced no output; the trace records why each
+    /// candidate was skipped or rejected.
+    #[error("no competitive encode candidate succeeded")]
+    NoCandidateSucceeded(Box<crate::trace::SelectionTrace>),
     /// Color management error.
     #[cfg(feature = "cms")]
     #[error("color management error: {0}")]
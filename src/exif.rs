@@ -832,6 +832,65 @@ pub fn parse_exif(data: &[u8]) -> Result<ExifData, ExifError> {
     Ok(exif)
 }
 
+/// Overwrite the IFD0 orientation tag in place.
+///
+/// Accepts the same input as [`parse_exif`] (with or without the
+/// `Exif\0\0` prefix). The tag value is patched without changing the
+/// payload size, so the bytes can be written straight back into a JPEG
+/// APP1 segment. Returns `false` if the data has no orientation tag (adding
+/// one would require rewriting the IFD) or `orientation` is not 1-8.
+pub fn set_orientation(data: &mut [u8], orientation: u16) -> bool {
+    if !(1..=8).contains(&orientation) {
+        return false;
+    }
+    let tiff_start = if data.len() >= 6 && &data[..6] == b"Exif\0\0" {
+        6
+    } else {
+        0
+    };
+    let tiff = &data[tiff_start..];
+    if tiff.len() < 8 {
+        return false;
+    }
+    let little_endian = match &tiff[..2] {
+        b"II" => true,
+        b"MM" => false,
+        _ => return false,
+    };
+    let reader = Reader::new(tiff, little_endian);
+    if reader.u16_at(2) != Some(42) {
+        return false;
+    }
+    let Some(ifd0_offset) = reader.u32_at(4) else {
+        return false;
+    };
+    let Ok(ifd0) = parse_ifd(&reader, ifd0_offset as usize) else {
+        return false;
+    };
+    let Some((entry, entry_offset)) = find_entry(&ifd0, TAG_ORIENTATION) else {
+        return false;
+    };
+    if entry.type_id != TYPE_SHORT || entry.count < 1 {
+        return false;
+    }
+    let Some(value_offset) = entry.data_offset(*entry_offset) else {
+        return false;
+    };
+    let bytes = if little_endian {
+        orientation.to_le_bytes()
+    } else {
+        orientation.to_be_bytes()
+    };
+    let start = tiff_start + value_offset;
+    match data.get_mut(start..start + 2) {
+        Some(slot) => {
+            slot.copy_from_slice(&bytes);
+            true
+        }
+        None => false,
+    }
+}
+
 /// Parse IFD0 tags into ExifData, including DNG-specific tags.
 fn parse_ifd0_tags(reader: &Reader<'_>, ifd: &ParsedIfd, exif: &mut ExifData) {
     for (entry, offset) in &ifd.entries {
@@ -1380,6 +1439,43 @@ mod tests {
         assert_eq!(exif.orientation, Some(3));
     }
 
+    #[test]
+    fn set_orientation_patches_in_place() {
+        for le in [true, false] {
+            let mut tiff = build_tiff(
+                le,
+                &[
+                    (TAG_MAKE, TYPE_ASCII, 6, b"Nikon\0"),
+                    (TAG_ORIENTATION, TYPE_SHORT, 1, &make_short_bytes(6, le)),
+                ],
+            );
+            let len = tiff.len();
+            assert!(set_orientation(&mut tiff, 1));
+            assert_eq!(tiff.len(), len);
+            let exif = parse_exif(&tiff).unwrap();
+            assert_eq!(exif.orientation, Some(1));
+            assert_eq!(exif.make.as_deref(), Some("Nikon"));
+        }
+
+        // JPEG-style prefix
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend(build_tiff(
+            true,
+            &[(TAG_ORIENTATION, TYPE_SHORT, 1, &make_short_bytes(3, true))],
+        ));
+        assert!(set_orientation(&mut app1, 8));
+        assert_eq!(parse_exif(&app1).unwrap().orientation, Some(8));
+    }
+
+    #[test]
+    fn set_orientation_without_tag_fails() {
+        let mut tiff = build_tiff(true, &[(TAG_MAKE, TYPE_ASCII, 6, b"Nikon\0")]);
+        let before = tiff.clone();
+        assert!(!set_orientation(&mut tiff, 1));
+        assert_eq!(tiff, before);
+        assert!(!set_orientation(&mut tiff, 9));
+    }
+
     // =====================================================================
     // 2. IFD entry types: BYTE, ASCII, SHORT, LONG, RATIONAL, SRATIONAL
     // =====================================================================
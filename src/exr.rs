@@ -0,0 +1,410 @@
+//! OpenEXR support (`exr` feature).
+//!
+//! EXR is a custom format (`ImageFormat::Custom(&EXR_FORMAT)`), handled by
+//! the pure-Rust `exr` crate. Through the regular requests it behaves like
+//! any other format: [`DecodeRequest`](crate::DecodeRequest) returns linear
+//! f16 or f32 RGB(A) pixels, and [`EncodeRequest`](crate::EncodeRequest)
+//! writes linear RGB(A) with ZIP, PIZ or no compression.
+//!
+//! EXR-specific operations live here:
+//!
+//! - [`layers`] lists the layers (parts) of a file with their channels.
+//! - [`decode_blocks`] streams one layer block by block (scanline groups or
+//!   tiles, in file order) without materializing the whole image.
+//!
+//! Layer choice, output precision and encode settings go through
+//! [`ExrDecodeConfig`] / [`ExrEncodeConfig`] on
+//! [`CodecConfig`](crate::config::CodecConfig).
+//!
+//! # Color
+//!
+//! Pixels are scene-linear with Rec.709 primaries (chromaticities
+//! attributes are not interpreted). EXR stores premultiplied alpha; decoded
+//! pixels are un-premultiplied and encoded pixels are premultiplied, so
+//! the pixel buffers use straight alpha like every other format.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::ImageFormat;
+use crate::error::Result;
+
+/// Format definition for OpenEXR.
+pub static EXR_FORMAT: zencodec::ImageFormatDefinition = zencodec::ImageFormatDefinition::new(
+    "exr",
+    None,
+    "OpenEXR",
+    "exr",
+    &["exr"],
+    "image/x-exr",
+    &["image/x-exr", "image/aces"],
+    true,
+    false,
+    true,
+    true,
+    4,
+    crate::codecs::exr::is_exr,
+);
+
+/// The EXR [`ImageFormat`].
+pub fn format() -> ImageFormat {
+    ImageFormat::Custom(&EXR_FORMAT)
+}
+
+/// Sample type of an EXR channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrSampleType {
+    /// 16-bit half float.
+    F16,
+    /// 32-bit float.
+    F32,
+    /// 32-bit unsigned integer (IDs, masks).
+    U32,
+}
+
+/// Compression for EXR output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrCompression {
+    /// Uncompressed.
+    None,
+    /// Deflate over 16-scanline blocks. Lossless; a good general default.
+    #[default]
+    Zip,
+    /// Wavelet compression. Lossless; best for grainy photographic content.
+    Piz,
+}
+
+/// Precision of decoded EXR pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrPrecision {
+    /// f16 when every decoded channel is half float, f32 otherwise.
+    #[default]
+    Native,
+    /// Always f16.
+    F16,
+    /// Always f32.
+    F32,
+}
+
+/// One channel of an EXR layer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExrChannel {
+    /// Full channel name, e.g. `"R"` or `"diffuse.R"`.
+    pub name: String,
+    /// Stored sample type.
+    pub sample_type: ExrSampleType,
+    /// Horizontal and vertical subsampling (1 = full resolution).
+    pub sampling: (u32, u32),
+}
+
+/// One layer (part) of an EXR file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExrLayer {
+    /// Layer name; single-part files usually have none.
+    pub name: Option<String>,
+    /// Data window width.
+    pub width: u32,
+    /// Data window height.
+    pub height: u32,
+    /// Channels, in file order (sorted by name).
+    pub channels: Vec<ExrChannel>,
+    /// Stored as tiles rather than scanline blocks.
+    pub tiled: bool,
+    /// Deep data (multiple samples per pixel). Deep layers can't be decoded.
+    pub deep: bool,
+}
+
+/// Samples of one channel within an [`ExrBlock`].
+#[derive(Clone, Debug)]
+pub struct ExrChannelSamples {
+    /// Channel name.
+    pub name: String,
+    /// `width * height` samples, row-major, converted to f32.
+    pub samples: Vec<f32>,
+}
+
+/// A decoded block of one layer, as passed to [`decode_blocks`].
+#[derive(Clone, Debug)]
+pub struct ExrBlock {
+    /// Left edge within the layer's data window.
+    pub x: u32,
+    /// Top edge within the layer's data window.
+    pub y: u32,
+    /// Block width.
+    pub width: u32,
+    /// Block height.
+    pub height: u32,
+    /// Every full-resolution channel of the layer, in file order;
+    /// subsampled (chroma) channels are skipped. Samples are stored values:
+    /// color is premultiplied by alpha.
+    pub channels: Vec<ExrChannelSamples>,
+}
+
+/// EXR decode settings.
+#[derive(Clone, Debug, Default)]
+pub struct ExrDecodeConfig {
+    pub(crate) layer: Option<String>,
+    pub(crate) precision: ExrPrecision,
+}
+
+impl ExrDecodeConfig {
+    /// Default settings: first layer with color channels, native precision.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the layer with this name instead of the first one.
+    pub fn with_layer(mut self, name: impl Into<String>) -> Self {
+        self.layer = Some(name.into());
+        self
+    }
+
+    /// Set the output precision.
+    pub fn with_precision(mut self, precision: ExrPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+}
+
+/// EXR encode settings.
+#[derive(Clone, Debug, Default)]
+pub struct ExrEncodeConfig {
+    pub(crate) compression: ExrCompression,
+    pub(crate) half: bool,
+    pub(crate) tile_size: Option<u32>,
+    pub(crate) layer_name: Option<String>,
+}
+
+impl ExrEncodeConfig {
+    /// Default settings: ZIP, f32 samples, scanline blocks, unnamed layer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the compression.
+    pub fn with_compression(mut self, compression: ExrCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Store half-float samples instead of f32. Halves the size; plenty of
+    /// range for display-referred HDR, lossy for high-precision data.
+    pub fn with_half_float(mut self, half: bool) -> Self {
+        self.half = half;
+        self
+    }
+
+    /// Store square tiles of this size instead of scanline blocks.
+    pub fn with_tiles(mut self, size: u32) -> Self {
+        self.tile_size = Some(size.max(1));
+        self
+    }
+
+    /// Name the written layer.
+    pub fn with_layer_name(mut self, name: impl Into<String>) -> Self {
+        self.layer_name = Some(name.into());
+        self
+    }
+}
+
+/// List the layers of an EXR file from its headers.
+pub fn layers(data: &[u8]) -> Result<Vec<ExrLayer>> {
+    crate::codecs::exr::layers(data)
+}
+
+/// Decode one layer block by block, calling `sink` for each block in file
+/// order.
+///
+/// Only the layer selected by `config` (see [`ExrDecodeConfig::with_layer`])
+/// is decompressed; `config`'s precision is ignored since blocks are always
+/// f32. `limits` apply to the layer's dimensions. An error returned by
+/// `sink` stops decoding and is returned as is.
+pub fn decode_blocks(
+    data: &[u8],
+    config: &ExrDecodeConfig,
+    limits: Option<&crate::Limits>,
+    stop: Option<crate::StopToken>,
+    sink: impl FnMut(ExrBlock) -> Result<()>,
+) -> Result<()> {
+    crate::codecs::exr::decode_blocks(data, config, limits, stop, sink)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::config::CodecConfig;
+    use crate::{DecodeRequest, EncodeRequest};
+    use zenpixels::PixelSlice;
+
+    fn gradient(width: usize, height: usize) -> imgref::ImgVec<rgb::Rgba<f32>> {
+        let pixels = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f32, (i / width) as f32);
+                rgb::Rgba {
+                    r: x * 0.25,
+                    g: y * 2.0,
+                    b: 8.0,
+                    a: if x == 0.0 { 0.5 } else { 1.0 },
+                }
+            })
+            .collect();
+        imgref::ImgVec::new(pixels, width, height)
+    }
+
+    fn encode(config: ExrEncodeConfig, img: &imgref::ImgVec<rgb::Rgba<f32>>) -> Vec<u8> {
+        let codec_config = CodecConfig::default().with_exr_encoder(config);
+        EncodeRequest::new(format())
+            .with_codec_config(&codec_config)
+            .encode(PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap()
+            .into_vec()
+    }
+
+    fn decoded_f32(data: &[u8]) -> Vec<f32> {
+        let config = CodecConfig::default()
+            .with_exr_decoder(ExrDecodeConfig::new().with_precision(ExrPrecision::F32));
+        let output = DecodeRequest::new(data)
+            .with_codec_config(&config)
+            .decode_full_frame()
+            .unwrap();
+        assert_eq!(output.info().format, format());
+        let pixels = output.pixels();
+        let row_bytes = pixels.width() as usize * 16;
+        let bytes = pixels.as_strided_bytes();
+        (0..pixels.rows() as usize)
+            .flat_map(|y| bytes[y * pixels.stride()..][..row_bytes].chunks_exact(4))
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_every_compression() {
+        let img = gradient(37, 21);
+        let expected: Vec<f32> = img
+            .buf()
+            .iter()
+            .flat_map(|p| [p.r, p.g, p.b, p.a])
+            .collect();
+        for compression in [
+            ExrCompression::None,
+            ExrCompression::Zip,
+            ExrCompression::Piz,
+        ] {
+            let data = encode(ExrEncodeConfig::new().with_compression(compression), &img);
+            assert!(crate::codecs::exr::is_exr(&data));
+            let info = crate::from_bytes(&data).unwrap();
+            assert_eq!((info.width, info.height), (37, 21));
+            assert!(info.has_alpha);
+            assert_eq!(decoded_f32(&data), expected, "{compression:?}");
+        }
+    }
+
+    #[test]
+    fn half_float_tiles_decode_natively_as_f16() {
+        let img = gradient(40, 40);
+        let data = encode(
+            ExrEncodeConfig::new()
+                .with_half_float(true)
+                .with_tiles(16)
+                .with_layer_name("beauty"),
+            &img,
+        );
+        let layers = layers(&data).unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].name.as_deref(), Some("beauty"));
+        assert!(layers[0].tiled);
+        assert_eq!(
+            layers[0]
+                .channels
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            ["A", "B", "G", "R"]
+        );
+        assert!(
+            layers[0]
+                .channels
+                .iter()
+                .all(|c| c.sample_type == ExrSampleType::F16)
+        );
+
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(
+            output.pixels().descriptor().channel_type(),
+            zenpixels::ChannelType::F16
+        );
+        // Every value in the gradient is exactly representable in f16.
+        let expected: Vec<f32> = img
+            .buf()
+            .iter()
+            .flat_map(|p| [p.r, p.g, p.b, p.a])
+            .collect();
+        assert_eq!(decoded_f32(&data), expected);
+    }
+
+    #[test]
+    fn decode_blocks_covers_the_layer_once() {
+        let img = gradient(33, 50);
+        let data = encode(
+            ExrEncodeConfig::new().with_compression(ExrCompression::None),
+            &img,
+        );
+        let mut covered = alloc::vec![0u8; 33 * 50];
+        decode_blocks(&data, &ExrDecodeConfig::new(), None, None, |block| {
+            assert_eq!(block.channels.len(), 4);
+            let green = &block.channels[2];
+            assert_eq!(green.name, "G");
+            for row in 0..block.height {
+                for col in 0..block.width {
+                    let (x, y) = (block.x + col, block.y + row);
+                    covered[(y * 33 + x) as usize] += 1;
+                    let sample = green.samples[(row * block.width + col) as usize];
+                    let alpha = if x == 0 { 0.5 } else { 1.0 };
+                    assert_eq!(sample, y as f32 * 2.0 * alpha);
+                }
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn sink_errors_and_missing_layers_surface() {
+        let data = encode(ExrEncodeConfig::new(), &gradient(8, 8));
+        let result = decode_blocks(&data, &ExrDecodeConfig::new(), None, None, |_| {
+            Err(whereat::at!(crate::CodecError::Cancelled))
+        });
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(crate::CodecError::Cancelled)
+        ));
+
+        let missing = ExrDecodeConfig::new().with_layer("specular");
+        let result = decode_blocks(&data, &missing, None, None, |_| Ok(()));
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(crate::CodecError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_data_window_is_rejected() {
+        // Grow the data window of a small file to 65536 × 65536: 64 GiB of
+        // RGBA f32, with a chunk table far larger than the file.
+        let mut data = encode(ExrEncodeConfig::new(), &gradient(8, 8));
+        let at = data
+            .windows(17)
+            .position(|w| w == b"dataWindow\0box2i\0")
+            .unwrap()
+            + 21;
+        data[at + 8..at + 16].copy_from_slice(&[0xff, 0xff, 0, 0, 0xff, 0xff, 0, 0]);
+        let result = DecodeRequest::new(&data).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(crate::CodecError::InvalidInput(_))
+        ));
+    }
+}
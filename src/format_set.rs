@@ -7,15 +7,19 @@ use crate::ImageFormat;
 /// Used by [`CodecPolicy`](crate::CodecPolicy) to restrict which output formats
 /// are candidates for auto-selection, and internally by the registry to track
 /// which formats are compiled in.
+///
+/// RAW camera files (DNG and vendor RAW, both `ImageFormat::Custom`) share
+/// one bit; OpenEXR, ICO/CUR, PSD, DDS, KTX2 and JPEG 2000 have their own. Other custom formats
+/// can't be represented and are never contained.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct FormatSet(u16);
+pub struct FormatSet(u64);
 
 impl FormatSet {
     /// Empty set — no formats.
     pub const EMPTY: Self = FormatSet(0);
 
     /// Map a format to its bit position.
-    pub(crate) const fn bit(format: ImageFormat) -> Option<u16> {
+    pub(crate) const fn bit(format: ImageFormat) -> Option<u64> {
         match format {
             ImageFormat::Jpeg => Some(1 << 0),
             ImageFormat::WebP => Some(1 << 1),
@@ -28,11 +32,18 @@ impl FormatSet {
             ImageFormat::Bmp => Some(1 << 8),
             ImageFormat::Farbfeld => Some(1 << 9),
             ImageFormat::Tiff => Some(1 << 10),
+            ImageFormat::Qoi => Some(1 << 11),
+            ImageFormat::Tga => Some(1 << 12),
+            ImageFormat::Hdr => Some(1 << 13),
+            ImageFormat::Custom(def) => match custom_index(def.name) {
+                Some(i) => Some(custom_bit(i)),
+                None => None,
+            },
             _ => None,
         }
     }
 
-    const ALL_FORMATS: [ImageFormat; 11] = [
+    const ALL_FORMATS: [ImageFormat; 14] = [
         ImageFormat::Jpeg,
         ImageFormat::WebP,
         ImageFormat::Gif,
@@ -44,13 +55,20 @@ impl FormatSet {
         ImageFormat::Bmp,
         ImageFormat::Farbfeld,
         ImageFormat::Tiff,
+        ImageFormat::Qoi,
+        ImageFormat::Tga,
+        ImageFormat::Hdr,
     ];
 
-    /// All known formats.
+    /// All built-in formats, plus the custom formats (RAW/DNG, OpenEXR,
+    /// ICO, PSD, DDS, KTX2, JPEG 2000) whose features are compiled in.
     pub fn all() -> Self {
-        let mut bits = 0u16;
-        // All 11 formats: bits 0-10
-        bits |= (1 << 11) - 1;
+        let mut bits = (1 << Self::ALL_FORMATS.len()) - 1;
+        for (i, custom) in CUSTOM_FORMATS.iter().enumerate() {
+            if custom.format.is_some() {
+                bits |= custom_bit(i);
+            }
+        }
         FormatSet(bits)
     }
 
@@ -122,11 +140,22 @@ impl FormatSet {
     }
 
     /// Iterate over formats in the set.
+    ///
+    /// RAW/DNG is yielded as the DNG format, and only with the `raw-decode`
+    /// feature (the format definition lives in zenraw). OpenEXR, ICO, PSD,
+    /// DDS, KTX2 and JPEG 2000 are yielded only with their `exr`, `ico`,
+    /// `psd`, `dds`, `ktx2` and `jp2-decode` features.
     pub fn iter(&self) -> impl Iterator<Item = ImageFormat> + use<> {
         let bits = self.0;
+        let custom = CUSTOM_FORMATS
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| (bits & custom_bit(i)) != 0)
+            .filter_map(|(_, custom)| custom.format.map(|format| format()));
         Self::ALL_FORMATS
             .into_iter()
             .filter(move |&f| Self::bit(f).is_some_and(|b| (bits & b) != 0))
+            .chain(custom)
     }
 
     /// Intersection of two sets.
@@ -140,6 +169,94 @@ impl FormatSet {
     }
 }
 
+/// A custom format with its own bit, after the built-in formats.
+struct CustomFormat {
+    /// `ImageFormat::Custom` names sharing the bit.
+    names: &'static [&'static str],
+    /// Definition yielded by [`FormatSet::iter`]; `None` when the feature
+    /// providing it is off.
+    format: Option<fn() -> ImageFormat>,
+}
+
+/// `Some(format)` when `feature` is enabled, else `None`.
+macro_rules! compiled {
+    ($feature:literal, $format:path) => {{
+        #[cfg(feature = $feature)]
+        let format: Option<fn() -> ImageFormat> = Some($format);
+        #[cfg(not(feature = $feature))]
+        let format: Option<fn() -> ImageFormat> = None;
+        format
+    }};
+}
+
+/// Custom formats in bit order. RAW camera files are yielded as DNG.
+const CUSTOM_FORMATS: [CustomFormat; 7] = [
+    CustomFormat {
+        names: &["dng", "raw"],
+        format: compiled!("raw-decode", crate::codecs::raw::dng_format),
+    },
+    CustomFormat {
+        names: &["exr"],
+        format: compiled!("exr", crate::exr::format),
+    },
+    CustomFormat {
+        names: &["ico"],
+        format: compiled!("ico", crate::ico::format),
+    },
+    CustomFormat {
+        names: &["psd"],
+        format: compiled!("psd", crate::psd::format),
+    },
+    CustomFormat {
+        names: &["dds"],
+        format: compiled!("dds", crate::dds::format),
+    },
+    CustomFormat {
+        names: &["ktx2"],
+        format: compiled!("ktx2", crate::ktx2::format),
+    },
+    CustomFormat {
+        names: &["jp2"],
+        format: compiled!("jp2-decode", crate::jp2::format),
+    },
+];
+
+/// Bit of the custom format at `index` in [`CUSTOM_FORMATS`].
+const fn custom_bit(index: usize) -> u64 {
+    1 << (FormatSet::ALL_FORMATS.len() + index)
+}
+
+/// Index in [`CUSTOM_FORMATS`] of the entry named `name`.
+const fn custom_index(name: &str) -> Option<usize> {
+    let mut i = 0;
+    while i < CUSTOM_FORMATS.len() {
+        let names = CUSTOM_FORMATS[i].names;
+        let mut j = 0;
+        while j < names.len() {
+            if bytes_eq(names[j].as_bytes(), name.as_bytes()) {
+                return Some(i);
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
 impl Default for FormatSet {
     /// Default is all formats.
     fn default() -> Self {
@@ -163,10 +280,59 @@ mod tests {
     fn all_set() {
         let set = FormatSet::all();
         assert!(!set.is_empty());
-        assert_eq!(set.len(), 11);
+        assert_eq!(set.len(), set.iter().count());
         assert!(set.contains(ImageFormat::Jpeg));
         assert!(set.contains(ImageFormat::Farbfeld));
         assert!(set.contains(ImageFormat::Tiff));
+        assert!(set.contains(ImageFormat::Qoi));
+        assert!(set.contains(ImageFormat::Tga));
+        assert!(set.contains(ImageFormat::Hdr));
+    }
+
+    #[test]
+    fn iter_covers_every_builtin_bit() {
+        let formats: alloc::vec::Vec<_> = FormatSet::all().iter().collect();
+        let optional = [
+            cfg!(feature = "raw-decode"),
+            cfg!(feature = "exr"),
+            cfg!(feature = "ico"),
+            cfg!(feature = "psd"),
+            cfg!(feature = "dds"),
+            cfg!(feature = "ktx2"),
+            cfg!(feature = "jp2-decode"),
+        ]
+        .into_iter()
+        .filter(|&enabled| enabled)
+        .count();
+        assert_eq!(formats.len(), 14 + optional);
+        assert_eq!(
+            formats[11..14],
+            [ImageFormat::Qoi, ImageFormat::Tga, ImageFormat::Hdr]
+        );
+    }
+
+    #[cfg(feature = "raw-decode")]
+    #[test]
+    fn raw_and_dng_share_a_bit() {
+        let set = FormatSet::EMPTY.with(crate::codecs::raw::raw_format());
+        assert!(set.contains(crate::codecs::raw::dng_format()));
+        assert_eq!(set.len(), 1);
+        assert_eq!(
+            set.without(crate::codecs::raw::dng_format()),
+            FormatSet::EMPTY
+        );
+    }
+
+    #[cfg(feature = "exr")]
+    #[test]
+    fn exr_has_its_own_bit() {
+        let set = FormatSet::EMPTY.with(crate::exr::format());
+        assert_eq!(set.len(), 1);
+        assert!(!set.contains(ImageFormat::Hdr));
+        assert_eq!(
+            set.iter().collect::<alloc::vec::Vec<_>>(),
+            [crate::exr::format()]
+        );
     }
 
     #[test]
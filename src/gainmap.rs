@@ -131,12 +131,27 @@ pub struct DecodedGainMap {
     pub source_format: ImageFormat,
 }
 
+/// Pixel encoding of a display-adapted HDR rendition.
+///
+/// See [`DecodeRequest::with_display_headroom`](crate::DecodeRequest::with_display_headroom).
+#[cfg(feature = "jpeg-ultrahdr")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HdrOutput {
+    /// Linear-light BT.709 f32, 1.0 at SDR white (203 cd/m²). Values above
+    /// 1.0 are highlights.
+    #[default]
+    Linear,
+    /// SMPTE ST 2084 (PQ) code values in BT.2020, as f32 in 0-1.
+    Pq,
+}
+
 /// Source of gain map data for encoding.
 ///
 /// When encoding an image with a gain map, you can either provide a
 /// pre-computed gain map (for passthrough/transcode) or have the encoder
 /// compute one from HDR source pixels.
 #[cfg(feature = "jpeg-ultrahdr")]
+#[derive(Clone, Copy)]
 pub enum GainMapSource<'a> {
     /// Pre-computed gain map (for passthrough/transcode).
     ///
@@ -168,6 +183,439 @@ impl DecodedGainMap {
             self.gain_map.channels,
         )
     }
+
+    /// Resample the gain map for a base image resized from
+    /// `base_width`×`base_height` to `target_width`×`target_height`, keeping
+    /// the gain map's ratio to the base (bilinear).
+    ///
+    /// Pair with the same resize of the base image before passing both to
+    /// [`GainMapSource::Precomputed`].
+    #[must_use]
+    pub fn resize(
+        &self,
+        base_width: u32,
+        base_height: u32,
+        target_width: u32,
+        target_height: u32,
+    ) -> Self {
+        let gm = &self.gain_map;
+        let scaled = |len: u32, base: u32, target: u32| {
+            if base == 0 {
+                return 0;
+            }
+            ((u64::from(len) * u64::from(target) + u64::from(base) / 2) / u64::from(base)).max(1)
+                as u32
+        };
+        let (width, height) = if target_width == 0 || target_height == 0 {
+            (0, 0)
+        } else {
+            (
+                scaled(gm.width, base_width, target_width),
+                scaled(gm.height, base_height, target_height),
+            )
+        };
+        self.with_gain_map_data(resize_bilinear(gm, width, height), width, height)
+    }
+
+    /// Crop the gain map to match a crop of the base image.
+    ///
+    /// `x`, `y`, `width`, `height` are in base image pixels; the base is
+    /// `base_width`×`base_height`. The gain map region covers the crop
+    /// rounded outwards to whole gain map pixels.
+    ///
+    /// # Errors
+    ///
+    /// [`CodecError::InvalidInput`](crate::CodecError::InvalidInput) if the
+    /// crop is empty or extends past the base image.
+    pub fn crop(
+        &self,
+        base_width: u32,
+        base_height: u32,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> crate::error::Result<Self> {
+        let fits = |start: u32, len: u32, base: u32| {
+            len > 0 && start.checked_add(len).is_some_and(|end| end <= base)
+        };
+        if !fits(x, width, base_width) || !fits(y, height, base_height) {
+            return Err(whereat::at!(crate::CodecError::InvalidInput(
+                alloc::format!(
+                    "crop {width}x{height}+{x}+{y} is outside the {base_width}x{base_height} base image"
+                )
+            )));
+        }
+        let gm = &self.gain_map;
+        if gm.width == 0 || gm.height == 0 {
+            return Ok(self.clone());
+        }
+        // Base-pixel span → gain-map-pixel span, rounding outwards.
+        let span = |start: u32, len: u32, base: u32, gm_len: u32| {
+            let (start, end, base, gm_len) = (
+                u64::from(start),
+                u64::from(start + len),
+                u64::from(base),
+                u64::from(gm_len),
+            );
+            let lo = start * gm_len / base;
+            let hi = (end * gm_len).div_ceil(base).clamp(lo + 1, gm_len);
+            (lo as usize, hi as usize)
+        };
+        let (x0, x1) = span(x, width, base_width, gm.width);
+        let (y0, y1) = span(y, height, base_height, gm.height);
+
+        let channels = usize::from(gm.channels.max(1));
+        let row_bytes = gm.width as usize * channels;
+        let mut data = alloc::vec::Vec::with_capacity((x1 - x0) * (y1 - y0) * channels);
+        for row in gm.data.chunks_exact(row_bytes).skip(y0).take(y1 - y0) {
+            data.extend_from_slice(&row[x0 * channels..x1 * channels]);
+        }
+        Ok(self.with_gain_map_data(data, (x1 - x0) as u32, (y1 - y0) as u32))
+    }
+
+    /// Rotate/flip the gain map the same way
+    /// [`orient_pixels`](crate::orient::orient_pixels) orients the base.
+    ///
+    /// Use this with [`DecodeRequest::with_auto_orient`](crate::DecodeRequest::with_auto_orient)
+    /// or when orienting the base yourself, since gain maps are decoded in
+    /// stored order.
+    #[must_use]
+    pub fn orient(&self, orientation: zencodec::Orientation) -> Self {
+        let gm = &self.gain_map;
+        let channels = usize::from(gm.channels.max(1));
+        let (data, width, height) = crate::orient::orient_bytes(
+            &gm.data,
+            gm.width,
+            gm.height,
+            gm.width as usize * channels,
+            channels,
+            orientation.to_exif(),
+        );
+        self.with_gain_map_data(data, width, height)
+    }
+
+    /// A copy with the gain map pixels replaced.
+    fn with_gain_map_data(&self, data: alloc::vec::Vec<u8>, width: u32, height: u32) -> Self {
+        let mut out = self.clone();
+        out.gain_map.data = data;
+        out.gain_map.width = width;
+        out.gain_map.height = height;
+        out
+    }
+}
+
+/// Bilinear resample of gain map bytes to `width`×`height`, one channel
+/// at a time through the depth map resampler.
+#[cfg(feature = "jpeg-ultrahdr")]
+fn resize_bilinear(gm: &GainMap, width: u32, height: u32) -> alloc::vec::Vec<u8> {
+    let channels = usize::from(gm.channels.max(1));
+    if (gm.width, gm.height) == (width, height) {
+        return gm.data.clone();
+    }
+    if gm.width == 0 || gm.height == 0 || width == 0 || height == 0 {
+        return alloc::vec::Vec::new();
+    }
+    let mut out = alloc::vec![0u8; width as usize * height as usize * channels];
+    for c in 0..channels {
+        let mut plane: alloc::vec::Vec<f32> = gm
+            .data
+            .iter()
+            .skip(c)
+            .step_by(channels)
+            .map(|&v| f32::from(v))
+            .collect();
+        // A short buffer reads as zeros, like a short depth map.
+        plane.resize(gm.width as usize * gm.height as usize, 0.0);
+        let resized =
+            crate::depthmap::resize_bilinear_f32(&plane, gm.width, gm.height, width, height);
+        for (o, v) in out.iter_mut().skip(c).step_by(channels).zip(resized) {
+            *o = (v + 0.5).clamp(0.0, 255.0) as u8;
+        }
+    }
+    out
+}
+
+#[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+impl DecodedGainMap {
+    /// ISO 21496-1 weight of the gain map for a display with `headroom`
+    /// (log2 of display peak over SDR white): 0 renders the base image,
+    /// 1 the alternate.
+    pub fn weight_for_headroom(&self, headroom: f64) -> f32 {
+        let base = self.metadata.base_hdr_headroom;
+        let span = self.metadata.alternate_hdr_headroom - base;
+        if span.abs() < f64::EPSILON {
+            return 1.0;
+        }
+        ((headroom - base) / span).clamp(0.0, 1.0) as f32
+    }
+
+    /// Apply the gain map at `weight` to linear base pixels in place.
+    ///
+    /// `pixels` is `width`×`height` linear RGB in the base rendition's
+    /// primaries, 1.0 at SDR white. The gain map is sampled nearest-neighbor
+    /// when it is smaller than the base.
+    ///
+    /// Fails with [`CodecError::InvalidInput`](crate::CodecError::InvalidInput)
+    /// when the gain map's data is shorter than its dimensions and channel
+    /// count require.
+    pub fn apply_linear(
+        &self,
+        pixels: &mut [[f32; 3]],
+        width: usize,
+        height: usize,
+        weight: f32,
+    ) -> crate::error::Result<()> {
+        let meta = &self.metadata;
+        let gm = &self.gain_map;
+        let (gm_w, gm_h) = (gm.width as usize, gm.height as usize);
+        if gm_w == 0 || gm_h == 0 || width == 0 {
+            return Ok(());
+        }
+        let channels = usize::from(gm.channels.max(1));
+        let needed = gm_w
+            .checked_mul(gm_h)
+            .and_then(|n| n.checked_mul(channels))
+            .filter(|&n| n <= gm.data.len());
+        if needed.is_none() {
+            return Err(whereat::at!(crate::CodecError::InvalidInput(
+                alloc::format!(
+                    "gain map data is {} bytes, too short for {gm_w}x{gm_h}x{channels}",
+                    gm.data.len()
+                )
+            )));
+        }
+        for (i, px) in pixels.iter_mut().enumerate().take(width * height) {
+            let (x, y) = (i % width, i / width);
+            let at = ((y * gm_h / height) * gm_w + x * gm_w / width) * channels;
+            for (c, v) in px.iter_mut().enumerate() {
+                let g = f32::from(gm.data[at + if channels == 3 { c } else { 0 }]) / 255.0;
+                let recovery = g.powf(1.0 / meta.gamma[c] as f32);
+                let log_boost = meta.gain_map_min[c] as f32 * (1.0 - recovery)
+                    + meta.gain_map_max[c] as f32 * recovery;
+                *v = (*v + meta.base_offset[c] as f32) * (log_boost * weight).exp2()
+                    - meta.alternate_offset[c] as f32;
+            }
+        }
+        Ok(())
+    }
+
+    /// Render `base` for a display with `headroom` (log2 of display peak over
+    /// SDR white).
+    ///
+    /// `base` is the image the gain map was decoded with and `transfer` its
+    /// transfer function. The gain map is weighted per ISO 21496-1: at or
+    /// below the lower of the two renditions' headrooms the SDR rendition
+    /// comes back, at or above the higher one the full HDR rendition, and
+    /// in between a log-linear blend.
+    pub fn render_for_display(
+        &self,
+        base: zenpixels::PixelSlice<'_>,
+        transfer: zenpixels::TransferFunction,
+        headroom: f32,
+        output: HdrOutput,
+    ) -> crate::error::Result<zenpixels::PixelBuffer> {
+        let (width, height) = (base.width(), base.rows());
+        let (mut rgb, alpha) = crate::tonemap::linear_pixels(&base, transfer)?;
+        let weight = self.weight_for_headroom(f64::from(headroom));
+        self.apply_linear(&mut rgb, width as usize, height as usize, weight)?;
+        crate::tonemap::hdr_from_linear(rgb, alpha.as_deref(), width, height, output)
+    }
+
+    /// The same mapping expressed from the other rendition: the alternate
+    /// image becomes the base.
+    ///
+    /// Needed when a target container expects the other direction, e.g.
+    /// an UltraHDR JPEG (SDR base) transcoded to JXL (HDR base). Gains are
+    /// negated, offsets and headrooms swap, and each gain map sample is
+    /// re-encoded so that the recovered log gain is exactly negated.
+    pub fn inverted(&self) -> Self {
+        let m = &self.metadata;
+        let mut metadata = m.clone();
+        metadata.gain_map_min = m.gain_map_max.map(|v| -v);
+        metadata.gain_map_max = m.gain_map_min.map(|v| -v);
+        metadata.base_offset = m.alternate_offset;
+        metadata.alternate_offset = m.base_offset;
+        metadata.base_hdr_headroom = m.alternate_hdr_headroom;
+        metadata.alternate_hdr_headroom = m.base_hdr_headroom;
+
+        let channels = usize::from(self.gain_map.channels.max(1));
+        let mut gain_map = self.gain_map.clone();
+        for (i, g) in gain_map.data.iter_mut().enumerate() {
+            let gamma = m.gamma[if channels == 3 { i % 3 } else { 0 }] as f32;
+            let recovery = (f32::from(*g) / 255.0).powf(1.0 / gamma);
+            *g = ((1.0 - recovery).powf(gamma) * 255.0 + 0.5) as u8;
+        }
+
+        Self {
+            gain_map,
+            metadata,
+            base_is_hdr: !self.base_is_hdr,
+            source_format: self.source_format,
+        }
+    }
+}
+
+// =========================================================================
+// Gain map computation from an HDR + SDR pair
+// =========================================================================
+
+/// Options for [`compute_gain_map`].
+#[cfg(feature = "jpeg-ultrahdr")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GainMapComputeOptions {
+    scale: u8,
+    channels: u8,
+    gamma: f32,
+}
+
+#[cfg(feature = "jpeg-ultrahdr")]
+impl Default for GainMapComputeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "jpeg-ultrahdr")]
+impl GainMapComputeOptions {
+    /// Quarter-resolution, single-channel map with gamma 1.0 — the
+    /// UltraHDR defaults.
+    pub fn new() -> Self {
+        Self {
+            scale: 4,
+            channels: 1,
+            gamma: 1.0,
+        }
+    }
+
+    /// Downscale factor from the base image to the gain map (1 = full
+    /// resolution). Default: 4.
+    pub fn with_scale(mut self, scale: u8) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// 1 for a luminance-only map, 3 for per-channel gains (preserves
+    /// color grading differences between the renditions). Default: 1.
+    pub fn with_channels(mut self, channels: u8) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Gamma applied to the encoded gain values; values above 1.0 spend
+    /// more code values on small gains. Default: 1.0.
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+}
+
+/// Compute an ISO 21496-1 gain map from a graded SDR rendition and an HDR
+/// rendition of the same image.
+///
+/// `hdr` is linear-light BT.709 RGB with 1.0 at SDR white; `sdr` is 8-bit
+/// sRGB. Both must have the same dimensions and be pixel-aligned. The
+/// result maps `sdr` (the base) to `hdr`, and can be embedded with
+/// [`GainMapSource::Precomputed`] when encoding `sdr` to JPEG, AVIF or JXL.
+///
+/// Use this when both renditions were graded by hand;
+/// [`EncodeRequest::encode_ultrahdr_rgb_f32`](crate::EncodeRequest::encode_ultrahdr_rgb_f32)
+/// derives the SDR base itself.
+///
+/// # Example
+///
+/// ```no_run
+/// use zencodecs::gainmap::{GainMapComputeOptions, compute_gain_map};
+/// use zencodecs::pixel::{ImgVec, Rgb};
+/// use zencodecs::{EncodeRequest, GainMapSource, ImageFormat};
+///
+/// # fn example(hdr: ImgVec<Rgb<f32>>, sdr: ImgVec<Rgb<u8>>) -> zencodecs::Result<()> {
+/// let (gain_map, metadata) =
+///     compute_gain_map(hdr.as_ref(), sdr.as_ref(), &GainMapComputeOptions::new().with_channels(3))?;
+/// let jpeg = EncodeRequest::new(ImageFormat::Jpeg)
+///     .with_gain_map(GainMapSource::Precomputed {
+///         gain_map: &gain_map,
+///         metadata: &metadata,
+///     })
+///     .encode(zenpixels::PixelSlice::from(sdr.as_ref()).erase(), false)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// [`CodecError::InvalidInput`](crate::CodecError::InvalidInput) if the
+/// images differ in size or the options are out of range (scale 0,
+/// channels other than 1 or 3, non-positive gamma).
+#[cfg(feature = "jpeg-ultrahdr")]
+pub fn compute_gain_map(
+    hdr: crate::pixel::ImgRef<'_, crate::pixel::Rgb<f32>>,
+    sdr: crate::pixel::ImgRef<'_, crate::pixel::Rgb<u8>>,
+    options: &GainMapComputeOptions,
+) -> crate::error::Result<(GainMap, GainMapMetadata)> {
+    use alloc::vec::Vec;
+    use whereat::at;
+    use zenjpeg::ultrahdr::{
+        GainMapConfig, UhdrColorGamut, UhdrColorTransfer, UhdrPixelFormat, UhdrRawImage,
+        Unstoppable, compute_gainmap,
+    };
+
+    if hdr.width() != sdr.width() || hdr.height() != sdr.height() {
+        return Err(at!(crate::CodecError::InvalidInput(alloc::format!(
+            "gain map: HDR is {}x{} but SDR is {}x{}",
+            hdr.width(),
+            hdr.height(),
+            sdr.width(),
+            sdr.height()
+        ))));
+    }
+    if options.scale == 0
+        || !matches!(options.channels, 1 | 3)
+        || options.gamma.is_nan()
+        || options.gamma <= 0.0
+    {
+        return Err(at!(crate::CodecError::InvalidInput(alloc::format!(
+            "gain map: invalid options {options:?}"
+        ))));
+    }
+    let width = hdr.width() as u32;
+    let height = hdr.height() as u32;
+    let to_codec = |e| at!(crate::CodecError::from_codec(ImageFormat::Jpeg, e));
+
+    let mut hdr_bytes = Vec::with_capacity(hdr.width() * hdr.height() * 16);
+    for px in hdr.pixels() {
+        for v in [px.r, px.g, px.b, 1.0] {
+            hdr_bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    let hdr_image = UhdrRawImage::from_data(
+        width,
+        height,
+        UhdrPixelFormat::Rgba32F,
+        UhdrColorGamut::Bt709,
+        UhdrColorTransfer::Linear,
+        hdr_bytes,
+    )
+    .map_err(to_codec)?;
+
+    let sdr_bytes: Vec<u8> = sdr.pixels().flat_map(|px| [px.r, px.g, px.b]).collect();
+    let sdr_image = UhdrRawImage::from_data(
+        width,
+        height,
+        UhdrPixelFormat::Rgb8,
+        UhdrColorGamut::Bt709,
+        UhdrColorTransfer::Srgb,
+        sdr_bytes,
+    )
+    .map_err(to_codec)?;
+
+    let config = GainMapConfig {
+        scale_factor: options.scale,
+        gamma: options.gamma,
+        multi_channel: options.channels == 3,
+        ..GainMapConfig::default()
+    };
+    compute_gainmap(&hdr_image, &sdr_image, &config, Unstoppable).map_err(to_codec)
 }
 
 // =========================================================================
@@ -285,6 +733,206 @@ mod tests {
     #[allow(unused_imports)]
     use super::*;
 
+    #[cfg(feature = "jpeg-ultrahdr")]
+    fn test_gain_map(data: alloc::vec::Vec<u8>, width: u32, height: u32) -> DecodedGainMap {
+        DecodedGainMap {
+            gain_map: GainMap {
+                data,
+                width,
+                height,
+                channels: 1,
+            },
+            metadata: GainMapMetadata::new(),
+            base_is_hdr: false,
+            source_format: ImageFormat::Jpeg,
+        }
+    }
+
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn resize_keeps_ratio_to_base() {
+        // Quarter-resolution map of a 400×200 base.
+        let gm = test_gain_map(alloc::vec![7; 100 * 50], 100, 50);
+        let resized = gm.resize(400, 200, 200, 100);
+        assert_eq!((resized.gain_map.width, resized.gain_map.height), (50, 25));
+        assert_eq!(resized.gain_map.data.len(), 50 * 25);
+        assert!(resized.gain_map.data.iter().all(|&v| v == 7));
+        // Never collapses to nothing for a non-empty target.
+        let tiny = gm.resize(400, 200, 1, 1);
+        assert_eq!((tiny.gain_map.width, tiny.gain_map.height), (1, 1));
+    }
+
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn resize_interpolates() {
+        let gm = test_gain_map(alloc::vec![0, 255], 2, 1);
+        let resized = gm.resize(2, 1, 4, 1);
+        assert_eq!(resized.gain_map.data, alloc::vec![0, 64, 191, 255]);
+
+        let mut rgb = test_gain_map(alloc::vec![0, 255, 100, 255, 0, 100], 2, 1);
+        rgb.gain_map.channels = 3;
+        let resized = rgb.resize(2, 1, 4, 1);
+        assert_eq!(
+            resized.gain_map.data,
+            alloc::vec![0, 255, 100, 64, 191, 100, 191, 64, 100, 255, 0, 100]
+        );
+    }
+
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn crop_maps_base_rect_to_gain_map() {
+        // 4×2 map at half resolution of an 8×4 base.
+        let gm = test_gain_map(alloc::vec![0, 1, 2, 3, 4, 5, 6, 7], 4, 2);
+        let cropped = gm.crop(8, 4, 2, 0, 4, 2).unwrap();
+        assert_eq!((cropped.gain_map.width, cropped.gain_map.height), (2, 1));
+        assert_eq!(cropped.gain_map.data, alloc::vec![1, 2]);
+        // Odd edges round outwards.
+        let cropped = gm.crop(8, 4, 1, 1, 2, 2).unwrap();
+        assert_eq!((cropped.gain_map.width, cropped.gain_map.height), (2, 2));
+        assert_eq!(cropped.gain_map.data, alloc::vec![0, 1, 4, 5]);
+        assert!(gm.crop(8, 4, 6, 0, 4, 2).is_err());
+        assert!(gm.crop(8, 4, 0, 0, 0, 2).is_err());
+    }
+
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn orient_rotates_gain_map() {
+        let gm = test_gain_map(alloc::vec![1, 2, 3, 4, 5, 6], 3, 2);
+        let rotated = gm.orient(zencodec::Orientation::from_exif(6).unwrap());
+        assert_eq!((rotated.gain_map.width, rotated.gain_map.height), (2, 3));
+        assert_eq!(rotated.gain_map.data, alloc::vec![4, 1, 5, 2, 6, 3]);
+    }
+
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    #[test]
+    fn inverted_gain_map_undoes_the_original() {
+        let mut metadata = GainMapMetadata::new();
+        metadata.gain_map_min = [0.0; 3];
+        metadata.gain_map_max = [2.0; 3];
+        metadata.gamma = [1.0; 3];
+        metadata.base_offset = [1.0 / 64.0; 3];
+        metadata.alternate_offset = [1.0 / 64.0; 3];
+        metadata.base_hdr_headroom = 0.0;
+        metadata.alternate_hdr_headroom = 2.0;
+        let forward = DecodedGainMap {
+            gain_map: GainMap {
+                data: alloc::vec![0, 85, 170, 255],
+                width: 2,
+                height: 2,
+                channels: 1,
+            },
+            metadata,
+            base_is_hdr: false,
+            source_format: ImageFormat::Jpeg,
+        };
+        let inverse = forward.inverted();
+        assert!(inverse.base_is_hdr);
+        assert_eq!(inverse.metadata.base_hdr_headroom, 2.0);
+        assert_eq!(inverse.gain_map.data, alloc::vec![255, 170, 85, 0]);
+
+        let original = [[0.25f32, 0.5, 0.75]; 4];
+        let mut pixels = original;
+        forward
+            .apply_linear(&mut pixels, 2, 2, forward.weight_for_headroom(2.0))
+            .unwrap();
+        inverse
+            .apply_linear(&mut pixels, 2, 2, inverse.weight_for_headroom(0.0))
+            .unwrap();
+        for (px, orig) in pixels.iter().zip(original) {
+            for c in 0..3 {
+                assert!((px[c] - orig[c]).abs() < 1e-4, "{px:?} vs {orig:?}");
+            }
+        }
+    }
+
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    #[test]
+    fn short_gain_map_data_is_rejected() {
+        let mut gm = test_gain_map(alloc::vec![0; 6], 3, 2);
+        gm.gain_map.channels = 3;
+        let mut pixels = [[0.5f32; 3]; 4];
+        let result = gm.apply_linear(&mut pixels, 2, 2, 1.0);
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(crate::CodecError::InvalidInput(_))
+        ));
+        assert_eq!(pixels, [[0.5f32; 3]; 4]);
+    }
+
+    #[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+    #[test]
+    fn weight_for_headroom_interpolates() {
+        let mut metadata = GainMapMetadata::new();
+        metadata.base_hdr_headroom = 0.0;
+        metadata.alternate_hdr_headroom = 2.0;
+        let gm = DecodedGainMap {
+            gain_map: GainMap {
+                data: alloc::vec![255],
+                width: 1,
+                height: 1,
+                channels: 1,
+            },
+            metadata,
+            base_is_hdr: false,
+            source_format: ImageFormat::Jpeg,
+        };
+        assert_eq!(gm.weight_for_headroom(0.0), 0.0);
+        assert_eq!(gm.weight_for_headroom(1.0), 0.5);
+        assert_eq!(gm.weight_for_headroom(3.0), 1.0);
+    }
+
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn compute_gain_map_rejects_mismatched_sizes() {
+        use crate::pixel::{ImgVec, Rgb};
+
+        let hdr = ImgVec::new(alloc::vec![Rgb { r: 2.0f32, g: 2.0, b: 2.0 }; 16], 4, 4);
+        let sdr = ImgVec::new(alloc::vec![Rgb { r: 188u8, g: 188, b: 188 }; 8], 4, 2);
+        let result = compute_gain_map(hdr.as_ref(), sdr.as_ref(), &GainMapComputeOptions::new());
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(crate::CodecError::InvalidInput(_))
+        ));
+
+        let sdr = ImgVec::new(alloc::vec![Rgb { r: 188u8, g: 188, b: 188 }; 16], 4, 4);
+        let bad = GainMapComputeOptions::new().with_channels(2);
+        assert!(compute_gain_map(hdr.as_ref(), sdr.as_ref(), &bad).is_err());
+    }
+
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn compute_gain_map_uniform_boost() {
+        use crate::pixel::{ImgVec, Rgb};
+
+        // sRGB 188 ≈ 0.5 linear; the HDR rendition is two stops brighter.
+        let hdr = ImgVec::new(alloc::vec![Rgb { r: 2.0f32, g: 2.0, b: 2.0 }; 64], 8, 8);
+        let sdr = ImgVec::new(alloc::vec![Rgb { r: 188u8, g: 188, b: 188 }; 64], 8, 8);
+        let options = GainMapComputeOptions::new().with_scale(2);
+        let (gain_map, metadata) = compute_gain_map(hdr.as_ref(), sdr.as_ref(), &options).unwrap();
+        assert_eq!((gain_map.width, gain_map.height), (4, 4));
+        assert_eq!(gain_map.channels, 1);
+        assert!(
+            metadata.gain_map_max[0] > 1.5,
+            "{:?}",
+            metadata.gain_map_max
+        );
+    }
+
+    /// SDR base, two stops of headroom. `GainMapMetadata` is non-exhaustive.
+    #[cfg(feature = "jpeg-ultrahdr")]
+    fn two_stop_metadata() -> GainMapMetadata {
+        let mut meta = GainMapMetadata::default();
+        meta.gain_map_max = [2.0; 3];
+        meta.gain_map_min = [0.0; 3];
+        meta.gamma = [1.0; 3];
+        meta.base_offset = [1.0 / 64.0; 3];
+        meta.alternate_offset = [1.0 / 64.0; 3];
+        meta.base_hdr_headroom = 0.0;
+        meta.alternate_hdr_headroom = 2.0;
+        meta.use_base_color_space = true;
+        meta
+    }
+
     #[cfg(feature = "jpeg-ultrahdr")]
     #[test]
     fn decoded_gainmap_jpeg_sdr_base() {
@@ -295,17 +943,7 @@ mod tests {
                 height: 2,
                 channels: 1,
             },
-            metadata: GainMapMetadata {
-                gain_map_max: [2.0; 3],
-                gain_map_min: [0.0; 3],
-                gamma: [1.0; 3],
-                base_offset: [1.0 / 64.0; 3],
-                alternate_offset: [1.0 / 64.0; 3],
-                base_hdr_headroom: 0.0,
-                alternate_hdr_headroom: 2.0,
-                use_base_color_space: true,
-                ..GainMapMetadata::default()
-            },
+            metadata: two_stop_metadata(),
             base_is_hdr: false,
             source_format: ImageFormat::Jpeg,
         };
@@ -340,17 +978,7 @@ mod tests {
             height: 8,
             channels: 1,
         };
-        let meta = GainMapMetadata {
-            gain_map_max: [2.0; 3],
-            gain_map_min: [0.0; 3],
-            gamma: [1.0; 3],
-            base_offset: [1.0 / 64.0; 3],
-            alternate_offset: [1.0 / 64.0; 3],
-            base_hdr_headroom: 0.0,
-            alternate_hdr_headroom: 2.0,
-            use_base_color_space: true,
-            ..GainMapMetadata::default()
-        };
+        let meta = two_stop_metadata();
         let source = GainMapSource::Precomputed {
             gain_map: &img,
             metadata: &meta,
@@ -368,17 +996,7 @@ mod tests {
     #[cfg(feature = "jpeg-ultrahdr")]
     #[test]
     fn params_metadata_roundtrip() {
-        let meta = GainMapMetadata {
-            gain_map_max: [2.0; 3],
-            gain_map_min: [0.0; 3],
-            gamma: [1.0; 3],
-            base_offset: [1.0 / 64.0; 3],
-            alternate_offset: [1.0 / 64.0; 3],
-            base_hdr_headroom: 0.0,
-            alternate_hdr_headroom: 2.0,
-            use_base_color_space: true,
-            ..GainMapMetadata::default()
-        };
+        let meta = two_stop_metadata();
         let params = metadata_to_params(&meta);
         let meta2 = params_to_metadata(&params);
         for i in 0..3 {
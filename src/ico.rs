@@ -0,0 +1,350 @@
+//! ICO and CUR containers (`ico` feature).
+//!
+//! An ICO (or its cursor variant, CUR) file is a directory of independent
+//! images, each stored either as a PNG or as a headerless BMP with a 1-bit
+//! transparency mask. Both are one custom format,
+//! `ImageFormat::Custom(&ICO_FORMAT)`:
+//!
+//! - [`directory`] lists every entry without decoding it.
+//! - [`DecodeRequest`](crate::DecodeRequest) decodes one entry to RGBA8
+//!   with the PNG/BMP codecs: the largest by default, or the one picked by
+//!   [`IcoDecodeConfig`].
+//! - [`EncodeRequest`](crate::EncodeRequest) downscales the source to every
+//!   size in [`IcoEncodeConfig`] and packs the PNG-compressed results into
+//!   one `.ico`.
+
+use alloc::vec::Vec;
+
+use crate::ImageFormat;
+use crate::error::Result;
+
+/// Format definition for ICO/CUR.
+pub static ICO_FORMAT: zencodec::ImageFormatDefinition = zencodec::ImageFormatDefinition::new(
+    "ico",
+    None,
+    "ICO",
+    "ico",
+    &["ico", "cur"],
+    "image/x-icon",
+    &["image/x-icon", "image/vnd.microsoft.icon"],
+    true,
+    false,
+    true,
+    false,
+    6,
+    crate::codecs::ico::is_ico,
+);
+
+/// The ICO [`ImageFormat`].
+pub fn format() -> ImageFormat {
+    ImageFormat::Custom(&ICO_FORMAT)
+}
+
+/// Whether a file is an icon or a cursor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcoKind {
+    /// `.ico`
+    Icon,
+    /// `.cur`; entries carry a hotspot.
+    Cursor,
+}
+
+/// How an entry's image is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcoPayload {
+    /// A complete PNG file.
+    Png,
+    /// A BMP without file header, followed by an AND transparency mask.
+    Bmp,
+}
+
+/// One image in an ICO/CUR directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IcoEntry {
+    /// Position in the directory.
+    pub index: usize,
+    /// Width in pixels (PNG entries report the PNG's own size).
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Bits per pixel of the stored image.
+    pub bit_depth: u16,
+    /// Storage of the image data.
+    pub payload: IcoPayload,
+    /// Cursor hotspot `(x, y)`; `None` for icons.
+    pub hotspot: Option<(u16, u16)>,
+    /// Offset of the image data from the start of the file.
+    pub offset: u32,
+    /// Size of the image data in bytes.
+    pub byte_len: u32,
+}
+
+/// Parsed ICO/CUR directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IcoDirectory {
+    /// Icon or cursor.
+    pub kind: IcoKind,
+    /// Every entry, in file order.
+    pub entries: Vec<IcoEntry>,
+}
+
+impl IcoDirectory {
+    /// Entry a decode would pick for `config`.
+    pub fn select(&self, config: &IcoDecodeConfig) -> Option<&IcoEntry> {
+        let quality = |e: &&IcoEntry| (u64::from(e.width) * u64::from(e.height), e.bit_depth);
+        match config.selection {
+            Selection::Largest => self.entries.iter().max_by_key(quality),
+            Selection::Index(i) => self.entries.get(i),
+            Selection::BestFit(w, h) => self
+                .entries
+                .iter()
+                .filter(|e| e.width >= w && e.height >= h)
+                .min_by_key(|e| {
+                    (
+                        u64::from(e.width) * u64::from(e.height),
+                        u16::MAX - e.bit_depth,
+                    )
+                })
+                .or_else(|| self.entries.iter().max_by_key(quality)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Selection {
+    #[default]
+    Largest,
+    Index(usize),
+    BestFit(u32, u32),
+}
+
+/// ICO/CUR decode settings: which entry to decode.
+#[derive(Clone, Debug, Default)]
+pub struct IcoDecodeConfig {
+    selection: Selection,
+}
+
+impl IcoDecodeConfig {
+    /// Decode the largest entry, preferring higher bit depth on ties.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the entry at `index` in the directory.
+    pub fn with_entry(mut self, index: usize) -> Self {
+        self.selection = Selection::Index(index);
+        self
+    }
+
+    /// Decode the smallest entry at least `width`×`height`, or the largest
+    /// when none is big enough.
+    pub fn with_best_fit(mut self, width: u32, height: u32) -> Self {
+        self.selection = Selection::BestFit(width, height);
+        self
+    }
+}
+
+/// ICO encode settings.
+#[derive(Clone, Debug)]
+pub struct IcoEncodeConfig {
+    pub(crate) sizes: Vec<u32>,
+}
+
+impl Default for IcoEncodeConfig {
+    fn default() -> Self {
+        Self {
+            sizes: alloc::vec![16, 32, 48, 256],
+        }
+    }
+}
+
+impl IcoEncodeConfig {
+    /// Default sizes: 16, 32, 48 and 256 pixels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Square sizes to generate, each 1–256. Sizes larger than the source
+    /// are skipped unless none fits, in which case the source size is used.
+    pub fn with_sizes(mut self, sizes: &[u32]) -> Self {
+        self.sizes = sizes.iter().map(|&s| s.clamp(1, 256)).collect();
+        self
+    }
+}
+
+/// Parse the ICO/CUR directory.
+pub fn directory(data: &[u8]) -> Result<IcoDirectory> {
+    crate::codecs::ico::directory(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::config::CodecConfig;
+    use crate::{DecodeRequest, EncodeRequest};
+    use zenpixels::{PixelDescriptor, PixelSlice};
+
+    /// 2×2 24-bit BMP cursor with hotspot (1, 0): red, green / blue,
+    /// white, the white pixel masked out.
+    fn bmp_cursor() -> Vec<u8> {
+        let mut dib = Vec::new();
+        for v in [40u32, 2, 4] {
+            dib.extend_from_slice(&v.to_le_bytes());
+        }
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&24u16.to_le_bytes());
+        dib.extend_from_slice(&[0; 24]);
+        // Color rows, bottom-up, BGR padded to 8 bytes.
+        dib.extend_from_slice(&[255, 0, 0, 255, 255, 255, 0, 0]);
+        dib.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+        // AND mask rows, bottom-up, padded to 4 bytes.
+        dib.extend_from_slice(&[0b0100_0000, 0, 0, 0]);
+        dib.extend_from_slice(&[0, 0, 0, 0]);
+
+        let mut file = vec![0, 0, 2, 0, 1, 0, 2, 2, 0, 0];
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&0u16.to_le_bytes());
+        file.extend_from_slice(&(dib.len() as u32).to_le_bytes());
+        file.extend_from_slice(&22u32.to_le_bytes());
+        file.extend_from_slice(&dib);
+        file
+    }
+
+    /// 2×1 32-bit BI_BITFIELDS icon: red, green with the given alpha
+    /// bytes and AND mask row.
+    fn bitfields_icon(alpha: [u8; 2], and_mask: u8) -> Vec<u8> {
+        let mut dib = Vec::new();
+        for v in [40u32, 2, 2] {
+            dib.extend_from_slice(&v.to_le_bytes());
+        }
+        dib.extend_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&32u16.to_le_bytes());
+        dib.extend_from_slice(&3u32.to_le_bytes());
+        dib.extend_from_slice(&[0; 20]);
+        for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF] {
+            dib.extend_from_slice(&mask.to_le_bytes());
+        }
+        dib.extend_from_slice(&[0, 0, 255, alpha[0], 0, 255, 0, alpha[1]]);
+        dib.extend_from_slice(&[and_mask, 0, 0, 0]);
+
+        let mut file = vec![0, 0, 1, 0, 1, 0, 2, 1, 0, 0];
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&32u16.to_le_bytes());
+        file.extend_from_slice(&(dib.len() as u32).to_le_bytes());
+        file.extend_from_slice(&22u32.to_le_bytes());
+        file.extend_from_slice(&dib);
+        file
+    }
+
+    fn rgba(output: &crate::DecodeOutput) -> Vec<u8> {
+        let pixels = output.pixels();
+        let row_bytes = pixels.width() as usize * 4;
+        let bytes = pixels.as_strided_bytes();
+        (0..pixels.rows() as usize)
+            .flat_map(|y| &bytes[y * pixels.stride()..][..row_bytes])
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn bmp_entries_apply_the_and_mask() {
+        let data = bmp_cursor();
+        let dir = directory(&data).unwrap();
+        assert_eq!(dir.kind, IcoKind::Cursor);
+        assert_eq!(dir.entries.len(), 1);
+        let entry = &dir.entries[0];
+        assert_eq!((entry.width, entry.height, entry.bit_depth), (2, 2, 24));
+        assert_eq!(entry.payload, IcoPayload::Bmp);
+        assert_eq!(entry.hotspot, Some((1, 0)));
+
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(output.info().format, format());
+        assert_eq!(
+            rgba(&output),
+            [
+                255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn bitfields_entries_use_alpha_then_the_and_mask() {
+        let decode = |data: Vec<u8>| rgba(&DecodeRequest::new(&data).decode_full_frame().unwrap());
+        assert_eq!(
+            decode(bitfields_icon([128, 0], 0)),
+            [255, 0, 0, 128, 0, 255, 0, 0]
+        );
+        // All-zero alpha falls back to the AND mask.
+        assert_eq!(
+            decode(bitfields_icon([0, 0], 0b0100_0000)),
+            [255, 0, 0, 255, 0, 255, 0, 0]
+        );
+    }
+
+    #[test]
+    fn encodes_every_size_and_decodes_the_best_fit() {
+        // 64×32 opaque red: non-square sources are centered on a
+        // transparent square.
+        let src: Vec<u8> = [255, 0, 0, 255].repeat(64 * 32);
+        let slice = PixelSlice::new(&src, 64, 32, 64 * 4, PixelDescriptor::RGBA8_SRGB).unwrap();
+        let config = CodecConfig::default()
+            .with_ico_encoder(IcoEncodeConfig::new().with_sizes(&[16, 32, 256]));
+        let data = EncodeRequest::new(format())
+            .with_codec_config(&config)
+            .encode(slice, false)
+            .unwrap()
+            .into_vec();
+
+        let dir = directory(&data).unwrap();
+        assert_eq!(dir.kind, IcoKind::Icon);
+        let sizes: Vec<_> = dir.entries.iter().map(|e| (e.width, e.height)).collect();
+        // 256 is larger than the source and skipped.
+        assert_eq!(sizes, [(16, 16), (32, 32)]);
+        assert!(dir.entries.iter().all(|e| e.payload == IcoPayload::Png));
+        assert_eq!(crate::from_bytes(&data).unwrap().width, 32);
+
+        let pick =
+            CodecConfig::default().with_ico_decoder(IcoDecodeConfig::new().with_best_fit(12, 12));
+        let output = DecodeRequest::new(&data)
+            .with_codec_config(&pick)
+            .decode_full_frame()
+            .unwrap();
+        let pixels = rgba(&output);
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (16, 16));
+        // Top rows are padding, the middle is the source.
+        assert_eq!(pixels[3], 0);
+        assert_eq!(pixels[(8 * 16 + 8) * 4..][..4], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn selection_rules() {
+        let entry = |index, size: u32, bit_depth| IcoEntry {
+            index,
+            width: size,
+            height: size,
+            bit_depth,
+            payload: IcoPayload::Png,
+            hotspot: None,
+            offset: 0,
+            byte_len: 0,
+        };
+        let dir = IcoDirectory {
+            kind: IcoKind::Icon,
+            entries: vec![
+                entry(0, 32, 8),
+                entry(1, 48, 32),
+                entry(2, 32, 32),
+                entry(3, 16, 32),
+            ],
+        };
+        let pick = |config: IcoDecodeConfig| dir.select(&config).map(|e| e.index);
+        assert_eq!(pick(IcoDecodeConfig::new()), Some(1));
+        assert_eq!(pick(IcoDecodeConfig::new().with_best_fit(20, 20)), Some(2));
+        assert_eq!(pick(IcoDecodeConfig::new().with_best_fit(64, 64)), Some(1));
+        assert_eq!(pick(IcoDecodeConfig::new().with_entry(3)), Some(3));
+        assert_eq!(pick(IcoDecodeConfig::new().with_entry(9)), None);
+    }
+}
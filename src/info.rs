@@ -18,6 +18,32 @@ pub(crate) fn detect_format(data: &[u8]) -> Option<ImageFormat> {
     if let Some(fmt) = crate::codecs::raw::detect_raw_format(data) {
         return Some(fmt);
     }
+    // OpenEXR, ICO, PSD, DDS and KTX2 are custom formats the common
+    // registry doesn't know.
+    #[cfg(feature = "exr")]
+    if crate::codecs::exr::is_exr(data) {
+        return Some(crate::exr::format());
+    }
+    #[cfg(feature = "ico")]
+    if crate::codecs::ico::is_ico(data) {
+        return Some(crate::ico::format());
+    }
+    #[cfg(feature = "psd")]
+    if crate::codecs::psd::is_psd(data) {
+        return Some(crate::psd::format());
+    }
+    #[cfg(feature = "dds")]
+    if crate::codecs::dds::is_dds(data) {
+        return Some(crate::dds::format());
+    }
+    #[cfg(feature = "ktx2")]
+    if crate::codecs::ktx2::is_ktx2(data) {
+        return Some(crate::ktx2::format());
+    }
+    #[cfg(feature = "jp2-decode")]
+    if crate::codecs::jp2::is_jp2(data) {
+        return Some(crate::jp2::format());
+    }
     // Try common formats (JPEG, PNG, GIF, WebP, TIFF, etc.)
     if let Some(fmt) = zencodec::ImageFormatRegistry::common().detect(data) {
         return Some(fmt);
@@ -180,86 +206,134 @@ fn finalize_gain_map_presence(info: &mut ImageInfo) {
 }
 
 /// Dispatch to the format-specific codec probe (requires codec feature).
+#[cfg_attr(
+    not(any(
+        feature = "jpeg",
+        feature = "webp",
+        feature = "gif",
+        feature = "png",
+        feature = "avif-decode",
+        feature = "jxl-decode",
+        feature = "heic-decode",
+        feature = "bitmaps",
+        feature = "bitmaps-bmp",
+        feature = "bitmaps-qoi",
+        feature = "bitmaps-tga",
+        feature = "bitmaps-hdr",
+        feature = "tiff",
+        feature = "raw-decode",
+        feature = "exr",
+        feature = "ico",
+        feature = "psd",
+        feature = "dds",
+        feature = "ktx2",
+        feature = "jp2-decode",
+    )),
+    allow(unused_variables)
+)]
 fn probe_codec(data: &[u8], format: ImageFormat) -> Result<ImageInfo> {
     let mut info = match format {
         #[cfg(feature = "jpeg")]
-        ImageFormat::Jpeg => crate::codecs::jpeg::probe(data)?,
+        ImageFormat::Jpeg => crate::codecs::jpeg::probe(data),
         #[cfg(not(feature = "jpeg"))]
-        ImageFormat::Jpeg => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Jpeg => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "webp")]
-        ImageFormat::WebP => crate::codecs::webp::probe(data)?,
+        ImageFormat::WebP => crate::codecs::webp::probe(data),
         #[cfg(not(feature = "webp"))]
-        ImageFormat::WebP => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::WebP => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "gif")]
-        ImageFormat::Gif => crate::codecs::gif::probe(data)?,
+        ImageFormat::Gif => crate::codecs::gif::probe(data),
         #[cfg(not(feature = "gif"))]
-        ImageFormat::Gif => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Gif => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "png")]
-        ImageFormat::Png => crate::codecs::png::probe(data)?,
+        ImageFormat::Png => crate::codecs::png::probe(data),
         #[cfg(not(feature = "png"))]
-        ImageFormat::Png => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Png => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "avif-decode")]
-        ImageFormat::Avif => crate::codecs::avif_dec::probe(data)?,
+        ImageFormat::Avif => crate::codecs::avif_dec::probe(data),
         #[cfg(not(feature = "avif-decode"))]
-        ImageFormat::Avif => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Avif => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "jxl-decode")]
-        ImageFormat::Jxl => crate::codecs::jxl_dec::probe(data)?,
+        ImageFormat::Jxl => crate::codecs::jxl_dec::probe(data),
         #[cfg(not(feature = "jxl-decode"))]
-        ImageFormat::Jxl => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Jxl => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "heic-decode")]
-        ImageFormat::Heic => crate::codecs::heic::probe(data)?,
+        ImageFormat::Heic => crate::codecs::heic::probe(data),
         #[cfg(not(feature = "heic-decode"))]
-        ImageFormat::Heic => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Heic => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "bitmaps")]
-        ImageFormat::Pnm => crate::codecs::pnm::probe(data)?,
+        ImageFormat::Pnm => crate::codecs::pnm::probe(data),
         #[cfg(not(feature = "bitmaps"))]
-        ImageFormat::Pnm => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Pnm => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "bitmaps-bmp")]
-        ImageFormat::Bmp => crate::codecs::bmp::probe(data)?,
+        ImageFormat::Bmp => crate::codecs::bmp::probe(data),
         #[cfg(not(feature = "bitmaps-bmp"))]
-        ImageFormat::Bmp => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Bmp => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "bitmaps")]
-        ImageFormat::Farbfeld => crate::codecs::farbfeld::probe(data)?,
+        ImageFormat::Farbfeld => crate::codecs::farbfeld::probe(data),
         #[cfg(not(feature = "bitmaps"))]
-        ImageFormat::Farbfeld => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Farbfeld => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "tiff")]
-        ImageFormat::Tiff => crate::codecs::tiff::probe(data)?,
+        ImageFormat::Tiff => crate::codecs::tiff::probe(data),
         #[cfg(not(feature = "tiff"))]
-        ImageFormat::Tiff => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Tiff => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "bitmaps-qoi")]
-        ImageFormat::Qoi => crate::codecs::qoi::probe(data)?,
+        ImageFormat::Qoi => crate::codecs::qoi::probe(data),
         #[cfg(not(feature = "bitmaps-qoi"))]
-        ImageFormat::Qoi => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Qoi => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "bitmaps-tga")]
-        ImageFormat::Tga => crate::codecs::tga::probe(data)?,
+        ImageFormat::Tga => crate::codecs::tga::probe(data),
         #[cfg(not(feature = "bitmaps-tga"))]
-        ImageFormat::Tga => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Tga => Err(at!(CodecError::UnsupportedFormat(format))),
 
         #[cfg(feature = "bitmaps-hdr")]
-        ImageFormat::Hdr => crate::codecs::hdr::probe(data)?,
+        ImageFormat::Hdr => crate::codecs::hdr::probe(data),
         #[cfg(not(feature = "bitmaps-hdr"))]
-        ImageFormat::Hdr => return Err(at!(CodecError::UnsupportedFormat(format))),
+        ImageFormat::Hdr => Err(at!(CodecError::UnsupportedFormat(format))),
 
         // RAW/DNG: Custom format from zenraw
         #[cfg(feature = "raw-decode")]
         ImageFormat::Custom(def) if def.name == "dng" || def.name == "raw" => {
-            crate::codecs::raw::probe(data)?
+            crate::codecs::raw::probe(data)
         }
 
-        _ => return Err(at!(CodecError::UnsupportedFormat(format))),
-    };
+        #[cfg(feature = "exr")]
+        ImageFormat::Custom(def) if def.name == "exr" => crate::codecs::exr::probe(data),
+
+        #[cfg(feature = "ico")]
+        ImageFormat::Custom(def) if def.name == "ico" => crate::codecs::ico::probe(data),
+
+        #[cfg(feature = "psd")]
+        ImageFormat::Custom(def) if def.name == "psd" => crate::codecs::psd::probe(data),
+
+        #[cfg(feature = "dds")]
+        ImageFormat::Custom(def) if def.name == "dds" => crate::codecs::dds::probe(data),
+
+        #[cfg(feature = "ktx2")]
+        ImageFormat::Custom(def) if def.name == "ktx2" => crate::codecs::ktx2::probe(data),
+
+        #[cfg(feature = "jp2-decode")]
+        ImageFormat::Custom(def) if def.name == "jp2" => crate::codecs::jp2::probe(data),
+
+        _ => Err(at!(CodecError::UnsupportedFormat(format))),
+    }?;
+    // Report ink channels for CMYK TIFFs even when the codec decodes to RGB.
+    #[cfg(feature = "tiff")]
+    if format == ImageFormat::Tiff && crate::cmyk::detect(data).is_some() {
+        info = info.with_channel_count(4);
+    }
     // For formats that conventionally assume sRGB when no color metadata is
     // present (JPEG, PNG, WebP, GIF), set implicit CICP so downstream
     // encoders always have a color space signal.
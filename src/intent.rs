@@ -60,6 +60,12 @@ pub struct CodecIntent {
     /// Matte color for alpha compositing. From `bgcolor=` when encoding
     /// to a format without alpha (e.g., RGBA source to JPEG output).
     pub matte: Option<[u8; 3]>,
+    /// Never exceed the source's own quality.
+    ///
+    /// When set and [`ImageFacts::source_quality`](crate::ImageFacts::source_quality)
+    /// is known, the effective quality is lowered to the source estimate.
+    /// Re-encoding a q60 JPEG at a "high" profile only adds bytes.
+    pub cap_to_source_quality: bool,
 }
 
 /// Explicit format choice from `format=`.
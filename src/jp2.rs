@@ -0,0 +1,412 @@
+//! JPEG 2000 (`jp2-decode` feature).
+//!
+//! JP2 files and raw J2K codestreams (ITU-T T.800 Part 1) are one custom
+//! format, `ImageFormat::Custom(&JP2_FORMAT)`, decoded by a built-in
+//! decoder:
+//!
+//! - [`header`] reads dimensions, components, wavelet levels, quality
+//!   layers, tiling and the color specification, including an embedded ICC
+//!   profile, without decoding pixels.
+//! - [`DecodeRequest`](crate::DecodeRequest) decodes to gray or RGB, with
+//!   alpha from a channel definition or a trailing component, at 8 bits
+//!   when every component fits and 16 otherwise. Palettes are expanded,
+//!   sYCC is converted to RGB and subsampled components are upsampled.
+//! - [`Jp2DecodeConfig`] asks for a reduced resolution, which skips the
+//!   finest wavelet levels entirely, and for a region, which skips tiles
+//!   outside it. Both are cheap ways to get thumbnails and crops of large
+//!   scans.
+//!
+//! Part 2 (JPX) extensions, High-Throughput (HTJ2K) code-blocks, ROI
+//! shifts, progression order changes and packed packet headers are
+//! rejected with [`UnsupportedOperation`](crate::CodecError::UnsupportedOperation).
+
+use alloc::vec::Vec;
+
+use crate::ImageFormat;
+use crate::error::Result;
+
+/// Format definition for JPEG 2000.
+pub static JP2_FORMAT: zencodec::ImageFormatDefinition = zencodec::ImageFormatDefinition::new(
+    "jp2",
+    None,
+    "JPEG 2000",
+    "jp2",
+    &["jp2", "j2k", "j2c", "jpc", "jpf"],
+    "image/jp2",
+    &["image/jp2", "image/j2c", "image/jpx"],
+    true,
+    false,
+    true,
+    true,
+    12,
+    crate::codecs::jp2::is_jp2,
+);
+
+/// The JPEG 2000 [`ImageFormat`].
+pub fn format() -> ImageFormat {
+    ImageFormat::Custom(&JP2_FORMAT)
+}
+
+/// Color space from the JP2 `colr` box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Jp2ColorSpace {
+    /// Enumerated sRGB.
+    Srgb,
+    /// Enumerated grayscale.
+    Gray,
+    /// Enumerated sYCC; converted to RGB on decode.
+    Sycc,
+    /// Described by [`Jp2Header::icc_profile`].
+    Icc,
+    /// Another enumerated space, or a raw codestream without boxes.
+    Unspecified,
+}
+
+/// One codestream component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Jp2Component {
+    /// Bits per sample, 1–30.
+    pub precision: u8,
+    /// Two's complement samples.
+    pub signed: bool,
+    /// Horizontal subsampling.
+    pub dx: u32,
+    /// Vertical subsampling.
+    pub dy: u32,
+}
+
+/// Image and coding parameters of a JP2 file or J2K codestream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Jp2Header {
+    /// A bare codestream without JP2 boxes.
+    pub codestream_only: bool,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Codestream components, before palette mapping.
+    pub components: Vec<Jp2Component>,
+    /// Wavelet decomposition levels: the largest usable reduction.
+    pub levels: u8,
+    /// Quality layers.
+    pub layers: u16,
+    /// Tile width on the reference grid.
+    pub tile_width: u32,
+    /// Tile height on the reference grid.
+    pub tile_height: u32,
+    /// Number of tiles.
+    pub tiles: u32,
+    /// Lossless 5/3 wavelet (otherwise the irreversible 9/7).
+    pub reversible: bool,
+    /// Color specification.
+    pub color_space: Jp2ColorSpace,
+    /// ICC profile from the `colr` box.
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl Jp2Header {
+    /// Output size after dropping `reduce` resolution levels.
+    pub fn reduced_size(&self, reduce: u8) -> (u32, u32) {
+        let scale = |v: u32| v.div_ceil(1 << reduce.min(31));
+        (scale(self.width), scale(self.height))
+    }
+
+    /// The largest reduction whose output still covers `width` × `height`,
+    /// for thumbnails.
+    pub fn reduce_for(&self, width: u32, height: u32) -> u8 {
+        (0..=self.levels)
+            .rev()
+            .find(|&r| {
+                let (w, h) = self.reduced_size(r);
+                w >= width && h >= height
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// An area of the image in full-resolution pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Jp2Region {
+    /// Left edge.
+    pub x: u32,
+    /// Top edge.
+    pub y: u32,
+    /// Width.
+    pub width: u32,
+    /// Height.
+    pub height: u32,
+}
+
+/// JPEG 2000 decode settings: resolution reduction and region.
+#[derive(Clone, Debug, Default)]
+pub struct Jp2DecodeConfig {
+    pub(crate) reduce: u8,
+    pub(crate) region: Option<Jp2Region>,
+}
+
+impl Jp2DecodeConfig {
+    /// Decode the whole image at full resolution.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop `levels` wavelet resolution levels, halving width and height
+    /// for each; clamped to [`Jp2Header::levels`].
+    pub fn with_reduce(mut self, levels: u8) -> Self {
+        self.reduce = levels;
+        self
+    }
+
+    /// Decode only this area, given in full-resolution pixels and scaled
+    /// along with the reduction. Clipped to the image.
+    pub fn with_region(mut self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        self.region = Some(Jp2Region {
+            x,
+            y,
+            width,
+            height,
+        });
+        self
+    }
+}
+
+/// Read the image and coding parameters.
+pub fn header(data: &[u8]) -> Result<Jp2Header> {
+    crate::codecs::jp2::header(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::config::CodecConfig;
+    use crate::{AllowedFormats, CodecError, DecodeRequest};
+
+    /// Lossless 8×8 RGB codestream, two 5/3 levels with the RCT, of
+    /// `[x * 30, y * 30, (x + y) * 15]`.
+    const CODESTREAM: [u8; 222] = [
+        0xff, 0x4f, 0xff, 0x51, 0x00, 0x2f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
+        0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00,
+        0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x07, 0x01, 0x01,
+        0x07, 0x01, 0x01, 0x07, 0x01, 0x01, 0xff, 0x52, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x01, 0x01,
+        0x02, 0x04, 0x04, 0x00, 0x01, 0xff, 0x5c, 0x00, 0x0a, 0x40, 0x48, 0x50, 0x50, 0x58, 0x50,
+        0x50, 0x58, 0xff, 0x64, 0x00, 0x06, 0x00, 0x01, 0x68, 0x69, 0xff, 0x90, 0x00, 0x0a, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x87, 0x00, 0x01, 0xff, 0x93, 0xc7, 0xda, 0x0a, 0x06, 0x48, 0x7d,
+        0xe8, 0x33, 0xc7, 0xda, 0x0a, 0x0b, 0xaf, 0xa6, 0x05, 0x7f, 0xcf, 0xc0, 0x10, 0x0b, 0xaf,
+        0xa6, 0x17, 0xc0, 0xf9, 0xc1, 0xc1, 0xf5, 0x02, 0x40, 0x0e, 0x84, 0x0c, 0xfa, 0x5f, 0x0b,
+        0x3c, 0x65, 0x83, 0x04, 0x48, 0xc1, 0xf5, 0x02, 0x41, 0xf5, 0x01, 0x80, 0x0d, 0x02, 0xff,
+        0x7f, 0x0c, 0x18, 0x04, 0xc3, 0xed, 0x04, 0x87, 0xda, 0x08, 0x0d, 0x02, 0xff, 0x7f, 0x0c,
+        0x18, 0x15, 0xe7, 0xc0, 0x7c, 0x81, 0x60, 0x7c, 0xe1, 0xa0, 0x02, 0x40, 0x22, 0x19, 0xc7,
+        0x21, 0x17, 0x03, 0x7f, 0x9d, 0xeb, 0x39, 0x07, 0x14, 0x2d, 0x0c, 0xaf, 0xc0, 0x7c, 0x80,
+        0xe0, 0x3e, 0x40, 0x60, 0x22, 0x18, 0x7f, 0x00, 0xce, 0x63, 0xc0, 0xf9, 0xc2, 0x40, 0xf9,
+        0xc2, 0x00, 0x22, 0x18, 0x80, 0x77, 0x00, 0xce, 0x61, 0x27, 0xff, 0xd9,
+    ];
+
+    fn expected(x: u32, y: u32) -> [u8; 3] {
+        [(x * 30) as u8, (y * 30) as u8, ((x + y) * 15) as u8]
+    }
+
+    fn rgb(output: &crate::DecodeOutput) -> Vec<u8> {
+        let pixels = output.pixels();
+        let row_bytes = pixels.width() as usize * 3;
+        let bytes = pixels.as_strided_bytes();
+        (0..pixels.rows() as usize)
+            .flat_map(|y| &bytes[y * pixels.stride()..][..row_bytes])
+            .copied()
+            .collect()
+    }
+
+    fn decode(data: &[u8], config: Jp2DecodeConfig) -> crate::DecodeOutput {
+        DecodeRequest::new(data)
+            .with_codec_config(&CodecConfig::default().with_jp2_decoder(config))
+            .decode_full_frame()
+            .unwrap()
+    }
+
+    fn boxed(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// The codestream wrapped in a JP2 file with an ICC color specification.
+    fn jp2_with_icc() -> Vec<u8> {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&8u32.to_be_bytes());
+        ihdr.extend_from_slice(&8u32.to_be_bytes());
+        ihdr.extend_from_slice(&[0, 3, 7, 7, 0, 0]);
+        let mut colr = alloc::vec![2, 0, 0];
+        colr.extend_from_slice(b"icc-profile");
+        let mut header = boxed(b"ihdr", &ihdr);
+        header.extend(boxed(b"colr", &colr));
+
+        let mut data = boxed(b"jP  ", &[0x0D, 0x0A, 0x87, 0x0A]);
+        data.extend(boxed(b"ftyp", b"jp2 \0\0\0\0jp2 "));
+        data.extend(boxed(b"jp2h", &header));
+        data.extend(boxed(b"jp2c", &CODESTREAM));
+        data
+    }
+
+    #[test]
+    fn lossless_codestream_round_trip() {
+        assert_eq!(crate::info::detect_format(&CODESTREAM), Some(format()));
+        let header = header(&CODESTREAM).unwrap();
+        assert!(header.codestream_only);
+        assert_eq!((header.width, header.height), (8, 8));
+        assert_eq!(header.components.len(), 3);
+        assert_eq!(header.components[0].precision, 8);
+        assert_eq!((header.levels, header.layers, header.tiles), (2, 1, 1));
+        assert!(header.reversible);
+        assert_eq!(header.color_space, Jp2ColorSpace::Unspecified);
+
+        let info = crate::from_bytes(&CODESTREAM).unwrap();
+        assert_eq!((info.width, info.height), (8, 8));
+        assert!(!info.has_alpha);
+
+        let output = DecodeRequest::new(&CODESTREAM).decode_full_frame().unwrap();
+        assert_eq!(output.info().format, format());
+        let want: Vec<u8> = (0..64).flat_map(|i| expected(i % 8, i / 8)).collect();
+        assert_eq!(rgb(&output), want);
+    }
+
+    #[test]
+    fn jp2_boxes_carry_icc_profile() {
+        let data = jp2_with_icc();
+        assert_eq!(crate::info::detect_format(&data), Some(format()));
+        let header = header(&data).unwrap();
+        assert!(!header.codestream_only);
+        assert_eq!(header.color_space, Jp2ColorSpace::Icc);
+        assert_eq!(header.icc_profile.as_deref(), Some(&b"icc-profile"[..]));
+
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(
+            output.info().source_color.icc_profile.as_deref(),
+            Some(&b"icc-profile"[..])
+        );
+        assert_eq!(&rgb(&output)[..6], [0, 0, 0, 30, 0, 15]);
+    }
+
+    #[test]
+    fn reduced_resolution() {
+        let header = header(&CODESTREAM).unwrap();
+        assert_eq!(header.reduced_size(1), (4, 4));
+        assert_eq!(header.reduce_for(3, 3), 1);
+        assert_eq!(header.reduce_for(1, 1), 2);
+
+        let output = decode(&CODESTREAM, Jp2DecodeConfig::new().with_reduce(1));
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (4, 4));
+        // Beyond the available levels: clamped to the smallest resolution.
+        let output = decode(&CODESTREAM, Jp2DecodeConfig::new().with_reduce(9));
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (2, 2));
+    }
+
+    #[test]
+    fn region_decode() {
+        let output = decode(&CODESTREAM, Jp2DecodeConfig::new().with_region(2, 3, 4, 4));
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (4, 4));
+        let want: Vec<u8> = (0..16)
+            .flat_map(|i| expected(2 + i % 4, 3 + i / 4))
+            .collect();
+        assert_eq!(rgb(&output), want);
+
+        // Clipped to the image, and scaled with the reduction.
+        let output = decode(
+            &CODESTREAM,
+            Jp2DecodeConfig::new().with_region(6, 6, 10, 10),
+        );
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (2, 2));
+        let config = Jp2DecodeConfig::new()
+            .with_reduce(1)
+            .with_region(2, 3, 4, 4);
+        let output = decode(&CODESTREAM, config);
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (2, 2));
+
+        let outside =
+            CodecConfig::default().with_jp2_decoder(Jp2DecodeConfig::new().with_region(8, 0, 4, 4));
+        let result = DecodeRequest::new(&CODESTREAM)
+            .with_codec_config(&outside)
+            .decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn disabled_and_malformed() {
+        let registry = AllowedFormats::all().with_decode(format(), false);
+        let result = DecodeRequest::new(&CODESTREAM)
+            .with_registry(&registry)
+            .decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::DisabledFormat(_))
+        ));
+
+        // Cut inside the main header.
+        let result = DecodeRequest::new(&CODESTREAM[..60]).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+
+        // A JP2 signature without a codestream box.
+        let data = jp2_with_icc();
+        let result =
+            DecodeRequest::new(&data[..data.len() - CODESTREAM.len() - 8]).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_code_blocks_are_rejected() {
+        // COD stores the code-block width and height exponents minus 2
+        // after the marker, length, Scod, SGcod and level count.
+        let cod = CODESTREAM
+            .windows(2)
+            .position(|w| w == [0xFF, 0x52])
+            .unwrap();
+        for (xcb, ycb) in [(0xFE, 4), (4, 0xFF), (0xFF, 0xFF), (9, 0), (5, 4)] {
+            let mut data = CODESTREAM;
+            data[cod + 10] = xcb;
+            data[cod + 11] = ycb;
+            let result = DecodeRequest::new(&data).decode_full_frame();
+            assert!(
+                matches!(
+                    result.as_ref().map_err(|e| e.error()),
+                    Err(CodecError::InvalidInput(_))
+                ),
+                "xcb {xcb}, ycb {ycb}"
+            );
+        }
+    }
+
+    #[test]
+    fn oversized_canvas_is_rejected() {
+        // A bit-flipped SIZ declaring a 2^24 × 2^24 single-tile image: the
+        // planes alone would need petabytes.
+        let mut data = CODESTREAM;
+        for at in [8, 12, 24, 28] {
+            data[at..at + 4].copy_from_slice(&(1u32 << 24).to_be_bytes());
+        }
+        let result = DecodeRequest::new(&data).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::LimitExceeded(_))
+        ));
+
+        // A small region of it still allocates the whole tile's bands.
+        let config = Jp2DecodeConfig::new().with_region(0, 0, 8, 8);
+        let result = DecodeRequest::new(&data)
+            .with_codec_config(&CodecConfig::default().with_jp2_decoder(config))
+            .decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::LimitExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,505 @@
+//! Lossless JPEG transforms in the DCT coefficient domain.
+//!
+//! Rotation by 90/180/270, flips, transpose/transverse, progressive ↔
+//! baseline conversion and Huffman optimization, all without decoding to
+//! pixels — no generation loss, and much faster than a decode and
+//! re-encode. The equivalent of `jpegtran`, minus cropping: zenjpeg's
+//! lossless pipeline has no MCU-aligned crop yet.
+//!
+//! Transforms are expressed as [`Orientation`] values: the geometric
+//! operation the EXIF tag of that value would ask a viewer to perform.
+//! By default the source's EXIF orientation is baked into the coefficients
+//! as well and the tag is reset to 1, so every viewer shows the same image.
+//!
+//! Flips and rotations move the right or bottom edge blocks to the top or
+//! left, so they need whole MCUs (8 or 16 px) on that edge. Images that
+//! aren't MCU-aligned are rejected unless
+//! [`with_trim_partial_blocks`](JpegTransform::with_trim_partial_blocks)
+//! allows dropping the partial blocks, as `jpegtran -trim` does.
+//! [`transcode`](crate::transcode()) falls back to the pixel path instead.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use zencodecs::jpeg_transform::JpegTransform;
+//!
+//! // Apply the EXIF orientation losslessly and reset the tag
+//! let upright = JpegTransform::new().apply(&jpeg_bytes)?;
+//!
+//! // Rotate the displayed image a further 90° clockwise, as progressive
+//! let rotated = JpegTransform::new()
+//!     .with_orientation(zencodec::Orientation::from_exif(6).unwrap())
+//!     .with_progressive(true)
+//!     .apply(&jpeg_bytes)?;
+//! ```
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::error::Result;
+use crate::{CodecError, ImageFormat, StopToken};
+use enough::Stop;
+use whereat::at;
+use zencodec::Orientation;
+use zenjpeg::lossless::{
+    EdgeHandling, LosslessTransform, OutputMode, RestartInterval, RestructureConfig,
+    TransformConfig,
+};
+
+/// A lossless JPEG transform. Build with the `with_*` methods, then
+/// [`apply`](Self::apply).
+#[derive(Clone, Debug)]
+pub struct JpegTransform {
+    orientation: Orientation,
+    auto_orient: bool,
+    progressive: Option<bool>,
+    optimize_huffman: bool,
+    trim_partial_blocks: bool,
+    stop: Option<StopToken>,
+}
+
+impl Default for JpegTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JpegTransform {
+    /// Bake the EXIF orientation; no other change.
+    pub fn new() -> Self {
+        Self {
+            orientation: Orientation::default(),
+            auto_orient: true,
+            progressive: None,
+            optimize_huffman: false,
+            trim_partial_blocks: false,
+            stop: None,
+        }
+    }
+
+    /// Rotate/flip the displayed image by the operation `orientation`
+    /// describes (e.g. EXIF 6 = rotate 90° clockwise).
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    /// Bake the source's EXIF orientation into the coefficients and reset
+    /// the tag to 1 (default: on).
+    ///
+    /// When off, only [`with_orientation`](Self::with_orientation) is
+    /// applied to the coefficients and the EXIF tag is rewritten so viewers
+    /// still display the intended result.
+    pub fn with_auto_orient(mut self, auto_orient: bool) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// Convert to progressive (`true`) or baseline (`false`) scan order.
+    /// Default: keep the source's.
+    pub fn with_progressive(mut self, progressive: bool) -> Self {
+        self.progressive = Some(progressive);
+        self
+    }
+
+    /// Rewrite the file with optimal Huffman tables even when nothing else
+    /// changes (default: off). Any other change already rebuilds them.
+    pub fn with_optimized_huffman(mut self, optimize: bool) -> Self {
+        self.optimize_huffman = optimize;
+        self
+    }
+
+    /// Drop partial edge blocks that a flip or rotation would otherwise
+    /// move into the image (default: off).
+    ///
+    /// Crops up to 15 px from the right and/or bottom of the stored image.
+    /// When off, such transforms fail with
+    /// [`CodecError::UnsupportedOperation`]; decode, orient and re-encode
+    /// to keep every pixel.
+    pub fn with_trim_partial_blocks(mut self, trim: bool) -> Self {
+        self.trim_partial_blocks = trim;
+        self
+    }
+
+    /// Set a cancellation token.
+    pub fn with_stop(mut self, stop: StopToken) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Whether this transform changes nothing but geometry.
+    fn is_geometry_only(&self) -> bool {
+        self.progressive.is_none() && !self.optimize_huffman
+    }
+
+    /// Transform `jpeg` and return the new file.
+    ///
+    /// When the result would be identical to the input (identity geometry,
+    /// no re-encoding options), the input is returned unchanged.
+    pub fn apply(&self, jpeg: &[u8]) -> Result<Vec<u8>> {
+        if !jpeg.starts_with(&[0xFF, 0xD8]) {
+            return Err(at!(CodecError::InvalidInput(
+                "lossless JPEG transform: input is not a JPEG".into()
+            )));
+        }
+        let source = Op::from_exif(
+            exif_segment(jpeg)
+                .and_then(|r| crate::exif::parse_exif(&jpeg[r]).ok())
+                .and_then(|e| e.orientation)
+                .unwrap_or(1),
+        );
+        let requested = Op::from_exif(u16::from(self.orientation.to_exif()));
+
+        // Coefficient op and the EXIF tag to write afterwards. The viewer
+        // must end up showing `requested ∘ source` applied to the stored image.
+        let (coefficients, tag) = if self.auto_orient {
+            (requested.then_after(source), Op::IDENTITY)
+        } else {
+            (
+                requested,
+                requested.then_after(source).then_after(requested.inverse()),
+            )
+        };
+
+        if coefficients == Op::IDENTITY && tag == source && self.is_geometry_only() {
+            return Ok(jpeg.to_vec());
+        }
+
+        let transform = coefficients.lossless();
+        if !self.trim_partial_blocks && has_partial_blocks(jpeg, transform) {
+            return Err(at!(CodecError::UnsupportedOperation {
+                format: ImageFormat::Jpeg,
+                detail: "lossless transform of partial edge blocks without trimming",
+            }));
+        }
+
+        let progressive = self.progressive.unwrap_or_else(|| is_progressive(jpeg));
+        let config = RestructureConfig {
+            output_mode: if progressive {
+                OutputMode::Progressive
+            } else {
+                OutputMode::Sequential
+            },
+            restart_interval: RestartInterval::None,
+            transform: (coefficients != Op::IDENTITY).then_some(TransformConfig {
+                transform,
+                edge_handling: if self.trim_partial_blocks {
+                    EdgeHandling::TrimPartialBlocks
+                } else {
+                    EdgeHandling::RejectPartialBlocks
+                },
+            }),
+        };
+        let stop: &dyn Stop = match &self.stop {
+            Some(s) => s,
+            None => &enough::Unstoppable,
+        };
+        let mut out = zenjpeg::lossless::restructure(jpeg, &config, stop).map_err(|e| {
+            if stop.should_stop() {
+                at!(CodecError::Cancelled)
+            } else {
+                at!(CodecError::from_codec(ImageFormat::Jpeg, e))
+            }
+        })?;
+
+        if tag != source || coefficients != Op::IDENTITY {
+            set_jpeg_orientation(&mut out, tag.exif());
+        }
+        Ok(out)
+    }
+}
+
+/// Apply a JPEG's EXIF orientation losslessly and reset the tag to 1.
+///
+/// Returns `None` when the image is already upright (no tag, or tag 1).
+/// Fails with [`CodecError::UnsupportedOperation`] when the rotation would
+/// have to trim partial edge blocks.
+pub fn auto_orient_jpeg(jpeg: &[u8]) -> Result<Option<Vec<u8>>> {
+    let orientation = exif_segment(jpeg)
+        .and_then(|r| crate::exif::parse_exif(&jpeg[r]).ok())
+        .and_then(|e| e.orientation)
+        .unwrap_or(1);
+    if orientation <= 1 || orientation > 8 {
+        return Ok(None);
+    }
+    JpegTransform::new().apply(jpeg).map(Some)
+}
+
+/// Rewrite the EXIF orientation tag of a JPEG in place.
+///
+/// Returns `false` if the JPEG has no EXIF orientation tag to patch.
+pub fn set_jpeg_orientation(jpeg: &mut [u8], orientation: u16) -> bool {
+    match exif_segment(jpeg) {
+        Some(range) => crate::exif::set_orientation(&mut jpeg[range], orientation),
+        None => false,
+    }
+}
+
+/// Byte range of the first `Exif\0\0` APP1 payload, before the first scan.
+fn exif_segment(jpeg: &[u8]) -> Option<Range<usize>> {
+    find_segment(jpeg, |marker, payload| {
+        marker == 0xE1 && payload.starts_with(b"Exif\0\0")
+    })
+}
+
+/// Whether the frame header (SOF2/6/10/14) declares progressive scans.
+fn is_progressive(jpeg: &[u8]) -> bool {
+    find_segment(jpeg, |marker, _| {
+        matches!(marker, 0xC2 | 0xC6 | 0xCA | 0xCE)
+    })
+    .is_some()
+}
+
+/// Whether `transform` would move a partial MCU from the right or bottom
+/// edge into the image, which only trimming can handle.
+fn has_partial_blocks(jpeg: &[u8], transform: LosslessTransform) -> bool {
+    // SOF0-15 minus DHT (C4), JPG (C8) and DAC (CC).
+    let Some(sof) = find_segment(jpeg, |marker, _| {
+        matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC)
+    }) else {
+        return false;
+    };
+    let sof = &jpeg[sof];
+    if sof.len() < 6 {
+        return false;
+    }
+    let height = u32::from(u16::from_be_bytes([sof[1], sof[2]]));
+    let width = u32::from(u16::from_be_bytes([sof[3], sof[4]]));
+    let sampling = sof[6..].chunks_exact(3).map(|c| c[1]);
+    let mcu_width = 8 * u32::from(sampling.clone().map(|s| s >> 4).max().unwrap_or(1).max(1));
+    let mcu_height = 8 * u32::from(sampling.map(|s| s & 15).max().unwrap_or(1).max(1));
+    let partial_h = width % mcu_width != 0;
+    let partial_v = height % mcu_height != 0;
+    match transform {
+        LosslessTransform::FlipHorizontal | LosslessTransform::Rotate270 => partial_h,
+        LosslessTransform::FlipVertical | LosslessTransform::Rotate90 => partial_v,
+        LosslessTransform::Rotate180 | LosslessTransform::Transverse => partial_h || partial_v,
+        LosslessTransform::Transpose | LosslessTransform::None => false,
+    }
+}
+
+/// Payload range of the first marker segment before the first scan that
+/// `matches` accepts.
+fn find_segment(jpeg: &[u8], matches: impl Fn(u8, &[u8]) -> bool) -> Option<Range<usize>> {
+    let mut pos = 2;
+    loop {
+        if *jpeg.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *jpeg.get(pos + 1)?;
+        pos += 2;
+        match marker {
+            0xFF => {
+                pos -= 1;
+                continue;
+            }
+            0x01 | 0xD0..=0xD7 => continue,
+            0xDA | 0xD9 => return None,
+            _ => {}
+        }
+        let len = u16::from_be_bytes([*jpeg.get(pos)?, *jpeg.get(pos + 1)?]) as usize;
+        if len < 2 {
+            return None;
+        }
+        let payload = pos + 2..pos + len;
+        if payload.end > jpeg.len() {
+            return None;
+        }
+        if matches(marker, &jpeg[payload.clone()]) {
+            return Some(payload);
+        }
+        pos += len;
+    }
+}
+
+/// An element of the 8-member rotation/flip group, as the 2×2 matrix it
+/// applies to (x, y) in y-down image coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Op([i8; 4]);
+
+/// Matrices for EXIF orientations 1-8, indexed by `value - 1`.
+const EXIF_OPS: [Op; 8] = [
+    Op([1, 0, 0, 1]),   // 1: identity
+    Op([-1, 0, 0, 1]),  // 2: flip horizontal
+    Op([-1, 0, 0, -1]), // 3: rotate 180
+    Op([1, 0, 0, -1]),  // 4: flip vertical
+    Op([0, 1, 1, 0]),   // 5: transpose
+    Op([0, -1, 1, 0]),  // 6: rotate 90 clockwise
+    Op([0, -1, -1, 0]), // 7: transverse
+    Op([0, 1, -1, 0]),  // 8: rotate 270 clockwise
+];
+
+impl Op {
+    const IDENTITY: Op = EXIF_OPS[0];
+
+    fn from_exif(value: u16) -> Op {
+        match value {
+            1..=8 => EXIF_OPS[value as usize - 1],
+            _ => Op::IDENTITY,
+        }
+    }
+
+    fn exif(self) -> u16 {
+        EXIF_OPS.iter().position(|&op| op == self).unwrap_or(0) as u16 + 1
+    }
+
+    /// `self ∘ first`: apply `first`, then `self`.
+    fn then_after(self, first: Op) -> Op {
+        let [a, b, c, d] = self.0;
+        let [e, f, g, h] = first.0;
+        Op([a * e + b * g, a * f + b * h, c * e + d * g, c * f + d * h])
+    }
+
+    /// Orthogonal matrix: the inverse is the transpose.
+    fn inverse(self) -> Op {
+        let [a, b, c, d] = self.0;
+        Op([a, c, b, d])
+    }
+
+    fn lossless(self) -> LosslessTransform {
+        match self.exif() {
+            2 => LosslessTransform::FlipHorizontal,
+            3 => LosslessTransform::Rotate180,
+            4 => LosslessTransform::FlipVertical,
+            5 => LosslessTransform::Transpose,
+            6 => LosslessTransform::Rotate90,
+            7 => LosslessTransform::Transverse,
+            8 => LosslessTransform::Rotate270,
+            _ => LosslessTransform::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_closure_and_inverses() {
+        for a in 1..=8u16 {
+            let op = Op::from_exif(a);
+            assert_eq!(op.then_after(op.inverse()), Op::IDENTITY, "exif {a}");
+            assert_eq!(Op::from_exif(a).exif(), a);
+            for b in 1..=8u16 {
+                // Every product is another orientation
+                assert!(EXIF_OPS.contains(&op.then_after(Op::from_exif(b))));
+            }
+        }
+        // Two quarter turns make a half turn; four make nothing.
+        let rot90 = Op::from_exif(6);
+        assert_eq!(rot90.then_after(rot90).exif(), 3);
+        assert_eq!(rot90.then_after(rot90).then_after(rot90).exif(), 8);
+        // Undoing a 90° rotation is a 270° rotation.
+        assert_eq!(rot90.inverse().exif(), 8);
+        // Flips are their own inverse.
+        assert_eq!(Op::from_exif(2).inverse().exif(), 2);
+        assert_eq!(Op::from_exif(5).inverse().exif(), 5);
+    }
+
+    /// SOI + APP1(Exif, orientation) + SOS.
+    fn jpeg_header(orientation: u16) -> Vec<u8> {
+        let mut tiff = alloc::vec![b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0];
+        tiff.extend_from_slice(&0x0112u16.to_le_bytes());
+        tiff.extend_from_slice(&3u16.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        tiff.extend_from_slice(&orientation.to_le_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut jpeg = alloc::vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(&tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xDA]);
+        jpeg
+    }
+
+    #[test]
+    fn patches_jpeg_orientation() {
+        let mut jpeg = jpeg_header(6);
+        assert!(set_jpeg_orientation(&mut jpeg, 1));
+        let range = exif_segment(&jpeg).unwrap();
+        let exif = crate::exif::parse_exif(&jpeg[range]).unwrap();
+        assert_eq!(exif.orientation, Some(1));
+
+        let mut no_exif = alloc::vec![0xFF, 0xD8, 0xFF, 0xDA];
+        assert!(!set_jpeg_orientation(&mut no_exif, 1));
+    }
+
+    #[test]
+    fn upright_jpeg_needs_no_auto_orient() {
+        assert_eq!(auto_orient_jpeg(&jpeg_header(1)).unwrap(), None);
+    }
+
+    #[test]
+    fn scan_mode_is_kept_unless_requested() {
+        let img = imgref::ImgVec::new(
+            alloc::vec![rgb::Rgb { r: 40u8, g: 80, b: 120 }; 16 * 16],
+            16,
+            16,
+        );
+        let jpeg = crate::EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(90.0)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let baseline = JpegTransform::new()
+            .with_progressive(false)
+            .apply(jpeg.data())
+            .unwrap();
+        assert!(!is_progressive(&baseline));
+
+        let progressive = JpegTransform::new()
+            .with_progressive(true)
+            .apply(&baseline)
+            .unwrap();
+        assert!(is_progressive(&progressive));
+
+        let rotated = JpegTransform::new()
+            .with_orientation(Orientation::from_exif(3).unwrap())
+            .apply(&progressive)
+            .unwrap();
+        assert!(is_progressive(&rotated));
+    }
+
+    #[test]
+    fn rotation_round_trip_preserves_pixels() {
+        use crate::{DecodeRequest, EncodeRequest};
+        use zenpixels_convert::PixelBufferConvertTypedExt as _;
+
+        // 32×16 so rotation swaps dimensions; MCU-aligned for 4:2:0.
+        let img = imgref::ImgVec::new(
+            (0..32 * 16)
+                .map(|i| rgb::Rgb {
+                    r: (i % 32 * 8) as u8,
+                    g: (i / 32 * 16) as u8,
+                    b: 0u8,
+                })
+                .collect(),
+            32,
+            16,
+        );
+        let jpeg = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(90.0)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+
+        let rot90 = Orientation::from_exif(6).unwrap();
+        let once = JpegTransform::new()
+            .with_orientation(rot90)
+            .apply(jpeg.data())
+            .unwrap();
+        let info = crate::from_bytes(&once).unwrap();
+        assert_eq!((info.width, info.height), (16, 32));
+
+        let back = (0..3).fold(once, |data, _| {
+            JpegTransform::new()
+                .with_orientation(rot90)
+                .apply(&data)
+                .unwrap()
+        });
+        let original = DecodeRequest::new(jpeg.data()).decode_full_frame().unwrap();
+        let restored = DecodeRequest::new(&back).decode_full_frame().unwrap();
+        assert_eq!(
+            original.into_buffer().to_rgb8().as_imgref().buf(),
+            restored.into_buffer().to_rgb8().as_imgref().buf()
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Khronos KTX2 textures (`ktx2` feature).
+//!
+//! Formats are identified by `vkFormat`; see [`crate::texture`] for which
+//! ones decode and what encode produces. Supercompressed files and Basis
+//! Universal payloads are rejected with
+//! [`UnsupportedOperation`](crate::CodecError::UnsupportedOperation). KTX2
+//! is a custom format, `ImageFormat::Custom(&KTX2_FORMAT)`.
+
+use crate::ImageFormat;
+use crate::error::Result;
+use crate::texture::TextureInfo;
+
+/// Format definition for KTX2.
+pub static KTX2_FORMAT: zencodec::ImageFormatDefinition = zencodec::ImageFormatDefinition::new(
+    "ktx2",
+    None,
+    "KTX2",
+    "ktx2",
+    &["ktx2"],
+    "image/ktx2",
+    &["image/ktx2"],
+    true,
+    false,
+    true,
+    true,
+    12,
+    crate::codecs::ktx2::is_ktx2,
+);
+
+/// The KTX2 [`ImageFormat`].
+pub fn format() -> ImageFormat {
+    ImageFormat::Custom(&KTX2_FORMAT)
+}
+
+/// Read the header: format, dimensions, layers and the mip chain.
+pub fn info(data: &[u8]) -> Result<TextureInfo> {
+    crate::codecs::ktx2::info(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::config::CodecConfig;
+    use crate::texture::{TextureDecodeConfig, TextureEncodeConfig, TextureFormat};
+    use crate::{AllowedFormats, CodecError, DecodeRequest, EncodeRequest};
+    use zenpixels::{PixelDescriptor, PixelSlice};
+
+    fn rgba(output: &crate::DecodeOutput) -> Vec<u8> {
+        let pixels = output.pixels();
+        let row_bytes = pixels.width() as usize * 4;
+        let bytes = pixels.as_strided_bytes();
+        (0..pixels.rows() as usize)
+            .flat_map(|y| &bytes[y * pixels.stride()..][..row_bytes])
+            .copied()
+            .collect()
+    }
+
+    /// 6×4 opaque horizontal gradient.
+    fn source() -> Vec<u8> {
+        (0..4 * 6u8)
+            .flat_map(|i| {
+                let x = i % 6;
+                [x * 40, 200 - x * 30, 64, 255]
+            })
+            .collect()
+    }
+
+    fn encode(config: TextureEncodeConfig) -> Vec<u8> {
+        let src = source();
+        let slice = PixelSlice::new(&src, 6, 4, 6 * 4, PixelDescriptor::RGBA8_SRGB).unwrap();
+        EncodeRequest::new(format())
+            .with_codec_config(&CodecConfig::default().with_texture_encoder(config))
+            .encode(slice, false)
+            .unwrap()
+            .into_vec()
+    }
+
+    #[test]
+    fn bc7_round_trip_with_mip_chain() {
+        let data = encode(TextureEncodeConfig::new().with_mipmaps(true));
+        // vkFormat BC7_SRGB_BLOCK, three levels.
+        assert_eq!(data[12], 146);
+        assert_eq!(data[40], 3);
+        let texture = info(&data).unwrap();
+        assert_eq!(texture.format, TextureFormat::Bc7);
+        assert!(texture.srgb);
+        let dims: Vec<_> = texture
+            .mip_levels
+            .iter()
+            .map(|l| (l.width, l.height))
+            .collect();
+        assert_eq!(dims, [(6, 4), (3, 2), (1, 1)]);
+        // Smallest level first, each aligned to the block size.
+        let offsets: Vec<_> = texture.mip_levels.iter().map(|l| l.offset).collect();
+        assert!(offsets[0] > offsets[1] && offsets[1] > offsets[2]);
+        assert!(offsets.iter().all(|o| o % 16 == 0));
+
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(output.info().format, format());
+        let max_error = rgba(&output)
+            .iter()
+            .zip(source())
+            .map(|(&a, b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+        assert!(max_error <= 8, "max error {max_error}");
+
+        let second = CodecConfig::default()
+            .with_texture_decoder(TextureDecodeConfig::new().with_mip_level(1));
+        let output = DecodeRequest::new(&data)
+            .with_codec_config(&second)
+            .decode_full_frame()
+            .unwrap();
+        assert_eq!((output.pixels().width(), output.pixels().rows()), (3, 2));
+    }
+
+    #[test]
+    fn linear_rgba8_is_exact() {
+        let data = encode(
+            TextureEncodeConfig::new()
+                .with_format(TextureFormat::Rgba8)
+                .with_srgb(false),
+        );
+        assert_eq!(data[12], 37);
+        // DFD color model RGBSDA, linear transfer.
+        let dfd = u32::from_le_bytes([data[48], data[49], data[50], data[51]]) as usize;
+        assert_eq!(data[dfd + 12..dfd + 15], [1, 1, 1]);
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(rgba(&output), source());
+    }
+
+    #[test]
+    fn rejects_supercompressed_and_disabled() {
+        let mut data = encode(TextureEncodeConfig::new());
+        let registry = AllowedFormats::all().with_decode(format(), false);
+        let result = DecodeRequest::new(&data)
+            .with_registry(&registry)
+            .decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::DisabledFormat(_))
+        ));
+
+        // Zstandard supercompression.
+        data[44] = 2;
+        assert!(matches!(
+            info(&data).as_ref().map_err(|e| e.error()),
+            Err(CodecError::UnsupportedOperation { .. })
+        ));
+    }
+}
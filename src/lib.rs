@@ -88,10 +88,14 @@ whereat::define_at_crate_info!();
 
 #[cfg(feature = "cms")]
 pub mod cms;
+#[cfg(any(feature = "jpeg", feature = "tiff", feature = "psd"))]
+pub mod cmyk;
 pub mod codec_id;
 mod codecs;
 pub mod color;
 pub mod config;
+#[cfg(feature = "dds")]
+pub mod dds;
 pub mod decision;
 mod decode;
 pub mod depthmap;
@@ -100,18 +104,38 @@ mod dyn_dispatch;
 mod encode;
 mod error;
 pub mod exif;
+#[cfg(feature = "exr")]
+pub mod exr;
 mod format_set;
 pub mod gainmap;
+#[cfg(feature = "ico")]
+pub mod ico;
 mod info;
 pub mod intent;
+#[cfg(feature = "jp2-decode")]
+pub mod jp2;
+#[cfg(feature = "jpeg")]
+pub mod jpeg_transform;
+#[cfg(feature = "ktx2")]
+pub mod ktx2;
 mod limits;
+#[cfg(feature = "metrics")]
+pub mod metric;
+pub mod orient;
 pub mod pixel;
 pub mod policy;
+#[cfg(feature = "psd")]
+pub mod psd;
 pub mod quality;
 mod registry;
 #[cfg(feature = "riapi")]
 pub mod riapi_parse;
 pub mod select;
+pub mod source_quality;
+mod target;
+#[cfg(any(feature = "dds", feature = "ktx2"))]
+pub mod texture;
+pub mod tonemap;
 pub mod trace;
 pub mod transcode;
 #[cfg(feature = "zennode")]
@@ -131,14 +155,19 @@ pub use info::ImageInfo;
 pub use info::{decode_info, decode_info_with_config};
 pub use info::{from_bytes, from_bytes_format, from_bytes_with_registry};
 pub use intent::{BoolKeep, CodecIntent, FormatChoice, PerCodecHints};
+#[cfg(feature = "jpeg")]
+pub use jpeg_transform::{JpegTransform, auto_orient_jpeg};
 pub use limits::{Limits, Stop};
 pub use policy::CodecPolicy;
 pub use quality::{QualityIntent, QualityProfile};
 pub use registry::AllowedFormats;
 #[cfg(feature = "riapi")]
 pub use riapi_parse::{CodecEngine, parse_codec_keys};
-pub use select::ImageFacts;
 pub use select::select_format_from_intent;
+pub use select::{ContentKind, ImageFacts};
+pub use source_quality::{SourceQuality, estimate_source_quality};
+pub use target::TargetEncodeOutput;
+pub use tonemap::{ToneMapOperator, ToneMapping};
 pub use trace::SelectionTrace;
 pub use transcode::{
     SupplementPolicy, SupplementSet, TranscodeOptions, TranscodeOutput, TranscodeSink,
@@ -156,13 +185,16 @@ pub use color::{SourceColorExt, icc_profile_is_srgb};
 // Gain map types (format-agnostic)
 pub use gainmap::decode_gain_map_source;
 #[cfg(feature = "jpeg-ultrahdr")]
-pub use gainmap::{DecodedGainMap, GainMap, GainMapMetadata, GainMapSource};
+pub use gainmap::{
+    DecodedGainMap, GainMap, GainMapComputeOptions, GainMapMetadata, GainMapSource, HdrOutput,
+    compute_gain_map,
+};
 pub use zencodec::gainmap::{GainMapDirection, GainMapInfo, GainMapParams, GainMapPresence};
 
 // Depth map types (format-agnostic)
 pub use depthmap::{
-    DecodedDepthMap, DepthFormat, DepthImage, DepthMapMetadata, DepthMeasureType, DepthPixelFormat,
-    DepthSource, DepthUnits,
+    DecodedDepthMap, DepthExport, DepthFormat, DepthImage, DepthMapMetadata, DepthMeasureType,
+    DepthPixelFormat, DepthSource, DepthUnits, JpegDepthContainer,
 };
 
 // zencodec trait re-exports
@@ -0,0 +1,197 @@
+//! Perceptual quality metrics.
+//!
+//! Compares a distorted image against a reference, for
+//! [`EncodeRequest::with_target_score`](crate::EncodeRequest::with_target_score)
+//! and for calibration tooling (the `quality_calibrate` example measures
+//! through it). Both images are converted to sRGB RGB8 before measuring;
+//! alpha is ignored.
+//!
+//! Only SSIMULACRA2 is provided. Butteraugli is out of scope for now: none
+//! of the crate's dependencies implement it and it has not been ported. The
+//! score search is already direction-agnostic
+//! (see [`QualityMetric::higher_is_better`]), and [`QualityMetric`] is
+//! non-exhaustive, so it can be added without breaking callers.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::CodecError;
+use crate::error::Result;
+use imgref::ImgVec;
+use whereat::at;
+use zenpixels::{PixelDescriptor, PixelSlice};
+
+/// A perceptual image quality metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QualityMetric {
+    /// SSIMULACRA2. Higher is better: ~90 is visually lossless,
+    /// ~70 is high quality, ~50 is medium.
+    Ssimulacra2,
+}
+
+impl QualityMetric {
+    /// Whether larger scores mean closer to the reference.
+    pub fn higher_is_better(self) -> bool {
+        match self {
+            QualityMetric::Ssimulacra2 => true,
+        }
+    }
+
+    /// Whether `score` is at least as good as `target`.
+    pub fn meets(self, score: f64, target: f64) -> bool {
+        if self.higher_is_better() {
+            score >= target
+        } else {
+            score <= target
+        }
+    }
+}
+
+/// A reference image prepared for repeated comparisons.
+///
+/// Preprocessing the reference once pays off when the same source is
+/// compared against many candidate encodes.
+pub struct MetricReference {
+    metric: QualityMetric,
+    width: usize,
+    height: usize,
+    ssim2: fast_ssim2::Ssimulacra2Reference,
+}
+
+impl MetricReference {
+    /// Prepare `pixels` as the reference for `metric`.
+    pub fn new(metric: QualityMetric, pixels: &PixelSlice<'_>) -> Result<Self> {
+        let img = to_rgb8(pixels)?;
+        let ssim2 = fast_ssim2::Ssimulacra2Reference::new(img.as_ref()).map_err(|e| {
+            at!(CodecError::InvalidInput(format!(
+                "ssimulacra2 reference: {e}"
+            )))
+        })?;
+        Ok(Self {
+            metric,
+            width: img.width(),
+            height: img.height(),
+            ssim2,
+        })
+    }
+
+    /// The metric this reference was prepared for.
+    pub fn metric(&self) -> QualityMetric {
+        self.metric
+    }
+
+    /// Score `distorted` against the reference.
+    ///
+    /// Dimensions must match the reference.
+    pub fn compare(&self, distorted: &PixelSlice<'_>) -> Result<f64> {
+        let img = to_rgb8(distorted)?;
+        if img.width() != self.width || img.height() != self.height {
+            return Err(at!(CodecError::InvalidInput(format!(
+                "metric dimensions differ: reference {}x{}, distorted {}x{}",
+                self.width,
+                self.height,
+                img.width(),
+                img.height()
+            ))));
+        }
+        match self.metric {
+            QualityMetric::Ssimulacra2 => self
+                .ssim2
+                .compare(img.as_ref())
+                .map_err(|e| at!(CodecError::InvalidInput(format!("ssimulacra2: {e}")))),
+        }
+    }
+}
+
+/// Convert any pixel format to packed sRGB RGB8.
+fn to_rgb8(pixels: &PixelSlice<'_>) -> Result<ImgVec<[u8; 3]>> {
+    let adapted = zenpixels_convert::adapt::adapt_for_encode_cow(
+        pixels.as_strided_bytes(),
+        pixels.descriptor(),
+        pixels.width(),
+        pixels.rows(),
+        pixels.stride(),
+        &[PixelDescriptor::RGB8_SRGB],
+    )
+    .map_err(|e| {
+        at!(CodecError::InvalidInput(format!(
+            "pixel format for metric: {e}"
+        )))
+    })?;
+    let rgb = adapted.as_slice();
+    let data: Vec<[u8; 3]> = rgb
+        .contiguous_bytes()
+        .chunks_exact(3)
+        .map(|c| [c[0], c[1], c[2]])
+        .collect();
+    Ok(ImgVec::new(data, rgb.width() as usize, rgb.rows() as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn gradient(offset: u8) -> ImgVec<rgb::Rgb<u8>> {
+        let mut pixels = vec::Vec::with_capacity(64 * 64);
+        for y in 0..64u8 {
+            for x in 0..64u8 {
+                pixels.push(rgb::Rgb {
+                    r: x.wrapping_mul(4).saturating_add(offset),
+                    g: y.wrapping_mul(4),
+                    b: 128,
+                });
+            }
+        }
+        ImgVec::new(pixels, 64, 64)
+    }
+
+    #[test]
+    fn identical_scores_highest() {
+        let img = gradient(0);
+        let ps = PixelSlice::from(img.as_ref()).erase();
+        let reference = MetricReference::new(QualityMetric::Ssimulacra2, &ps).unwrap();
+        let same = reference.compare(&ps).unwrap();
+
+        let shifted = gradient(40);
+        let worse = reference
+            .compare(&PixelSlice::from(shifted.as_ref()).erase())
+            .unwrap();
+        assert!(same > worse, "identical {same} should beat shifted {worse}");
+        assert!(QualityMetric::Ssimulacra2.meets(same, 90.0));
+    }
+
+    #[test]
+    fn padded_rows_match_packed() {
+        let img = gradient(0);
+        // A 48-wide view of the 64-wide gradient keeps its stride.
+        let padded = img.sub_image(0, 0, 48, 64);
+        let packed = ImgVec::new(padded.pixels().collect(), 48, 64);
+        let reference = MetricReference::new(
+            QualityMetric::Ssimulacra2,
+            &PixelSlice::from(packed.as_ref()).erase(),
+        )
+        .unwrap();
+        let score = reference
+            .compare(&PixelSlice::from(padded).erase())
+            .unwrap();
+        assert!(QualityMetric::Ssimulacra2.meets(score, 99.0), "{score}");
+    }
+
+    #[test]
+    fn dimension_mismatch_errors() {
+        let img = gradient(0);
+        let reference = MetricReference::new(
+            QualityMetric::Ssimulacra2,
+            &PixelSlice::from(img.as_ref()).erase(),
+        )
+        .unwrap();
+        let small = ImgVec::new(vec![rgb::Rgb { r: 0u8, g: 0, b: 0 }; 32 * 32], 32, 32);
+        assert!(
+            reference
+                .compare(&PixelSlice::from(small.as_ref()).erase())
+                .is_err()
+        );
+    }
+}
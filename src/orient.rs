@@ -0,0 +1,368 @@
+//! Applying EXIF-style orientation to decoded pixels.
+//!
+//! Most decoders return pixels in stored order. The orientation a viewer
+//! should apply comes from the EXIF tag (JPEG, WebP, PNG, TIFF) or the AVIF
+//! `irot`/`imir` properties, surfaced as
+//! [`ImageInfo::orientation`](crate::ImageInfo). The HEIC and JXL decoders
+//! apply `irot`/`imir` and the JXL header orientation themselves. The
+//! functions here bake the remaining orientation into the pixels so the
+//! result displays correctly everywhere, and reset the orientation so an
+//! encoder doesn't apply it a second time.
+//!
+//! [`DecodeRequest::with_auto_orient`](crate::DecodeRequest::with_auto_orient)
+//! is the usual way in.
+
+use alloc::vec::Vec;
+
+use crate::error::Result;
+use crate::{CodecError, ImageFormat, ImageInfo, Limits};
+use whereat::at;
+use zencodec::Orientation;
+use zencodec::decode::{DecodeRowSink, SinkError};
+use zenpixels::{PixelBuffer, PixelDescriptor, PixelSlice, PixelSliceMut};
+
+/// Rotate/flip `pixels` so that they display upright under `orientation`.
+///
+/// Orientations 5-8 swap width and height. Identity copies the pixels.
+pub fn orient_pixels(pixels: PixelSlice<'_>, orientation: Orientation) -> Result<PixelBuffer> {
+    let descriptor = pixels.descriptor();
+    let (data, width, height) = orient_bytes(
+        pixels.as_strided_bytes(),
+        pixels.width(),
+        pixels.rows(),
+        pixels.stride(),
+        descriptor.bytes_per_pixel(),
+        orientation.to_exif(),
+    );
+    PixelBuffer::from_vec(data, width, height, descriptor).map_err(|_| {
+        at!(CodecError::InvalidInput(
+            "failed to create PixelBuffer for oriented image".into()
+        ))
+    })
+}
+
+/// Whether `orientation` swaps width and height (EXIF 5-8).
+pub(crate) fn swaps_dimensions(orientation: Orientation) -> bool {
+    (5..=8).contains(&orientation.to_exif())
+}
+
+/// Check `limits` before orienting a `width`×`height` frame of `bpp`-byte
+/// pixels: the oriented dimensions, and memory for the stored frame plus
+/// its oriented copy, which are alive at the same time.
+pub(crate) fn check_limits(
+    limits: Option<&Limits>,
+    orientation: Orientation,
+    width: u32,
+    height: u32,
+    bpp: usize,
+) -> core::result::Result<(), &'static str> {
+    let Some(limits) = limits else {
+        return Ok(());
+    };
+    let (w, h) = if swaps_dimensions(orientation) {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    limits.check_dimensions(u64::from(w), u64::from(h))?;
+    let frame = u64::from(width)
+        .saturating_mul(u64::from(height))
+        .saturating_mul(bpp as u64);
+    limits.check_memory(frame.saturating_mul(2))
+}
+
+/// The orientation still to apply to an image's decoded pixels.
+///
+/// HEIC and JXL decoders orient the pixels themselves, so nothing is left to
+/// do whatever the header or EXIF says. Otherwise this is the
+/// decoder-reported value, falling back to the raw EXIF tag only for the
+/// formats whose decoders don't read orientation (JPEG, PNG, WebP, TIFF).
+pub(crate) fn source_orientation(info: &ImageInfo) -> Orientation {
+    match info.format {
+        ImageFormat::Heic | ImageFormat::Jxl => return Orientation::Identity,
+        _ if info.orientation.to_exif() != 1 => return info.orientation,
+        ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Tiff => {}
+        _ => return Orientation::Identity,
+    }
+    info.embedded_metadata
+        .exif
+        .as_deref()
+        .and_then(|exif| crate::exif::parse_exif(exif).ok())
+        .and_then(|exif| exif.orientation)
+        .and_then(|o| u8::try_from(o).ok())
+        .and_then(Orientation::from_exif)
+        .unwrap_or_default()
+}
+
+/// `info` for pixels that have had their orientation applied: identity
+/// orientation, swapped dimensions for 5-8, and the EXIF tag reset to 1 so
+/// metadata carried to an encoder doesn't rotate the image again.
+pub(crate) fn normalized_info(info: &ImageInfo, width: u32, height: u32) -> ImageInfo {
+    let mut info = info.clone().with_orientation(Orientation::default());
+    info.width = width;
+    info.height = height;
+    if let Some(exif) = info.embedded_metadata.exif.as_deref() {
+        let mut patched = exif.to_vec();
+        if crate::exif::set_orientation(&mut patched, 1) {
+            info = info.with_exif(patched);
+        }
+    }
+    info
+}
+
+/// Map output pixel `(x, y)` back to the stored pixel it comes from.
+///
+/// `width`/`height` are the stored dimensions.
+fn source_coord(exif: u8, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+    match exif {
+        2 => (width - 1 - x, y),
+        3 => (width - 1 - x, height - 1 - y),
+        4 => (x, height - 1 - y),
+        5 => (y, x),
+        6 => (y, height - 1 - x),
+        7 => (width - 1 - y, height - 1 - x),
+        8 => (width - 1 - y, x),
+        _ => (x, y),
+    }
+}
+
+/// Orient a strided buffer of `bpp`-byte pixels. Returns tightly packed
+/// output and its dimensions.
+pub(crate) fn orient_bytes(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    bpp: usize,
+    exif: u8,
+) -> (Vec<u8>, u32, u32) {
+    let (w, h) = (width as usize, height as usize);
+    let (out_w, out_h) = if (5..=8).contains(&exif) {
+        (h, w)
+    } else {
+        (w, h)
+    };
+    let mut out = Vec::with_capacity(out_w * out_h * bpp);
+    for y in 0..out_h {
+        for x in 0..out_w {
+            let (sx, sy) = source_coord(exif, x, y, w, h);
+            let at = sy * stride + sx * bpp;
+            out.extend_from_slice(&src[at..at + bpp]);
+        }
+    }
+    (out, out_w as u32, out_h as u32)
+}
+
+/// Row sink that collects a push decode and replays it, oriented, into
+/// another sink once the decoder finishes.
+///
+/// Rotations and vertical flips need the last stored row before the first
+/// output row is known, so the whole frame is buffered.
+pub(crate) struct OrientingSink<'a> {
+    inner: &'a mut dyn DecodeRowSink,
+    orientation: Orientation,
+    limits: Option<&'a Limits>,
+    width: u32,
+    height: u32,
+    descriptor: Option<PixelDescriptor>,
+    frame: Vec<u8>,
+    strip: Vec<u8>,
+    pending: Option<(u32, u32)>,
+}
+
+impl<'a> OrientingSink<'a> {
+    pub(crate) fn new(
+        inner: &'a mut dyn DecodeRowSink,
+        orientation: Orientation,
+        limits: Option<&'a Limits>,
+    ) -> Self {
+        Self {
+            inner,
+            orientation,
+            limits,
+            width: 0,
+            height: 0,
+            descriptor: None,
+            frame: Vec::new(),
+            strip: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Copy the strip the decoder last wrote into the frame buffer.
+    fn store_pending(&mut self) {
+        let (Some((y, rows)), Some(descriptor)) = (self.pending.take(), self.descriptor) else {
+            return;
+        };
+        let row_bytes = self.width as usize * descriptor.bytes_per_pixel();
+        let start = y as usize * row_bytes;
+        let len = rows as usize * row_bytes;
+        if let Some(dst) = self.frame.get_mut(start..start + len) {
+            dst.copy_from_slice(&self.strip[..len]);
+        }
+    }
+}
+
+impl DecodeRowSink for OrientingSink<'_> {
+    fn begin(
+        &mut self,
+        width: u32,
+        height: u32,
+        descriptor: PixelDescriptor,
+    ) -> core::result::Result<(), SinkError> {
+        check_limits(
+            self.limits,
+            self.orientation,
+            width,
+            height,
+            descriptor.bytes_per_pixel(),
+        )
+        .map_err(|msg| -> SinkError { alloc::format!("auto-orient: {msg}").into() })?;
+        self.width = width;
+        self.height = height;
+        self.descriptor = Some(descriptor);
+        self.pending = None;
+        self.frame.clear();
+        self.frame.resize(
+            width as usize * height as usize * descriptor.bytes_per_pixel(),
+            0,
+        );
+        Ok(())
+    }
+
+    fn provide_next_buffer(
+        &mut self,
+        y: u32,
+        height: u32,
+        width: u32,
+        descriptor: PixelDescriptor,
+    ) -> core::result::Result<PixelSliceMut<'_>, SinkError> {
+        self.store_pending();
+        if width != self.width || Some(descriptor) != self.descriptor {
+            return Err("auto-orient: strip does not match the announced frame".into());
+        }
+        let stride = width as usize * descriptor.bytes_per_pixel();
+        let needed = stride * height as usize;
+        self.strip.resize(needed, 0);
+        self.pending = Some((y, height));
+        PixelSliceMut::new(&mut self.strip[..needed], width, height, stride, descriptor)
+            .map_err(|e| -> SinkError { alloc::format!("pixel slice: {e}").into() })
+    }
+
+    fn finish(&mut self) -> core::result::Result<(), SinkError> {
+        self.store_pending();
+        let Some(descriptor) = self.descriptor else {
+            return self.inner.finish();
+        };
+        let bpp = descriptor.bytes_per_pixel();
+        let (oriented, width, height) = orient_bytes(
+            &self.frame,
+            self.width,
+            self.height,
+            self.width as usize * bpp,
+            bpp,
+            self.orientation.to_exif(),
+        );
+        self.frame = Vec::new();
+
+        self.inner.begin(width, height, descriptor)?;
+        let row_bytes = width as usize * bpp;
+        let mut dst = self
+            .inner
+            .provide_next_buffer(0, height, width, descriptor)?;
+        for (y, row) in oriented.chunks_exact(row_bytes).enumerate() {
+            dst.row_mut(y as u32)[..row_bytes].copy_from_slice(row);
+        }
+        drop(dst);
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// 3×2 single-byte image:
+    /// ```text
+    /// 1 2 3
+    /// 4 5 6
+    /// ```
+    const SRC: [u8; 6] = [1, 2, 3, 4, 5, 6];
+
+    fn orient(exif: u8) -> (Vec<u8>, u32, u32) {
+        orient_bytes(&SRC, 3, 2, 3, 1, exif)
+    }
+
+    #[test]
+    fn all_eight_orientations() {
+        assert_eq!(orient(1), (vec![1, 2, 3, 4, 5, 6], 3, 2));
+        assert_eq!(orient(2), (vec![3, 2, 1, 6, 5, 4], 3, 2));
+        assert_eq!(orient(3), (vec![6, 5, 4, 3, 2, 1], 3, 2));
+        assert_eq!(orient(4), (vec![4, 5, 6, 1, 2, 3], 3, 2));
+        // Transpose
+        assert_eq!(orient(5), (vec![1, 4, 2, 5, 3, 6], 2, 3));
+        // 90° clockwise: the left column becomes the top row.
+        assert_eq!(orient(6), (vec![4, 1, 5, 2, 6, 3], 2, 3));
+        // Transverse
+        assert_eq!(orient(7), (vec![6, 3, 5, 2, 4, 1], 2, 3));
+        // 90° counter-clockwise: the right column becomes the top row.
+        assert_eq!(orient(8), (vec![3, 6, 2, 5, 1, 4], 2, 3));
+    }
+
+    #[test]
+    fn respects_stride_and_pixel_size() {
+        // 2×2 RG pixels with two bytes of row padding.
+        let src = [1, 1, 2, 2, 0, 0, 3, 3, 4, 4, 0, 0];
+        let (out, w, h) = orient_bytes(&src, 2, 2, 6, 2, 6);
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(out, vec![3, 3, 1, 1, 4, 4, 2, 2]);
+    }
+
+    #[test]
+    fn inverse_pairs_round_trip() {
+        // 6 and 8 undo each other; 2, 3, 4, 5, 7 are their own inverses.
+        for (a, b) in [(6, 8), (8, 6), (2, 2), (3, 3), (4, 4), (5, 5), (7, 7)] {
+            let (once, w, h) = orient(a);
+            let (back, w2, h2) = orient_bytes(&once, w, h, w as usize, 1, b);
+            assert_eq!((back, w2, h2), (SRC.to_vec(), 3, 2), "{a} then {b}");
+        }
+    }
+
+    #[test]
+    fn exif_fallback_only_where_decoders_ignore_orientation() {
+        let mut tiff = vec![
+            b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0, 0x12, 0x01, 3, 0, 1, 0, 0, 0,
+        ];
+        tiff.extend_from_slice(&[6, 0, 0, 0, 0, 0, 0, 0]);
+        let rotate = Orientation::from_exif(6).unwrap();
+        let info = |format| ImageInfo::new(4, 2, format).with_exif(tiff.clone());
+
+        assert_eq!(source_orientation(&info(ImageFormat::Jpeg)), rotate);
+        assert_eq!(source_orientation(&info(ImageFormat::Tiff)), rotate);
+        // HEIC and JXL pixels are already oriented; AVIF reports irot/imir.
+        for format in [ImageFormat::Heic, ImageFormat::Jxl, ImageFormat::Avif] {
+            assert_eq!(source_orientation(&info(format)), Orientation::Identity);
+        }
+        let avif = info(ImageFormat::Avif).with_orientation(rotate);
+        assert_eq!(source_orientation(&avif), rotate);
+        let jxl = info(ImageFormat::Jxl).with_orientation(rotate);
+        assert_eq!(source_orientation(&jxl), Orientation::Identity);
+    }
+
+    #[test]
+    fn limits_cover_both_frames_and_swapped_dimensions() {
+        let rotate = Orientation::from_exif(6).unwrap();
+        let flip = Orientation::from_exif(2).unwrap();
+        // 100×50 RGBA: 20 000 bytes stored, 40 000 while orienting.
+        let memory = Limits::default().with_max_memory_bytes(39_999);
+        assert!(check_limits(Some(&memory), flip, 100, 50, 4).is_err());
+        let memory = Limits::default().with_max_memory_bytes(40_000);
+        assert!(check_limits(Some(&memory), flip, 100, 50, 4).is_ok());
+
+        let narrow = Limits::default().with_max_width(60);
+        assert!(check_limits(Some(&narrow), rotate, 50, 100, 4).is_err());
+        assert!(check_limits(Some(&narrow), rotate, 100, 50, 4).is_ok());
+        assert!(check_limits(None, rotate, u32::MAX, u32::MAX, 16).is_ok());
+    }
+}
@@ -154,6 +154,26 @@ impl CodecPolicy {
         true
     }
 
+    /// Whether the built-in decoder for `format` is allowed.
+    ///
+    /// Formats without a built-in decoder pass unless an allowlist is set.
+    pub fn is_decoder_allowed(&self, format: ImageFormat) -> bool {
+        match CodecId::decoder_for(format) {
+            Some(id) => self.is_codec_allowed(id),
+            None => self.allowed.is_none(),
+        }
+    }
+
+    /// Whether the built-in encoder for `format` is allowed.
+    ///
+    /// Formats without a built-in encoder pass unless an allowlist is set.
+    pub fn is_encoder_allowed(&self, format: ImageFormat) -> bool {
+        match CodecId::encoder_for(format) {
+            Some(id) => self.is_codec_allowed(id),
+            None => self.allowed.is_none(),
+        }
+    }
+
     /// Compute effective priority for a codec entry.
     ///
     /// Adds preference bonus to the base priority from registration.
@@ -278,6 +298,23 @@ mod tests {
         assert!(policy.is_codec_allowed(CodecId::PngDecode));
     }
 
+    #[test]
+    fn killbits_apply_per_format() {
+        let policy = CodecPolicy::new()
+            .with_disabled(CodecId::QoiEncode)
+            .with_disabled(CodecId::ZenrawDecode);
+        assert!(!policy.is_encoder_allowed(ImageFormat::Qoi));
+        assert!(policy.is_decoder_allowed(ImageFormat::Qoi));
+        assert!(policy.is_encoder_allowed(ImageFormat::Tga));
+        #[cfg(feature = "raw-decode")]
+        assert!(!policy.is_decoder_allowed(crate::codecs::raw::dng_format()));
+
+        let policy = CodecPolicy::new().with_allowed(&[CodecId::HdrDecode]);
+        assert!(policy.is_decoder_allowed(ImageFormat::Hdr));
+        assert!(!policy.is_encoder_allowed(ImageFormat::Hdr));
+        assert!(!policy.is_encoder_allowed(ImageFormat::Heic));
+    }
+
     #[test]
     fn preference_bonus() {
         let policy =
@@ -0,0 +1,320 @@
+//! Photoshop PSD and PSB documents (`psd` feature).
+//!
+//! Only the flattened composite that Photoshop stores after the layers is
+//! decoded; layers, effects and adjustment data are ignored. Files saved
+//! without "Maximize Compatibility" may carry a blank composite. Both
+//! variants are one custom format, `ImageFormat::Custom(&PSD_FORMAT)`:
+//!
+//! - [`header`] reads dimensions, depth and color mode.
+//! - [`resources`] extracts the ICC profile, XMP, IPTC, EXIF and the
+//!   embedded JPEG thumbnail without touching pixel data.
+//! - [`DecodeRequest`](crate::DecodeRequest) decodes the composite:
+//!   bitmap, grayscale, duotone (as grayscale), indexed, RGB and CMYK at
+//!   1/8/16/32 bits, raw or RLE-compressed. Output is gray or RGB at the
+//!   source depth (32-bit as linear f32), with alpha when the document has
+//!   merged transparency. 8- and 16-bit CMYK is converted to sRGB through
+//!   [`crate::cmyk`], using the embedded ICC profile with the `cms` feature
+//!   and a naive inversion otherwise (always for 32-bit). The CMYK profile
+//!   is not attached to the RGB output.
+
+use alloc::vec::Vec;
+
+use crate::ImageFormat;
+use crate::error::Result;
+
+/// Format definition for PSD/PSB.
+pub static PSD_FORMAT: zencodec::ImageFormatDefinition = zencodec::ImageFormatDefinition::new(
+    "psd",
+    None,
+    "PSD",
+    "psd",
+    &["psd", "psb"],
+    "image/vnd.adobe.photoshop",
+    &["image/vnd.adobe.photoshop", "application/x-photoshop"],
+    true,
+    false,
+    true,
+    false,
+    6,
+    crate::codecs::psd::is_psd,
+);
+
+/// The PSD [`ImageFormat`].
+pub fn format() -> ImageFormat {
+    ImageFormat::Custom(&PSD_FORMAT)
+}
+
+/// Document color mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PsdColorMode {
+    /// 1-bit black and white.
+    Bitmap,
+    /// Single gray channel.
+    Grayscale,
+    /// 8-bit palette indices.
+    Indexed,
+    /// Red, green, blue.
+    Rgb,
+    /// Cyan, magenta, yellow, black.
+    Cmyk,
+    /// Independent spot channels (not decodable).
+    Multichannel,
+    /// Grayscale data printed with duotone inks.
+    Duotone,
+    /// CIE L*a*b* (not decodable).
+    Lab,
+}
+
+/// Fixed header of a PSD/PSB file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PsdHeader {
+    /// PSB ("large document format").
+    pub large: bool,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Channels in the composite, including alpha and spot channels.
+    pub channels: u16,
+    /// Bits per channel: 1, 8, 16 or 32.
+    pub depth: u16,
+    /// Color mode.
+    pub color_mode: PsdColorMode,
+}
+
+/// Embedded JPEG thumbnail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PsdThumbnail {
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Complete JPEG file.
+    pub jpeg: Vec<u8>,
+}
+
+/// Metadata stored as image resources.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PsdResources {
+    /// ICC profile of the document's color mode.
+    pub icc_profile: Option<Vec<u8>>,
+    /// XMP packet.
+    pub xmp: Option<Vec<u8>>,
+    /// IPTC-NAA record.
+    pub iptc: Option<Vec<u8>>,
+    /// EXIF data (TIFF structure).
+    pub exif: Option<Vec<u8>>,
+    /// Preview thumbnail.
+    pub thumbnail: Option<PsdThumbnail>,
+}
+
+/// Parse the PSD/PSB header.
+pub fn header(data: &[u8]) -> Result<PsdHeader> {
+    crate::codecs::psd::header(data)
+}
+
+/// Extract metadata and the thumbnail from the image resources.
+pub fn resources(data: &[u8]) -> Result<PsdResources> {
+    crate::codecs::psd::resources(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{CodecError, DecodeRequest};
+
+    fn resource(id: u16, body: &[u8]) -> Vec<u8> {
+        let mut out = b"8BIM".to_vec();
+        out.extend_from_slice(&id.to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    /// Minimal PSD: header, empty color data unless indexed, `resources`,
+    /// a layer section holding only `layer_count`, then `image_data`.
+    fn psd(
+        channels: u16,
+        (width, height): (u32, u32),
+        depth: u16,
+        mode: u16,
+        resources: &[u8],
+        layer_count: Option<i16>,
+        image_data: &[u8],
+    ) -> Vec<u8> {
+        let mut out = b"8BPS".to_vec();
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&channels.to_be_bytes());
+        out.extend_from_slice(&height.to_be_bytes());
+        out.extend_from_slice(&width.to_be_bytes());
+        out.extend_from_slice(&depth.to_be_bytes());
+        out.extend_from_slice(&mode.to_be_bytes());
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(&(resources.len() as u32).to_be_bytes());
+        out.extend_from_slice(resources);
+        match layer_count {
+            Some(count) => {
+                out.extend_from_slice(&6u32.to_be_bytes());
+                out.extend_from_slice(&2u32.to_be_bytes());
+                out.extend_from_slice(&count.to_be_bytes());
+            }
+            None => out.extend_from_slice(&0u32.to_be_bytes()),
+        }
+        out.extend_from_slice(image_data);
+        out
+    }
+
+    fn samples(output: &crate::DecodeOutput, bytes_per_pixel: usize) -> Vec<u8> {
+        let pixels = output.pixels();
+        let row_bytes = pixels.width() as usize * bytes_per_pixel;
+        let bytes = pixels.as_strided_bytes();
+        (0..pixels.rows() as usize)
+            .flat_map(|y| &bytes[y * pixels.stride()..][..row_bytes])
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn rle_rgb_with_merged_transparency_and_resources() {
+        let mut resources = resource(1039, b"icc-profile");
+        resources.extend(resource(1060, b"<x:xmpmeta/>"));
+        resources.extend(resource(1028, b"iptc"));
+        let mut thumb = Vec::new();
+        for v in [1u32, 4, 3, 12, 36, 5] {
+            thumb.extend_from_slice(&v.to_be_bytes());
+        }
+        thumb.extend_from_slice(&24u16.to_be_bytes());
+        thumb.extend_from_slice(&1u16.to_be_bytes());
+        thumb.extend_from_slice(&[0xff, 0xd8, 0xff, 0xd9, 0]);
+        resources.extend(resource(1036, &thumb));
+
+        // 2×1 RGBA: opaque red, then half-transparent red matted on white.
+        // R is a two-byte repeat run; the other rows are literals.
+        let rows: [&[u8]; 4] = [&[0xff, 255], &[1, 0, 127], &[1, 0, 127], &[1, 255, 128]];
+        let mut image = 1u16.to_be_bytes().to_vec();
+        for row in rows {
+            image.extend_from_slice(&(row.len() as u16).to_be_bytes());
+        }
+        for row in rows {
+            image.extend_from_slice(row);
+        }
+        let data = psd(4, (2, 1), 8, 3, &resources, Some(-1), &image);
+
+        let header = header(&data).unwrap();
+        assert_eq!((header.width, header.height), (2, 1));
+        assert_eq!(header.color_mode, PsdColorMode::Rgb);
+        assert!(!header.large);
+
+        let res = super::resources(&data).unwrap();
+        assert_eq!(res.icc_profile.as_deref(), Some(&b"icc-profile"[..]));
+        assert_eq!(res.xmp.as_deref(), Some(&b"<x:xmpmeta/>"[..]));
+        assert_eq!(res.iptc.as_deref(), Some(&b"iptc"[..]));
+        let thumb = res.thumbnail.unwrap();
+        assert_eq!((thumb.width, thumb.height), (4, 3));
+        assert!(thumb.jpeg.starts_with(&[0xff, 0xd8]));
+
+        let info = crate::from_bytes(&data).unwrap();
+        assert_eq!(info.format, format());
+        assert!(info.has_alpha);
+
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(samples(&output, 4), [255, 0, 0, 255, 255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn raw_gray16_and_extra_channels_without_transparency() {
+        // A spot channel, but no negative layer count: no alpha.
+        let mut image = 0u16.to_be_bytes().to_vec();
+        for v in [0u16, 0x8000, 0xffff, 7, 7, 7] {
+            image.extend_from_slice(&v.to_be_bytes());
+        }
+        let data = psd(2, (3, 1), 16, 1, &[], None, &image);
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert!(!output.info().has_alpha);
+        let values: Vec<u16> = samples(&output, 2)
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(values, [0, 0x8000, 0xffff]);
+    }
+
+    #[test]
+    fn cmyk_is_converted_and_zip_is_rejected() {
+        // Stored inverted: no cyan, full magenta and yellow, no black.
+        let mut image = 0u16.to_be_bytes().to_vec();
+        image.extend_from_slice(&[255, 0, 0, 255]);
+        let data = psd(4, (1, 1), 8, 4, &resource(1039, b"cmyk"), None, &image);
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(samples(&output, 3), [255, 0, 0]);
+        assert_eq!(
+            super::resources(&data).unwrap().icc_profile.as_deref(),
+            Some(&b"cmyk"[..])
+        );
+
+        let zip = psd(3, (1, 1), 8, 3, &[], None, &[0, 2, 0x78, 0x9c]);
+        let result = DecodeRequest::new(&zip).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::UnsupportedOperation { .. })
+        ));
+    }
+
+    #[test]
+    fn cmyk16_is_converted() {
+        // Stored inverted: full cyan, half black.
+        let mut image = 0u16.to_be_bytes().to_vec();
+        for v in [0u16, 0xffff, 0xffff, 0x8000] {
+            image.extend_from_slice(&v.to_be_bytes());
+        }
+        let data = psd(4, (1, 1), 16, 4, &[], None, &image);
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        let values: Vec<u16> = samples(&output, 6)
+            .chunks_exact(2)
+            .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(values, [0, 0x8000, 0x8000]);
+    }
+
+    #[test]
+    fn short_rle_rows_are_rejected_before_allocating() {
+        // 30000×30000 gray with all-zero row byte counts: a 900 MB plane
+        // that the data can't fill.
+        let mut image = 1u16.to_be_bytes().to_vec();
+        image.resize(2 + 30_000 * 2, 0);
+        let data = psd(1, (30_000, 30_000), 8, 1, &[], None, &image);
+        let result = DecodeRequest::new(&data).decode_full_frame();
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn indexed_uses_palette_and_transparent_index() {
+        let mut palette = vec![0u8; 768];
+        palette[1] = 10;
+        palette[256 + 1] = 20;
+        palette[512 + 1] = 30;
+        let mut data = psd(
+            1,
+            (2, 1),
+            8,
+            2,
+            &resource(1047, &0u16.to_be_bytes()),
+            None,
+            &[0, 0, 1, 0],
+        );
+        // Splice the palette into the color mode data section.
+        data.splice(26..30, 768u32.to_be_bytes().into_iter().chain(palette));
+        let output = DecodeRequest::new(&data).decode_full_frame().unwrap();
+        assert_eq!(samples(&output, 4), [10, 20, 30, 255, 0, 0, 0, 0]);
+    }
+}
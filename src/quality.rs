@@ -301,6 +301,44 @@ fn interpolate(table: &[AnchorPoint], quality: f32) -> f32 {
     table[table.len() - 1].1
 }
 
+/// Inverse of [`interpolate`]: the generic quality that maps to `native`.
+///
+/// The table's native values must be non-decreasing. Values outside the
+/// table range are clamped to the nearest endpoint.
+fn invert(table: &[AnchorPoint], native: f32) -> f32 {
+    if table.is_empty() {
+        return native;
+    }
+    if native <= table[0].1 {
+        return table[0].0;
+    }
+    if native >= table[table.len() - 1].1 {
+        return table[table.len() - 1].0;
+    }
+    for window in table.windows(2) {
+        let (q0, v0) = window[0];
+        let (q1, v1) = window[1];
+        if native >= v0 && native <= v1 {
+            if v1 == v0 {
+                return q0;
+            }
+            let t = (native - v0) / (v1 - v0);
+            return q0 + t * (q1 - q0);
+        }
+    }
+    table[table.len() - 1].0
+}
+
+/// Generic quality (0-100) that would produce this native JPEG quality.
+pub fn generic_quality_from_jpeg(jpeg_quality: f32) -> f32 {
+    invert(&JPEG_TABLE, jpeg_quality)
+}
+
+/// Generic quality (0-100) that would produce this native WebP lossy quality.
+pub fn generic_quality_from_webp(webp_quality: f32) -> f32 {
+    invert(&WEBP_TABLE, webp_quality)
+}
+
 // Anchor points: (generic_quality, codec_native_value)
 // Derived from imageflow's QualityProfileHints calibration.
 
@@ -466,6 +504,19 @@ mod tests {
         assert_eq!(jpeg_q, 82); // Linear between 73 and 91
     }
 
+    #[test]
+    fn invert_round_trips_calibration() {
+        for q in [20.0f32, 40.0, 55.0, 73.0, 82.0, 91.0, 99.0] {
+            let intent = QualityIntent::from_quality(q);
+            assert!((generic_quality_from_webp(intent.webp_quality()) - q).abs() < 0.01);
+            let jpeg = interpolate(&JPEG_TABLE, q);
+            assert!((generic_quality_from_jpeg(jpeg) - q).abs() < 0.01);
+        }
+        // Clamped to the table's endpoints
+        assert_eq!(generic_quality_from_jpeg(5.0), 15.0);
+        assert_eq!(generic_quality_from_webp(100.0), 100.0);
+    }
+
     #[test]
     fn interpolation_clamping() {
         // Below minimum anchor
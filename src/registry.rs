@@ -34,8 +34,22 @@ const fn compiled_both() -> FormatSet {
         .with_const(ImageFormat::Farbfeld);
     #[cfg(feature = "bitmaps-bmp")]
     let s = s.with_const(ImageFormat::Bmp);
+    #[cfg(feature = "bitmaps-qoi")]
+    let s = s.with_const(ImageFormat::Qoi);
+    #[cfg(feature = "bitmaps-tga")]
+    let s = s.with_const(ImageFormat::Tga);
+    #[cfg(feature = "bitmaps-hdr")]
+    let s = s.with_const(ImageFormat::Hdr);
     #[cfg(feature = "tiff")]
     let s = s.with_const(ImageFormat::Tiff);
+    #[cfg(feature = "exr")]
+    let s = s.with_const(ImageFormat::Custom(&crate::exr::EXR_FORMAT));
+    #[cfg(feature = "ico")]
+    let s = s.with_const(ImageFormat::Custom(&crate::ico::ICO_FORMAT));
+    #[cfg(feature = "dds")]
+    let s = s.with_const(ImageFormat::Custom(&crate::dds::DDS_FORMAT));
+    #[cfg(feature = "ktx2")]
+    let s = s.with_const(ImageFormat::Custom(&crate::ktx2::KTX2_FORMAT));
     s
 }
 
@@ -48,6 +62,13 @@ const COMPILED_DECODE: FormatSet = {
     let s = s.with_const(ImageFormat::Jxl);
     #[cfg(feature = "heic-decode")]
     let s = s.with_const(ImageFormat::Heic);
+    // Covers both DNG and generic RAW (they share a bit).
+    #[cfg(feature = "raw-decode")]
+    let s = s.with_const(ImageFormat::Custom(&zenraw::DNG_FORMAT));
+    #[cfg(feature = "psd")]
+    let s = s.with_const(ImageFormat::Custom(&crate::psd::PSD_FORMAT));
+    #[cfg(feature = "jp2-decode")]
+    let s = s.with_const(ImageFormat::Custom(&crate::jp2::JP2_FORMAT));
     s
 };
 
@@ -71,7 +92,7 @@ const COMPILED_ENCODE: FormatSet = {
 /// Compile-time features determine which codecs are *linked in*; this struct
 /// controls which are *allowed* at runtime.
 ///
-/// `Copy` — 4 bytes (two `u16` bitflag sets). Pass by value.
+/// `Copy` — 16 bytes (two `u64` bitflag sets). Pass by value.
 ///
 /// # Format capabilities
 ///
@@ -85,9 +106,12 @@ const COMPILED_ENCODE: FormatSet = {
 ///
 /// # Custom formats
 ///
-/// Custom formats (e.g., RAW/DNG via `ImageFormat::Custom`) are not tracked
-/// by the bitflag sets and are always considered disabled. Use format-specific
-/// decode APIs for custom formats.
+/// The crate's own `ImageFormat::Custom` formats each have a bit in the
+/// sets, after the built-in formats: OpenEXR, ICO, PSD, DDS, KTX2 and
+/// JPEG 2000, plus RAW/DNG (zenraw's definitions), which share one bit so
+/// enabling or disabling either affects both. They are matched by name and
+/// can be toggled like any built-in format. Custom formats defined outside
+/// this crate have no bit and are always considered disabled.
 #[derive(Clone, Copy, Debug)]
 pub struct AllowedFormats {
     decode: FormatSet,
@@ -342,4 +366,32 @@ mod tests {
             assert!(af.can_encode(ImageFormat::Gif));
         }
     }
+
+    #[test]
+    #[cfg(all(
+        feature = "bitmaps-qoi",
+        feature = "bitmaps-tga",
+        feature = "bitmaps-hdr"
+    ))]
+    fn bitmap_extras_are_first_class() {
+        let af = AllowedFormats::all();
+        for format in [ImageFormat::Qoi, ImageFormat::Tga, ImageFormat::Hdr] {
+            assert!(af.can_decode(format), "{format:?}");
+            assert!(af.can_encode(format), "{format:?}");
+        }
+        let af = af.with_decode(ImageFormat::Tga, false);
+        assert!(!af.can_decode(ImageFormat::Tga));
+        assert!(af.can_encode(ImageFormat::Tga));
+    }
+
+    #[test]
+    #[cfg(feature = "raw-decode")]
+    fn raw_is_decode_only() {
+        let af = AllowedFormats::all();
+        assert!(af.can_decode(crate::codecs::raw::dng_format()));
+        assert!(af.can_decode(crate::codecs::raw::raw_format()));
+        assert!(!af.can_encode(crate::codecs::raw::dng_format()));
+        let af = af.with_decode(crate::codecs::raw::raw_format(), false);
+        assert!(!af.can_decode(crate::codecs::raw::dng_format()));
+    }
 }
@@ -91,6 +91,7 @@ fn parse_intent(keys: &BTreeMap<String, String>) -> CodecIntent {
         allowed,
         hints,
         matte,
+        cap_to_source_quality: false,
     }
 }
 
@@ -22,6 +22,8 @@ use crate::quality::QualityIntent;
 use crate::registry::AllowedFormats;
 use crate::trace::{SelectionStep, SelectionTrace};
 use crate::{CodecError, ImageFormat};
+use whereat::at;
+use zenpixels::{PixelDescriptor, PixelSlice};
 
 /// Facts about the image being encoded. Drives format selection.
 ///
@@ -41,6 +43,38 @@ pub struct ImageFacts {
     pub is_hdr: bool,
     /// Source format, if known. Used for `FormatChoice::Keep`.
     pub source_format: Option<ImageFormat>,
+    /// Every alpha value is either 0 or 255 (cutout transparency).
+    ///
+    /// Only meaningful when `has_alpha` is set.
+    pub alpha_is_binary: bool,
+    /// Color data is stored as RGB but every pixel has R == G == B.
+    pub is_grayscale: bool,
+    /// Number of distinct RGBA colors, when there are at most 256.
+    ///
+    /// `None` means either "more than 256" or "not analyzed".
+    pub palette_size: Option<u16>,
+    /// Photo vs. synthetic graphic classification.
+    pub content: ContentKind,
+    /// Estimated quality of a lossy source, on the generic 0-100 scale.
+    ///
+    /// Set by [`with_source_quality`](Self::with_source_quality). Used by
+    /// [`CodecIntent::cap_to_source_quality`].
+    pub source_quality: Option<f32>,
+}
+
+/// Coarse content classification from pixel statistics.
+///
+/// Graphics (screenshots, logos, line art, UI) compress far better
+/// losslessly than photos, and lossy codecs smear their hard edges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContentKind {
+    /// Not analyzed, or too small to classify.
+    #[default]
+    Unknown,
+    /// Natural image: noise and smooth gradients everywhere.
+    Photo,
+    /// Synthetic image: large flat regions and hard edges.
+    Graphic,
 }
 
 impl ImageFacts {
@@ -58,13 +92,208 @@ impl ImageFacts {
                     | ImageFormat::Farbfeld
             ),
             pixel_count: info.width as u64 * info.height as u64,
+            // Scene-linear float sources (EXR) are HDR without a PQ/HLG tag.
             is_hdr: info.source_color.cicp.as_ref().is_some_and(|c| {
                 matches!(
                     c.transfer_function_enum(),
                     zenpixels::TransferFunction::Pq | zenpixels::TransferFunction::Hlg
                 )
-            }),
+            }) || matches!(info.format, ImageFormat::Custom(def) if def.name == "exr"),
             source_format: Some(info.format),
+            ..Default::default()
+        }
+    }
+
+    /// Estimate the source's encode quality from its header.
+    ///
+    /// Reads JPEG quantization tables or the WebP quantizer from the
+    /// encoded `data`; see [`estimate_source_quality`](crate::source_quality::estimate_source_quality).
+    /// Leaves `source_quality` as `None` for other formats.
+    pub fn with_source_quality(mut self, data: &[u8]) -> Self {
+        self.source_quality =
+            crate::source_quality::estimate_source_quality(data).map(|sq| sq.generic);
+        self
+    }
+
+    /// Derive facts by scanning decoded pixels.
+    ///
+    /// Unlike [`from_image_info`](Self::from_image_info), which trusts the
+    /// header, this only reports `has_alpha` when some alpha value is
+    /// actually below 255. Source-level facts (`source_format`,
+    /// `has_animation`, `is_hdr`, `is_lossless_source`) are left at their
+    /// defaults; use [`with_analysis`](Self::with_analysis) to refine facts
+    /// obtained from the header instead.
+    pub fn analyze(pixels: &PixelSlice<'_>) -> crate::Result<Self> {
+        Self::default().with_analysis(pixels)
+    }
+
+    /// Refine these facts with statistics from decoded pixels.
+    ///
+    /// Overwrites `has_alpha`, `alpha_is_binary`, `is_grayscale`,
+    /// `palette_size`, `content` and `pixel_count`; keeps everything else.
+    pub fn with_analysis(mut self, pixels: &PixelSlice<'_>) -> crate::Result<Self> {
+        let width = pixels.width();
+        let rows = pixels.rows();
+        // Normalize to packed RGBA8 so the scan below has one code path.
+        let adapted = zenpixels_convert::adapt::adapt_for_encode_cow(
+            pixels.as_strided_bytes(),
+            pixels.descriptor(),
+            width,
+            rows,
+            pixels.stride(),
+            &[PixelDescriptor::RGBA8_SRGB],
+        )
+        .map_err(|e| {
+            at!(CodecError::InvalidInput(alloc::format!(
+                "pixel format for analysis: {e}"
+            )))
+        })?;
+        let rgba = adapted.as_slice();
+
+        let stats = PixelStats::scan(&rgba.contiguous_bytes(), width as usize);
+        self.pixel_count = width as u64 * rows as u64;
+        self.has_alpha = stats.has_alpha;
+        self.alpha_is_binary = stats.has_alpha && stats.alpha_is_binary;
+        self.is_grayscale = stats.is_grayscale;
+        self.palette_size = stats.palette_size();
+        self.content = stats.classify();
+        Ok(self)
+    }
+
+    /// Content looks synthetic enough that a lossless encode is likely both
+    /// smaller and sharper than a lossy one.
+    pub fn prefers_lossless(&self) -> bool {
+        self.content == ContentKind::Graphic
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════
+// Pixel statistics
+// ═══════════════════════════════════════════════════════════════════════
+
+/// Images with at most this many colors fit a PNG/WebP palette.
+const MAX_PALETTE_COLORS: usize = 256;
+
+/// Below this many neighbor pairs, edge statistics are too noisy to classify.
+const MIN_CLASSIFY_PAIRS: u64 = 64;
+
+/// Luma steps up to this size count as "smooth gradient" (photo texture).
+const SMOOTH_STEP_MAX: u8 = 8;
+
+/// Luma steps above this size count as "hard edge".
+const HARD_EDGE_MIN: u8 = 48;
+
+/// Accumulated statistics from one pass over RGBA8 pixels.
+struct PixelStats {
+    has_alpha: bool,
+    alpha_is_binary: bool,
+    is_grayscale: bool,
+    /// Distinct colors seen, sorted; abandoned (None) past the palette limit.
+    colors: Option<alloc::vec::Vec<u32>>,
+    /// Horizontal neighbor pairs examined.
+    pairs: u64,
+    /// Pairs with identical luma.
+    flat: u64,
+    /// Pairs with a small nonzero luma step.
+    smooth: u64,
+    /// Pairs with a large luma step.
+    edges: u64,
+}
+
+impl PixelStats {
+    fn scan(rgba: &[u8], width: usize) -> Self {
+        let mut stats = PixelStats {
+            has_alpha: false,
+            alpha_is_binary: true,
+            is_grayscale: true,
+            colors: Some(alloc::vec::Vec::new()),
+            pairs: 0,
+            flat: 0,
+            smooth: 0,
+            edges: 0,
+        };
+        if width == 0 {
+            return stats;
+        }
+
+        for row in rgba.chunks_exact(width * 4) {
+            let mut prev_luma: Option<u8> = None;
+            let mut prev_color: Option<u32> = None;
+            for px in row.chunks_exact(4) {
+                let (r, g, b, a) = (px[0], px[1], px[2], px[3]);
+                if a != 255 {
+                    stats.has_alpha = true;
+                    if a != 0 {
+                        stats.alpha_is_binary = false;
+                    }
+                }
+                if r != g || g != b {
+                    stats.is_grayscale = false;
+                }
+
+                let color = u32::from_be_bytes([r, g, b, a]);
+                if prev_color != Some(color) {
+                    stats.note_color(color);
+                    prev_color = Some(color);
+                }
+
+                let luma = ((r as u16 * 2 + g as u16 * 5 + b as u16) >> 3) as u8;
+                if let Some(prev) = prev_luma {
+                    let step = luma.abs_diff(prev);
+                    stats.pairs += 1;
+                    if step == 0 {
+                        stats.flat += 1;
+                    } else if step <= SMOOTH_STEP_MAX {
+                        stats.smooth += 1;
+                    } else if step >= HARD_EDGE_MIN {
+                        stats.edges += 1;
+                    }
+                }
+                prev_luma = Some(luma);
+            }
+        }
+        stats
+    }
+
+    fn note_color(&mut self, color: u32) {
+        if let Some(colors) = &mut self.colors
+            && let Err(pos) = colors.binary_search(&color)
+        {
+            if colors.len() == MAX_PALETTE_COLORS {
+                self.colors = None;
+            } else {
+                colors.insert(pos, color);
+            }
+        }
+    }
+
+    fn palette_size(&self) -> Option<u16> {
+        self.colors.as_ref().map(|c| c.len() as u16)
+    }
+
+    /// Photo vs. graphic from edge/gradient statistics.
+    ///
+    /// Photos are dominated by sensor noise and soft gradients, so exact
+    /// repeats of neighboring luma are uncommon. Graphics are dominated by
+    /// flat fills, with transitions that are mostly hard edges (or short
+    /// anti-aliasing ramps).
+    fn classify(&self) -> ContentKind {
+        if self.pairs < MIN_CLASSIFY_PAIRS {
+            return ContentKind::Unknown;
+        }
+        // Few colors is conclusive for color images; a grayscale photo
+        // never has more than 256 levels, so it proves nothing there.
+        if self.colors.is_some() && !self.is_grayscale {
+            return ContentKind::Graphic;
+        }
+        let pairs = self.pairs as f64;
+        let flat = self.flat as f64 / pairs;
+        let smooth = self.smooth as f64 / pairs;
+        let edges = self.edges as f64 / pairs;
+        if (flat >= 0.6 && smooth < 0.2) || (flat >= 0.4 && edges > smooth) {
+            ContentKind::Graphic
+        } else {
+            ContentKind::Photo
         }
     }
 }
@@ -73,6 +302,11 @@ impl ImageFacts {
 pub struct FormatSelection {
     /// The chosen format.
     pub format: ImageFormat,
+    /// Encode the chosen format losslessly.
+    ///
+    /// Set when lossless was requested, and also when lossy was requested
+    /// but the content is a graphic routed to a lossless-capable format.
+    pub lossless: bool,
     /// Decision audit trail.
     pub trace: SelectionTrace,
 }
@@ -89,6 +323,7 @@ pub struct FormatSelection {
 /// | Lossless | JXL → WebP → PNG → AVIF |
 /// | Animation (lossy) | AVIF → WebP → GIF |
 /// | Animation (lossless) | WebP → GIF |
+/// | Graphic content (lossy) | WebP → PNG → JXL (lossless), then AVIF → JPEG |
 /// | Alpha (lossy) | JXL → AVIF → WebP → PNG |
 /// | Lossy opaque (< 3MP) | JXL → AVIF → JPEG → WebP → PNG |
 /// | Lossy opaque (≥ 3MP) | JXL → JPEG → AVIF → WebP → PNG |
@@ -116,6 +351,13 @@ pub fn select_format(
             });
             return false;
         }
+        if !policy.is_encoder_allowed(format) {
+            trace.push(SelectionStep::FormatSkipped {
+                format,
+                reason: "encoder disabled by policy",
+            });
+            return false;
+        }
         if intent.lossless && !format.supports_lossless() {
             trace.push(SelectionStep::FormatSkipped {
                 format,
@@ -134,8 +376,15 @@ pub fn select_format(
                 format: *format,
                 reason,
             });
+            let lossless = intent.lossless
+                || (routes_graphic_to_lossless(facts, intent)
+                    && matches!(
+                        format,
+                        ImageFormat::WebP | ImageFormat::Png | ImageFormat::Jxl
+                    ));
             return Ok(FormatSelection {
                 format: *format,
+                lossless,
                 trace,
             });
         }
@@ -166,10 +415,23 @@ pub fn select_format_from_intent(
     policy: &CodecPolicy,
 ) -> crate::Result<FormatDecision> {
     let lossless = intent.resolve_lossless(facts.is_lossless_source);
-    let quality_value = intent.effective_quality();
+    let mut trace_steps = alloc::vec::Vec::new();
+
+    let mut quality_value = intent.effective_quality();
+    if intent.cap_to_source_quality
+        && !lossless
+        && let Some(source) = facts.source_quality
+        && source < quality_value
+    {
+        quality_value = source;
+        trace_steps.push(SelectionStep::Info {
+            message: "quality capped at estimated source quality",
+        });
+    }
     let quality_intent = QualityIntent::from_quality(quality_value).with_lossless(lossless);
 
-    let mut trace_steps = alloc::vec::Vec::new();
+    // Auto-selection may upgrade a lossy request to lossless for graphics.
+    let mut lossless = lossless;
 
     // Resolve format choice
     let format = match intent.format {
@@ -178,7 +440,7 @@ pub fn select_format_from_intent(
             if !registry.can_encode(fmt) {
                 return Err(whereat::at!(CodecError::UnsupportedFormat(fmt)));
             }
-            if !policy.is_format_allowed(fmt) {
+            if !policy.is_format_allowed(fmt) || !policy.is_encoder_allowed(fmt) {
                 return Err(whereat::at!(CodecError::UnsupportedFormat(fmt)));
             }
             trace_steps.push(SelectionStep::FormatChosen {
@@ -190,7 +452,10 @@ pub fn select_format_from_intent(
         Some(FormatChoice::Keep) => {
             // Use source format if known
             if let Some(src_fmt) = facts.source_format {
-                if registry.can_encode(src_fmt) && policy.is_format_allowed(src_fmt) {
+                if registry.can_encode(src_fmt)
+                    && policy.is_format_allowed(src_fmt)
+                    && policy.is_encoder_allowed(src_fmt)
+                {
                     trace_steps.push(SelectionStep::FormatChosen {
                         format: src_fmt,
                         reason: "keep source format",
@@ -210,6 +475,7 @@ pub fn select_format_from_intent(
                         &intent.allowed,
                     )?;
                     trace_steps.extend(sel.trace.steps().iter().cloned());
+                    lossless = sel.lossless;
                     sel.format
                 }
             } else {
@@ -225,6 +491,7 @@ pub fn select_format_from_intent(
                     &intent.allowed,
                 )?;
                 trace_steps.extend(sel.trace.steps().iter().cloned());
+                lossless = sel.lossless;
                 sel.format
             }
         }
@@ -237,6 +504,7 @@ pub fn select_format_from_intent(
                 &intent.allowed,
             )?;
             trace_steps.extend(sel.trace.steps().iter().cloned());
+            lossless = sel.lossless;
             sel.format
         }
     };
@@ -244,12 +512,27 @@ pub fn select_format_from_intent(
     // Extract per-codec hints for the selected format
     let hints: BTreeMap<String, String> = intent.hints.for_format(format).clone();
 
+    // The only lossless way to JPEG is to keep the source's coefficients:
+    // rebuilt from a JXL's `jbrd` box, or copied from a JPEG.
+    let lossless_jpeg_transcode = lossless
+        && format == ImageFormat::Jpeg
+        && matches!(
+            facts.source_format,
+            Some(ImageFormat::Jxl | ImageFormat::Jpeg)
+        );
+    if lossless_jpeg_transcode {
+        trace_steps.push(SelectionStep::Info {
+            message: "lossless transcode to JPEG: keep the source's DCT coefficients",
+        });
+    }
+
     Ok(FormatDecision {
         format,
         quality: quality_intent,
         lossless,
         hints,
         matte: intent.matte,
+        lossless_jpeg_transcode,
         trace: trace_steps,
     })
 }
@@ -269,7 +552,7 @@ fn select_format_with_allowed(
             .cloned()
             .unwrap_or_else(FormatSet::all);
         let merged = base_formats.intersection(allowed);
-        CodecPolicy::new().with_allowed_formats(merged)
+        policy.clone().with_allowed_formats(merged)
     } else {
         policy.clone()
     };
@@ -281,13 +564,69 @@ fn select_format_with_allowed(
 pub fn available_encode_formats(registry: &AllowedFormats, policy: &CodecPolicy) -> FormatSet {
     let mut set = FormatSet::EMPTY;
     for format in registry.encodable_formats() {
-        if policy.is_format_allowed(format) {
+        if policy.is_format_allowed(format) && policy.is_encoder_allowed(format) {
             set.insert(format);
         }
     }
     set
 }
 
+/// Formats that take part in competitive encoding, strongest first.
+///
+/// Legacy and uncompressed formats are left out: they never win on size.
+/// GIF is left out too; for stills, PNG covers the same palette cases.
+const COMPETITION_FORMATS: [ImageFormat; 5] = [
+    ImageFormat::Jxl,
+    ImageFormat::Avif,
+    ImageFormat::WebP,
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+];
+
+/// Candidate formats for competitive encoding ("smallest acceptable wins").
+///
+/// Applies the same registry, policy, alpha and lossless checks as
+/// [`select_format`], recording skipped formats in `trace`. With
+/// `searchable_only`, only formats that support quality search (JPEG,
+/// WebP, AVIF, JXL) are kept.
+pub(crate) fn competition_candidates(
+    facts: &ImageFacts,
+    intent: &QualityIntent,
+    searchable_only: bool,
+    registry: &AllowedFormats,
+    policy: &CodecPolicy,
+    trace: &mut SelectionTrace,
+) -> alloc::vec::Vec<ImageFormat> {
+    let mut candidates = alloc::vec::Vec::new();
+    for format in COMPETITION_FORMATS {
+        let reason = if !registry.can_encode(format) {
+            Some("not registered or compiled")
+        } else if !policy.is_format_allowed(format) {
+            Some("not allowed by policy")
+        } else if !policy.is_encoder_allowed(format) {
+            Some("encoder disabled by policy")
+        } else if intent.lossless && !format.supports_lossless() {
+            Some("no lossless support")
+        } else if facts.has_alpha && !format.supports_alpha() {
+            Some("no alpha support")
+        } else if searchable_only
+            && !matches!(
+                format,
+                ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Avif | ImageFormat::Jxl
+            )
+        {
+            Some("no quality search support")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => trace.push(SelectionStep::FormatSkipped { format, reason }),
+            None => candidates.push(format),
+        }
+    }
+    candidates
+}
+
 /// Build the preference order for format selection.
 ///
 /// Returns a list of (format, reason) pairs in preference order.
@@ -298,8 +637,17 @@ fn build_preference_order<'a>(
 ) -> alloc::vec::Vec<(ImageFormat, &'a str)> {
     let mut order = alloc::vec::Vec::with_capacity(6);
 
-    if intent.lossless {
+    if intent.lossless && facts.has_animation {
+        // Lossless animation path
+        order.push((ImageFormat::Jxl, "best lossless animated compression"));
+        order.push((ImageFormat::WebP, "good lossless animated compression"));
+        order.push((ImageFormat::Png, "APNG, universal lossless animation"));
+    } else if intent.lossless {
         // Lossless path
+        #[cfg(feature = "exr")]
+        if facts.is_hdr {
+            order.push((crate::exr::format(), "lossless float HDR"));
+        }
         order.push((ImageFormat::Jxl, "best lossless compression"));
         order.push((ImageFormat::WebP, "good lossless compression"));
         order.push((ImageFormat::Png, "universal lossless"));
@@ -309,6 +657,17 @@ fn build_preference_order<'a>(
         order.push((ImageFormat::Avif, "best animated compression"));
         order.push((ImageFormat::WebP, "animated, good compression"));
         order.push((ImageFormat::Gif, "animated, universal fallback"));
+        order.push((ImageFormat::Png, "APNG, lossless animated fallback"));
+    } else if routes_graphic_to_lossless(facts, intent) {
+        // Screenshots, logos, line art: lossless beats lossy on both size
+        // and fidelity. Lossy formats stay as fallbacks for strict policies.
+        order.push((ImageFormat::WebP, "graphic content, compact lossless"));
+        order.push((ImageFormat::Png, "graphic content, universal lossless"));
+        order.push((ImageFormat::Jxl, "graphic content, lossless"));
+        order.push((ImageFormat::Avif, "graphic content, lossy fallback"));
+        if !facts.has_alpha {
+            order.push((ImageFormat::Jpeg, "graphic content, last resort"));
+        }
     } else if facts.has_alpha {
         // Lossy with alpha
         order.push((ImageFormat::Jxl, "best lossy alpha compression"));
@@ -335,6 +694,11 @@ fn build_preference_order<'a>(
     order
 }
 
+/// A lossy, still request whose content analysis says "graphic".
+fn routes_graphic_to_lossless(facts: &ImageFacts, intent: &QualityIntent) -> bool {
+    !intent.lossless && !facts.has_animation && facts.prefers_lossless()
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::string::ToString;
@@ -426,6 +790,24 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "exr")]
+    #[test]
+    fn lossless_hdr_prefers_exr() {
+        let facts = ImageFacts {
+            pixel_count: 1_000_000,
+            is_hdr: true,
+            ..Default::default()
+        };
+        let intent = QualityIntent::from_quality(100.0).with_lossless(true);
+        assert_eq!(select(&facts, &intent), crate::exr::format());
+
+        let sdr = ImageFacts {
+            is_hdr: false,
+            ..facts
+        };
+        assert_ne!(select(&sdr, &intent), crate::exr::format());
+    }
+
     #[test]
     fn policy_restricts_format() {
         let facts = ImageFacts {
@@ -445,6 +827,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn killbit_skips_format() {
+        let facts = ImageFacts {
+            pixel_count: 10_000_000,
+            ..Default::default()
+        };
+        let intent = QualityIntent::from_quality(73.0);
+        let registry = AllowedFormats::all();
+        let policy =
+            CodecPolicy::web_safe_output().with_disabled(crate::codec_id::CodecId::ZenjpegEncode);
+        let available = available_encode_formats(&registry, &policy);
+        assert!(!available.contains(ImageFormat::Jpeg));
+        let result = select_format(&facts, &intent, &registry, &policy);
+        if available.is_empty() {
+            // Only JPEG was compiled in, so nothing is left to pick.
+            assert!(matches!(
+                result.as_ref().map_err(|e| e.error()),
+                Err(CodecError::NoSuitableEncoder)
+            ));
+        } else {
+            assert_ne!(result.unwrap().format, ImageFormat::Jpeg);
+        }
+
+        // Killbits survive the intent's format allowlist.
+        let intent = CodecIntent {
+            allowed: FormatSet::web_safe(),
+            ..Default::default()
+        };
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy);
+        if available.is_empty() {
+            assert!(decision.is_err());
+        } else {
+            assert_ne!(decision.unwrap().format, ImageFormat::Jpeg);
+        }
+    }
+
     #[test]
     fn trace_records_decisions() {
         let facts = ImageFacts::default();
@@ -480,6 +898,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lossless_animation_prefers_jxl_webp_then_apng() {
+        let facts = ImageFacts {
+            has_animation: true,
+            pixel_count: 500_000,
+            ..Default::default()
+        };
+        let intent = QualityIntent::from_quality(100.0).with_lossless(true);
+        let order: alloc::vec::Vec<_> = build_preference_order(&facts, &intent)
+            .into_iter()
+            .map(|(f, _)| f)
+            .collect();
+        assert_eq!(
+            order,
+            [ImageFormat::Jxl, ImageFormat::WebP, ImageFormat::Png]
+        );
+
+        #[cfg(feature = "png")]
+        {
+            let registry = AllowedFormats::all();
+            let policy = CodecPolicy::new().with_allowed_formats(
+                FormatSet::EMPTY
+                    .with(ImageFormat::Png)
+                    .with(ImageFormat::Gif),
+            );
+            let sel = select_format(&facts, &intent, &registry, &policy).unwrap();
+            assert_eq!(sel.format, ImageFormat::Png);
+            assert!(sel.lossless);
+        }
+    }
+
     #[test]
     fn no_encoder_returns_error() {
         let facts = ImageFacts::default();
@@ -490,6 +939,226 @@ mod tests {
         assert!(result.is_err());
     }
 
+    // ═══════════════════════════════════════════════════════════════════
+    // Pixel content analysis
+    // ═══════════════════════════════════════════════════════════════════
+
+    fn analyze_rgba(w: usize, h: usize, f: impl Fn(usize, usize) -> [u8; 4]) -> ImageFacts {
+        let mut pixels = alloc::vec::Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                let [r, g, b, a] = f(x, y);
+                pixels.push(rgb::Rgba { r, g, b, a });
+            }
+        }
+        let img = imgref::ImgVec::new(pixels, w, h);
+        ImageFacts::analyze(&zenpixels::PixelSlice::from(img.as_ref()).erase()).unwrap()
+    }
+
+    /// Deterministic pseudo-noise in [0, 16).
+    fn noise(x: usize, y: usize) -> u8 {
+        ((x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)).wrapping_mul(2_654_435_761) >> 7) as u8
+            & 15
+    }
+
+    #[test]
+    fn analyze_opaque_alpha_is_not_alpha() {
+        let facts = analyze_rgba(32, 32, |x, y| [x as u8, y as u8, 0, 255]);
+        assert!(!facts.has_alpha, "all-255 alpha must not count as alpha");
+        assert!(!facts.alpha_is_binary);
+        assert_eq!(facts.pixel_count, 32 * 32);
+    }
+
+    #[test]
+    fn analyze_binary_alpha() {
+        let facts = analyze_rgba(32, 32, |x, _| [200, 10, 10, if x < 16 { 0 } else { 255 }]);
+        assert!(facts.has_alpha);
+        assert!(facts.alpha_is_binary);
+
+        let facts = analyze_rgba(32, 32, |x, _| [200, 10, 10, (x * 8) as u8]);
+        assert!(facts.has_alpha);
+        assert!(!facts.alpha_is_binary);
+    }
+
+    #[test]
+    fn analyze_grayscale_in_rgb() {
+        let facts = analyze_rgba(16, 16, |x, y| {
+            let v = (x * 16 + y) as u8;
+            [v, v, v, 255]
+        });
+        assert!(facts.is_grayscale);
+
+        let facts = analyze_rgba(16, 16, |x, _| [x as u8, 0, 0, 255]);
+        assert!(!facts.is_grayscale);
+    }
+
+    #[test]
+    fn analyze_ignores_row_padding() {
+        // Gray, opaque pixels; the padding past each row is red and clear.
+        let (w, h, stride) = (16, 8, 20);
+        let pixels = (0..stride * h)
+            .map(|i| {
+                let v = (i % stride * 10) as u8;
+                if i % stride < w {
+                    rgb::Rgba {
+                        r: v,
+                        g: v,
+                        b: v,
+                        a: 255,
+                    }
+                } else {
+                    rgb::Rgba {
+                        r: 255,
+                        g: 0,
+                        b: 0,
+                        a: 0,
+                    }
+                }
+            })
+            .collect();
+        let img = imgref::ImgVec::new_stride(pixels, w, h, stride);
+        let facts =
+            ImageFacts::analyze(&zenpixels::PixelSlice::from(img.as_ref()).erase()).unwrap();
+        assert!(facts.is_grayscale);
+        assert!(!facts.has_alpha);
+        assert_eq!(facts.pixel_count, (w * h) as u64);
+    }
+
+    #[test]
+    fn analyze_palette_size() {
+        let facts = analyze_rgba(64, 64, |x, _| [(x / 16) as u8 * 60, 0, 255, 255]);
+        assert_eq!(facts.palette_size, Some(4));
+
+        let facts = analyze_rgba(64, 64, |x, y| [x as u8, y as u8, 7, 255]);
+        assert_eq!(
+            facts.palette_size, None,
+            "4096 colors is not palette-eligible"
+        );
+    }
+
+    #[test]
+    fn analyze_classifies_graphic_and_photo() {
+        // Flat panels with hard borders: a UI screenshot in miniature.
+        let graphic = analyze_rgba(128, 128, |x, y| {
+            if (x / 32 + y / 32) % 2 == 0 {
+                [30, 30, 30, 255]
+            } else {
+                [240, 240, 240, 255]
+            }
+        });
+        assert_eq!(graphic.content, ContentKind::Graphic);
+        assert!(graphic.prefers_lossless());
+
+        // Smooth gradient plus sensor-like noise in every channel.
+        let photo = analyze_rgba(128, 128, |x, y| {
+            let n = noise(x, y);
+            [
+                (x as u8).wrapping_add(n),
+                (y as u8).wrapping_add(n / 2),
+                ((x + y) as u8 / 2).wrapping_add(n / 3),
+                255,
+            ]
+        });
+        assert_eq!(photo.content, ContentKind::Photo);
+        assert!(!photo.prefers_lossless());
+    }
+
+    #[test]
+    fn analyze_tiny_image_is_unknown() {
+        let facts = analyze_rgba(4, 4, |_, _| [0, 0, 0, 255]);
+        assert_eq!(facts.content, ContentKind::Unknown);
+    }
+
+    #[test]
+    fn with_analysis_keeps_source_facts() {
+        let pixels = alloc::vec![rgb::Rgba { r: 1u8, g: 2, b: 3, a: 255 }; 16];
+        let img = imgref::ImgVec::new(pixels, 4, 4);
+        let facts = ImageFacts {
+            has_alpha: true,
+            source_format: Some(ImageFormat::Png),
+            is_lossless_source: true,
+            ..Default::default()
+        }
+        .with_analysis(&zenpixels::PixelSlice::from(img.as_ref()).erase())
+        .unwrap();
+        assert!(!facts.has_alpha, "header alpha overridden by pixel scan");
+        assert_eq!(facts.source_format, Some(ImageFormat::Png));
+        assert!(facts.is_lossless_source);
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    fn graphic_content_prefers_lossless_webp_or_png() {
+        let facts = ImageFacts {
+            pixel_count: 1_000_000,
+            content: ContentKind::Graphic,
+            ..Default::default()
+        };
+        let intent = QualityIntent::from_quality(73.0);
+        let order = build_preference_order(&facts, &intent);
+        assert_eq!(order[0].0, ImageFormat::WebP);
+        assert_eq!(order[1].0, ImageFormat::Png);
+
+        let registry = AllowedFormats::all();
+        let policy = CodecPolicy::new();
+        let sel = select_format(&facts, &intent, &registry, &policy).unwrap();
+        assert_eq!(sel.format, ImageFormat::WebP);
+        assert!(
+            sel.lossless,
+            "graphic routed to WebP should encode lossless"
+        );
+    }
+
+    #[test]
+    fn graphic_content_lossy_fallback_stays_lossy() {
+        let facts = ImageFacts {
+            pixel_count: 1_000_000,
+            content: ContentKind::Graphic,
+            ..Default::default()
+        };
+        let intent = QualityIntent::from_quality(73.0);
+        let registry = AllowedFormats::all();
+        let policy =
+            CodecPolicy::new().with_allowed_formats(FormatSet::EMPTY.with(ImageFormat::Jpeg));
+        let sel = select_format(&facts, &intent, &registry, &policy).unwrap();
+        assert_eq!(sel.format, ImageFormat::Jpeg);
+        assert!(!sel.lossless);
+    }
+
+    #[test]
+    fn photo_content_keeps_lossy_order() {
+        let facts = ImageFacts {
+            pixel_count: 1_000_000,
+            content: ContentKind::Photo,
+            ..Default::default()
+        };
+        let intent = QualityIntent::from_quality(73.0);
+        let registry = AllowedFormats::all();
+        let policy = CodecPolicy::new();
+        let sel = select_format(&facts, &intent, &registry, &policy).unwrap();
+        assert!(!sel.lossless);
+        assert_ne!(sel.format, ImageFormat::Png);
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    fn intent_auto_graphic_resolves_lossless() {
+        let intent = CodecIntent {
+            format: Some(FormatChoice::Auto),
+            ..Default::default()
+        };
+        let facts = ImageFacts {
+            pixel_count: 1_000_000,
+            content: ContentKind::Graphic,
+            ..Default::default()
+        };
+        let registry = AllowedFormats::all();
+        let policy = CodecPolicy::new();
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert_eq!(decision.format, ImageFormat::WebP);
+        assert!(decision.lossless);
+    }
+
     // ═══════════════════════════════════════════════════════════════════
     // select_format_from_intent tests
     // ═══════════════════════════════════════════════════════════════════
@@ -636,6 +1305,80 @@ mod tests {
         assert_eq!(decision.hints.get("progressive"), Some(&"true".to_string()));
     }
 
+    #[test]
+    fn intent_lossless_to_jpeg_keeps_coefficients() {
+        let facts = ImageFacts {
+            source_format: Some(ImageFormat::Jxl),
+            ..Default::default()
+        };
+        let registry = AllowedFormats::all();
+        let policy = CodecPolicy::new();
+        let mut intent = CodecIntent {
+            format: Some(FormatChoice::Specific(ImageFormat::Jpeg)),
+            lossless: Some(crate::intent::BoolKeep::True),
+            ..Default::default()
+        };
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert!(decision.lossless_jpeg_transcode);
+
+        // A lossy request re-encodes at the requested quality
+        intent.lossless = Some(crate::intent::BoolKeep::False);
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert!(!decision.lossless_jpeg_transcode);
+
+        // JPEG sources keep their coefficients too; others have none
+        intent.lossless = Some(crate::intent::BoolKeep::True);
+        let facts = ImageFacts {
+            source_format: Some(ImageFormat::Jpeg),
+            ..facts
+        };
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert!(decision.lossless_jpeg_transcode);
+        let facts = ImageFacts {
+            source_format: Some(ImageFormat::Png),
+            ..facts
+        };
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert!(!decision.lossless_jpeg_transcode);
+    }
+
+    #[test]
+    fn intent_caps_quality_at_source() {
+        let facts = ImageFacts {
+            source_format: Some(ImageFormat::Jpeg),
+            source_quality: Some(60.0),
+            ..Default::default()
+        };
+        let registry = AllowedFormats::all();
+        let policy = CodecPolicy::new();
+        let mut intent = CodecIntent {
+            format: Some(FormatChoice::Specific(ImageFormat::Jpeg)),
+            quality_profile: Some(crate::quality::QualityProfile::High),
+            cap_to_source_quality: true,
+            ..Default::default()
+        };
+
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert_eq!(decision.quality.quality, 60.0);
+
+        // Off by default: the profile wins
+        intent.cap_to_source_quality = false;
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert_eq!(
+            decision.quality.quality,
+            crate::quality::QualityProfile::High.generic_quality()
+        );
+
+        // Never raises quality above the request
+        intent.cap_to_source_quality = true;
+        intent.quality_profile = Some(crate::quality::QualityProfile::Low);
+        let decision = select_format_from_intent(&intent, &facts, &registry, &policy).unwrap();
+        assert_eq!(
+            decision.quality.quality,
+            crate::quality::QualityProfile::Low.generic_quality()
+        );
+    }
+
     #[test]
     fn intent_with_matte() {
         let intent = CodecIntent {
@@ -0,0 +1,512 @@
+//! Estimate the quality a lossy source was saved at.
+//!
+//! Re-encoding a JPEG saved at quality 60 with a "high" profile makes the
+//! output larger without making it look better: the artifacts are already
+//! baked in. [`estimate_source_quality`] reads the quantizers straight from
+//! the bitstream header so the output quality can be capped at the
+//! source's: by the selector ([`CodecIntent::cap_to_source_quality`](crate::CodecIntent::cap_to_source_quality)),
+//! by [`EncodeRequest::with_source_quality_cap`](crate::EncodeRequest::with_source_quality_cap)
+//! and by [`TranscodeOptions::cap_to_source_quality`](crate::TranscodeOptions::cap_to_source_quality).
+//! [`DecodeRequest::probe_facts`](crate::DecodeRequest::probe_facts) reports
+//! the estimate alongside the other probed facts.
+//!
+//! Only headers are parsed; no pixels are decoded.
+//!
+//! - **JPEG**: the luma quantization table is compared against the IJG
+//!   (libjpeg) reference table. Encoders that ship their own base tables
+//!   (mozjpeg's default, Photoshop) still produce a usable estimate, since
+//!   the overall quantizer magnitude is what matters.
+//! - **WebP**: the VP8 base quantizer index (averaged over segments) is run
+//!   back through libwebp's quality-to-quantizer curve.
+//!
+//! Lossless sources (VP8L) and other formats return `None`.
+//!
+//! # Example
+//!
+//! ```
+//! use zencodecs::source_quality::estimate_source_quality;
+//!
+//! # let data: &[u8] = &[];
+//! if let Some(sq) = estimate_source_quality(data) {
+//!     println!("{:?} saved at ~q{:.0}", sq.format, sq.native);
+//! }
+//! ```
+
+use crate::ImageFormat;
+
+/// Estimated encode quality of a lossy source image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SourceQuality {
+    /// Source format (JPEG or WebP).
+    pub format: ImageFormat,
+    /// Quality on the source codec's native 0-100 scale (libjpeg / libwebp).
+    pub native: f32,
+    /// The same quality mapped onto the generic 0-100 scale used by
+    /// [`QualityIntent`](crate::QualityIntent).
+    pub generic: f32,
+}
+
+/// Estimate the quality a JPEG or lossy WebP was encoded at.
+///
+/// Returns `None` for other formats, lossless WebP, or malformed headers.
+pub fn estimate_source_quality(data: &[u8]) -> Option<SourceQuality> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        let native = estimate_jpeg_quality(data)?;
+        Some(SourceQuality {
+            format: ImageFormat::Jpeg,
+            native,
+            generic: crate::quality::generic_quality_from_jpeg(native),
+        })
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        let native = estimate_webp_quality(data)?;
+        Some(SourceQuality {
+            format: ImageFormat::WebP,
+            native,
+            generic: crate::quality::generic_quality_from_webp(native),
+        })
+    } else {
+        None
+    }
+}
+
+// =========================================================================
+// JPEG
+// =========================================================================
+
+/// IJG reference luminance quantization table (ITU-T T.81 Annex K.1).
+///
+/// Only the sum is used, so the order (natural vs. zigzag) doesn't matter.
+const IJG_LUMA: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// Estimate libjpeg quality from the luma (id 0) quantization table.
+///
+/// libjpeg scales the reference table by `S = 5000 / q` below q50 and
+/// `S = 200 - 2q` above, so the ratio of table sums recovers `S`.
+fn estimate_jpeg_quality(data: &[u8]) -> Option<f32> {
+    let table_sum = jpeg_luma_table_sum(data)?;
+    let reference_sum: u32 = IJG_LUMA.iter().map(|&v| u32::from(v)).sum();
+    let scale = table_sum as f32 * 100.0 / reference_sum as f32;
+    let quality = if scale <= 100.0 {
+        (200.0 - scale) / 2.0
+    } else {
+        5000.0 / scale
+    };
+    Some(quality.clamp(1.0, 100.0))
+}
+
+/// Sum of the 64 entries of quantization table 0, from the first DQT
+/// segment that defines it.
+fn jpeg_luma_table_sum(data: &[u8]) -> Option<u32> {
+    let mut pos = 2;
+    loop {
+        // Skip fill bytes before a marker
+        while *data.get(pos)? == 0xFF && *data.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        pos += 2;
+        match marker {
+            // Standalone markers: TEM, RSTn
+            0x01 | 0xD0..=0xD7 => continue,
+            // SOS or EOI before any DQT
+            0xDA | 0xD9 => return None,
+            _ => {}
+        }
+        let len = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        if len < 2 {
+            return None;
+        }
+        let segment = data.get(pos + 2..pos + len)?;
+        if marker == 0xDB {
+            let mut i = 0;
+            while i < segment.len() {
+                let precision = segment[i] >> 4;
+                let id = segment[i] & 0x0F;
+                i += 1;
+                let entry_size = if precision == 0 { 1 } else { 2 };
+                let table = segment.get(i..i + 64 * entry_size)?;
+                if id == 0 {
+                    let sum = if entry_size == 1 {
+                        table.iter().map(|&v| u32::from(v)).sum()
+                    } else {
+                        table
+                            .chunks_exact(2)
+                            .map(|c| u32::from(u16::from_be_bytes([c[0], c[1]])))
+                            .sum()
+                    };
+                    return Some(sum);
+                }
+                i += 64 * entry_size;
+            }
+        }
+        pos += len;
+    }
+}
+
+// =========================================================================
+// WebP (VP8)
+// =========================================================================
+
+/// Estimate libwebp quality from the VP8 quantizer index.
+fn estimate_webp_quality(data: &[u8]) -> Option<f32> {
+    let vp8 = find_vp8_chunk(data)?;
+    let q_index = vp8_average_quantizer(vp8)?;
+    Some(webp_quality_from_quantizer(q_index))
+}
+
+/// Invert libwebp's quality → quantizer mapping.
+///
+/// libwebp computes `c = quality / 100`, linearizes it (`2c/3` below 0.75,
+/// `2c - 1` above), takes the cube root and sets `q = 127 * (1 - root)`.
+fn webp_quality_from_quantizer(q_index: f32) -> f32 {
+    let root = 1.0 - q_index.clamp(0.0, 127.0) / 127.0;
+    let linear = root * root * root;
+    let c = if linear < 0.5 {
+        linear * 1.5
+    } else {
+        (linear + 1.0) / 2.0
+    };
+    (c * 100.0).clamp(0.0, 100.0)
+}
+
+/// Payload of the `VP8 ` chunk, walking `VP8X` containers. `None` for
+/// lossless (`VP8L`) files.
+fn find_vp8_chunk(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let fourcc = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let payload = data.get(pos + 8..(pos + 8).checked_add(size)?)?;
+        match fourcc {
+            b"VP8 " => return Some(payload),
+            b"VP8L" => return None,
+            // Animated: the first frame's ANMF payload holds the bitstream
+            // after a 16-byte frame header.
+            b"ANMF" => return find_vp8_in(payload.get(16..)?),
+            _ => {}
+        }
+        // Chunks are padded to even sizes
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+/// Find a `VP8 ` sub-chunk inside an `ANMF` frame payload.
+fn find_vp8_in(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let payload = data.get(pos + 8..(pos + 8).checked_add(size)?)?;
+        match &data[pos..pos + 4] {
+            b"VP8 " => return Some(payload),
+            b"VP8L" => return None,
+            _ => {}
+        }
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+/// Parse the VP8 key frame header up to the quantizer indices (RFC 6386
+/// §9.2–9.6) and return the luma AC index, averaged over segments when
+/// segmentation overrides it.
+fn vp8_average_quantizer(vp8: &[u8]) -> Option<f32> {
+    // 3-byte frame tag, then the key frame start code and dimensions.
+    let tag = *vp8.first()?;
+    if tag & 1 != 0 || vp8.get(3..6)? != [0x9D, 0x01, 0x2A] {
+        // Not a key frame
+        return None;
+    }
+    let mut br = BoolReader::new(vp8.get(10..)?)?;
+    br.literal(1); // color space
+    br.literal(1); // clamping type
+
+    let mut segment_quant: Option<(bool, [i32; 4])> = None;
+    if br.flag() {
+        let update_map = br.flag();
+        if br.flag() {
+            let absolute = br.flag();
+            let mut quant = [0i32; 4];
+            for q in &mut quant {
+                *q = br.optional_signed(7);
+            }
+            for _ in 0..4 {
+                br.optional_signed(6); // loop filter level
+            }
+            segment_quant = Some((absolute, quant));
+        }
+        if update_map {
+            for _ in 0..3 {
+                if br.flag() {
+                    br.literal(8);
+                }
+            }
+        }
+    }
+
+    br.literal(1); // filter type
+    br.literal(6); // loop filter level
+    br.literal(3); // sharpness
+    if br.flag() && br.flag() {
+        for _ in 0..8 {
+            br.optional_signed(6); // ref frame / mode deltas
+        }
+    }
+    br.literal(2); // log2 of DCT partition count
+
+    let base = br.literal(7) as i32;
+    if br.exhausted() {
+        return None;
+    }
+    let average = match segment_quant {
+        Some((absolute, quant)) => {
+            let sum: i32 = quant
+                .iter()
+                .map(|&q| if absolute { q } else { base + q }.clamp(0, 127))
+                .sum();
+            sum as f32 / 4.0
+        }
+        None => base as f32,
+    };
+    Some(average)
+}
+
+/// VP8 boolean entropy decoder (RFC 6386 §7.3), just enough for headers.
+struct BoolReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    value: u32,
+    range: u32,
+    bit_count: u32,
+    /// Set once reads run past the end of `data`.
+    overrun: bool,
+}
+
+impl<'a> BoolReader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let value = (u32::from(*data.first()?) << 8) | u32::from(*data.get(1)?);
+        Some(Self {
+            data,
+            pos: 2,
+            value,
+            range: 255,
+            bit_count: 0,
+            overrun: false,
+        })
+    }
+
+    fn read_bool(&mut self, probability: u32) -> bool {
+        let split = 1 + (((self.range - 1) * probability) >> 8);
+        let big_split = split << 8;
+        let bit = if self.value >= big_split {
+            self.range -= split;
+            self.value -= big_split;
+            true
+        } else {
+            self.range = split;
+            false
+        };
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                match self.data.get(self.pos) {
+                    Some(&b) => self.value |= u32::from(b),
+                    None => self.overrun = true,
+                }
+                self.pos += 1;
+            }
+        }
+        bit
+    }
+
+    fn flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    fn literal(&mut self, bits: u32) -> u32 {
+        (0..bits).fold(0, |acc, _| (acc << 1) | u32::from(self.flag()))
+    }
+
+    /// A flag-guarded signed value: presence flag, magnitude, sign.
+    fn optional_signed(&mut self, bits: u32) -> i32 {
+        if !self.flag() {
+            return 0;
+        }
+        let magnitude = self.literal(bits) as i32;
+        if self.flag() { -magnitude } else { magnitude }
+    }
+
+    fn exhausted(&self) -> bool {
+        self.overrun
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Minimal JPEG prefix: SOI + DQT with an IJG-scaled luma table.
+    fn jpeg_with_quality(quality: u32) -> Vec<u8> {
+        let scale = if quality < 50 {
+            5000 / quality
+        } else {
+            200 - quality * 2
+        };
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xDB, 0x00, 67, 0x00];
+        for &v in &IJG_LUMA {
+            data.push(((u32::from(v) * scale + 50) / 100).clamp(1, 255) as u8);
+        }
+        data.extend_from_slice(&[0xFF, 0xDA]);
+        data
+    }
+
+    #[test]
+    fn jpeg_ijg_tables_round_trip() {
+        for q in [20, 50, 60, 75, 85, 95] {
+            let sq = estimate_source_quality(&jpeg_with_quality(q)).unwrap();
+            assert_eq!(sq.format, ImageFormat::Jpeg);
+            assert!(
+                (sq.native - q as f32).abs() <= 1.0,
+                "q{q} estimated as {}",
+                sq.native
+            );
+        }
+    }
+
+    #[test]
+    fn jpeg_without_dqt_is_none() {
+        assert_eq!(estimate_source_quality(&[0xFF, 0xD8, 0xFF, 0xDA]), None);
+        assert_eq!(estimate_source_quality(&[0xFF, 0xD8]), None);
+    }
+
+    #[test]
+    fn webp_quantizer_curve_inverts_libwebp() {
+        // libwebp maps quality 75 → linear 0.5 → q = 127 * (1 - 0.5^(1/3)) ≈ 26.2
+        assert!((webp_quality_from_quantizer(26.2) - 75.0).abs() < 0.5);
+        assert_eq!(webp_quality_from_quantizer(0.0), 100.0);
+        assert_eq!(webp_quality_from_quantizer(127.0), 0.0);
+    }
+
+    #[test]
+    fn other_formats_are_none() {
+        assert_eq!(estimate_source_quality(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(estimate_source_quality(b"RIFF\0\0\0\0WEBPVP8L"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn encoded_jpeg_quality_is_ordered() {
+        use crate::EncodeRequest;
+        let img = imgref::ImgVec::new(
+            (0..64 * 64)
+                .map(|i| rgb::Rgb {
+                    r: (i % 64 * 4) as u8,
+                    g: (i / 64 * 4) as u8,
+                    b: 128u8,
+                })
+                .collect(),
+            64,
+            64,
+        );
+        let estimate = |q| {
+            let out = EncodeRequest::new(ImageFormat::Jpeg)
+                .with_quality(q)
+                .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+                .unwrap();
+            estimate_source_quality(out.data()).unwrap().generic
+        };
+        assert!(estimate(40.0) < estimate(90.0));
+    }
+
+    #[test]
+    #[cfg(feature = "webp")]
+    fn encoded_webp_quality_is_ordered() {
+        use crate::EncodeRequest;
+        let img = imgref::ImgVec::new(
+            (0..64 * 64)
+                .map(|i| rgb::Rgb {
+                    r: (i % 64 * 4) as u8,
+                    g: (i / 64 * 4) as u8,
+                    b: 128u8,
+                })
+                .collect(),
+            64,
+            64,
+        );
+        let estimate = |q| {
+            let out = EncodeRequest::new(ImageFormat::WebP)
+                .with_quality(q)
+                .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+                .unwrap();
+            estimate_source_quality(out.data()).unwrap().generic
+        };
+        assert!(estimate(40.0) < estimate(90.0));
+    }
+
+    #[test]
+    #[cfg(feature = "jpeg")]
+    fn cap_applies_to_encode_and_transcode() {
+        use crate::transcode::{TranscodeOptions, transcode};
+        use crate::{AllowedFormats, DecodeRequest, EncodeRequest, FormatDecision};
+        let img = imgref::ImgVec::new(
+            (0..64 * 64)
+                .map(|i| rgb::Rgb {
+                    r: (i % 64 * 4) as u8,
+                    g: (i / 64 * 4) as u8,
+                    b: (i % 7 * 30) as u8,
+                })
+                .collect(),
+            64,
+            64,
+        );
+        let source = EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(40.0)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let facts = DecodeRequest::new(source.data()).probe_facts().unwrap();
+        let estimate = facts.source_quality.unwrap();
+        assert!(estimate < 60.0, "estimate {estimate}");
+
+        let reencode = |cap| {
+            EncodeRequest::new(ImageFormat::Jpeg)
+                .with_quality(95.0)
+                .with_image_facts(facts.clone())
+                .with_source_quality_cap(cap)
+                .encode_to_target(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+                .unwrap()
+        };
+        assert_eq!(reencode(true).quality, estimate);
+        assert_eq!(reencode(false).quality, 95.0);
+
+        let mut decision = FormatDecision::for_format(ImageFormat::Jpeg);
+        decision.quality = crate::QualityIntent::from_quality(95.0);
+        let run = |cap_to_source_quality| {
+            let opts = TranscodeOptions {
+                cap_to_source_quality,
+                ..Default::default()
+            };
+            transcode(source.data(), &decision, &opts, &AllowedFormats::all())
+                .unwrap()
+                .data
+        };
+        let (capped, uncapped) = (run(true), run(false));
+        assert!(capped.len() < uncapped.len());
+    }
+}
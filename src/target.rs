@@ -0,0 +1,290 @@
+//! Quality search for budget- and score-constrained encoding.
+//!
+//! [`EncodeRequest::with_target_size`](crate::EncodeRequest::with_target_size)
+//! re-encodes at different generic quality values until the output fits a
+//! byte budget; `with_target_score` (feature `metrics`) searches for the
+//! smallest output that reaches a perceptual score. The search runs on the
+//! generic 0–100 scale, so every lossy codec goes through the same
+//! calibrated [`QualityIntent`](crate::QualityIntent) mapping rather than a
+//! codec-specific rate control.
+
+use alloc::format;
+
+use crate::error::Result;
+use crate::limits::Stop;
+use crate::trace::SelectionTrace;
+use crate::{CodecError, EncodeOutput, StopToken};
+use whereat::at;
+
+/// Default cap on encode attempts per search.
+///
+/// Bisection over 0–100 reaches [`QUALITY_TOLERANCE`] in 7 steps; one more
+/// covers the initial attempt at the ceiling.
+pub(crate) const DEFAULT_MAX_ITERATIONS: u32 = 8;
+
+/// Stop bisecting once the bracket is narrower than this.
+const QUALITY_TOLERANCE: f32 = 1.0;
+
+/// What a quality search optimizes for.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum EncodeTarget {
+    /// Highest quality whose output fits in this many bytes.
+    Size(usize),
+    /// Smallest output whose metric score reaches the value.
+    #[cfg(feature = "metrics")]
+    Score(crate::metric::QualityMetric, f64),
+}
+
+/// Result of a searched encode.
+#[derive(Debug)]
+pub struct TargetEncodeOutput {
+    /// The chosen encode.
+    pub output: EncodeOutput,
+    /// Generic quality (0–100) that produced `output`.
+    pub quality: f32,
+    /// Number of encodes performed, including rejected ones.
+    pub iterations: u32,
+    /// Metric score of `output`, for score-targeted searches.
+    ///
+    /// If the target was unreachable this is below the target and `output`
+    /// is the best-scoring attempt.
+    pub score: Option<f64>,
+    /// Format selection audit trail.
+    ///
+    /// Empty for explicit formats. Auto-selection records its preference
+    /// walk; competitive mode records every candidate's size.
+    pub trace: SelectionTrace,
+}
+
+/// Find the highest quality in `[0, ceiling]` whose encode fits `budget` bytes.
+///
+/// Tries `ceiling` first, so a generous budget costs a single encode.
+/// Returns `LimitExceeded` if nothing fit within `max_iterations` attempts.
+pub(crate) fn search_size(
+    budget: usize,
+    ceiling: f32,
+    max_iterations: u32,
+    stop: Option<&StopToken>,
+    mut encode: impl FnMut(f32) -> Result<EncodeOutput>,
+) -> Result<TargetEncodeOutput> {
+    let max_iterations = max_iterations.max(1);
+    let ceiling = ceiling.clamp(0.0, 100.0);
+
+    let output = encode(ceiling)?;
+    let mut iterations = 1;
+    if output.data().len() <= budget {
+        return Ok(TargetEncodeOutput {
+            output,
+            quality: ceiling,
+            iterations,
+            score: None,
+            trace: SelectionTrace::new(),
+        });
+    }
+
+    let mut smallest = output.data().len();
+    let mut best: Option<(EncodeOutput, f32)> = None;
+    // Invariant: `hi` is known not to fit; `lo` is the best known fit (or 0).
+    let (mut lo, mut hi) = (0.0f32, ceiling);
+    while iterations < max_iterations && hi - lo > QUALITY_TOLERANCE {
+        check_stop(stop)?;
+        let mid = (lo + hi) / 2.0;
+        let output = encode(mid)?;
+        iterations += 1;
+        let len = output.data().len();
+        smallest = smallest.min(len);
+        if len <= budget {
+            lo = mid;
+            best = Some((output, mid));
+        } else {
+            hi = mid;
+        }
+    }
+
+    match best {
+        Some((output, quality)) => Ok(TargetEncodeOutput {
+            output,
+            quality,
+            iterations,
+            score: None,
+            trace: SelectionTrace::new(),
+        }),
+        None => Err(at!(CodecError::LimitExceeded(format!(
+            "smallest encode ({smallest} bytes after {iterations} attempts) exceeds target size {budget}"
+        )))),
+    }
+}
+
+/// Find the lowest quality whose encode reaches `target` on `metric`.
+///
+/// Starts at `start` (a good guess saves iterations), then bisects. When
+/// the target can't be reached the best-scoring attempt is returned, with
+/// its score below the target.
+#[cfg(feature = "metrics")]
+pub(crate) fn search_score(
+    metric: crate::metric::QualityMetric,
+    target: f64,
+    start: f32,
+    max_iterations: u32,
+    stop: Option<&StopToken>,
+    mut encode: impl FnMut(f32) -> Result<EncodeOutput>,
+    mut measure: impl FnMut(&EncodeOutput) -> Result<f64>,
+) -> Result<TargetEncodeOutput> {
+    let max_iterations = max_iterations.max(1);
+    // Smallest passing attempt, and best failing attempt as a fallback.
+    let mut best: Option<(EncodeOutput, f32, f64)> = None;
+    let mut fallback: Option<(EncodeOutput, f32, f64)> = None;
+    // Invariant: `lo` is known (or assumed, at 0) to fail; `hi` passes or is
+    // the untested maximum.
+    let (mut lo, mut hi) = (0.0f32, 100.0f32);
+    let mut quality = start.clamp(0.0, 100.0);
+    let mut iterations = 0;
+
+    loop {
+        if iterations > 0 {
+            check_stop(stop)?;
+        }
+        let output = encode(quality)?;
+        let score = measure(&output)?;
+        iterations += 1;
+
+        if metric.meets(score, target) {
+            hi = quality;
+            best = Some((output, quality, score));
+        } else {
+            lo = quality;
+            if fallback
+                .as_ref()
+                .is_none_or(|(_, _, s)| metric.meets(score, *s))
+            {
+                fallback = Some((output, quality, score));
+            }
+        }
+
+        if iterations >= max_iterations {
+            break;
+        }
+        if hi - lo <= QUALITY_TOLERANCE {
+            // Bracket closed. If the top was never verified, check it once
+            // so an unreachable target is reported from the best encode.
+            if best.is_none() && quality < 100.0 {
+                quality = 100.0;
+                continue;
+            }
+            break;
+        }
+        quality = (lo + hi) / 2.0;
+    }
+
+    let (output, quality, score) = best
+        .or(fallback)
+        .ok_or_else(|| at!(CodecError::NoSuitableEncoder))?;
+    Ok(TargetEncodeOutput {
+        output,
+        quality,
+        iterations,
+        score: Some(score),
+        trace: SelectionTrace::new(),
+    })
+}
+
+fn check_stop(stop: Option<&StopToken>) -> Result<()> {
+    if let Some(s) = stop
+        && s.check().is_err()
+    {
+        return Err(at!(CodecError::Cancelled));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ImageFormat;
+    use alloc::vec;
+
+    /// Fake encoder: output grows linearly with quality.
+    fn linear(q: f32) -> Result<EncodeOutput> {
+        Ok(EncodeOutput::new(
+            vec![0u8; 1000 + (q * 100.0) as usize],
+            ImageFormat::Jpeg,
+        ))
+    }
+
+    #[test]
+    fn generous_budget_uses_ceiling() {
+        let r = search_size(1_000_000, 85.0, 8, None, linear).unwrap();
+        assert_eq!(r.quality, 85.0);
+        assert_eq!(r.iterations, 1);
+    }
+
+    #[test]
+    fn finds_highest_fitting_quality() {
+        // 1000 + q*100 <= 6000  ⇔  q <= 50
+        let r = search_size(6000, 100.0, 16, None, linear).unwrap();
+        assert!(r.output.data().len() <= 6000);
+        assert!(r.quality <= 50.0 && r.quality > 50.0 - QUALITY_TOLERANCE);
+    }
+
+    #[test]
+    fn respects_iteration_cap() {
+        let mut calls = 0;
+        let r = search_size(6000, 100.0, 3, None, |q| {
+            calls += 1;
+            linear(q)
+        })
+        .unwrap();
+        assert_eq!(calls, 3);
+        assert_eq!(r.iterations, 3);
+        assert!(r.output.data().len() <= 6000);
+    }
+
+    /// Fake metric: score rises linearly with the encoded size.
+    #[cfg(feature = "metrics")]
+    fn size_score(out: &EncodeOutput) -> Result<f64> {
+        Ok((out.data().len() as f64 - 1000.0) / 100.0)
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn score_search_finds_lowest_passing_quality() {
+        use crate::metric::QualityMetric;
+        let r = search_score(
+            QualityMetric::Ssimulacra2,
+            60.0,
+            73.0,
+            16,
+            None,
+            linear,
+            size_score,
+        )
+        .unwrap();
+        let score = r.score.unwrap();
+        assert!(score >= 60.0);
+        assert!(r.quality >= 60.0 && r.quality < 60.0 + QUALITY_TOLERANCE + 0.01);
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn score_search_unreachable_returns_best_attempt() {
+        use crate::metric::QualityMetric;
+        let r = search_score(
+            QualityMetric::Ssimulacra2,
+            500.0,
+            50.0,
+            16,
+            None,
+            linear,
+            size_score,
+        )
+        .unwrap();
+        assert_eq!(r.quality, 100.0);
+        assert!(r.score.unwrap() < 500.0);
+    }
+
+    #[test]
+    fn impossible_budget_errors() {
+        let err = search_size(10, 90.0, 8, None, linear).unwrap_err();
+        assert!(matches!(err.error(), CodecError::LimitExceeded(_)));
+    }
+}
@@ -0,0 +1,307 @@
+//! GPU texture containers (`dds` and `ktx2` features).
+//!
+//! DDS and KTX2 files hold a texture in its GPU layout: a chain of mip
+//! levels, optionally array layers or cube faces, stored uncompressed or as
+//! 4×4 BC blocks. Decoding happens on the CPU:
+//!
+//! - [`dds::info`](crate::dds::info) and [`ktx2::info`](crate::ktx2::info)
+//!   describe the texture and enumerate its mip levels.
+//! - [`DecodeRequest`](crate::DecodeRequest) decodes one mip level (the base
+//!   level unless [`TextureDecodeConfig`] picks another) of the first layer
+//!   and face. LDR formats decode to RGBA8, HDR formats (BC6H, half and
+//!   float) to linear RGBA f32.
+//! - [`EncodeRequest`](crate::EncodeRequest) compresses RGBA8 to the
+//!   format in [`TextureEncodeConfig`], optionally with a box-filtered mip
+//!   chain.
+
+use alloc::vec::Vec;
+
+/// Pixel or block format of a texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// 8-bit R, G, B, A.
+    Rgba8,
+    /// 8-bit B, G, R, A.
+    Bgra8,
+    /// 8-bit B, G, R and an unused byte.
+    Bgrx8,
+    /// 8-bit B, G, R (24 bits per pixel).
+    Bgr8,
+    /// One 8-bit channel (red or luminance), decoded as gray.
+    R8,
+    /// 8-bit R, G; decoded with blue 0.
+    Rg8,
+    /// Half-float R, G, B, A.
+    Rgba16f,
+    /// Float R, G, B, A.
+    Rgba32f,
+    /// BC1 (DXT1). `alpha` enables 1-bit punch-through transparency.
+    Bc1 {
+        /// Whether the three-color mode encodes transparency.
+        alpha: bool,
+    },
+    /// BC2 (DXT3): BC1 color with explicit 4-bit alpha.
+    Bc2,
+    /// BC3 (DXT5): BC1 color with interpolated alpha.
+    Bc3,
+    /// BC4: one unsigned channel, decoded as gray.
+    Bc4,
+    /// BC5: two unsigned channels, decoded as red and green.
+    Bc5,
+    /// BC6H: HDR RGB half floats.
+    Bc6h {
+        /// The signed (`SF16`) variant.
+        signed: bool,
+    },
+    /// BC7: high-quality RGBA.
+    Bc7,
+}
+
+impl TextureFormat {
+    /// Whether the format stores 4×4 blocks.
+    pub fn is_block_compressed(self) -> bool {
+        matches!(
+            self,
+            Self::Bc1 { .. }
+                | Self::Bc2
+                | Self::Bc3
+                | Self::Bc4
+                | Self::Bc5
+                | Self::Bc6h { .. }
+                | Self::Bc7
+        )
+    }
+
+    /// Whether the format decodes to linear f32 instead of RGBA8.
+    pub fn is_hdr(self) -> bool {
+        matches!(self, Self::Rgba16f | Self::Rgba32f | Self::Bc6h { .. })
+    }
+
+    /// Whether the format can carry alpha.
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            Self::Rgba8
+                | Self::Bgra8
+                | Self::Rgba16f
+                | Self::Rgba32f
+                | Self::Bc1 { alpha: true }
+                | Self::Bc2
+                | Self::Bc3
+                | Self::Bc7
+        )
+    }
+
+    /// Bytes per 4×4 block, or per pixel for uncompressed formats.
+    pub(crate) fn unit_bytes(self) -> usize {
+        match self {
+            Self::R8 => 1,
+            Self::Rg8 => 2,
+            Self::Bgr8 => 3,
+            Self::Rgba8 | Self::Bgra8 | Self::Bgrx8 => 4,
+            Self::Rgba16f | Self::Bc1 { .. } | Self::Bc4 => 8,
+            Self::Rgba32f | Self::Bc2 | Self::Bc3 | Self::Bc5 | Self::Bc6h { .. } | Self::Bc7 => 16,
+        }
+    }
+
+    /// Size of one `width`×`height` image in bytes.
+    pub(crate) fn image_bytes(self, width: u32, height: u32) -> usize {
+        let (w, h) = (width as usize, height as usize);
+        if self.is_block_compressed() {
+            w.div_ceil(4) * h.div_ceil(4) * self.unit_bytes()
+        } else {
+            w * h * self.unit_bytes()
+        }
+    }
+}
+
+/// One level of the mip chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MipLevel {
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// File offset of the level's first image (first layer, face and
+    /// depth slice).
+    pub offset: usize,
+    /// Size of that image in bytes.
+    pub byte_len: usize,
+}
+
+/// Description of a DDS or KTX2 texture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextureInfo {
+    /// Base level width in pixels.
+    pub width: u32,
+    /// Base level height in pixels.
+    pub height: u32,
+    /// Base level depth; 1 unless this is a volume texture.
+    pub depth: u32,
+    /// Pixel or block format.
+    pub format: TextureFormat,
+    /// Color values are sRGB-encoded.
+    pub srgb: bool,
+    /// Array layers (1 for a plain texture).
+    pub array_layers: u32,
+    /// Faces: 6 for cube maps, otherwise 1.
+    pub faces: u32,
+    /// Mip levels, base level first.
+    pub mip_levels: Vec<MipLevel>,
+}
+
+/// Texture decode settings: which mip level to decode.
+#[derive(Clone, Debug, Default)]
+pub struct TextureDecodeConfig {
+    pub(crate) mip_level: usize,
+}
+
+impl TextureDecodeConfig {
+    /// Decode the base level.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode mip level `level` (0 is the base level).
+    pub fn with_mip_level(mut self, level: usize) -> Self {
+        self.mip_level = level;
+        self
+    }
+}
+
+/// Texture encode settings.
+#[derive(Clone, Debug)]
+pub struct TextureEncodeConfig {
+    pub(crate) format: TextureFormat,
+    pub(crate) srgb: bool,
+    pub(crate) mipmaps: bool,
+}
+
+impl Default for TextureEncodeConfig {
+    fn default() -> Self {
+        Self {
+            format: TextureFormat::Bc7,
+            srgb: true,
+            mipmaps: false,
+        }
+    }
+}
+
+impl TextureEncodeConfig {
+    /// BC7, tagged sRGB, base level only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Output format: `Rgba8`, `Bc1`–`Bc5` or `Bc7`. BC4 and BC5 take the
+    /// red and red/green channels. Other formats fail to encode.
+    pub fn with_format(mut self, format: TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Tag the texture as sRGB (default) or linear. Only affects the
+    /// formats that have an sRGB variant: RGBA8, BC1–BC3 and BC7.
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Generate the full mip chain down to 1×1 with a 2×2 box filter.
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::codecs::bcn;
+
+    #[test]
+    fn bc1_modes() {
+        // c0 = red, c1 = blue, c0 > c1: four colors.
+        let mut block = [0u8; 8];
+        block[..2].copy_from_slice(&0xF800u16.to_le_bytes());
+        block[2..4].copy_from_slice(&0x001Fu16.to_le_bytes());
+        block[4] = 0b11_10_01_00;
+        let mut out = [[0u8; 4]; 16];
+        bcn::decode_bc1(&block, true, &mut out);
+        assert_eq!(out[0], [255, 0, 0, 255]);
+        assert_eq!(out[1], [0, 0, 255, 255]);
+        assert_eq!(out[2], [170, 0, 85, 255]);
+        assert_eq!(out[3], [85, 0, 170, 255]);
+
+        // Swapped: three colors and transparent black.
+        block.copy_within(0..2, 2);
+        block[..2].copy_from_slice(&0x001Fu16.to_le_bytes());
+        bcn::decode_bc1(&block, true, &mut out);
+        assert_eq!(out[2], [128, 0, 128, 255]);
+        assert_eq!(out[3], [0, 0, 0, 0]);
+        bcn::decode_bc1(&block, false, &mut out);
+        assert_eq!(out[3], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc4_interpolates_between_endpoints() {
+        // a0 = 255 > a1 = 0: index 2 is 6/7 of a0.
+        let block = [255, 0, 0b010, 0, 0, 0, 0, 0];
+        let mut out = [[0u8; 4]; 16];
+        bcn::decode_bc4(&block, &mut out);
+        assert_eq!(out[0], [219, 219, 219, 255]);
+        assert_eq!(out[1], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn bc6h_single_region_mode() {
+        // Mode 0b00011: 10-bit endpoints, rw/gw/bw = 0 and rx/gx/bx = 1023
+        // from bit 35. Indices start at bit 65, pixel 0 with 3 bits.
+        let mut value = 0b00011u128;
+        for c in 0..3 {
+            value |= 1023u128 << (35 + 10 * c);
+        }
+        value |= 15u128 << 68;
+        let mut out = [[0.0f32; 3]; 16];
+        bcn::decode_bc6h(&value.to_le_bytes(), false, &mut out);
+        assert_eq!(out[0], [0.0; 3]);
+        assert_eq!(out[1], [65504.0; 3]);
+    }
+
+    #[test]
+    fn encoders_round_trip() {
+        // Four RGBA values on a line.
+        let block: bcn::Rgba8Block = core::array::from_fn(|i| {
+            let t = (i % 4) as u8;
+            [t * 85, 255 - t * 85, 64, 255 - t * 21]
+        });
+        let error = |decoded: &bcn::Rgba8Block, channels: usize| {
+            (0..16)
+                .flat_map(|i| (0..channels).map(move |c| (i, c)))
+                .map(|(i, c)| i32::from(decoded[i][c]).abs_diff(i32::from(block[i][c])))
+                .max()
+                .unwrap()
+        };
+        let mut out = [[0u8; 4]; 16];
+
+        bcn::decode_bc7(&bcn::encode_bc7(&block), &mut out);
+        assert!(error(&out, 4) <= 4, "bc7 error {}", error(&out, 4));
+        bcn::decode_bc3(&bcn::encode_bc3(&block), &mut out);
+        assert!(error(&out, 4) <= 4, "bc3 error {}", error(&out, 4));
+        bcn::decode_bc2(&bcn::encode_bc2(&block), &mut out);
+        assert!(error(&out, 4) <= 8, "bc2 error {}", error(&out, 4));
+        bcn::decode_bc1(&bcn::encode_bc1(&block, false), false, &mut out);
+        assert!(error(&out, 3) <= 4, "bc1 error {}", error(&out, 3));
+        bcn::decode_bc5(&bcn::encode_bc5(&block), &mut out);
+        assert!(error(&out, 2) <= 12, "bc5 error {}", error(&out, 2));
+
+        // Punch-through alpha keeps transparent pixels transparent.
+        let mut cutout = block;
+        for (i, px) in cutout.iter_mut().enumerate() {
+            px[3] = if i % 3 == 0 { 0 } else { 255 };
+        }
+        bcn::decode_bc1(&bcn::encode_bc1(&cutout, true), true, &mut out);
+        for (decoded, source) in out.iter().zip(&cutout) {
+            assert_eq!(decoded[3], source[3]);
+        }
+    }
+}
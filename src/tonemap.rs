@@ -0,0 +1,642 @@
+//! HDR → SDR tone mapping.
+//!
+//! When PQ or HLG content is encoded to a format that can't signal HDR
+//! (JPEG, PNG, WebP, GIF, bitmaps), writing the code values through
+//! unchanged clips every highlight above SDR white. A [`ToneMapping`] on
+//! [`TranscodeOptions`](crate::TranscodeOptions) or
+//! [`EncodeRequest::with_tone_mapping`](crate::EncodeRequest::with_tone_mapping)
+//! compresses the highlights into the SDR range instead.
+//!
+//! Operators work on the maximum of R, G and B so hue is preserved. PQ and
+//! HLG input is taken to have BT.2020 primaries (the only combination in
+//! practical use) and is converted to BT.709 before mapping. Output is
+//! 8-bit sRGB.
+//!
+//! The pixel functions need floating-point math from `std`. Without the
+//! `std` feature, encoding HDR input to an SDR-only format fails with
+//! [`CodecError::UnsupportedOperation`](crate::CodecError::UnsupportedOperation)
+//! unless the operator is [`Clip`](ToneMapOperator::Clip).
+
+/// Tone mapping curve.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// ITU-R BT.2390 EETF: identity below a knee, Hermite roll-off above
+    /// it, evaluated in the PQ domain. Keeps mid-tones exactly as mastered.
+    #[default]
+    Bt2390,
+
+    /// Extended Reinhard, with the white point at the source peak.
+    Reinhard,
+
+    /// Filmic curve fitted to the ACES reference rendering (Narkowicz).
+    /// Adds contrast and desaturates highlights.
+    Aces,
+
+    /// Use the image's own gain map to produce the SDR rendition, as the
+    /// author graded it. Falls back to [`Bt2390`](Self::Bt2390) when the
+    /// image has no gain map with an HDR base.
+    GainMap,
+
+    /// No tone mapping: values above SDR white are clipped.
+    Clip,
+}
+
+/// Tone mapping settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    /// Curve to apply.
+    pub operator: ToneMapOperator,
+
+    /// Luminance of SDR white in the output, in cd/m² (default: 203, the
+    /// BT.2408 HDR reference white).
+    pub target_peak_nits: f32,
+
+    /// Source peak luminance in cd/m². `None` measures the brightest pixel
+    /// (PQ) or assumes the 1000 cd/m² HLG reference display.
+    pub source_peak_nits: Option<f32>,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            operator: ToneMapOperator::default(),
+            target_peak_nits: 203.0,
+            source_peak_nits: None,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Tone mapping with `operator` and default peaks.
+    pub fn new(operator: ToneMapOperator) -> Self {
+        Self {
+            operator,
+            ..Self::default()
+        }
+    }
+
+    /// Set the output SDR white luminance in cd/m².
+    pub fn with_target_peak_nits(mut self, nits: f32) -> Self {
+        self.target_peak_nits = nits;
+        self
+    }
+
+    /// Set the source peak luminance in cd/m² instead of measuring it.
+    pub fn with_source_peak_nits(mut self, nits: f32) -> Self {
+        self.source_peak_nits = Some(nits);
+        self
+    }
+}
+
+/// Whether `format` can carry HDR pixels, so no tone mapping is needed.
+pub fn format_supports_hdr(format: crate::ImageFormat) -> bool {
+    use crate::ImageFormat;
+    matches!(
+        format,
+        ImageFormat::Avif | ImageFormat::Jxl | ImageFormat::Heic | ImageFormat::Hdr
+    ) || matches!(format, ImageFormat::Custom(def) if def.name == "exr")
+}
+
+/// Whether `transfer` is an HDR transfer function (PQ or HLG).
+pub fn is_hdr_transfer(transfer: zenpixels::TransferFunction) -> bool {
+    matches!(
+        transfer,
+        zenpixels::TransferFunction::Pq | zenpixels::TransferFunction::Hlg
+    )
+}
+
+/// Transfer function of decoded pixels: the source's CICP tag, else what
+/// the decoder put in the pixel descriptor.
+pub(crate) fn source_transfer(
+    info: &crate::ImageInfo,
+    descriptor: zenpixels::PixelDescriptor,
+) -> zenpixels::TransferFunction {
+    info.source_color
+        .cicp
+        .as_ref()
+        .map(|c| c.transfer_function_enum())
+        .unwrap_or_else(|| descriptor.transfer())
+}
+
+/// Whether encoding `transfer` pixels to `format` under `mapping` calls
+/// for tone mapping.
+pub(crate) fn needs_tone_mapping(
+    format: crate::ImageFormat,
+    transfer: zenpixels::TransferFunction,
+    mapping: &ToneMapping,
+) -> bool {
+    mapping.operator != ToneMapOperator::Clip
+        && !format_supports_hdr(format)
+        && is_hdr_transfer(transfer)
+}
+
+/// The error for HDR input that needs tone mapping in a build without the
+/// float math to do it.
+#[cfg(not(feature = "std"))]
+pub(crate) fn unavailable(format: crate::ImageFormat) -> whereat::At<crate::CodecError> {
+    whereat::at!(crate::CodecError::UnsupportedOperation {
+        format,
+        detail: "tone mapping requires the `std` feature; use ToneMapOperator::Clip to encode HDR as-is",
+    })
+}
+
+#[cfg(feature = "std")]
+pub use imp::tone_map_to_sdr;
+#[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+pub use imp::tone_map_with_gain_map;
+#[cfg(all(feature = "std", feature = "jpeg-ultrahdr"))]
+pub(crate) use imp::{hdr_from_linear, linear_pixels, sdr_from_linear};
+
+#[cfg(feature = "std")]
+mod imp {
+    use alloc::vec::Vec;
+
+    use super::{ToneMapOperator, ToneMapping};
+    use crate::CodecError;
+    use crate::error::Result;
+    #[cfg(feature = "jpeg-ultrahdr")]
+    use crate::gainmap::HdrOutput;
+    use whereat::at;
+    use zenpixels::{
+        ChannelLayout, ChannelType, PixelBuffer, PixelDescriptor, PixelSlice, TransferFunction,
+    };
+
+    /// PQ (SMPTE ST 2084) constants.
+    const M1: f32 = 0.159_301_76;
+    const M2: f32 = 78.843_75;
+    const C1: f32 = 0.835_937_5;
+    const C2: f32 = 18.851_563;
+    const C3: f32 = 18.6875;
+
+    /// HLG (ARIB STD-B67) constants.
+    const HLG_A: f32 = 0.178_832_77;
+    const HLG_B: f32 = 0.284_668_92;
+    const HLG_C: f32 = 0.559_910_7;
+
+    /// HLG reference display peak in cd/m².
+    const HLG_PEAK_NITS: f32 = 1000.0;
+
+    /// BT.2020 → BT.709 in linear light.
+    const BT2020_TO_BT709: [[f32; 3]; 3] = [
+        [1.660_491, -0.587_641_1, -0.072_849_9],
+        [-0.124_550_5, 1.132_9, -0.008_349_4],
+        [-0.018_150_8, -0.100_578_9, 1.118_729_7],
+    ];
+
+    /// BT.709 → BT.2020 in linear light.
+    #[cfg(feature = "jpeg-ultrahdr")]
+    const BT709_TO_BT2020: [[f32; 3]; 3] = [
+        [0.627_404, 0.329_283, 0.043_313],
+        [0.069_097, 0.919_540, 0.011_362],
+        [0.016_391, 0.088_013, 0.895_595],
+    ];
+
+    /// PQ code value (0-1) → cd/m².
+    fn pq_to_nits(e: f32) -> f32 {
+        let p = e.max(0.0).powf(1.0 / M2);
+        10000.0 * ((p - C1).max(0.0) / (C2 - C3 * p)).powf(1.0 / M1)
+    }
+
+    /// cd/m² → PQ code value (0-1).
+    fn nits_to_pq(nits: f32) -> f32 {
+        let y = (nits / 10000.0).clamp(0.0, 1.0).powf(M1);
+        ((C1 + C2 * y) / (1.0 + C3 * y)).powf(M2)
+    }
+
+    /// HLG code value (0-1) → scene-linear (0-1).
+    fn hlg_to_scene(e: f32) -> f32 {
+        if e <= 0.5 {
+            e * e / 3.0
+        } else {
+            (((e - HLG_C) / HLG_A).exp() + HLG_B) / 12.0
+        }
+    }
+
+    /// sRGB-encoded (0-1) → linear (0-1).
+    fn srgb_decode(e: f32) -> f32 {
+        if e <= 0.040_45 {
+            e / 12.92
+        } else {
+            ((e + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    /// Linear (0-1) → sRGB-encoded 8-bit.
+    fn srgb_encode(v: f32) -> u8 {
+        let v = v.clamp(0.0, 1.0);
+        let e = if v <= 0.003_130_8 {
+            v * 12.92
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        };
+        (e * 255.0 + 0.5) as u8
+    }
+
+    /// Read every pixel of `pixels` as normalized RGBA code values.
+    fn for_each_pixel(pixels: &PixelSlice<'_>, mut f: impl FnMut([f32; 4])) -> Result<()> {
+        let descriptor = pixels.descriptor();
+        let channels = match descriptor.layout() {
+            ChannelLayout::Gray => 1,
+            ChannelLayout::GrayAlpha => 2,
+            ChannelLayout::Rgb => 3,
+            ChannelLayout::Rgba => 4,
+            other => {
+                return Err(at!(CodecError::InvalidInput(alloc::format!(
+                    "tone mapping: unsupported channel layout {other:?}"
+                ))));
+            }
+        };
+        let channel_type = descriptor.channel_type();
+        let sample_bytes = match channel_type {
+            ChannelType::U8 => 1,
+            ChannelType::U16 | ChannelType::F16 => 2,
+            ChannelType::F32 => 4,
+            other => {
+                return Err(at!(CodecError::InvalidInput(alloc::format!(
+                    "tone mapping: unsupported sample type {other:?}"
+                ))));
+            }
+        };
+        let sample = |b: &[u8]| -> f32 {
+            match channel_type {
+                ChannelType::U8 => f32::from(b[0]) / 255.0,
+                ChannelType::U16 => f32::from(u16::from_ne_bytes([b[0], b[1]])) / 65535.0,
+                ChannelType::F16 => crate::depthmap::f16_to_f32(u16::from_ne_bytes([b[0], b[1]])),
+                _ => f32::from_ne_bytes([b[0], b[1], b[2], b[3]]),
+            }
+        };
+
+        let data = pixels.as_strided_bytes();
+        let row_bytes = pixels.width() as usize * channels * sample_bytes;
+        for y in 0..pixels.rows() as usize {
+            let row = &data[y * pixels.stride()..][..row_bytes];
+            for px in row.chunks_exact(channels * sample_bytes) {
+                let s = |i: usize| sample(&px[i * sample_bytes..]);
+                f(match channels {
+                    1 => [s(0), s(0), s(0), 1.0],
+                    2 => [s(0), s(0), s(0), s(1)],
+                    3 => [s(0), s(1), s(2), 1.0],
+                    _ => [s(0), s(1), s(2), s(3)],
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Code values → display-linear BT.709 RGB in cd/m².
+    fn to_nits(rgb: [f32; 3], transfer: TransferFunction) -> [f32; 3] {
+        let bt2020 = match transfer {
+            TransferFunction::Pq => rgb.map(pq_to_nits),
+            TransferFunction::Hlg => {
+                // Inverse OETF, then the BT.2100 OOTF (γ = 1.2 at 1000 cd/m²).
+                let scene = rgb.map(hlg_to_scene);
+                let ys = 0.2627 * scene[0] + 0.6780 * scene[1] + 0.0593 * scene[2];
+                let gain = HLG_PEAK_NITS * ys.max(0.0).powf(0.2);
+                scene.map(|v| v * gain)
+            }
+            // Linear light with 1.0 at SDR reference white.
+            TransferFunction::Linear => return rgb.map(|v| v * 203.0),
+            _ => return rgb.map(|v| srgb_decode(v) * 203.0),
+        };
+        let m = &BT2020_TO_BT709;
+        [0, 1, 2]
+            .map(|i| (m[i][0] * bt2020[0] + m[i][1] * bt2020[1] + m[i][2] * bt2020[2]).max(0.0))
+    }
+
+    /// The tone curve: source cd/m² → output relative to SDR white (0-1).
+    struct Curve {
+        operator: ToneMapOperator,
+        target: f32,
+        peak: f32,
+        pq_peak: f32,
+        pq_target: f32,
+    }
+
+    impl Curve {
+        fn new(operator: ToneMapOperator, target: f32, peak: f32) -> Self {
+            let peak = peak.max(target);
+            Self {
+                operator,
+                target,
+                peak,
+                pq_peak: nits_to_pq(peak),
+                pq_target: nits_to_pq(target),
+            }
+        }
+
+        fn apply(&self, nits: f32) -> f32 {
+            let x = nits / self.target;
+            match self.operator {
+                ToneMapOperator::Clip => x,
+                ToneMapOperator::Reinhard => {
+                    let w = self.peak / self.target;
+                    x * (1.0 + x / (w * w)) / (1.0 + x)
+                }
+                ToneMapOperator::Aces => {
+                    let x = x * 0.6;
+                    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).min(1.0)
+                }
+                ToneMapOperator::Bt2390 | ToneMapOperator::GainMap => {
+                    // Normalized to the source range in the PQ domain.
+                    let e = (nits_to_pq(nits) / self.pq_peak).min(1.0);
+                    let max_lum = self.pq_target / self.pq_peak;
+                    let ks = 1.5 * max_lum - 0.5;
+                    let e2 = if e < ks || ks >= 1.0 {
+                        e
+                    } else {
+                        let t = (e - ks) / (1.0 - ks);
+                        let (t2, t3) = (t * t, t * t * t);
+                        (2.0 * t3 - 3.0 * t2 + 1.0) * ks
+                            + (t3 - 2.0 * t2 + t) * (1.0 - ks)
+                            + (-2.0 * t3 + 3.0 * t2) * max_lum
+                    };
+                    pq_to_nits(e2 * self.pq_peak) / self.target
+                }
+            }
+        }
+
+        /// Map display-linear RGB by its maximum component.
+        fn map(&self, rgb: [f32; 3]) -> [f32; 3] {
+            let m = rgb[0].max(rgb[1]).max(rgb[2]);
+            if m <= 0.0 {
+                return [0.0; 3];
+            }
+            let scale = self.apply(m) / m;
+            rgb.map(|v| v * scale)
+        }
+    }
+
+    /// Write 8-bit sRGB output, keeping alpha when the source has it.
+    fn write_sdr(out: &mut Vec<u8>, rgb: [f32; 3], alpha: f32, has_alpha: bool) {
+        out.extend(rgb.map(srgb_encode));
+        if has_alpha {
+            out.push((alpha.clamp(0.0, 1.0) * 255.0 + 0.5) as u8);
+        }
+    }
+
+    fn sdr_buffer(data: Vec<u8>, width: u32, height: u32, has_alpha: bool) -> Result<PixelBuffer> {
+        let descriptor = if has_alpha {
+            PixelDescriptor::RGBA8_SRGB
+        } else {
+            PixelDescriptor::RGB8_SRGB
+        };
+        PixelBuffer::from_vec(data, width, height, descriptor).map_err(|_| {
+            at!(CodecError::InvalidInput(
+                "failed to create PixelBuffer for tone-mapped image".into()
+            ))
+        })
+    }
+
+    /// Tone-map HDR `pixels` encoded with `transfer` to 8-bit sRGB.
+    ///
+    /// [`ToneMapOperator::GainMap`] behaves like
+    /// [`Bt2390`](ToneMapOperator::Bt2390) here; use
+    /// [`tone_map_with_gain_map`] when a gain map is available.
+    pub fn tone_map_to_sdr(
+        pixels: PixelSlice<'_>,
+        transfer: TransferFunction,
+        mapping: &ToneMapping,
+    ) -> Result<PixelBuffer> {
+        let target = mapping.target_peak_nits.max(1.0);
+        let peak = match (mapping.source_peak_nits, transfer) {
+            (Some(peak), _) => peak,
+            (None, TransferFunction::Hlg) => HLG_PEAK_NITS,
+            (None, _) => {
+                let mut peak = 0.0f32;
+                for_each_pixel(&pixels, |px| {
+                    let nits = to_nits([px[0], px[1], px[2]], transfer);
+                    peak = peak.max(nits[0].max(nits[1]).max(nits[2]));
+                })?;
+                peak.min(10000.0)
+            }
+        };
+        let curve = Curve::new(mapping.operator, target, peak);
+
+        let has_alpha = pixels.descriptor().has_alpha();
+        let mut out = Vec::with_capacity(
+            pixels.width() as usize * pixels.rows() as usize * if has_alpha { 4 } else { 3 },
+        );
+        for_each_pixel(&pixels, |px| {
+            let rgb = curve.map(to_nits([px[0], px[1], px[2]], transfer));
+            write_sdr(&mut out, rgb, px[3], has_alpha);
+        })?;
+        sdr_buffer(out, pixels.width(), pixels.rows(), has_alpha)
+    }
+
+    /// Produce the SDR rendition of an HDR-base image from its gain map
+    /// (ISO 21496-1 application at the headroom of `target_peak_nits`).
+    ///
+    /// Falls back to [`tone_map_to_sdr`] with BT.2390 when the gain map's
+    /// base is SDR (JPEG, AVIF) — the base itself is then the SDR image
+    /// and `pixels` shouldn't be HDR.
+    #[cfg(feature = "jpeg-ultrahdr")]
+    pub fn tone_map_with_gain_map(
+        pixels: PixelSlice<'_>,
+        transfer: TransferFunction,
+        gain_map: &crate::gainmap::DecodedGainMap,
+        mapping: &ToneMapping,
+    ) -> Result<PixelBuffer> {
+        if !gain_map.base_is_hdr || gain_map.gain_map.width == 0 || gain_map.gain_map.height == 0 {
+            let fallback = ToneMapping {
+                operator: ToneMapOperator::Bt2390,
+                ..*mapping
+            };
+            return tone_map_to_sdr(pixels, transfer, &fallback);
+        }
+
+        let (width, height) = (pixels.width(), pixels.rows());
+        let (mut rgb, alpha) = linear_pixels(&pixels, transfer)?;
+        let headroom = f64::from(mapping.target_peak_nits.max(1.0) / 203.0).log2();
+        gain_map.apply_linear(
+            &mut rgb,
+            width as usize,
+            height as usize,
+            gain_map.weight_for_headroom(headroom),
+        )?;
+        sdr_from_linear(&rgb, alpha.as_deref(), width, height)
+    }
+
+    /// Linear RGB plus optional alpha, one entry per pixel.
+    #[cfg(feature = "jpeg-ultrahdr")]
+    type LinearPixels = (Vec<[f32; 3]>, Option<Vec<f32>>);
+
+    /// Decode `pixels` to linear BT.709 RGB with 1.0 at SDR reference white
+    /// (203 cd/m²), plus alpha when the layout has it.
+    #[cfg(feature = "jpeg-ultrahdr")]
+    pub(crate) fn linear_pixels(
+        pixels: &PixelSlice<'_>,
+        transfer: TransferFunction,
+    ) -> Result<LinearPixels> {
+        let count = pixels.width() as usize * pixels.rows() as usize;
+        let has_alpha = pixels.descriptor().has_alpha();
+        let mut rgb = Vec::with_capacity(count);
+        let mut alpha = Vec::with_capacity(if has_alpha { count } else { 0 });
+        for_each_pixel(pixels, |px| {
+            rgb.push(to_nits([px[0], px[1], px[2]], transfer).map(|v| v / 203.0));
+            if has_alpha {
+                alpha.push(px[3]);
+            }
+        })?;
+        Ok((rgb, has_alpha.then_some(alpha)))
+    }
+
+    /// Encode linear BT.709 RGB (1.0 = SDR white) as 8-bit sRGB, clipping
+    /// above white.
+    #[cfg(feature = "jpeg-ultrahdr")]
+    pub(crate) fn sdr_from_linear(
+        rgb: &[[f32; 3]],
+        alpha: Option<&[f32]>,
+        width: u32,
+        height: u32,
+    ) -> Result<PixelBuffer> {
+        let has_alpha = alpha.is_some();
+        let mut out = Vec::with_capacity(rgb.len() * if has_alpha { 4 } else { 3 });
+        for (i, px) in rgb.iter().enumerate() {
+            write_sdr(&mut out, *px, alpha.map_or(1.0, |a| a[i]), has_alpha);
+        }
+        sdr_buffer(out, width, height, has_alpha)
+    }
+
+    /// Store linear BT.709 RGB (1.0 = SDR white) as an f32 buffer: linear
+    /// BT.709, or PQ-encoded BT.2020 for [`HdrOutput::Pq`].
+    #[cfg(feature = "jpeg-ultrahdr")]
+    pub(crate) fn hdr_from_linear(
+        rgb: Vec<[f32; 3]>,
+        alpha: Option<&[f32]>,
+        width: u32,
+        height: u32,
+        output: HdrOutput,
+    ) -> Result<PixelBuffer> {
+        let encode = |px: [f32; 3]| match output {
+            HdrOutput::Linear => px,
+            HdrOutput::Pq => {
+                let m = &BT709_TO_BT2020;
+                [0, 1, 2].map(|i| {
+                    nits_to_pq((m[i][0] * px[0] + m[i][1] * px[1] + m[i][2] * px[2]) * 203.0)
+                })
+            }
+        };
+        let (w, h) = (width as usize, height as usize);
+        let (data, descriptor) = match alpha {
+            Some(alpha) => {
+                let pixels: Vec<rgb::Rgba<f32>> = rgb
+                    .into_iter()
+                    .zip(alpha)
+                    .map(|(px, &a)| {
+                        let [r, g, b] = encode(px);
+                        rgb::Rgba { r, g, b, a }
+                    })
+                    .collect();
+                let img = imgref::ImgVec::new(pixels, w, h);
+                let descriptor = PixelSlice::from(img.as_ref()).erase().descriptor();
+                (bytemuck::cast_slice(img.buf()).to_vec(), descriptor)
+            }
+            None => {
+                let pixels: Vec<rgb::Rgb<f32>> = rgb
+                    .into_iter()
+                    .map(|px| {
+                        let [r, g, b] = encode(px);
+                        rgb::Rgb { r, g, b }
+                    })
+                    .collect();
+                let img = imgref::ImgVec::new(pixels, w, h);
+                let descriptor = PixelSlice::from(img.as_ref()).erase().descriptor();
+                (bytemuck::cast_slice(img.buf()).to_vec(), descriptor)
+            }
+        };
+        let descriptor = match output {
+            HdrOutput::Linear => descriptor,
+            HdrOutput::Pq => descriptor.with_transfer(TransferFunction::Pq),
+        };
+        PixelBuffer::from_vec(data, width, height, descriptor).map_err(|_| {
+            at!(CodecError::InvalidInput(
+                "failed to create PixelBuffer for HDR rendition".into()
+            ))
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn pq_round_trips() {
+            for nits in [0.1f32, 1.0, 100.0, 203.0, 1000.0, 4000.0, 10000.0] {
+                let back = pq_to_nits(nits_to_pq(nits));
+                assert!((back - nits).abs() / nits < 1e-3, "{nits} → {back}");
+            }
+        }
+
+        #[test]
+        fn curves_are_monotonic_and_bounded() {
+            for op in [
+                ToneMapOperator::Bt2390,
+                ToneMapOperator::Reinhard,
+                ToneMapOperator::Aces,
+            ] {
+                let curve = Curve::new(op, 203.0, 4000.0);
+                let mut prev = 0.0;
+                for step in 1..=400 {
+                    let y = curve.apply(step as f32 * 10.0);
+                    assert!(y >= prev - 1e-5, "{op:?} not monotonic at {}", step * 10);
+                    assert!(y <= 1.0 + 1e-3, "{op:?} exceeds SDR white: {y}");
+                    prev = y;
+                }
+                assert!(curve.apply(4000.0) > 0.9, "{op:?} loses the peak");
+            }
+        }
+
+        #[test]
+        fn bt2390_keeps_shadows() {
+            let curve = Curve::new(ToneMapOperator::Bt2390, 203.0, 1000.0);
+            let y = curve.apply(20.0);
+            assert!((y - 20.0 / 203.0).abs() < 1e-3, "{y}");
+        }
+
+        #[test]
+        fn pq_highlights_are_compressed_not_clipped() {
+            // Two grey PQ pixels at 1000 and 4000 cd/m².
+            let code = |nits: f32| (nits_to_pq(nits) * 255.0 + 0.5) as u8;
+            let data = [code(1000.0); 3]
+                .into_iter()
+                .chain([code(4000.0); 3])
+                .collect::<Vec<u8>>();
+            let slice = PixelSlice::new(&data, 2, 1, 6, PixelDescriptor::RGB8_SRGB).unwrap();
+            let sdr =
+                tone_map_to_sdr(slice, TransferFunction::Pq, &ToneMapping::default()).unwrap();
+            let bytes = sdr.as_slice().as_strided_bytes().to_vec();
+            assert!(bytes[0] < bytes[3], "highlights clipped: {bytes:?}");
+        }
+
+        #[test]
+        fn f16_input_matches_f32() {
+            // PQ code values 0.5 and 0.75, exact in both precisions.
+            let half: Vec<u8> = [0x3800u16, 0x3800, 0x3800, 0x3A00, 0x3A00, 0x3A00]
+                .iter()
+                .flat_map(|h| h.to_ne_bytes())
+                .collect();
+            let single: Vec<u8> = [0.5f32, 0.5, 0.5, 0.75, 0.75, 0.75]
+                .iter()
+                .flat_map(|v| v.to_ne_bytes())
+                .collect();
+            let map = |data: &[u8], channel_type, stride| {
+                let descriptor = PixelDescriptor::new(
+                    channel_type,
+                    ChannelLayout::Rgb,
+                    None,
+                    TransferFunction::Pq,
+                );
+                let slice = PixelSlice::new(data, 2, 1, stride, descriptor).unwrap();
+                tone_map_to_sdr(slice, TransferFunction::Pq, &ToneMapping::default())
+                    .unwrap()
+                    .as_slice()
+                    .as_strided_bytes()
+                    .to_vec()
+            };
+            assert_eq!(
+                map(&half, ChannelType::F16, 12),
+                map(&single, ChannelType::F32, 24)
+            );
+        }
+    }
+}
@@ -62,6 +62,18 @@ pub enum SelectionStep {
     DecoderFailed { id: CodecId, error: String },
     /// Fallback from one decoder to another.
     FallbackAttempt { from: CodecId, to: CodecId },
+    /// A format was encoded as a candidate in competitive mode.
+    CandidateEncoded {
+        format: ImageFormat,
+        /// Encoded size in bytes.
+        bytes: usize,
+        /// Generic quality used.
+        quality: f32,
+        /// Metric score, when a score target was set.
+        score: Option<f64>,
+    },
+    /// A candidate encode failed or missed the quality threshold.
+    CandidateRejected { format: ImageFormat, reason: String },
     /// Informational note.
     Info { message: &'static str },
 }
@@ -106,6 +118,17 @@ impl SelectionTrace {
         })
     }
 
+    /// Candidates encoded in competitive mode, as `(format, bytes)`.
+    pub fn candidate_sizes(&self) -> Vec<(ImageFormat, usize)> {
+        self.steps
+            .iter()
+            .filter_map(|s| match s {
+                SelectionStep::CandidateEncoded { format, bytes, .. } => Some((*format, *bytes)),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Whether any decoder failed during selection (indicating fallback was attempted).
     pub fn had_failures(&self) -> bool {
         self.steps
@@ -159,6 +182,24 @@ impl core::fmt::Display for SelectionStep {
             Self::FallbackAttempt { from, to } => {
                 write!(f, "[fall]   {from} -> {to}")
             }
+            Self::CandidateEncoded {
+                format,
+                bytes,
+                quality,
+                score,
+            } => {
+                write!(
+                    f,
+                    "[cand]   {format:?}: {bytes} bytes at quality {quality:.1}"
+                )?;
+                if let Some(score) = score {
+                    write!(f, ", score {score:.2}")?;
+                }
+                Ok(())
+            }
+            Self::CandidateRejected { format, reason } => {
+                write!(f, "[reject] {format:?}: {reason}")
+            }
             Self::Info { message } => {
                 write!(f, "[info]   {message}")
             }
@@ -218,6 +259,34 @@ mod tests {
         assert!(trace.had_failures());
     }
 
+    #[test]
+    fn candidate_sizes_collects_encoded() {
+        let mut trace = SelectionTrace::new();
+        trace.push(SelectionStep::CandidateEncoded {
+            format: ImageFormat::Jpeg,
+            bytes: 5000,
+            quality: 73.0,
+            score: None,
+        });
+        trace.push(SelectionStep::CandidateRejected {
+            format: ImageFormat::Avif,
+            reason: "encode failed".into(),
+        });
+        trace.push(SelectionStep::CandidateEncoded {
+            format: ImageFormat::WebP,
+            bytes: 4000,
+            quality: 73.0,
+            score: Some(81.5),
+        });
+        assert_eq!(
+            trace.candidate_sizes(),
+            alloc::vec![(ImageFormat::Jpeg, 5000), (ImageFormat::WebP, 4000)]
+        );
+        let s = alloc::format!("{trace}");
+        assert!(s.contains("[cand]   WebP: 4000 bytes"));
+        assert!(s.contains("score 81.50"));
+    }
+
     #[test]
     fn display_format() {
         let step = SelectionStep::FormatChosen {
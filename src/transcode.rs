@@ -10,7 +10,7 @@
 //!
 //! [`TranscodeSink`] is the low-level streaming bridge. It implements
 //! [`DecodeRowSink`] and forwards decoded strips directly to an encoder's
-//! `push_rows()`, converting pixel formats per-strip via `adapt_for_encode`.
+//! `push_rows()`, converting pixel formats per-strip via `adapt_for_encode_cow`.
 //! No full-image buffer is ever allocated by the sink — only a strip-sized
 //! conversion buffer when the decoded pixel format doesn't match the
 //! encoder's native format.
@@ -28,7 +28,8 @@ use zenpixels::{PixelDescriptor, PixelSliceMut};
 
 use crate::decision::FormatDecision;
 use crate::error::Result;
-use crate::{AllowedFormats, CodecError, ImageFormat};
+use crate::tonemap::ToneMapping;
+use crate::{AllowedFormats, CodecError, ImageFormat, StopToken};
 use whereat::at;
 
 // ═══════════════════════════════════════════════════════════════════════
@@ -55,6 +56,27 @@ pub struct TranscodeOptions {
     ///
     /// `None` defaults to white `[255, 255, 255]`.
     pub matte: Option<[u8; 3]>,
+
+    /// HDR → SDR tone mapping, used when a PQ/HLG source is encoded to a
+    /// format that can't carry HDR (see
+    /// [`format_supports_hdr`](crate::tonemap::format_supports_hdr)).
+    ///
+    /// Defaults to BT.2390 at 203 cd/m². The source's ICC profile and CICP
+    /// tags are dropped from the output when tone mapping is applied.
+    /// Requires the `std` feature; without it, HDR sources that need tone
+    /// mapping fail with [`CodecError::UnsupportedOperation`] unless the
+    /// operator is `Clip`.
+    pub tone_mapping: ToneMapping,
+
+    /// Never encode above the source's own quality.
+    ///
+    /// For lossy output, lowers `decision.quality` to the estimate from
+    /// [`estimate_source_quality`](crate::estimate_source_quality) when the
+    /// source is a JPEG or lossy WebP encoded below it.
+    pub cap_to_source_quality: bool,
+
+    /// Cancellation token for the decode, encode and lossless JPEG paths.
+    pub stop: Option<StopToken>,
 }
 
 /// What to do with container supplements (gain maps, depth maps, etc.)
@@ -66,6 +88,13 @@ pub enum SupplementPolicy {
     /// Gain maps, depth maps, and auxiliary images are extracted from the
     /// source container and re-embedded in the output container.
     /// Supplements that the target format can't represent are silently dropped.
+    ///
+    /// Gain maps move between UltraHDR JPEG, AVIF `tmap` and JXL `jhgm`.
+    /// JXL stores the HDR rendition as the base, so JPEG/AVIF ↔ JXL renders
+    /// the other rendition as the new base and inverts the gain map.
+    /// Depth maps move into JPEG (GDepth or Dynamic Depth) and AVIF
+    /// (auxiliary image); a target that also receives a gain map keeps
+    /// only the gain map.
     #[default]
     Preserve,
 
@@ -161,6 +190,7 @@ pub struct TranscodeOutput {
 ///     lossless: false,
 ///     hints: Default::default(),
 ///     matte: None,
+///     lossless_jpeg_transcode: false,
 ///     trace: Vec::new(),
 /// };
 ///
@@ -178,25 +208,94 @@ pub fn transcode(
     opts: &TranscodeOptions,
     registry: &AllowedFormats,
 ) -> Result<TranscodeOutput> {
-    // Determine whether we need gain map data from the decode side.
-    let wants_gain_map = match opts.supplements {
+    // JXL → JPEG reconstruction: the rebuilt file carries its own
+    // metadata, so an explicit metadata override forces the pixel path.
+    #[cfg(feature = "jxl-decode")]
+    if decision.lossless_jpeg_transcode
+        && opts.metadata.is_none()
+        && let Some(output) = reconstruct_jpeg(data, decision, registry)?
+    {
+        return Ok(output);
+    }
+    // JPEG → JPEG: copy the DCT coefficients, applying the EXIF
+    // orientation in the coefficient domain.
+    #[cfg(feature = "jpeg")]
+    if decision.lossless_jpeg_transcode
+        && opts.metadata.is_none()
+        && let Some(output) = transform_jpeg(data, decision, opts, registry)?
+    {
+        return Ok(output);
+    }
+
+    // Determine which supplements we need from the decode side.
+    let wants = |supplement: SupplementSet| match opts.supplements {
         SupplementPolicy::Preserve => true,
-        SupplementPolicy::Only(set) => set.contains(SupplementSet::GAIN_MAP),
+        SupplementPolicy::Only(set) => set.contains(supplement),
         SupplementPolicy::Strip => false,
     };
+    let wants_gain_map = wants(SupplementSet::GAIN_MAP);
+
+    let format = decision.format;
+    #[cfg(feature = "jpeg-ultrahdr")]
+    let preserve_gain_map = wants_gain_map && carries_gain_map(format);
+    let preserve_depth_map =
+        wants(SupplementSet::DEPTH_MAP) && crate::depthmap::carries_depth_map(format);
 
     // Step 1: Decode the source image (full materialization for now)
-    let decoded = crate::DecodeRequest::new(data)
+    let mut request = crate::DecodeRequest::new(data)
         .with_registry(registry)
-        .with_gain_map_extraction(wants_gain_map)
-        .decode_full_frame()?;
+        .with_gain_map_extraction(wants_gain_map);
+    if let Some(stop) = &opts.stop {
+        request = request.with_stop(stop.clone());
+    }
+    #[cfg(feature = "jpeg-ultrahdr")]
+    let needs_gain_map = preserve_gain_map
+        || (cfg!(feature = "std")
+            && opts.tone_mapping.operator == crate::tonemap::ToneMapOperator::GainMap
+            && !crate::tonemap::format_supports_hdr(format));
+    #[cfg(not(feature = "jpeg-ultrahdr"))]
+    let needs_gain_map = false;
+    let (decoded, supplements) = if needs_gain_map || preserve_depth_map {
+        request.decode_supplements(needs_gain_map, preserve_depth_map)?
+    } else {
+        (request.decode_full_frame()?, Default::default())
+    };
+    #[cfg(feature = "jpeg-ultrahdr")]
+    let gain_map = supplements.gain_map;
 
-    // Step 2: Determine metadata to embed
+    // Step 2: Re-render the base if the target stores the gain map in the
+    // other direction, otherwise tone-map HDR sources for SDR-only targets
+    let transfer = crate::tonemap::source_transfer(decoded.info(), decoded.descriptor());
+    let buffer = decoded.into_buffer();
+    #[cfg(feature = "jpeg-ultrahdr")]
+    let (tone_mapped, gain_map) = match gain_map {
+        Some(gm) if preserve_gain_map => fit_gain_map_to_target(&buffer, transfer, gm, format)?,
+        gm => (
+            tone_map_for_target(&buffer, transfer, format, &opts.tone_mapping, gm.as_ref())?,
+            None,
+        ),
+    };
+    #[cfg(not(feature = "jpeg-ultrahdr"))]
+    let tone_mapped = tone_map_for_target(&buffer, transfer, format, &opts.tone_mapping)?;
+
+    // Step 3: Determine metadata to embed
     let metadata = match opts.metadata.clone() {
         Some(m) => m,
         None => {
             // Roundtrip metadata from source via probe
             match crate::info::from_bytes_with_registry(data, registry) {
+                // The source's color tags describe the original base's
+                // code values, not the re-rendered pixels.
+                Ok(info) if tone_mapped.is_some() => {
+                    let mut meta = zencodec::Metadata::none();
+                    if let Some(ref exif) = info.embedded_metadata.exif {
+                        meta = meta.with_exif(exif.clone());
+                    }
+                    if let Some(ref xmp) = info.embedded_metadata.xmp {
+                        meta = meta.with_xmp(xmp.clone());
+                    }
+                    meta
+                }
                 Ok(info) => info.metadata(),
                 Err(_) => {
                     // No metadata to roundtrip — proceed without it
@@ -205,27 +304,53 @@ pub fn transcode(
             }
         }
     };
+    let buffer = tone_mapped.unwrap_or(buffer);
 
-    // Step 3: Encode to the target format
-    let format = decision.format;
+    // Step 4: Encode to the target format
     if !registry.can_encode(format) {
         return Err(at!(CodecError::DisabledFormat(format)));
     }
 
     // Build the encode request from the decision
+    let mut quality = decision.quality.quality;
+    if opts.cap_to_source_quality
+        && !decision.lossless
+        && let Some(source) = crate::source_quality::estimate_source_quality(data)
+    {
+        quality = quality.min(source.generic);
+    }
     let mut request = crate::EncodeRequest::new(format)
-        .with_quality(decision.quality.quality)
+        .with_quality(quality)
         .with_metadata(metadata)
         .with_registry(registry);
 
     if decision.lossless {
         request = request.with_lossless(true);
     }
+    if let Some(stop) = &opts.stop {
+        request = request.with_stop(stop.clone());
+    }
     if let Some(effort) = decision.quality.effort {
         request = request.with_effort(effort);
     }
+    #[cfg(feature = "jpeg-ultrahdr")]
+    let has_gain_map = gain_map.is_some();
+    #[cfg(not(feature = "jpeg-ultrahdr"))]
+    let has_gain_map = false;
+    #[cfg(feature = "jpeg-ultrahdr")]
+    if let Some(gm) = &gain_map {
+        request = request.with_gain_map(crate::gainmap::GainMapSource::Precomputed {
+            gain_map: &gm.gain_map,
+            metadata: &gm.metadata,
+        });
+    }
+    // A container holds either a gain map or a depth map; the gain map wins.
+    if let Some(depth) = &supplements.depth_map
+        && !has_gain_map
+    {
+        request = request.with_depth_map(depth);
+    }
 
-    let buffer = decoded.into_buffer();
     let encode_output = request.encode(buffer.as_slice(), buffer.descriptor().has_alpha())?;
 
     Ok(TranscodeOutput {
@@ -235,6 +360,171 @@ pub fn transcode(
     })
 }
 
+/// Whether `format` can embed a gain map in this build.
+#[cfg(feature = "jpeg-ultrahdr")]
+fn carries_gain_map(format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Jpeg => true,
+        #[cfg(all(feature = "jxl-encode", feature = "jxl-decode"))]
+        ImageFormat::Jxl => true,
+        #[cfg(all(feature = "avif-encode", feature = "avif-decode"))]
+        ImageFormat::Avif => true,
+        _ => false,
+    }
+}
+
+/// Match a decoded gain map to the direction `format` stores it in.
+///
+/// UltraHDR JPEG and AVIF `tmap` keep an SDR base; JXL `jhgm` keeps an HDR
+/// base. When the source has the other direction, the alternate rendition
+/// becomes the new base and the gain map is inverted. Returns the
+/// replacement base pixels (if any) and the gain map to embed. Without
+/// `std` a mismatched gain map is dropped.
+#[cfg(feature = "jpeg-ultrahdr")]
+#[cfg_attr(not(feature = "std"), allow(unused_variables))]
+fn fit_gain_map_to_target(
+    buffer: &zenpixels::PixelBuffer,
+    transfer: zenpixels::TransferFunction,
+    gain_map: crate::gainmap::DecodedGainMap,
+    format: ImageFormat,
+) -> Result<(
+    Option<zenpixels::PixelBuffer>,
+    Option<crate::gainmap::DecodedGainMap>,
+)> {
+    let hdr_base = format == ImageFormat::Jxl;
+    if gain_map.base_is_hdr == hdr_base {
+        return Ok((None, Some(gain_map)));
+    }
+    #[cfg(feature = "std")]
+    {
+        let (width, height) = (buffer.width(), buffer.height());
+        let (mut rgb, alpha) = crate::tonemap::linear_pixels(&buffer.as_slice(), transfer)?;
+        gain_map.apply_linear(&mut rgb, width as usize, height as usize, 1.0)?;
+        let base = if hdr_base {
+            crate::tonemap::hdr_from_linear(
+                rgb,
+                alpha.as_deref(),
+                width,
+                height,
+                crate::gainmap::HdrOutput::Linear,
+            )?
+        } else {
+            crate::tonemap::sdr_from_linear(&rgb, alpha.as_deref(), width, height)?
+        };
+        Ok((Some(base), Some(gain_map.inverted())))
+    }
+    #[cfg(not(feature = "std"))]
+    Ok((None, None))
+}
+
+/// Tone-map `buffer` to SDR when `format` can't carry HDR and the source
+/// is PQ/HLG (or has an HDR-base gain map to apply). `None` means encode
+/// `buffer` as-is.
+#[cfg(feature = "std")]
+fn tone_map_for_target(
+    buffer: &zenpixels::PixelBuffer,
+    transfer: zenpixels::TransferFunction,
+    format: ImageFormat,
+    mapping: &ToneMapping,
+    #[cfg(feature = "jpeg-ultrahdr")] gain_map: Option<&crate::gainmap::DecodedGainMap>,
+) -> Result<Option<zenpixels::PixelBuffer>> {
+    use crate::tonemap::ToneMapOperator;
+
+    if crate::tonemap::format_supports_hdr(format) || mapping.operator == ToneMapOperator::Clip {
+        return Ok(None);
+    }
+    #[cfg(feature = "jpeg-ultrahdr")]
+    if mapping.operator == ToneMapOperator::GainMap
+        && let Some(gm) = gain_map.filter(|gm| gm.base_is_hdr)
+    {
+        return crate::tonemap::tone_map_with_gain_map(buffer.as_slice(), transfer, gm, mapping)
+            .map(Some);
+    }
+    if !crate::tonemap::is_hdr_transfer(transfer) {
+        return Ok(None);
+    }
+    crate::tonemap::tone_map_to_sdr(buffer.as_slice(), transfer, mapping).map(Some)
+}
+
+/// Without `std` nothing can be tone-mapped: HDR input bound for an
+/// SDR-only format is an error unless the operator is `Clip`.
+#[cfg(not(feature = "std"))]
+fn tone_map_for_target(
+    _buffer: &zenpixels::PixelBuffer,
+    transfer: zenpixels::TransferFunction,
+    format: ImageFormat,
+    mapping: &ToneMapping,
+    #[cfg(feature = "jpeg-ultrahdr")] _gain_map: Option<&crate::gainmap::DecodedGainMap>,
+) -> Result<Option<zenpixels::PixelBuffer>> {
+    if crate::tonemap::needs_tone_mapping(format, transfer, mapping) {
+        return Err(crate::tonemap::unavailable(format));
+    }
+    Ok(None)
+}
+
+/// JXL → JPEG reconstruction from the JXL's `jbrd` box.
+///
+/// Returns `Ok(None)` when the format pair doesn't qualify (or the JXL has
+/// no `jbrd` box), so the caller falls back to decode + encode.
+#[cfg(feature = "jxl-decode")]
+fn reconstruct_jpeg(
+    data: &[u8],
+    decision: &FormatDecision,
+    registry: &AllowedFormats,
+) -> Result<Option<TranscodeOutput>> {
+    if decision.format != ImageFormat::Jpeg
+        || crate::info::detect_format(data) != Some(ImageFormat::Jxl)
+    {
+        return Ok(None);
+    }
+    if !registry.can_decode(ImageFormat::Jxl) {
+        return Err(at!(CodecError::DisabledFormat(ImageFormat::Jxl)));
+    }
+    Ok(
+        crate::codecs::jxl_dec::reconstruct_jpeg(data)?.map(|jpeg| TranscodeOutput {
+            data: jpeg,
+            format: ImageFormat::Jpeg,
+            mime_type: ImageFormat::Jpeg.mime_type(),
+        }),
+    )
+}
+
+/// JPEG → JPEG without decoding: the source's coefficients, with its EXIF
+/// orientation applied losslessly.
+///
+/// Returns `Ok(None)` when the format pair doesn't qualify, or when the
+/// rotation would have to trim partial edge blocks, so the caller falls
+/// back to decode + encode.
+#[cfg(feature = "jpeg")]
+fn transform_jpeg(
+    data: &[u8],
+    decision: &FormatDecision,
+    opts: &TranscodeOptions,
+    registry: &AllowedFormats,
+) -> Result<Option<TranscodeOutput>> {
+    if decision.format != ImageFormat::Jpeg
+        || crate::info::detect_format(data) != Some(ImageFormat::Jpeg)
+    {
+        return Ok(None);
+    }
+    if !registry.can_decode(ImageFormat::Jpeg) || !registry.can_encode(ImageFormat::Jpeg) {
+        return Err(at!(CodecError::DisabledFormat(ImageFormat::Jpeg)));
+    }
+    let mut transform = crate::JpegTransform::new();
+    if let Some(stop) = &opts.stop {
+        transform = transform.with_stop(stop.clone());
+    }
+    match transform.apply(data) {
+        Ok(jpeg) => Ok(Some(TranscodeOutput {
+            data: jpeg,
+            format: ImageFormat::Jpeg,
+            mime_type: ImageFormat::Jpeg.mime_type(),
+        })),
+        Err(e) if matches!(e.error(), CodecError::UnsupportedOperation { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════════
 // TranscodeSink — streaming decode→encode bridge
 // ═══════════════════════════════════════════════════════════════════════
@@ -331,7 +621,7 @@ impl<'a> TranscodeSink<'a> {
         let strip_data = &self.strip_buf[..data_len];
 
         // Adapt pixel format per-strip — zero-copy when format already matches
-        let adapted = zenpixels_convert::adapt::adapt_for_encode(
+        let adapted = zenpixels_convert::adapt::adapt_for_encode_cow(
             strip_data,
             pending.descriptor,
             pending.width,
@@ -341,18 +631,8 @@ impl<'a> TranscodeSink<'a> {
         )
         .map_err(|e| -> SinkError { alloc::format!("adapt: {e}").into() })?;
 
-        let adapted_stride = adapted.width as usize * adapted.descriptor.bytes_per_pixel();
-        let pixel_slice = zenpixels::PixelSlice::new(
-            &adapted.data,
-            adapted.width,
-            adapted.rows,
-            adapted_stride,
-            adapted.descriptor,
-        )
-        .map_err(|e| -> SinkError { alloc::format!("pixel slice: {e}").into() })?;
-
         encoder
-            .push_rows(pixel_slice)
+            .push_rows(adapted.as_slice())
             .map_err(|e| -> SinkError { alloc::format!("push_rows: {e}").into() })
     }
 }
@@ -419,6 +699,39 @@ mod tests {
         assert!(core::mem::size_of::<TranscodeSink<'_>>() > 0);
     }
 
+    #[cfg(feature = "png")]
+    #[test]
+    fn transcode_sink_forwards_strips() {
+        let (width, height) = (8u32, 6u32);
+        let expected: Vec<u8> = (0..width * height * 3).map(|i| (i * 5) as u8).collect();
+        let se = crate::EncodeRequest::new(ImageFormat::Png)
+            .with_lossless(true)
+            .build_streaming_encoder(width, height)
+            .unwrap();
+        let mut sink = TranscodeSink::new(se.encoder, se.supported);
+        let descriptor = PixelDescriptor::RGB8_SRGB;
+        sink.begin(width, height, descriptor).unwrap();
+        let row_bytes = (width * 3) as usize;
+        for (y, rows) in [(0, 4), (4, 2)] {
+            let mut strip = sink
+                .provide_next_buffer(y, rows, width, descriptor)
+                .unwrap();
+            for r in 0..rows {
+                let start = (y + r) as usize * row_bytes;
+                strip
+                    .row_mut(r)
+                    .copy_from_slice(&expected[start..start + row_bytes]);
+            }
+        }
+        sink.finish().unwrap();
+        let output = sink.finish_encode().unwrap();
+
+        let decoded = crate::DecodeRequest::new(output.data())
+            .decode_full_frame()
+            .unwrap();
+        assert_eq!(decoded.pixels().contiguous_bytes().as_ref(), &expected[..]);
+    }
+
     #[test]
     fn supplement_set_operations() {
         let set = SupplementSet::GAIN_MAP | SupplementSet::DEPTH_MAP;
@@ -436,12 +749,46 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "jpeg-ultrahdr")]
+    #[test]
+    fn gain_map_targets() {
+        assert!(carries_gain_map(ImageFormat::Jpeg));
+        assert!(!carries_gain_map(ImageFormat::Png));
+        assert!(!carries_gain_map(ImageFormat::WebP));
+    }
+
     #[test]
     fn transcode_options_default() {
         let opts = TranscodeOptions::default();
         assert!(opts.metadata.is_none());
         assert!(opts.matte.is_none());
         assert!(matches!(opts.supplements, SupplementPolicy::Preserve));
+        assert_eq!(
+            opts.tone_mapping.operator,
+            crate::tonemap::ToneMapOperator::Bt2390
+        );
+    }
+
+    /// Non-JPEG sources ignore the flag and take the pixel path.
+    #[cfg(all(feature = "png", feature = "webp"))]
+    #[test]
+    fn lossless_jpeg_transcode_ignored_for_other_sources() {
+        let img = imgref::ImgVec::new(alloc::vec![rgb::Rgb { r: 1u8, g: 2, b: 3 }; 8 * 8], 8, 8);
+        let png = crate::EncodeRequest::new(ImageFormat::Png)
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap();
+        let decision = FormatDecision {
+            lossless_jpeg_transcode: true,
+            ..FormatDecision::for_format(ImageFormat::WebP)
+        };
+        let out = transcode(
+            png.data(),
+            &decision,
+            &TranscodeOptions::default(),
+            &AllowedFormats::all(),
+        )
+        .unwrap();
+        assert_eq!(out.format, ImageFormat::WebP);
     }
 
     /// Round-trip: encode a tiny JPEG, transcode to WebP, verify output.
@@ -478,6 +825,7 @@ mod tests {
             lossless: false,
             hints: Default::default(),
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: alloc::vec::Vec::new(),
         };
 
@@ -531,6 +879,7 @@ mod tests {
             lossless: false,
             hints: Default::default(),
             matte: None,
+            lossless_jpeg_transcode: false,
             trace: alloc::vec::Vec::new(),
         };
 
@@ -555,6 +904,104 @@ mod tests {
         );
     }
 
+    /// A flat `width`×`height` JPEG tagged EXIF orientation 6.
+    #[cfg(feature = "jpeg")]
+    fn jpeg_rotated_90(width: usize, height: usize) -> Vec<u8> {
+        let img = imgref::ImgVec::new(
+            alloc::vec![rgb::Rgb { r: 90u8, g: 120, b: 150 }; width * height],
+            width,
+            height,
+        );
+        let mut tiff = alloc::vec![b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0];
+        tiff.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        crate::EncodeRequest::new(ImageFormat::Jpeg)
+            .with_quality(90.0)
+            .with_metadata(zencodec::Metadata::none().with_exif(tiff))
+            .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+            .unwrap()
+            .into_vec()
+    }
+
+    #[cfg(feature = "jpeg")]
+    fn lossless_to_jpeg() -> FormatDecision {
+        FormatDecision {
+            lossless_jpeg_transcode: true,
+            ..FormatDecision::for_format(ImageFormat::Jpeg)
+        }
+    }
+
+    /// JPEG → JPEG keeps the coefficients and bakes the EXIF orientation.
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn lossless_jpeg_transcode_applies_orientation() {
+        let jpeg = jpeg_rotated_90(32, 16);
+        let source = crate::from_bytes(&jpeg).unwrap();
+        assert_eq!(source.orientation.to_exif(), 6);
+
+        let out = transcode(
+            &jpeg,
+            &lossless_to_jpeg(),
+            &TranscodeOptions::default(),
+            &AllowedFormats::all(),
+        )
+        .unwrap();
+        assert_eq!(out.format, ImageFormat::Jpeg);
+        let info = crate::from_bytes(&out.data).unwrap();
+        assert_eq!((info.width, info.height), (16, 32));
+        assert_eq!(info.orientation.to_exif(), 1);
+    }
+
+    /// A rotation that would trim partial edge blocks re-encodes instead,
+    /// keeping every pixel and the EXIF tag.
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn lossless_jpeg_transcode_falls_back_for_partial_blocks() {
+        let jpeg = jpeg_rotated_90(32, 20);
+        let direct = crate::JpegTransform::new().apply(&jpeg);
+        assert!(matches!(
+            direct.as_ref().map_err(|e| e.error()),
+            Err(CodecError::UnsupportedOperation { .. })
+        ));
+
+        let out = transcode(
+            &jpeg,
+            &lossless_to_jpeg(),
+            &TranscodeOptions::default(),
+            &AllowedFormats::all(),
+        )
+        .unwrap();
+        let info = crate::from_bytes(&out.data).unwrap();
+        assert_eq!((info.width, info.height), (32, 20));
+        assert_eq!(info.orientation.to_exif(), 6);
+    }
+
+    #[cfg(feature = "jpeg")]
+    #[test]
+    fn lossless_jpeg_transcode_honors_stop() {
+        struct AlreadyStopped;
+        impl enough::Stop for AlreadyStopped {
+            fn check(&self) -> core::result::Result<(), enough::StopReason> {
+                Err(enough::StopReason::Cancelled)
+            }
+        }
+
+        let opts = TranscodeOptions {
+            stop: Some(StopToken::new(AlreadyStopped)),
+            ..Default::default()
+        };
+        let result = transcode(
+            &jpeg_rotated_90(32, 16),
+            &lossless_to_jpeg(),
+            &opts,
+            &AllowedFormats::all(),
+        );
+        assert!(matches!(
+            result.as_ref().map_err(|e| e.error()),
+            Err(CodecError::Cancelled)
+        ));
+    }
+
     /// Verify probe() returns correct info for a JPEG.
     #[cfg(feature = "jpeg")]
     #[test]
@@ -728,6 +728,7 @@ impl QualityIntentNode {
             allowed,
             hints: Default::default(),
             matte: None,
+            cap_to_source_quality: false,
         }
     }
 
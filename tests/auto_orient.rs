@@ -0,0 +1,175 @@
+//! `DecodeRequest::with_auto_orient` end to end.
+//!
+//! Every image here is dark on its stored left half and bright on its right,
+//! and displays rotated 90° clockwise, so upright output is dark on top.
+//!
+//! `heic_irot_exif6.heic` is a hand-built 32×16 HEIC: two PCM-coded CTUs
+//! with an `irot` property and an EXIF block that both say 90° clockwise,
+//! as an iPhone writes them. The HEIC decoder applies `irot` itself.
+
+use zencodec::decode::{DecodeRowSink, SinkError};
+use zencodecs::{DecodeOutput, DecodeRequest, EncodeRequest, ImageFormat};
+use zenpixels::{PixelDescriptor, PixelSliceMut};
+
+/// Width, height, and whether the top-left and bottom-right pixels are
+/// dark, for tightly or loosely packed RGB(A) 8-bit pixels.
+fn layout(
+    width: u32,
+    height: u32,
+    stride: usize,
+    bpp: usize,
+    bytes: &[u8],
+) -> (u32, u32, bool, bool) {
+    let last = (height as usize - 1) * stride + (width as usize - 1) * bpp;
+    (width, height, bytes[0] < 100, bytes[last] < 100)
+}
+
+fn output_layout(output: &DecodeOutput) -> (u32, u32, bool, bool) {
+    let pixels = output.pixels();
+    layout(
+        pixels.width(),
+        pixels.rows(),
+        pixels.stride(),
+        pixels.descriptor().bytes_per_pixel(),
+        pixels.as_strided_bytes(),
+    )
+}
+
+/// A 16×8 JPEG, dark left and bright right, with EXIF Orientation=6.
+#[cfg(feature = "jpeg")]
+fn jpeg_exif6() -> Vec<u8> {
+    let pixels: Vec<rgb::Rgb<u8>> = (0..16 * 8)
+        .map(|i| {
+            let v = if i % 16 < 8 { 40 } else { 200 };
+            rgb::Rgb { r: v, g: v, b: v }
+        })
+        .collect();
+    let img = imgref::ImgVec::new(pixels, 16, 8);
+    let encoded = EncodeRequest::new(ImageFormat::Jpeg)
+        .with_quality(95.0)
+        .encode(zenpixels::PixelSlice::from(img.as_ref()).erase(), false)
+        .unwrap();
+
+    let mut tiff = vec![b'I', b'I', 42, 0, 8, 0, 0, 0, 1, 0];
+    tiff.extend_from_slice(&0x0112u16.to_le_bytes());
+    tiff.extend_from_slice(&3u16.to_le_bytes());
+    tiff.extend_from_slice(&1u32.to_le_bytes());
+    tiff.extend_from_slice(&6u16.to_le_bytes());
+    tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+    jpeg.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+    jpeg.extend_from_slice(b"Exif\0\0");
+    jpeg.extend_from_slice(&tiff);
+    jpeg.extend_from_slice(&encoded.data()[2..]);
+    jpeg
+}
+
+/// Collects a push decode into one packed frame.
+#[derive(Default)]
+struct FrameSink {
+    width: u32,
+    height: u32,
+    bpp: usize,
+    frame: Vec<u8>,
+    finished: bool,
+}
+
+impl DecodeRowSink for FrameSink {
+    fn begin(
+        &mut self,
+        width: u32,
+        height: u32,
+        descriptor: PixelDescriptor,
+    ) -> Result<(), SinkError> {
+        self.width = width;
+        self.height = height;
+        self.bpp = descriptor.bytes_per_pixel();
+        self.frame = vec![0; width as usize * height as usize * self.bpp];
+        Ok(())
+    }
+
+    fn provide_next_buffer(
+        &mut self,
+        y: u32,
+        height: u32,
+        width: u32,
+        descriptor: PixelDescriptor,
+    ) -> Result<PixelSliceMut<'_>, SinkError> {
+        let stride = width as usize * descriptor.bytes_per_pixel();
+        let start = y as usize * stride;
+        let strip = &mut self.frame[start..start + height as usize * stride];
+        Ok(PixelSliceMut::new(
+            strip, width, height, stride, descriptor,
+        )?)
+    }
+
+    fn finish(&mut self) -> Result<(), SinkError> {
+        self.finished = true;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn jpeg_exif_orientation_is_applied() {
+    let jpeg = jpeg_exif6();
+    let stored = DecodeRequest::new(&jpeg).decode_full_frame().unwrap();
+    assert_eq!(output_layout(&stored), (16, 8, true, false));
+
+    let upright = DecodeRequest::new(&jpeg)
+        .with_auto_orient(true)
+        .decode_full_frame()
+        .unwrap();
+    assert_eq!(output_layout(&upright), (8, 16, true, false));
+    assert_eq!(upright.info().orientation.to_exif(), 1);
+    let exif = upright.info().embedded_metadata.exif.as_deref().unwrap();
+    assert_eq!(
+        zencodecs::exif::parse_exif(exif).unwrap().orientation,
+        Some(1)
+    );
+}
+
+#[cfg(feature = "jpeg")]
+#[test]
+fn push_decode_orients_through_the_sink() {
+    let jpeg = jpeg_exif6();
+    let mut sink = FrameSink::default();
+    let info = DecodeRequest::new(&jpeg)
+        .with_auto_orient(true)
+        .push_decode(&mut sink)
+        .unwrap();
+    assert!(sink.finished);
+    assert_eq!((info.width, info.height), (8, 16));
+    let stride = sink.width as usize * sink.bpp;
+    assert_eq!(
+        layout(sink.width, sink.height, stride, sink.bpp, &sink.frame),
+        (8, 16, true, false)
+    );
+}
+
+#[cfg(feature = "heic-decode")]
+#[test]
+fn heic_is_not_rotated_twice() {
+    const HEIC: &[u8] = include_bytes!("images/heic_irot_exif6.heic");
+    let stored = DecodeRequest::new(HEIC).decode_full_frame().unwrap();
+    // Already upright: the decoder applied irot, and EXIF still says 6.
+    assert_eq!(output_layout(&stored), (16, 32, true, false));
+    let exif = stored.info().embedded_metadata.exif.as_deref().unwrap();
+    assert_eq!(
+        zencodecs::exif::parse_exif(exif).unwrap().orientation,
+        Some(6)
+    );
+
+    let upright = DecodeRequest::new(HEIC)
+        .with_auto_orient(true)
+        .decode_full_frame()
+        .unwrap();
+    assert_eq!(output_layout(&upright), (16, 32, true, false));
+
+    let mut sink = FrameSink::default();
+    let info = DecodeRequest::new(HEIC)
+        .with_auto_orient(true)
+        .push_decode(&mut sink)
+        .unwrap();
+    assert_eq!((info.width, info.height), (16, 32));
+}
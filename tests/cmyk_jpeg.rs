@@ -0,0 +1,56 @@
+//! CMYK and YCCK JPEG decode through zencodecs.
+//!
+//! `cmyk_adobe.jpg` and `ycck_adobe.jpg` hold the same 80×16 image as Adobe
+//! CMYK (APP14 transform 0) and Adobe YCCK (transform 2), both without an
+//! ICC profile, so they must decode to the same RGB through the naive path.
+
+#![cfg(feature = "jpeg")]
+
+use zencodecs::DecodeRequest;
+use zencodecs::cmyk::{CmykKind, detect};
+
+const CMYK: &[u8] = include_bytes!("images/cmyk_adobe.jpg");
+const YCCK: &[u8] = include_bytes!("images/ycck_adobe.jpg");
+
+#[test]
+fn detects_adobe_cmyk_and_ycck() {
+    let cmyk = detect(CMYK).expect("CMYK detected");
+    assert_eq!(cmyk.kind, CmykKind::Cmyk);
+    assert!(cmyk.inverted);
+    assert!(cmyk.icc_profile.is_none());
+
+    let ycck = detect(YCCK).expect("YCCK detected");
+    assert_eq!(ycck.kind, CmykKind::Ycck);
+    assert!(ycck.inverted);
+}
+
+#[test]
+fn cmyk_and_ycck_decode_to_the_same_rgb() {
+    let decode = |data| {
+        let output = DecodeRequest::new(data).decode_full_frame().unwrap();
+        let pixels = output.pixels();
+        assert_eq!(pixels.descriptor().layout(), zenpixels::ChannelLayout::Rgb);
+        assert_eq!((pixels.width(), pixels.rows()), (80, 16));
+        let stride = pixels.stride();
+        pixels
+            .as_strided_bytes()
+            .chunks(stride)
+            .flat_map(|row| &row[..80 * 3])
+            .copied()
+            .collect::<Vec<u8>>()
+    };
+    let cmyk_rgb = decode(CMYK);
+    let ycck_rgb = decode(YCCK);
+
+    // Unpainted corner is white, the opposite one inked; YCCK only differs
+    // by chroma rounding.
+    assert_eq!(&cmyk_rgb[..3], &[255, 255, 255]);
+    assert!(cmyk_rgb[cmyk_rgb.len() - 3..].iter().all(|&v| v < 200));
+    assert!(
+        cmyk_rgb
+            .iter()
+            .zip(&ycck_rgb)
+            .all(|(a, b)| a.abs_diff(*b) <= 2),
+        "CMYK and YCCK renditions diverge"
+    );
+}
@@ -9,6 +9,7 @@
 use imgref::{ImgRef, ImgVec};
 use rgb::{Rgb, Rgba};
 use zencodecs::{DecodeRequest, DecodedGainMap, EncodeRequest, GainMapSource, ImageFormat};
+use zenpixels::PixelSlice;
 
 // ─── Helpers ────────────────────────────────────────────────────────────────
 
@@ -29,6 +30,13 @@ fn make_hdr_gradient(w: usize, h: usize, peak: f32) -> ImgVec<Rgb<f32>> {
     ImgVec::new(pixels, w, h)
 }
 
+/// Whether `data` is a JXL container (ISOBMFF) rather than a bare codestream.
+fn is_jxl_container(data: &[u8]) -> bool {
+    data.starts_with(&[
+        0, 0, 0, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+    ])
+}
+
 /// Encode to UltraHDR JPEG and return bytes.
 fn encode_ultrahdr(img: ImgRef<'_, Rgb<f32>>, quality: f32) -> Vec<u8> {
     EncodeRequest::new(ImageFormat::Jpeg)
@@ -220,7 +228,7 @@ fn e2e_gain_map_passthrough_jpeg_to_jpeg() {
     let _request = EncodeRequest::new(ImageFormat::Jpeg)
         .with_quality(75.0) // different quality
         .with_gain_map(source)
-        .encode_full_frame_rgb8(img_ref)
+        .encode(PixelSlice::from(img_ref).erase(), false)
         .expect("re-encode should succeed");
 
     // Verify the gain map data survived the passthrough
@@ -321,7 +329,7 @@ fn e2e_no_gainmap_from_webp_encode_decode() {
 
     let encoded = EncodeRequest::new(ImageFormat::WebP)
         .with_quality(80.0)
-        .encode_full_frame_rgb8(img.as_ref())
+        .encode(PixelSlice::from(img.as_ref()).erase(), false)
         .expect("webp encode failed");
 
     let (_, gainmap) = decode_with_gainmap(encoded.data());
@@ -343,7 +351,7 @@ fn e2e_no_gainmap_from_gif_encode_decode() {
     let img = ImgVec::new(pixels, 32, 32);
 
     let encoded = EncodeRequest::new(ImageFormat::Gif)
-        .encode_full_frame_rgba8(img.as_ref())
+        .encode(PixelSlice::from(img.as_ref()).erase(), true)
         .expect("gif encode failed");
 
     let (_, gainmap) = decode_with_gainmap(encoded.data());
@@ -428,17 +436,15 @@ mod jxl_gainmap {
         };
 
         // Step 3: Create ISO 21496-1 metadata (log2/f64 domain)
-        let metadata = GainMapMetadata {
-            gain_map_max: [2.0; 3],
-            gain_map_min: [0.0; 3],
-            gamma: [1.0; 3],
-            base_offset: [1.0 / 64.0; 3],
-            alternate_offset: [1.0 / 64.0; 3],
-            base_hdr_headroom: 0.0,
-            alternate_hdr_headroom: 2.0,
-            use_base_color_space: true,
-            ..GainMapMetadata::default()
-        };
+        let mut metadata = GainMapMetadata::default();
+        metadata.gain_map_max = [2.0; 3];
+        metadata.gain_map_min = [0.0; 3];
+        metadata.gamma = [1.0; 3];
+        metadata.base_offset = [1.0 / 64.0; 3];
+        metadata.alternate_offset = [1.0 / 64.0; 3];
+        metadata.base_hdr_headroom = 0.0;
+        metadata.alternate_hdr_headroom = 2.0;
+        metadata.use_base_color_space = true;
 
         // Step 4: Encode to JXL with gain map
         let source = GainMapSource::Precomputed {
@@ -448,7 +454,7 @@ mod jxl_gainmap {
         let output = EncodeRequest::new(ImageFormat::Jxl)
             .with_quality(85.0)
             .with_gain_map(source)
-            .encode_full_frame_rgb8(img.as_ref())
+            .encode(PixelSlice::from(img.as_ref()).erase(), false)
             .expect("JXL encode with gain map failed");
 
         assert!(!output.data().is_empty());
@@ -456,7 +462,7 @@ mod jxl_gainmap {
 
         // Step 5: Verify the output is in container format (jhgm requires container)
         assert!(
-            zenjxl::container::is_container(output.data()),
+            is_jxl_container(output.data()),
             "JXL output with gain map should be in container format"
         );
 
@@ -545,17 +551,15 @@ mod jxl_gainmap {
             channels: 3,
         };
 
-        let metadata = GainMapMetadata {
-            gain_map_max: [3.0f64.log2(), 3.5f64.log2(), 2.5f64.log2()],
-            gain_map_min: [0.0; 3],
-            gamma: [1.0; 3],
-            base_offset: [1.0 / 64.0; 3],
-            alternate_offset: [1.0 / 64.0; 3],
-            base_hdr_headroom: 0.0,
-            alternate_hdr_headroom: 3.5f64.log2(),
-            use_base_color_space: true,
-            ..GainMapMetadata::default()
-        };
+        let mut metadata = GainMapMetadata::default();
+        metadata.gain_map_max = [3.0f64.log2(), 3.5f64.log2(), 2.5f64.log2()];
+        metadata.gain_map_min = [0.0; 3];
+        metadata.gamma = [1.0; 3];
+        metadata.base_offset = [1.0 / 64.0; 3];
+        metadata.alternate_offset = [1.0 / 64.0; 3];
+        metadata.base_hdr_headroom = 0.0;
+        metadata.alternate_hdr_headroom = 3.5f64.log2();
+        metadata.use_base_color_space = true;
 
         let source = GainMapSource::Precomputed {
             gain_map: &gain_map,
@@ -564,10 +568,10 @@ mod jxl_gainmap {
         let output = EncodeRequest::new(ImageFormat::Jxl)
             .with_quality(90.0)
             .with_gain_map(source)
-            .encode_full_frame_rgb8(img.as_ref())
+            .encode(PixelSlice::from(img.as_ref()).erase(), false)
             .expect("JXL encode with RGB gain map failed");
 
-        assert!(zenjxl::container::is_container(output.data()));
+        assert!(is_jxl_container(output.data()));
 
         // Decode and verify
         let (_decoded, decoded_gm) = DecodeRequest::new(output.data())
@@ -580,3 +584,71 @@ mod jxl_gainmap {
         assert_eq!(gm.gain_map.channels, 3);
     }
 }
+
+// ─── E2E: Gain map survives transcode ───────────────────────────────────────
+
+/// Transcode UltraHDR `source` to `format` with default options.
+fn transcode_to(source: &[u8], format: ImageFormat) -> Vec<u8> {
+    zencodecs::transcode(
+        source,
+        &zencodecs::FormatDecision::for_format(format),
+        &zencodecs::TranscodeOptions::default(),
+        &zencodecs::AllowedFormats::all(),
+    )
+    .expect("transcode failed")
+    .data
+}
+
+#[test]
+fn e2e_transcode_jpeg_to_jpeg_keeps_gain_map() {
+    let hdr_img = make_hdr_gradient(64, 64, 3.0);
+    let original = encode_ultrahdr(hdr_img.as_ref(), 90.0);
+    let (_, source_gm) = decode_with_gainmap(&original);
+    let source_gm = source_gm.expect("source must have gain map");
+
+    let transcoded = transcode_to(&original, ImageFormat::Jpeg);
+    let (output, gm) = decode_with_gainmap(&transcoded);
+    let gm = gm.expect("gain map lost in transcode");
+
+    assert_eq!((output.width(), output.height()), (64, 64));
+    assert_gain_map_valid(&gm.gain_map);
+    assert!(!gm.base_is_hdr);
+    assert_eq!(
+        (gm.gain_map.width, gm.gain_map.height),
+        (source_gm.gain_map.width, source_gm.gain_map.height)
+    );
+    for c in 0..3 {
+        assert!(
+            (gm.metadata.gain_map_max[c] - source_gm.metadata.gain_map_max[c]).abs() < 0.05,
+            "gain_map_max[{c}]: {} vs {}",
+            gm.metadata.gain_map_max[c],
+            source_gm.metadata.gain_map_max[c]
+        );
+    }
+    assert!(
+        (gm.metadata.alternate_hdr_headroom - source_gm.metadata.alternate_hdr_headroom).abs()
+            < 0.05
+    );
+}
+
+/// JXL stores an HDR base, so the transcode re-renders the base and
+/// inverts the gain map.
+#[cfg(all(feature = "std", feature = "jxl-encode", feature = "jxl-decode"))]
+#[test]
+fn e2e_transcode_jpeg_to_jxl_inverts_gain_map() {
+    let hdr_img = make_hdr_gradient(64, 64, 3.0);
+    let original = encode_ultrahdr(hdr_img.as_ref(), 90.0);
+    let (_, source_gm) = decode_with_gainmap(&original);
+    let source_gm = source_gm.expect("source must have gain map");
+
+    let transcoded = transcode_to(&original, ImageFormat::Jxl);
+    let (output, gm) = decode_with_gainmap(&transcoded);
+    let gm = gm.expect("gain map lost in transcode");
+
+    assert_eq!((output.width(), output.height()), (64, 64));
+    assert_gain_map_valid(&gm.gain_map);
+    assert!(gm.base_is_hdr);
+    assert!(
+        (gm.metadata.base_hdr_headroom - source_gm.metadata.alternate_hdr_headroom).abs() < 0.05
+    );
+}
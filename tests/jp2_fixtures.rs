@@ -0,0 +1,166 @@
+//! JPEG 2000 decode against OpenJPEG.
+//!
+//! Each file in `images/jp2` is a 61×47 gradient-plus-noise image encoded by
+//! OpenJPEG 2.5, next to a `.raw` holding OpenJPEG's own decode of it:
+//! interleaved samples, one byte each up to 8 bits, big-endian `u16` above.
+//! Reversible 5/3 files must match exactly, up to rounding where 12-bit
+//! samples widen to 16-bit output; irreversible 9/7 files may differ by float
+//! rounding in the inverse transform.
+
+#![cfg(feature = "jp2-decode")]
+
+use zencodecs::jp2::header;
+use zencodecs::{DecodeRequest, Limits};
+
+const WIDTH: usize = 61;
+const HEIGHT: usize = 47;
+
+struct Fixture {
+    name: &'static str,
+    data: &'static [u8],
+    reference: &'static [u8],
+    channels: usize,
+    precision: u8,
+    /// Largest allowed difference in output samples.
+    tolerance: u32,
+}
+
+macro_rules! fixture {
+    ($name:literal, $ext:literal, $channels:expr, $precision:expr, $tolerance:expr) => {
+        Fixture {
+            name: $name,
+            data: include_bytes!(concat!("images/jp2/", $name, ".", $ext)),
+            reference: include_bytes!(concat!("images/jp2/", $name, ".raw")),
+            channels: $channels,
+            precision: $precision,
+            tolerance: $tolerance,
+        }
+    };
+}
+
+const FIXTURES: &[Fixture] = &[
+    fixture!("lrcp_53_tiles_layers", "j2k", 3, 8, 0),
+    fixture!("rlcp_53_precincts_sop_eph", "j2k", 3, 8, 0),
+    fixture!("rpcl_97_ict_layers", "jp2", 3, 8, 1),
+    fixture!("pcrl_97_tiles_precincts", "j2k", 3, 8, 1),
+    fixture!("cprl_53_gray12", "j2k", 1, 12, 1),
+    fixture!("lrcp_97_rgb16", "jp2", 3, 16, 3),
+];
+
+/// OpenJPEG's samples, rescaled to the 8- or 16-bit output range.
+fn reference(fixture: &Fixture) -> Vec<u32> {
+    let max = (1u64 << fixture.precision) - 1;
+    if fixture.precision <= 8 {
+        return fixture.reference.iter().map(|&v| u32::from(v)).collect();
+    }
+    fixture
+        .reference
+        .chunks_exact(2)
+        .map(|v| {
+            let v = u64::from(u16::from_be_bytes([v[0], v[1]]));
+            ((v * 65535 + max / 2) / max) as u32
+        })
+        .collect()
+}
+
+fn decoded(data: &[u8], channels: usize) -> Vec<u32> {
+    let output = DecodeRequest::new(data).decode_full_frame().unwrap();
+    let pixels = output.pixels();
+    assert_eq!(
+        (pixels.width(), pixels.rows()),
+        (WIDTH as u32, HEIGHT as u32)
+    );
+    let bytes_per_sample = pixels.descriptor().bytes_per_pixel() / channels;
+    let row_bytes = WIDTH * channels * bytes_per_sample;
+    let bytes = pixels.as_strided_bytes();
+    (0..HEIGHT)
+        .flat_map(|y| &bytes[y * pixels.stride()..][..row_bytes])
+        .copied()
+        .collect::<Vec<u8>>()
+        .chunks_exact(bytes_per_sample)
+        .map(|s| match s {
+            [v] => u32::from(*v),
+            [a, b] => u32::from(u16::from_ne_bytes([*a, *b])),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+#[test]
+fn headers_describe_the_encoder_settings() {
+    let tiles = |name: &str| {
+        let fixture = FIXTURES.iter().find(|f| f.name == name).unwrap();
+        let header = header(fixture.data).unwrap();
+        assert_eq!((header.width, header.height), (WIDTH as u32, HEIGHT as u32));
+        assert_eq!(header.components.len(), fixture.channels);
+        assert_eq!(header.components[0].precision, fixture.precision);
+        assert_eq!(header.reversible, name.contains("_53_"));
+        (header.tiles, header.layers, header.levels)
+    };
+    assert_eq!(tiles("lrcp_53_tiles_layers"), (4, 3, 3));
+    assert_eq!(tiles("rpcl_97_ict_layers"), (1, 3, 3));
+    assert_eq!(tiles("pcrl_97_tiles_precincts"), (4, 2, 2));
+    assert_eq!(tiles("cprl_53_gray12"), (4, 2, 3));
+}
+
+#[test]
+fn decodes_match_openjpeg() {
+    for fixture in FIXTURES {
+        let expected = reference(fixture);
+        let actual = decoded(fixture.data, fixture.channels);
+        assert_eq!(
+            actual.len(),
+            WIDTH * HEIGHT * fixture.channels,
+            "{}",
+            fixture.name
+        );
+        let worst = expected
+            .iter()
+            .zip(&actual)
+            .map(|(&e, &a)| e.abs_diff(a))
+            .max()
+            .unwrap();
+        assert!(
+            worst <= fixture.tolerance,
+            "{}: off by up to {worst}",
+            fixture.name
+        );
+    }
+}
+
+/// Damaged files must fail cleanly: flipped bytes and truncations anywhere
+/// in every fixture either decode or return an error, never panic. A pixel
+/// limit keeps headers corrupted into huge canvases from dominating the run.
+#[test]
+fn corrupted_fixtures_never_panic() {
+    let limits = Limits {
+        max_pixels: Some(1 << 16),
+        ..Limits::default()
+    };
+    let decode = |data: &[u8]| {
+        DecodeRequest::new(data)
+            .with_limits(&limits)
+            .decode_full_frame()
+    };
+    let mut seed = 0x2545_f491_u32;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as usize
+    };
+    for fixture in FIXTURES {
+        let len = fixture.data.len();
+        for cut in (0..len).step_by(len / 64 + 1) {
+            let _ = decode(&fixture.data[..cut]);
+        }
+        for _ in 0..100 {
+            let mut data = fixture.data.to_vec();
+            for _ in 0..1 + next() % 4 {
+                let at = next() % len;
+                data[at] ^= 1 << (next() % 8);
+            }
+            let _ = decode(&data);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! JXL → JPEG reconstruction through `transcode`.
+//!
+//! `jbrd_source.jxl` is `jbrd_source.jpg` (a 32×32 baseline gradient)
+//! losslessly recompressed by jxl-encoder with a `jbrd` box, so the JPEG
+//! comes back byte for byte.
+
+#![cfg(feature = "jxl-decode")]
+
+use zencodecs::{AllowedFormats, FormatDecision, ImageFormat, TranscodeOptions, transcode};
+
+const JPEG: &[u8] = include_bytes!("images/jbrd_source.jpg");
+const JXL: &[u8] = include_bytes!("images/jbrd_source.jxl");
+
+fn to_jpeg(lossless_jpeg_transcode: bool) -> FormatDecision {
+    FormatDecision {
+        lossless_jpeg_transcode,
+        ..FormatDecision::for_format(ImageFormat::Jpeg)
+    }
+}
+
+#[test]
+fn jbrd_rebuilds_the_original_jpeg() {
+    let out = transcode(
+        JXL,
+        &to_jpeg(true),
+        &TranscodeOptions::default(),
+        &AllowedFormats::all(),
+    )
+    .unwrap();
+    assert_eq!(out.format, ImageFormat::Jpeg);
+    assert_eq!(out.data, JPEG);
+}
+
+#[test]
+fn disabled_jxl_decode_is_an_error() {
+    let registry = AllowedFormats::all().with_decode(ImageFormat::Jxl, false);
+    let result = transcode(JXL, &to_jpeg(true), &TranscodeOptions::default(), &registry);
+    assert!(matches!(
+        result.as_ref().map_err(|e| e.error()),
+        Err(zencodecs::CodecError::DisabledFormat(ImageFormat::Jxl))
+    ));
+}
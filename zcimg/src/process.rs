@@ -1,4 +1,4 @@
-//! Unified processing pipeline: decode → orient → resize → encode.
+//! Unified processing pipeline: decode → orient → rotate/flip → resize → encode.
 //!
 //! Currently uses direct DecodeRequest/EncodeRequest (no resize support)
 //! because the zencodecs pipeline feature is disabled due to a zenresize
@@ -16,7 +16,7 @@ use zencodecs::{DecodeRequest, EncodeRequest, ImageFormat, Metadata, PixelBuffer
 
 use crate::batch::{self, BatchSummary, FileResult};
 use crate::output::OutputConfig;
-use crate::{PresetArg, ProcessArgs};
+use crate::{FlipArg, PresetArg, ProcessArgs};
 
 /// Run the `process` subcommand.
 pub fn run(args: ProcessArgs) -> anyhow::Result<()> {
@@ -178,13 +178,28 @@ fn process_inner(
     // Determine target format
     let target_format = args.resolve_format().unwrap_or(source_format);
 
+    if let Some(transformed) = lossless_jpeg_orient(&data, source_format, target_format, args)
+        .with_context(|| format!("transforming {}", input.display()))?
+    {
+        return write_output(input, &transformed, input_size, args, output_config, input_count);
+    }
+
     // Decode
     let decoded = DecodeRequest::new(&data)
+        .with_auto_orient(args.auto_orient)
         .decode_full_frame()
         .with_context(|| format!("decoding {}", input.display()))?;
 
     let info = decoded.info().clone();
     let has_alpha = decoded.has_alpha();
+    let is_grayscale = decoded.descriptor().is_grayscale();
+
+    // --rotate/--flip on top of the (auto-)oriented pixels.
+    let mut buffer = decoded.into_buffer();
+    for orientation in requested_transforms(args)? {
+        buffer = zencodecs::orient::orient_pixels(buffer.as_slice(), orientation)
+            .with_context(|| format!("transforming {}", input.display()))?;
+    }
 
     // Build metadata based on strip flags
     let owned_meta = build_metadata(&info, args);
@@ -230,20 +245,106 @@ fn process_inner(
     }
 
     // Encode based on pixel type
-    let is_grayscale = decoded.descriptor().is_grayscale();
     let encoded = if has_alpha {
-        let pixels = decoded.into_buffer().to_rgba8();
+        let pixels = buffer.to_rgba8();
         encode_req.encode_full_frame_rgba8(pixels.as_imgref())
     } else if is_grayscale {
-        let pixels = decoded.into_buffer().to_gray8();
+        let pixels = buffer.to_gray8();
         encode_req.encode_full_frame_gray8(pixels.as_imgref())
     } else {
-        let pixels = decoded.into_buffer().to_rgb8();
+        let pixels = buffer.to_rgb8();
         encode_req.encode_full_frame_rgb8(pixels.as_imgref())
     }
     .with_context(|| format!("encoding {} as {:?}", input.display(), target_format))?;
 
-    let output_size = encoded.data().len() as u64;
+    write_output(input, encoded.data(), input_size, args, output_config, input_count)
+}
+
+/// Orientation-only JPEG → JPEG: rotate/flip the DCT coefficients instead
+/// of decoding and re-encoding, so there is no generation loss.
+///
+/// Returns `None` when the request needs the pixel path (format change,
+/// quality/effort settings, resize, metadata stripping, a rotation that
+/// would trim partial edge blocks) or there is no orientation change to
+/// make.
+fn lossless_jpeg_orient(
+    data: &[u8],
+    source_format: ImageFormat,
+    target_format: ImageFormat,
+    args: &ProcessArgs,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    use zencodecs::JpegTransform;
+
+    let orientation_only = source_format == ImageFormat::Jpeg
+        && target_format == ImageFormat::Jpeg
+        && args.quality.is_none()
+        && args.preset.is_none()
+        && !args.lossless
+        && args.effort.is_none()
+        && args.resolve_dimensions()?.is_none()
+        && !(args.strip_all
+            || args.strip_icc
+            || args.strip_exif
+            || args.strip_xmp
+            || args.preserve_icc);
+    if !orientation_only {
+        return Ok(None);
+    }
+
+    let transforms = requested_transforms(args)?;
+    let transformed = || -> zencodecs::Result<Option<Vec<u8>>> {
+        let mut current: Option<Vec<u8>> = None;
+        if args.auto_orient {
+            current = zencodecs::auto_orient_jpeg(data)?;
+        }
+        for &orientation in &transforms {
+            let next = JpegTransform::new()
+                .with_auto_orient(args.auto_orient)
+                .with_orientation(orientation)
+                .apply(current.as_deref().unwrap_or(data))?;
+            current = Some(next);
+        }
+        Ok(current)
+    };
+    match transformed() {
+        Err(e) if matches!(e.error(), zencodecs::CodecError::UnsupportedOperation { .. }) => {
+            Ok(None)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// `--rotate` then `--flip`, as EXIF-style orientations relative to the
+/// displayed image. Applied in order.
+fn requested_transforms(args: &ProcessArgs) -> anyhow::Result<Vec<zencodec::Orientation>> {
+    let rotation = match args.rotate {
+        None | Some(0) => None,
+        Some(90) => Some(6),
+        Some(180) => Some(3),
+        Some(270) => Some(8),
+        Some(other) => anyhow::bail!("--rotate must be 90, 180 or 270, got {other}"),
+    };
+    let flip = args.flip.map(|f| match f {
+        FlipArg::H => 2,
+        FlipArg::V => 4,
+    });
+    Ok([rotation, flip]
+        .into_iter()
+        .flatten()
+        .filter_map(zencodec::Orientation::from_exif)
+        .collect())
+}
+
+/// Skip-if-larger check, then write `encoded` (or report a dry run).
+fn write_output(
+    input: &Path,
+    encoded: &[u8],
+    input_size: u64,
+    args: &ProcessArgs,
+    output_config: &OutputConfig,
+    input_count: usize,
+) -> anyhow::Result<(std::path::PathBuf, u64, bool)> {
+    let output_size = encoded.len() as u64;
 
     // Skip-if-larger check
     if args.skip_if_larger && output_size >= input_size {
@@ -266,7 +367,7 @@ fn process_inner(
         );
     } else {
         OutputConfig::ensure_parent(&output_path)?;
-        std::fs::write(&output_path, encoded.data())
+        std::fs::write(&output_path, encoded)
             .with_context(|| format!("writing {}", output_path.display()))?;
     }
 